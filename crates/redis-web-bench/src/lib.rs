@@ -6,7 +6,9 @@
 //! - [`process`] launches isolated `redis-web` processes for each variant.
 //! - [`suites`] runs the actual benchmark workloads.
 //! - [`report`] renders the Markdown summary artifact.
+//! - [`load`] drives an ad-hoc request mix against a single server.
 
+mod load;
 mod model;
 mod process;
 mod report;
@@ -14,6 +16,7 @@ mod spec;
 mod suites;
 mod summary;
 
+pub use load::{run_load, LoadArgs, LoadReport};
 pub use model::{
     BenchmarkResults, CompareSpec, ConfigDiff, EnvironmentSummary, MetricSummary, SuiteRegistry,
     SuiteResult, SuiteStatus, VariantBenchmarkResult, VariantSpec, WorkloadResult,
@@ -0,0 +1,372 @@
+//! Ad-hoc load generation against one running server.
+//!
+//! Unlike the comparison suites, `load` fires a configurable mix of requests
+//! at a single URL, so the same run can target redis-web or the original C
+//! Webdis. GET and SET use the URL form both servers accept
+//! (`/GET/<key>`, `/SET/<key>/<value>`); pipelines send several `SET`s in one
+//! request through a hiredis compat session, which only redis-web offers.
+//! Without `--url`, the server is started in-process from `--config`.
+
+use crate::model::MetricSummary;
+use crate::suites::summarize_metrics;
+use anyhow::{bail, Context, Result};
+use redis_web_core::config::Config;
+use redis_web_runtime::server;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Options of the `load` subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct LoadArgs {
+    /// Base URL of a running server, such as `http://127.0.0.1:7379`.
+    #[arg(long, conflicts_with = "config")]
+    pub url: Option<String>,
+    /// Config to start redis-web with in-process when `--url` is not given.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+    /// Total requests to send.
+    #[arg(long, default_value_t = 10_000)]
+    pub requests: u64,
+    /// Requests in flight at once.
+    #[arg(long, default_value_t = 32)]
+    pub concurrency: usize,
+    /// Relative weights of each request kind, such as `get=80,set=20`.
+    #[arg(long, default_value = "get=50,set=50")]
+    pub mix: String,
+    /// Commands per pipeline request.
+    #[arg(long, default_value_t = 16)]
+    pub pipeline_size: usize,
+    /// Distinct keys the requests cycle through.
+    #[arg(long, default_value_t = 1_000)]
+    pub keys: u64,
+    /// Size of SET values in bytes.
+    #[arg(long, default_value_t = 64)]
+    pub value_size: usize,
+    /// `compat_hiredis.path_prefix` of the target, used by pipelines.
+    #[arg(long, default_value = "/__compat")]
+    pub compat_prefix: String,
+    /// Print the report as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RequestKind {
+    Get,
+    Set,
+    Pipeline,
+}
+
+impl RequestKind {
+    fn name(self) -> &'static str {
+        match self {
+            RequestKind::Get => "get",
+            RequestKind::Set => "set",
+            RequestKind::Pipeline => "pipeline",
+        }
+    }
+}
+
+/// Weighted request kinds; request `n` gets the kind at `n` modulo the total
+/// weight, so every run sends the same sequence.
+#[derive(Clone, Debug, PartialEq)]
+struct Mix(Vec<(RequestKind, u64)>);
+
+impl Mix {
+    fn parse(text: &str) -> Result<Self> {
+        let mut weights = Vec::new();
+        for part in text
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            let (name, weight) = part
+                .split_once('=')
+                .with_context(|| format!("expected kind=weight, got `{part}`"))?;
+            let kind = match name.trim() {
+                "get" => RequestKind::Get,
+                "set" => RequestKind::Set,
+                "pipeline" => RequestKind::Pipeline,
+                other => bail!("unknown request kind `{other}`; use get, set, or pipeline"),
+            };
+            let weight: u64 = weight
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid weight in `{part}`"))?;
+            if weight > 0 {
+                weights.push((kind, weight));
+            }
+        }
+        if weights.is_empty() {
+            bail!("the mix needs at least one kind with a positive weight");
+        }
+        Ok(Self(weights))
+    }
+
+    fn kind(&self, idx: u64) -> RequestKind {
+        let total: u64 = self.0.iter().map(|(_, weight)| weight).sum();
+        let mut slot = idx % total;
+        for &(kind, weight) in &self.0 {
+            if slot < weight {
+                return kind;
+            }
+            slot -= weight;
+        }
+        unreachable!("slot is below the total weight")
+    }
+
+    fn has(&self, kind: RequestKind) -> bool {
+        self.0.iter().any(|&(candidate, _)| candidate == kind)
+    }
+}
+
+/// Latency and throughput of a load run, overall and per request kind.
+#[derive(Debug, Serialize)]
+pub struct LoadReport {
+    pub target: String,
+    pub elapsed_ms: f64,
+    pub total: MetricSummary,
+    pub by_kind: BTreeMap<String, MetricSummary>,
+}
+
+impl LoadReport {
+    /// Renders the report as a plain-text table.
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "target {}, {:.0} ms\n{:<10}{:>10}{:>8}{:>12}{:>10}{:>10}{:>10}\n",
+            self.target,
+            self.elapsed_ms,
+            "kind",
+            "requests",
+            "errors",
+            "req/s",
+            "p50 ms",
+            "p95 ms",
+            "p99 ms"
+        );
+        let rows = self
+            .by_kind
+            .iter()
+            .map(|(name, metrics)| (name.as_str(), metrics))
+            .chain([("total", &self.total)]);
+        for (name, metrics) in rows {
+            let ms = |value: Option<f64>| value.map_or("-".to_string(), |ms| format!("{ms:.3}"));
+            out.push_str(&format!(
+                "{:<10}{:>10}{:>8}{:>12.1}{:>10}{:>10}{:>10}\n",
+                name,
+                metrics.attempted_ops,
+                metrics.error_count,
+                metrics.throughput_per_sec,
+                ms(metrics.p50_ms),
+                ms(metrics.p95_ms),
+                ms(metrics.p99_ms),
+            ));
+        }
+        out
+    }
+}
+
+/// Runs the `load` subcommand.
+pub async fn run_load(args: &LoadArgs) -> Result<LoadReport> {
+    let mix = Arc::new(Mix::parse(&args.mix)?);
+    if args.concurrency == 0 || args.keys == 0 {
+        bail!("--concurrency and --keys must be positive");
+    }
+    match &args.url {
+        Some(url) => load(args, &mix, url.trim_end_matches('/')).await,
+        None => {
+            let path = args
+                .config
+                .as_ref()
+                .context("pass --url for a running server or --config to start one")?;
+            let config = Config::new(&path.to_string_lossy())
+                .map_err(|error| anyhow::anyhow!("failed to load {}: {error}", path.display()))?;
+            let components = server::build_runtime(&config)
+                .map_err(|error| anyhow::anyhow!("failed to build redis-web: {error}"))?;
+            let shutdown = components.app_state.shutdown.clone();
+            let app = server::build_router_from_components(&config, components);
+            let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+            let url = format!("http://{}", listener.local_addr()?);
+            let serving = tokio::spawn(server::serve_listener(listener, app, shutdown));
+            let report = load(args, &mix, &url).await;
+            serving.abort();
+            report
+        }
+    }
+}
+
+async fn load(args: &LoadArgs, mix: &Arc<Mix>, base_url: &str) -> Result<LoadReport> {
+    let client = reqwest::Client::new();
+    let value = "x".repeat(args.value_size);
+    let counter = Arc::new(AtomicU64::new(0));
+    let mut join_set = JoinSet::new();
+    let start = Instant::now();
+
+    for _ in 0..args.concurrency {
+        // Compat sessions serialize their commands, so each worker opens its own.
+        let session = if mix.has(RequestKind::Pipeline) {
+            Some(open_session(&client, base_url, &args.compat_prefix).await?)
+        } else {
+            None
+        };
+        let worker = Worker {
+            client: client.clone(),
+            base_url: base_url.to_string(),
+            cmd_url: session
+                .map(|session| format!("{base_url}{}/cmd/{session}.raw", args.compat_prefix)),
+            value: value.clone(),
+            keys: args.keys,
+            pipeline_size: args.pipeline_size.max(1),
+        };
+        let counter = counter.clone();
+        let requests = args.requests;
+        let mix = mix.clone();
+        join_set.spawn(async move {
+            let mut samples = Vec::new();
+            loop {
+                let idx = counter.fetch_add(1, Ordering::Relaxed);
+                if idx >= requests {
+                    break;
+                }
+                let kind = mix.kind(idx);
+                let started = Instant::now();
+                let ok = worker.send(kind, idx).await.is_ok();
+                samples.push((kind, ok.then(|| started.elapsed())));
+            }
+            samples
+        });
+    }
+
+    let mut samples = Vec::new();
+    while let Some(result) = join_set.join_next().await {
+        samples.extend(result.context("load worker task panicked")?);
+    }
+    let elapsed = start.elapsed();
+    Ok(LoadReport {
+        target: base_url.to_string(),
+        elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        total: summarize(samples.iter().copied(), elapsed),
+        by_kind: [RequestKind::Get, RequestKind::Set, RequestKind::Pipeline]
+            .into_iter()
+            .filter(|&kind| mix.has(kind))
+            .map(|kind| {
+                let of_kind = samples.iter().copied().filter(|(k, _)| *k == kind);
+                (kind.name().to_string(), summarize(of_kind, elapsed))
+            })
+            .collect(),
+    })
+}
+
+fn summarize(
+    samples: impl Iterator<Item = (RequestKind, Option<Duration>)>,
+    elapsed: Duration,
+) -> MetricSummary {
+    let mut attempted = 0;
+    let mut errors = 0;
+    let mut latencies = Vec::new();
+    for (_, latency) in samples {
+        attempted += 1;
+        match latency {
+            Some(latency) => latencies.push(latency),
+            None => errors += 1,
+        }
+    }
+    summarize_metrics(latencies, elapsed, attempted, errors)
+}
+
+async fn open_session(client: &reqwest::Client, base_url: &str, prefix: &str) -> Result<String> {
+    let reply: serde_json::Value = client
+        .post(format!("{base_url}{prefix}/session"))
+        .send()
+        .await?
+        .error_for_status()
+        .context("pipelines need compat_hiredis enabled on the target")?
+        .json()
+        .await?;
+    reply["session_id"]
+        .as_str()
+        .map(str::to_string)
+        .context("compat session reply has no session_id")
+}
+
+struct Worker {
+    client: reqwest::Client,
+    base_url: String,
+    cmd_url: Option<String>,
+    value: String,
+    keys: u64,
+    pipeline_size: usize,
+}
+
+impl Worker {
+    async fn send(&self, kind: RequestKind, idx: u64) -> Result<()> {
+        let key = format!("redis-web-bench:load:{}", idx % self.keys);
+        let request = match kind {
+            RequestKind::Get => self.client.get(format!("{}/GET/{key}", self.base_url)),
+            RequestKind::Set => self
+                .client
+                .get(format!("{}/SET/{key}/{}", self.base_url, self.value)),
+            RequestKind::Pipeline => {
+                let url = self.cmd_url.as_ref().context("no compat session")?;
+                let mut body = Vec::new();
+                for offset in 0..self.pipeline_size as u64 {
+                    let key = format!("redis-web-bench:load:{}", (idx + offset) % self.keys);
+                    body.extend(resp_command(&["SET", &key, &self.value]));
+                }
+                self.client.post(url).body(body)
+            }
+        };
+        let response = request.send().await?.error_for_status()?;
+        response.bytes().await?;
+        Ok(())
+    }
+}
+
+fn resp_command(args: &[&str]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        out.extend(format!("${}\r\n{arg}\r\n", arg.len()).into_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_parses_weights_and_cycles_through_kinds() {
+        let mix = Mix::parse("get=2, set=1,pipeline=0").unwrap();
+        assert_eq!(mix, Mix(vec![(RequestKind::Get, 2), (RequestKind::Set, 1)]));
+        let kinds: Vec<_> = (0..6).map(|idx| mix.kind(idx)).collect();
+        assert_eq!(
+            kinds,
+            [
+                RequestKind::Get,
+                RequestKind::Get,
+                RequestKind::Set,
+                RequestKind::Get,
+                RequestKind::Get,
+                RequestKind::Set
+            ]
+        );
+        assert!(!mix.has(RequestKind::Pipeline));
+
+        assert!(Mix::parse("get=0").is_err());
+        assert!(Mix::parse("del=1").is_err());
+        assert!(Mix::parse("get").is_err());
+    }
+
+    #[test]
+    fn resp_commands_are_length_prefixed() {
+        assert_eq!(
+            resp_command(&["SET", "k", "v"]),
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n"
+        );
+    }
+}
@@ -16,6 +16,9 @@ enum Commands {
         #[arg(long)]
         spec: PathBuf,
     },
+    /// Send a mix of GET/SET/pipeline requests to one server and report
+    /// latency percentiles and throughput.
+    Load(redis_web_bench::LoadArgs),
     /// Regenerate report.md files from existing results.json artifacts.
     RenderReports {
         /// Root directory containing per-run artifact subdirectories.
@@ -33,6 +36,14 @@ async fn main() -> anyhow::Result<()> {
             let artifact_dir = redis_web_bench::run_compare(&spec).await?;
             println!("Wrote benchmark artifacts to {}", artifact_dir.display());
         }
+        Commands::Load(args) => {
+            let report = redis_web_bench::run_load(&args).await?;
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report.render());
+            }
+        }
         Commands::RenderReports { root } => {
             let reports = redis_web_bench::regenerate_reports_under(&root)?;
             println!(
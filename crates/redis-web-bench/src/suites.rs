@@ -1656,7 +1656,7 @@ fn skipped_suite(name: &str, reason: &str) -> SuiteResult {
     }
 }
 
-fn summarize_metrics(
+pub(crate) fn summarize_metrics(
     mut latencies: Vec<Duration>,
     elapsed: Duration,
     attempted_ops: u64,
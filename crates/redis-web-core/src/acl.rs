@@ -1,9 +1,35 @@
-use crate::config::AclConfig;
-use crate::interfaces::AuthContext;
+use crate::config::{AclConfig, AclScheduleConfig, RedisAuthConfig};
+use crate::interfaces::{AuthContext, RedisCredentials};
+use crate::routing::glob_match;
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::HashMap;
 use std::net::IpAddr;
+use std::sync::RwLock;
+
+/// Rule lists at least this long memoize their command decisions; shorter
+/// ones are cheaper to scan than to look up.
+const CACHE_MIN_RULES: usize = 16;
+/// Decisions kept before the cache is emptied, bounding its memory when
+/// clients send many distinct credentials.
+const MAX_CACHED_DECISIONS: usize = 10_000;
 
 pub struct Acl {
     rules: Vec<AclRule>,
+    /// Command decisions per client identity. The rules never change after
+    /// construction, so a reloaded config builds a new `Acl` with an empty
+    /// cache. Unused when a rule has a schedule, since decisions then change
+    /// over time.
+    decisions: RwLock<HashMap<DecisionKey, bool>>,
+    scheduled: bool,
+}
+
+#[derive(Hash, PartialEq, Eq)]
+struct DecisionKey {
+    ip: IpAddr,
+    authorization: Option<String>,
+    /// Upper-cased, as rules match command names case-insensitively.
+    command: String,
 }
 
 struct AclRule {
@@ -11,6 +37,78 @@ struct AclRule {
     basic_auth: Option<String>,
     enabled: Vec<String>,
     disabled: Vec<String>,
+    channels: Vec<Vec<u8>>,
+    has_redis_auth: bool,
+    schedule: Option<AclSchedule>,
+}
+
+/// Parsed `schedule` of a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AclSchedule {
+    days: Option<Vec<Weekday>>,
+    hours: Option<(NaiveTime, NaiveTime)>,
+    timezone: Tz,
+}
+
+impl AclSchedule {
+    pub fn parse(config: &AclScheduleConfig) -> Result<Self, String> {
+        let days = config
+            .days
+            .as_ref()
+            .map(|days| {
+                days.iter()
+                    .map(|day| {
+                        day.parse::<Weekday>()
+                            .map_err(|_| format!("unknown day {day:?}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let hours = config
+            .hours
+            .as_deref()
+            .map(|hours| {
+                let window = hours.split_once('-').and_then(|(start, end)| {
+                    let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+                    let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+                    Some((start, end))
+                });
+                window.ok_or_else(|| format!("hours {hours:?} must look like \"09:00-17:00\""))
+            })
+            .transpose()?;
+        let timezone = match config.timezone.as_deref() {
+            Some(name) => name
+                .parse::<Tz>()
+                .map_err(|_| format!("unknown timezone {name:?}"))?,
+            None => Tz::UTC,
+        };
+        Ok(Self {
+            days,
+            hours,
+            timezone,
+        })
+    }
+
+    /// Whether `now` falls on one of the days and within the hours.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.timezone);
+        if let Some(days) = &self.days {
+            if !days.contains(&local.weekday()) {
+                return false;
+            }
+        }
+        match self.hours {
+            Some((start, end)) => {
+                let time = local.time();
+                match start.cmp(&end) {
+                    std::cmp::Ordering::Less => start <= time && time < end,
+                    std::cmp::Ordering::Greater => time >= start || time < end,
+                    std::cmp::Ordering::Equal => true,
+                }
+            }
+            None => true,
+        }
+    }
 }
 
 impl Acl {
@@ -24,13 +122,84 @@ impl Acl {
                     basic_auth: c.http_basic_auth,
                     enabled: c.enabled.unwrap_or_default(),
                     disabled: c.disabled.unwrap_or_default(),
+                    channels: c
+                        .channels
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(String::into_bytes)
+                        .collect(),
+                    has_redis_auth: c.redis_auth.is_some(),
+                    // Startup rejects invalid schedules; one that slips
+                    // through never applies.
+                    schedule: c.schedule.as_ref().map(|schedule| {
+                        AclSchedule::parse(schedule).unwrap_or(AclSchedule {
+                            days: Some(Vec::new()),
+                            hours: None,
+                            timezone: Tz::UTC,
+                        })
+                    }),
                 });
             }
         }
-        Self { rules }
+        let scheduled = rules.iter().any(|rule| rule.schedule.is_some());
+        Self {
+            rules,
+            decisions: RwLock::new(HashMap::new()),
+            scheduled,
+        }
     }
 
     pub fn check(&self, ip: IpAddr, command: &str, auth_header: Option<&str>) -> bool {
+        if self.rules.len() < CACHE_MIN_RULES || self.scheduled {
+            return self.evaluate(ip, command, None, auth_header, Utc::now());
+        }
+        let key = DecisionKey {
+            ip,
+            authorization: auth_header.map(str::to_string),
+            command: command.to_ascii_uppercase(),
+        };
+        if let Some(&allowed) = self
+            .decisions
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&key)
+        {
+            return allowed;
+        }
+        let allowed = self.evaluate(ip, command, None, auth_header, Utc::now());
+        let mut decisions = self
+            .decisions
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if decisions.len() >= MAX_CACHED_DECISIONS {
+            decisions.clear();
+        }
+        decisions.insert(key, allowed);
+        allowed
+    }
+
+    /// Checks a Pub/Sub subscription to `channel`.
+    ///
+    /// Rules with `channels` only apply when one of their patterns matches
+    /// `channel`; they never match other commands.
+    pub fn check_channel(&self, auth: &AuthContext, command: &str, channel: &str) -> bool {
+        self.evaluate(
+            auth.client_ip,
+            command,
+            Some(channel),
+            auth.authorization.as_deref(),
+            Utc::now(),
+        )
+    }
+
+    fn evaluate(
+        &self,
+        ip: IpAddr,
+        command: &str,
+        channel: Option<&str>,
+        auth_header: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> bool {
         if self.rules.is_empty() {
             return true; // No ACLs means everything is allowed (default)
         }
@@ -40,32 +209,20 @@ impl Acl {
                                 // "All commands being enabled by default"
 
         for rule in &self.rules {
-            let mut matches = true;
+            let mut matches = rule.matches_client(ip, auth_header, now);
 
-            // Check IP
-            if let Some(subnet) = &rule.ip_subnet {
-                if !subnet.contains(&ip) {
+            // Check channel patterns
+            if !rule.channels.is_empty() {
+                let channel_matches = channel.is_some_and(|channel| {
+                    rule.channels
+                        .iter()
+                        .any(|pattern| glob_match(pattern, channel.as_bytes()))
+                });
+                if !channel_matches {
                     matches = false;
                 }
             }
 
-            // Check Basic Auth
-            if let Some(required_auth) = &rule.basic_auth {
-                matches = false; // Default to no match if auth is required
-                if let Some(auth_val) = auth_header {
-                    if let Some(stripped) = auth_val.strip_prefix("Basic ") {
-                        use base64::{engine::general_purpose, Engine as _};
-                        if let Ok(decoded) = general_purpose::STANDARD.decode(stripped) {
-                            if let Ok(creds) = String::from_utf8(decoded) {
-                                if &creds == required_auth {
-                                    matches = true;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
             if matches {
                 // Check disabled first
                 for disabled_cmd in &rule.disabled {
@@ -89,4 +246,274 @@ impl Acl {
     pub fn check_auth(&self, auth: &AuthContext, command: &str) -> bool {
         self.check(auth.client_ip, command, auth.authorization.as_deref())
     }
+
+    /// The rule whose `redis_auth` commands from `auth` run as: the last
+    /// matching rule that has one. Rules with `channels` are skipped.
+    pub fn credentials_for(&self, auth: &AuthContext) -> Option<RedisCredentials> {
+        let now = Utc::now();
+        self.rules
+            .iter()
+            .rposition(|rule| {
+                rule.has_redis_auth
+                    && rule.channels.is_empty()
+                    && rule.matches_client(auth.client_ip, auth.authorization.as_deref(), now)
+            })
+            .map(RedisCredentials::for_rule)
+    }
+
+    /// The credentials of each rule in `rules` with `redis_auth`, as
+    /// [`Acl::credentials_for`] names them, for opening a pool per rule.
+    pub fn redis_credentials(
+        rules: &[AclConfig],
+    ) -> impl Iterator<Item = (RedisCredentials, &RedisAuthConfig)> {
+        rules.iter().enumerate().filter_map(|(index, rule)| {
+            Some((RedisCredentials::for_rule(index), rule.redis_auth.as_ref()?))
+        })
+    }
+
+    /// Whether some rule has `http_basic_auth`, so clients can authenticate.
+    pub fn has_basic_auth(&self) -> bool {
+        self.rules.iter().any(|rule| rule.basic_auth.is_some())
+    }
+
+    /// Whether some rule's `http_basic_auth` is exactly `user:password`.
+    pub fn knows_credentials(&self, credentials: &str) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.basic_auth
+                .as_deref()
+                .is_some_and(|required| credentials_match(credentials, required))
+        })
+    }
+}
+
+/// The `user:password` carried by an `Authorization: Basic` header. The
+/// scheme name is case-insensitive and surrounding whitespace is ignored, as
+/// RFC 7617 allows.
+pub fn basic_credentials(header: &str) -> Option<String> {
+    use base64::{engine::general_purpose, Engine as _};
+    let (scheme, encoded) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("Basic") {
+        return None;
+    }
+    let decoded = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+/// Compares credentials in time independent of where they first differ.
+fn credentials_match(given: &str, required: &str) -> bool {
+    given.len() == required.len()
+        && given
+            .bytes()
+            .zip(required.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl AclRule {
+    /// Whether the rule's `schedule` is active at `now` and the client's
+    /// address and `Authorization` header satisfy its `ip` and
+    /// `http_basic_auth`. A rule setting both needs both to match.
+    fn matches_client(&self, ip: IpAddr, auth_header: Option<&str>, now: DateTime<Utc>) -> bool {
+        if let Some(schedule) = &self.schedule {
+            if !schedule.is_active(now) {
+                return false;
+            }
+        }
+        if let Some(subnet) = &self.ip_subnet {
+            if !subnet.contains(&ip) {
+                return false;
+            }
+        }
+        let Some(required_auth) = &self.basic_auth else {
+            return true;
+        };
+        auth_header
+            .and_then(basic_credentials)
+            .is_some_and(|creds| credentials_match(&creds, required_auth))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedisAuthConfig;
+
+    fn rule(enabled: Option<&str>, disabled: Option<&str>, channel: Option<&str>) -> AclConfig {
+        let list = |name: Option<&str>| name.map(|name| vec![name.to_string()]);
+        AclConfig {
+            disabled: list(disabled),
+            enabled: list(enabled),
+            http_basic_auth: None,
+            ip: None,
+            channels: list(channel),
+            redis_auth: None,
+            schedule: None,
+        }
+    }
+
+    #[test]
+    fn channel_rules_only_apply_to_matching_subscriptions() {
+        let acl = Acl::new(Some(vec![
+            rule(None, Some("SUBSCRIBE"), None),
+            rule(Some("SUBSCRIBE"), None, Some("public.*")),
+        ]));
+        let auth = AuthContext {
+            client_ip: "127.0.0.1".parse().unwrap(),
+            authorization: None,
+        };
+
+        assert!(acl.check_channel(&auth, "SUBSCRIBE", "public.news"));
+        assert!(!acl.check_channel(&auth, "SUBSCRIBE", "private.news"));
+        // Without a channel, the channel rule never re-enables the command.
+        assert!(!acl.check_auth(&auth, "SUBSCRIBE"));
+        assert!(acl.check_auth(&auth, "GET"));
+    }
+
+    #[test]
+    fn known_credentials_match_basic_auth_rules() {
+        let mut with_auth = rule(Some("*"), None, None);
+        with_auth.http_basic_auth = Some("user:password".to_string());
+        let acl = Acl::new(Some(vec![rule(None, Some("*"), None), with_auth]));
+
+        assert!(acl.knows_credentials("user:password"));
+        assert!(!acl.knows_credentials("user:wrong"));
+    }
+
+    #[test]
+    fn basic_auth_rules_only_apply_to_matching_headers() {
+        let mut with_auth = rule(Some("DEBUG"), None, None);
+        with_auth.http_basic_auth = Some("user:password".to_string());
+        let acl = Acl::new(Some(vec![rule(None, Some("DEBUG"), None), with_auth]));
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let encoded =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, "user:password");
+
+        assert!(!acl.check(ip, "DEBUG", None));
+        assert!(acl.check(ip, "DEBUG", Some(&format!("Basic {encoded}"))));
+        assert!(acl.check(ip, "debug", Some(&format!("basic  {encoded} "))));
+        assert!(!acl.check(ip, "DEBUG", Some(&format!("Bearer {encoded}"))));
+        assert!(!acl.check(ip, "DEBUG", Some("Basic dXNlcjp3cm9uZw==")));
+        assert!(!acl.check(ip, "DEBUG", Some("Basic not-base64!")));
+        assert!(acl.check(ip, "GET", None));
+        assert_eq!(basic_credentials("BASIC dTpw").as_deref(), Some("u:p"));
+    }
+
+    #[test]
+    fn rules_with_ip_and_basic_auth_need_both() {
+        let mut internal_ops = rule(Some("FLUSHDB"), None, None);
+        internal_ops.ip = Some("10.0.0.0/8".to_string());
+        internal_ops.http_basic_auth = Some("ops:secret".to_string());
+        let acl = Acl::new(Some(vec![rule(None, Some("FLUSHDB"), None), internal_ops]));
+        let encoded =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, "ops:secret");
+        let header = format!("Basic {encoded}");
+
+        assert!(acl.check("10.1.2.3".parse().unwrap(), "FLUSHDB", Some(&header)));
+        assert!(!acl.check("10.1.2.3".parse().unwrap(), "FLUSHDB", None));
+        // The credentials alone used to satisfy the rule from any address.
+        assert!(!acl.check("203.0.113.7".parse().unwrap(), "FLUSHDB", Some(&header)));
+    }
+
+    #[test]
+    fn scheduled_rules_only_apply_in_their_window() {
+        let schedule = |days: Option<&[&str]>, hours: &str, timezone: &str| {
+            AclSchedule::parse(&AclScheduleConfig {
+                days: days.map(|days| days.iter().map(|day| day.to_string()).collect()),
+                hours: Some(hours.to_string()),
+                timezone: Some(timezone.to_string()),
+            })
+        };
+        let at = |text: &str| text.parse::<DateTime<Utc>>().unwrap();
+
+        // 2026-01-05 is a Monday; Paris is UTC+1 in winter.
+        let office = schedule(Some(&["mon", "Friday"]), "09:00-17:00", "Europe/Paris").unwrap();
+        assert!(office.is_active(at("2026-01-05T08:00:00Z")));
+        assert!(!office.is_active(at("2026-01-05T16:00:00Z")));
+        assert!(!office.is_active(at("2026-01-06T10:00:00Z")));
+        let night = schedule(None, "22:00-02:00", "UTC").unwrap();
+        assert!(night.is_active(at("2026-01-05T23:30:00Z")));
+        assert!(night.is_active(at("2026-01-06T01:59:00Z")));
+        assert!(!night.is_active(at("2026-01-06T02:00:00Z")));
+
+        assert!(schedule(Some(&["someday"]), "09:00-17:00", "UTC").is_err());
+        assert!(schedule(None, "9 to 5", "UTC").is_err());
+        assert!(schedule(None, "09:00-17:00", "Mars/Olympus").is_err());
+
+        let mut window = rule(Some("FLUSHDB"), None, None);
+        window.schedule = Some(AclScheduleConfig {
+            days: Some(vec!["sun".to_string()]),
+            hours: Some("02:00-04:00".to_string()),
+            timezone: None,
+        });
+        let acl = Acl::new(Some(vec![rule(None, Some("FLUSHDB"), None), window]));
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        assert!(acl.evaluate(ip, "FLUSHDB", None, None, at("2026-01-04T03:00:00Z")));
+        assert!(!acl.evaluate(ip, "FLUSHDB", None, None, at("2026-01-05T03:00:00Z")));
+    }
+
+    #[test]
+    fn credentials_come_from_the_last_matching_rule_with_redis_auth() {
+        let mut tenant = rule(Some("*"), None, None);
+        tenant.http_basic_auth = Some("tenant:secret".to_string());
+        tenant.redis_auth = Some(RedisAuthConfig::ACL(vec![
+            "tenant".to_string(),
+            "pw".to_string(),
+        ]));
+        let mut local = rule(None, None, None);
+        local.ip = Some("10.0.0.0/8".to_string());
+        local.redis_auth = Some(RedisAuthConfig::Legacy("pw".to_string()));
+        let rules = vec![local, rule(None, None, None), tenant];
+        let acl = Acl::new(Some(rules.clone()));
+        let auth = |ip: &str, authorization: Option<&str>| AuthContext {
+            client_ip: ip.parse().unwrap(),
+            authorization: authorization.map(str::to_string),
+        };
+        let tenant_header = format!(
+            "Basic {}",
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, "tenant:secret")
+        );
+
+        assert_eq!(acl.credentials_for(&auth("127.0.0.1", None)), None);
+        assert_eq!(
+            acl.credentials_for(&auth("10.1.2.3", None)),
+            Some(RedisCredentials::for_rule(0))
+        );
+        assert_eq!(
+            acl.credentials_for(&auth("10.1.2.3", Some(&tenant_header))),
+            Some(RedisCredentials::for_rule(2))
+        );
+        let pools: Vec<_> = Acl::redis_credentials(&rules)
+            .map(|(credentials, _)| credentials)
+            .collect();
+        assert_eq!(
+            pools,
+            [RedisCredentials::for_rule(0), RedisCredentials::for_rule(2)]
+        );
+    }
+
+    #[test]
+    fn cached_decisions_match_a_fresh_scan() {
+        let mut rules = vec![rule(None, Some("*"), None)];
+        rules.extend((0..CACHE_MIN_RULES).map(|idx| {
+            let mut admin = rule(Some(&format!("CMD{idx}")), None, None);
+            admin.http_basic_auth = Some("admin:secret".to_string());
+            admin
+        }));
+        rules.push(rule(Some("GET"), None, None));
+        let acl = Acl::new(Some(rules));
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let admin = format!(
+            "Basic {}",
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, "admin:secret")
+        );
+
+        for _ in 0..2 {
+            assert!(acl.check(ip, "get", None));
+            assert!(acl.check(ip, "GET", None));
+            assert!(!acl.check(ip, "CMD3", None));
+            assert!(acl.check(ip, "cmd3", Some(&admin)));
+            assert!(!acl.check(ip, "SET", Some(&admin)));
+        }
+        assert_eq!(acl.decisions.read().unwrap().len(), 4);
+    }
 }
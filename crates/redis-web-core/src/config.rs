@@ -1,18 +1,45 @@
 use config::{Config as ConfigLoader, ConfigError, File, FileFormat};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub const DEFAULT_HTTP_THREADS: usize = 4;
 pub const DEFAULT_POOL_SIZE_PER_THREAD: usize = 10;
+pub const DEFAULT_POOL_SHARDS: usize = 1;
+pub const DEFAULT_MULTIPLEXED_CONNECTIONS: usize = 1;
+pub const DEFAULT_PUBSUB_CONNECTIONS: usize = 1;
 pub const DEFAULT_HTTP_MAX_REQUEST_SIZE: usize = 128 * 1024 * 1024;
 pub const DEFAULT_VERBOSITY: usize = 4;
 pub const DEFAULT_DATABASE: u8 = 0;
+pub const DEFAULT_MAX_COMMAND_TIMEOUT_MS: u64 = 300_000;
+pub const DEFAULT_PUBSUB_REPLAY_HISTORY_SIZE: usize = 100;
+pub const DEFAULT_PUBSUB_REPLAY_RETENTION_MS: u64 = 30_000;
+pub const DEFAULT_METRICS_PATH: &str = "/_metrics";
+pub const DEFAULT_STATIC_PATH_PREFIX: &str = "/static";
+pub const DEFAULT_WEBSOCKET_CONNECTION_IDLE_SEC: u64 = 60;
+pub const DEFAULT_WEBSOCKET_PING_INTERVAL_SEC: u64 = 30;
+pub const DEFAULT_WEBSOCKET_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+pub const DEFAULT_WEBSOCKET_SEND_QUEUE_SIZE: usize = 100;
+pub const DEFAULT_WEBSOCKET_MAX_INFLIGHT_COMMANDS: usize = 1;
+pub const DEFAULT_SSE_KEEP_ALIVE_SEC: u64 = 15;
+pub const DEFAULT_SSE_SLOW_CLIENT_TIMEOUT_SEC: u64 = 30;
+pub const DEFAULT_MQTT_PORT: u16 = 1883;
+pub const DEFAULT_MQTT_MAX_PACKET_SIZE: usize = 256 * 1024;
 
+/// `remote = "Self"` keeps the derived code as inherent functions, so the
+/// `Serialize` impl below can write `redis_hosts` back into `redis_host`.
 #[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(remote = "Self")]
 pub struct Config {
     #[serde(default = "default_redis_host")]
     pub redis_host: String,
+    /// Failover hosts in priority order, populated when `redis_host` is given as a list.
+    ///
+    /// `redis_host` always holds the first entry. Entries may carry their own
+    /// port as `host:port`; otherwise `redis_port` applies. Serialized back as
+    /// the `redis_host` list.
+    #[serde(skip)]
+    pub redis_hosts: Vec<String>,
     #[serde(default = "default_redis_port")]
     pub redis_port: u16,
     /// Optional filesystem path to a Redis UNIX-domain socket.
@@ -20,6 +47,43 @@ pub struct Config {
     /// When set, Webdis will prefer connecting over the socket regardless of
     /// `redis_host` / `redis_port`. TLS (`ssl`) does not apply to UNIX sockets.
     pub redis_socket: Option<String>,
+    /// Optional Redis Cluster seed nodes.
+    ///
+    /// When set, commands are routed by hash slot across the cluster and
+    /// `redis_host` / `redis_port` / `redis_socket` are ignored for command traffic.
+    pub redis_cluster: Option<RedisClusterConfig>,
+    /// Seconds to keep retrying when Redis is unreachable at startup before
+    /// giving up. Unset or `0` starts without checking.
+    pub wait_for_redis_sec: Option<u64>,
+    /// Optional named Redis backends selected by a URL prefix (`/<name>/GET/key`).
+    ///
+    /// Requests without a backend prefix keep using `redis_host` / `redis_port`.
+    pub backends: Option<BTreeMap<String, BackendConfig>>,
+    /// Periodic `PING` checks of `backends`; failing backends are skipped by
+    /// `routes` until they recover.
+    pub backend_health: Option<BackendHealthConfig>,
+    /// Optional rules sending matching commands to a named backend.
+    ///
+    /// Rules are checked in order and only apply to requests without an
+    /// explicit backend prefix. The first match wins.
+    pub routes: Option<Vec<RouteConfig>>,
+    /// Copies a share of read commands to a named backend and compares the
+    /// replies, without affecting the responses.
+    pub shadow: Option<ShadowConfig>,
+    /// Backends in `backends` that unrouted commands are spread over by
+    /// consistent hashing of their key.
+    pub shards: Option<Vec<String>>,
+    /// Output formats for requests whose URL has no extension, chosen by the
+    /// first matching rule. Requests matching none reply in JSON.
+    pub output_formats: Option<Vec<OutputFormatRuleConfig>>,
+    /// File names for raw and text replies, chosen by the first matching
+    /// rule. Matching replies are sent as attachments.
+    pub downloads: Option<Vec<DownloadRuleConfig>>,
+    /// How Nil replies are rendered in JSON bodies.
+    #[serde(default)]
+    pub json_nil: JsonNil,
+    /// Shape of JSON error bodies; unset keeps `{"error": "<message>"}`.
+    pub error_format: Option<ErrorFormatConfig>,
     #[serde(default = "default_http_host")]
     pub http_host: String,
     #[serde(default = "default_http_port")]
@@ -28,6 +92,9 @@ pub struct Config {
     pub transport_mode: TransportMode,
     pub http_threads: Option<usize>,
     pub runtime_worker_threads: Option<usize>,
+    /// Tokio scheduler: the default work-stealing pool or a single thread.
+    #[serde(default)]
+    pub runtime_flavor: RuntimeFlavor,
     #[serde(default, rename = "threads", skip_serializing, alias = "threads")]
     legacy_http_threads: Option<usize>,
     #[serde(default)]
@@ -35,17 +102,88 @@ pub struct Config {
     pub pool_size_per_thread: Option<usize>,
     #[serde(default, rename = "pool_size", skip_serializing, alias = "pool_size")]
     legacy_pool_size_per_thread: Option<usize>,
+    /// Sub-pools the command pool of each database is split into, so worker
+    /// threads check out connections from their own shard. `pool` mode only.
+    pub pool_shards: Option<usize>,
+    /// Milliseconds a command waits for a pooled connection before failing
+    /// as pool exhausted. Unset waits without limit. `pool` mode only.
+    pub pool_wait_timeout_ms: Option<u64>,
+    /// Commands that may wait for a pooled connection at once; more fail at
+    /// once as pool exhausted. Unset allows any number. `pool` mode only.
+    pub pool_max_waiting: Option<usize>,
+    /// How command traffic obtains Redis connections: an exclusive checkout
+    /// pool or a few shared multiplexed connections.
+    #[serde(default)]
+    pub redis_connection_mode: RedisConnectionMode,
+    /// Number of shared connections per database in `multiplexed` mode.
+    pub multiplexed_connections: Option<usize>,
     #[serde(default)]
     pub websockets: bool,
+    /// Seconds a WebSocket may stay idle before its dedicated Redis connection
+    /// goes back to the pool. `0` keeps it for the socket's lifetime.
+    pub websocket_connection_idle_sec: Option<u64>,
+    /// Seconds between protocol pings on WebSocket connections. A socket that
+    /// has not answered by the next ping is closed. `0` disables pings.
+    pub websocket_ping_interval_sec: Option<u64>,
+    /// Seconds without a data frame in either direction before a WebSocket is
+    /// closed. Unset or `0` never closes idle sockets.
+    pub websocket_idle_timeout_sec: Option<u64>,
+    /// Largest WebSocket message in bytes, after reassembling fragments.
+    /// Falls back to `http_max_request_size`.
+    pub websocket_max_message_size: Option<usize>,
+    /// Largest single WebSocket frame in bytes.
+    pub websocket_max_frame_size: Option<usize>,
+    /// Frames a WebSocket may have waiting to be written. What happens when the
+    /// queue is full follows `sse.slow_client_policy`.
+    pub websocket_send_queue_size: Option<usize>,
+    /// Id-tagged commands a `/.json` socket may run at once. `1` runs every
+    /// command in order.
+    pub websocket_max_inflight_commands: Option<usize>,
+    /// Requires WebSocket clients to authenticate, at the upgrade or with
+    /// `AUTH`, before their other commands are accepted.
+    #[serde(default)]
+    pub websocket_require_auth: bool,
     pub ssl: Option<SslConfig>,
     pub acl: Option<Vec<AclConfig>>,
+    /// Prometheus metrics endpoint.
+    pub metrics: Option<MetricsConfig>,
+    /// Operator endpoints under `/_admin`.
+    pub admin: Option<AdminConfig>,
+    /// OpenAPI document at `/_openapi.json`.
+    pub openapi: Option<OpenApiConfig>,
     pub redis_auth: Option<RedisAuthConfig>,
+    /// Wire protocol negotiated on pooled command connections.
+    ///
+    /// `resp3` sends `HELLO 3` and surfaces maps, sets, doubles, and booleans in
+    /// replies. Pub/Sub and hiredis-compat sessions always stay on RESP2.
+    #[serde(default)]
+    pub redis_protocol: RedisProtocol,
+    /// Instance identifier embedded in Redis `CLIENT SETNAME` values
+    /// (`redis-web:<instance>:<purpose>`). Defaults to the host name.
+    pub instance_name: Option<String>,
     /// Optional Redis TCP keep-alive tuning settings for parity with the legacy Webdis.
     ///
     /// When `hiredis.keep_alive_sec` is set, Webdis configures TCP keep-alive on Redis
     /// **TCP/TLS** connections it opens. This does not apply to UNIX-domain socket
     /// connections (`redis_socket`).
     pub hiredis: Option<HiRedisConfig>,
+    /// Socket options for Redis TCP/TLS connections opened by the pool and Pub/Sub.
+    pub redis_tcp: Option<RedisTcpConfig>,
+    /// Number of Redis connections carrying channel and pattern subscriptions.
+    ///
+    /// Topics are spread across them by hash, so busy channels do not all
+    /// queue behind one socket.
+    pub pubsub_connections: Option<usize>,
+    /// SSE event ids and `Last-Event-ID` replay for Pub/Sub streams.
+    pub pubsub_replay: Option<PubSubReplayConfig>,
+    /// Per-channel rules filtering or rewriting Pub/Sub messages before they
+    /// reach subscribers. The first rule whose `channel` matches applies.
+    pub pubsub_filters: Option<Vec<PubSubFilterConfig>>,
+    /// Limits for HTTP Pub/Sub streams (SSE, chunked JSON, and JSONP).
+    pub sse: Option<SseConfig>,
+    /// MQTT listener bridging MQTT topics to Redis channels. Requires a build
+    /// with the `mqtt` feature.
+    pub mqtt: Option<MqttConfig>,
     /// Optional hiredis-compat runtime settings used by the `/__compat/*` bridge when
     /// explicitly enabled.
     #[serde(default)]
@@ -53,8 +191,96 @@ pub struct Config {
     #[serde(default = "default_grpc")]
     pub grpc: GrpcConfig,
     pub http_max_request_size: Option<usize>,
+    /// Largest value, in bytes, a write command such as `SET` or `HSET` may
+    /// carry; larger ones are refused before reaching Redis.
+    pub max_value_size: Option<usize>,
+    /// Per-key overrides of `max_value_size`; the first matching rule wins.
+    pub value_size_limits: Option<Vec<ValueSizeLimitConfig>>,
+    /// Streams `PUT /SET/<key>` bodies larger than this many bytes into Redis
+    /// as a `SET` followed by `APPEND`s instead of buffering them. Unset
+    /// buffers every body.
+    pub http_put_stream_chunk_size: Option<usize>,
+    /// Streams `GET` replies for values larger than this many bytes, read
+    /// from Redis with `GETRANGE` in chunks of this size. Unset reads every
+    /// value whole.
+    pub http_get_stream_chunk_size: Option<usize>,
+    /// Streams JSON `LRANGE` replies covering more than this many elements,
+    /// read from Redis with one `LRANGE` per page of this size. Unset reads
+    /// every range whole.
+    pub http_list_stream_page_size: Option<usize>,
+    /// Timeout (milliseconds) for Redis commands that do not ask for their own
+    /// with `?timeout=`. Unset or `0` leaves them without one, so blocking
+    /// commands such as `BLPOP` wait as long as they ask to.
+    pub command_timeout_ms: Option<u64>,
+    /// Upper bound (milliseconds) for per-request `?timeout=` overrides. `0` disables it.
+    pub max_command_timeout_ms: Option<u64>,
+    /// Caps the commands running at once on each backend, the top-level Redis
+    /// included; commands beyond the cap and its queue are refused.
+    pub command_concurrency: Option<CommandConcurrencyConfig>,
+    /// Daily and monthly usage caps per client identity, counted in Redis.
+    pub quotas: Option<QuotaConfig>,
+    /// Audit records of writes and denied requests, appended to a Redis
+    /// Stream.
+    pub audit: Option<AuditConfig>,
+    /// WebAssembly modules run on every client command before its routing
+    /// and ACL checks, and on its reply. Needs the `wasm-hooks` feature.
+    pub hooks: Option<Vec<HookConfig>>,
     pub default_root: Option<String>,
+    /// Commands served on `/` per HTTP method.
+    pub root_commands: Option<RootCommandsConfig>,
+    /// Directory of static files served under a URL prefix.
+    pub static_dir: Option<StaticDirConfig>,
+    /// Icon file served at `/favicon.ico`. Unset serves the built-in icon.
+    pub favicon: Option<String>,
+    /// Serves a `/robots.txt` that disallows all crawling.
+    #[serde(default)]
+    pub robots_txt: bool,
+    /// Adds a `Server-Timing` header with pool, Redis and total time to
+    /// every HTTP response.
+    #[serde(default)]
+    pub server_timing: bool,
+    /// Reads of a single key also fetch its `PTTL`, in the same pipeline, and
+    /// return it as an `X-TTL-Ms` header.
+    #[serde(default)]
+    pub ttl_header: bool,
+    /// Writes on command URLs also store their time under `<key>:__mtime__`,
+    /// which single-key reads return as `Last-Modified` and compare against
+    /// `If-Modified-Since`.
+    #[serde(default)]
+    pub last_modified: bool,
     pub verbosity: Option<usize>,
+    /// Where the loaded values came from.
+    #[serde(skip)]
+    pub sources: ConfigSources,
+}
+
+/// Origin of the values in a loaded [`Config`].
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// Top-level keys set in the config document.
+    pub document_keys: BTreeSet<String>,
+    /// Paths of values read from `$VARNAME` placeholders (such as
+    /// `admin.http_basic_auth`), mapped to the variable name.
+    pub env_vars: BTreeMap<String, String>,
+}
+
+impl ConfigSources {
+    /// Where the top-level `key` came from: `env` when any value under it was
+    /// read from an environment variable, `file` when the document set it, and
+    /// `default` otherwise.
+    pub fn source_of(&self, key: &str) -> &'static str {
+        let from_env = self.env_vars.keys().any(|path| {
+            path.strip_prefix(key)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+        });
+        if from_env {
+            "env"
+        } else if self.document_keys.contains(key) {
+            "file"
+        } else {
+            "default"
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
@@ -65,6 +291,45 @@ pub enum TransportMode {
     Grpc,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeFlavor {
+    #[default]
+    MultiThread,
+    /// Everything runs on the thread that started the server, and an unset
+    /// `http_threads` sizes the pools for one thread.
+    CurrentThread,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonNil {
+    /// `{"GET":null}`.
+    #[default]
+    Null,
+    /// `{"GET":false}`, as the original Webdis renders it.
+    False,
+    /// `{}`: the reply's key is left out, as are map entries holding Nil.
+    /// Nil array elements stay `null` so positions are kept.
+    Omit,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisConnectionMode {
+    #[default]
+    Pool,
+    Multiplexed,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisProtocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GrpcConfig {
     #[serde(default = "default_grpc_host")]
@@ -108,6 +373,26 @@ pub struct AclConfig {
     pub enabled: Option<Vec<String>>,
     pub http_basic_auth: Option<String>,
     pub ip: Option<String>,
+    /// Glob patterns limiting the rule to Pub/Sub subscriptions on matching
+    /// channels (or patterns, for `PSUBSCRIBE`).
+    pub channels: Option<Vec<String>>,
+    /// Redis credentials for commands of clients matching this rule, so each
+    /// HTTP tenant runs as its own Redis ACL user.
+    pub redis_auth: Option<RedisAuthConfig>,
+    /// Times at which the rule applies; outside them it matches no request.
+    pub schedule: Option<AclScheduleConfig>,
+}
+
+/// Days and hours during which an `acl` rule applies.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AclScheduleConfig {
+    /// Weekdays such as `"mon"` or `"saturday"`; unset means every day.
+    pub days: Option<Vec<String>>,
+    /// Time-of-day window as `"HH:MM-HH:MM"`, end excluded; a window ending
+    /// before it starts runs past midnight. Unset means all day.
+    pub hours: Option<String>,
+    /// IANA time zone the days and hours are read in. Defaults to UTC.
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -117,6 +402,207 @@ pub enum RedisAuthConfig {
     ACL(Vec<String>),
 }
 
+/// Connection settings for one named backend in `backends`.
+///
+/// Unset keys fall back to the same defaults as the top-level Redis settings;
+/// TLS, keep-alive, and pool sizing are shared with the top-level config.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BackendConfig {
+    #[serde(default = "default_redis_host")]
+    pub redis_host: String,
+    #[serde(default = "default_redis_port")]
+    pub redis_port: u16,
+    pub redis_socket: Option<String>,
+    #[serde(default)]
+    pub database: u8,
+    pub redis_auth: Option<RedisAuthConfig>,
+    /// ACL rules applied to requests for this backend, in addition to the top-level `acl`.
+    pub acl: Option<Vec<AclConfig>>,
+}
+
+/// Health checks of the named `backends`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct BackendHealthConfig {
+    /// Seconds between checks of each backend. Defaults to `5`.
+    pub interval_sec: Option<u64>,
+    /// Milliseconds a check may take before it counts as failed. Defaults to
+    /// `1000`.
+    pub timeout_ms: Option<u64>,
+    /// Failed checks in a row that eject a backend. Defaults to `3`.
+    pub unhealthy_threshold: Option<u32>,
+    /// Successful checks in a row that readmit an ejected backend. Defaults
+    /// to `2`.
+    pub healthy_threshold: Option<u32>,
+}
+
+/// Limit on the commands running at once on one backend.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CommandConcurrencyConfig {
+    /// Commands sent to a backend and not yet answered.
+    pub max_in_flight: usize,
+    /// Commands that may wait for a free slot; more are refused at once.
+    /// Defaults to `0`.
+    pub max_queued: Option<usize>,
+}
+
+/// Usage quotas on client commands from every entry point. The limits apply to
+/// every identity without an entry in `identities`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct QuotaConfig {
+    pub commands_per_day: Option<u64>,
+    pub commands_per_month: Option<u64>,
+    pub bytes_per_day: Option<u64>,
+    pub bytes_per_month: Option<u64>,
+    /// Prefix of the Redis keys holding the counters. Defaults to
+    /// `redis-web:quota:`.
+    pub key_prefix: Option<String>,
+    /// Limits per identity, a Basic auth user name or a client IP, replacing
+    /// the defaults above.
+    pub identities: Option<BTreeMap<String, QuotaLimits>>,
+}
+
+/// Audit trail of client commands, from every entry point, in a capped Redis
+/// Stream on the top-level Redis.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AuditConfig {
+    /// Stream key. Defaults to `redis-web:audit`.
+    pub stream: Option<String>,
+    /// Approximate number of records the stream keeps. Defaults to `100000`.
+    pub max_len: Option<u64>,
+    /// Records commands that write. Defaults to `true`.
+    pub writes: Option<bool>,
+    /// Records commands the `acl` rules refuse. Defaults to `true`.
+    pub denied: Option<bool>,
+}
+
+/// One entry in `hooks`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HookConfig {
+    /// Path of the module, in binary (`.wasm`) or text (`.wat`) format.
+    pub path: String,
+    /// Fuel, roughly the WebAssembly instructions, one call may use before
+    /// it is stopped. Calls block a worker thread while they run, so this is
+    /// capped at `1000000000`. Defaults to `10000000`.
+    pub fuel: Option<u64>,
+}
+
+/// Usage caps of one identity; unset caps are unlimited. Days and months
+/// follow UTC.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct QuotaLimits {
+    pub commands_per_day: Option<u64>,
+    pub commands_per_month: Option<u64>,
+    /// Reply bytes.
+    pub bytes_per_day: Option<u64>,
+    pub bytes_per_month: Option<u64>,
+}
+
+/// One entry in `value_size_limits`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ValueSizeLimitConfig {
+    /// Glob pattern (`*`, `?`, `[abc]`) matched against the written key.
+    pub key_pattern: String,
+    /// Largest value, in bytes, written to matching keys.
+    pub max_bytes: usize,
+}
+
+/// Shadow traffic sent to a named backend.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ShadowConfig {
+    /// Name of the backend in `backends` receiving the copies.
+    pub backend: String,
+    /// Share of eligible requests copied, from `0` to `100`. Defaults to
+    /// `100`.
+    pub percent: Option<f64>,
+    /// Command names (case-insensitive) to copy. Defaults to common read
+    /// commands.
+    pub commands: Option<Vec<String>>,
+}
+
+/// One entry in `routes`.
+///
+/// A rule matches when every set condition holds; a rule with neither
+/// `commands` nor `key_pattern` matches everything.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RouteConfig {
+    /// Command names (case-insensitive) this rule applies to.
+    pub commands: Option<Vec<String>>,
+    /// Glob pattern (`*`, `?`, `[abc]`) matched against the first command argument.
+    pub key_pattern: Option<String>,
+    /// Name of the backend in `backends` that serves matching requests.
+    pub backend: String,
+}
+
+/// One entry in `output_formats`, matched like a `routes` entry.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OutputFormatRuleConfig {
+    /// Command names (case-insensitive) this rule applies to.
+    pub commands: Option<Vec<String>>,
+    /// Glob pattern (`*`, `?`, `[abc]`) matched against the first command argument.
+    pub key_pattern: Option<String>,
+    /// Extension applied to matching requests, such as `raw`, `txt` or `png`.
+    pub format: String,
+}
+
+/// One entry in `downloads`, matched like a `routes` entry.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DownloadRuleConfig {
+    /// Command names (case-insensitive) this rule applies to.
+    pub commands: Option<Vec<String>>,
+    /// Glob pattern (`*`, `?`, `[abc]`) matched against the first command argument.
+    pub key_pattern: Option<String>,
+    /// File name offered to the browser; `{key}` stands for the first
+    /// command argument.
+    pub filename: String,
+}
+
+/// Shape of JSON error bodies.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ErrorFormatConfig {
+    /// Field holding the error message.
+    #[serde(default = "default_error_message_field")]
+    pub message_field: String,
+    /// Field holding a machine-readable code such as `forbidden`; unset
+    /// leaves the code out.
+    pub code_field: Option<String>,
+    /// Request header echoed into error bodies, such as `X-Request-Id`.
+    pub request_id_header: Option<String>,
+    /// Field holding the echoed request id.
+    #[serde(default = "default_request_id_field")]
+    pub request_id_field: String,
+    /// Key the fields are nested under; unset puts them at the top level.
+    pub envelope: Option<String>,
+}
+
+impl Default for ErrorFormatConfig {
+    fn default() -> Self {
+        Self {
+            message_field: default_error_message_field(),
+            code_field: None,
+            request_id_header: None,
+            request_id_field: default_request_id_field(),
+            envelope: None,
+        }
+    }
+}
+
+fn default_error_message_field() -> String {
+    "error".to_string()
+}
+
+fn default_request_id_field() -> String {
+    "request_id".to_string()
+}
+
+/// Redis Cluster connection settings.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RedisClusterConfig {
+    /// Seed nodes as `host:port` or full `redis://` / `rediss://` URLs.
+    ///
+    /// Only one reachable seed is needed; the full topology is discovered from it.
+    pub nodes: Vec<String>,
+}
+
 /// Legacy Hiredis options kept for compatibility with the original Webdis.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct HiRedisConfig {
@@ -129,6 +615,184 @@ pub struct HiRedisConfig {
     pub keep_alive_sec: Option<u64>,
 }
 
+/// Socket options for Redis TCP/TLS connections.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RedisTcpConfig {
+    /// Set `TCP_NODELAY` to disable Nagle's algorithm. Defaults to `true`, like hiredis.
+    pub nodelay: Option<bool>,
+    /// Maximum time (milliseconds) to establish a connection, including the handshake.
+    pub connect_timeout_ms: Option<u64>,
+    /// Maximum time (milliseconds) a pooled connection waits for a reply.
+    pub read_timeout_ms: Option<u64>,
+    /// Maximum time (milliseconds) written data may stay unacknowledged
+    /// (`TCP_USER_TIMEOUT`, Linux only).
+    pub write_timeout_ms: Option<u64>,
+}
+
+/// Replay settings for SSE Pub/Sub streams.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PubSubReplayConfig {
+    /// Assign SSE event ids and replay missed messages to clients reconnecting
+    /// with `Last-Event-ID`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Messages kept per channel (or pattern) for replay.
+    pub history_size: Option<usize>,
+    /// How long (milliseconds) a channel stays subscribed after its last client
+    /// disconnects, so messages published during a reconnect are kept.
+    pub retention_ms: Option<u64>,
+    /// Key prefix of capped Redis Streams mirroring each channel. When unset,
+    /// history is kept in memory.
+    pub redis_stream_prefix: Option<String>,
+}
+
+/// One entry in `pubsub_filters`.
+///
+/// A message is forwarded when it satisfies every set condition, then
+/// rewritten with `template` when one is given.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PubSubFilterConfig {
+    /// Glob pattern (`*`, `?`, `[abc]`) matched against the message channel.
+    pub channel: String,
+    /// Regular expression the payload must match.
+    pub regex: Option<String>,
+    /// JSONPath (`$.a.b[0]`) that must resolve to a non-null value in the
+    /// payload, which must then be JSON.
+    pub json_path: Option<String>,
+    /// Value the `json_path` result must equal. Strings compare by content,
+    /// other values by their JSON text.
+    pub equals: Option<String>,
+    /// Replacement payload. `{channel}`, `{payload}`, and `{$.path}` are
+    /// substituted.
+    pub template: Option<String>,
+}
+
+/// Settings for HTTP Pub/Sub streams on `/SUBSCRIBE`, `/PSUBSCRIBE`, and `/SSUBSCRIBE`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct SseConfig {
+    /// Maximum open streams across all clients; further requests get `503`.
+    pub max_connections: Option<usize>,
+    /// Maximum open streams per client IP; further requests get `429`.
+    pub max_connections_per_ip: Option<usize>,
+    /// Seconds between SSE keep-alive comments. `0` disables them.
+    pub keep_alive_sec: Option<u64>,
+    /// Text of the SSE keep-alive comment (`: <text>`). Empty by default.
+    pub keep_alive_text: Option<String>,
+    /// What to do with SSE and WebSocket clients that stop keeping up.
+    pub slow_client_policy: Option<SlowClientPolicy>,
+    /// Seconds a client's send queue may stay full before the `disconnect`
+    /// policy closes it. Defaults to `30`.
+    pub slow_client_timeout_sec: Option<u64>,
+}
+
+/// Handling of streaming clients that fall behind (`sse.slow_client_policy`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowClientPolicy {
+    /// Drop the oldest undelivered messages and send a `lagged` notice.
+    #[default]
+    DropOldest,
+    /// Close the stream once its queue has stayed full for the timeout.
+    Disconnect,
+}
+
+/// MQTT listener settings.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MqttConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Listen address. Defaults to `http_host`.
+    pub host: Option<String>,
+    /// Listen port. Defaults to `1883`.
+    pub port: Option<u16>,
+    /// Prepended to MQTT topics to form Redis channel names, and stripped from
+    /// channel names delivered to MQTT subscribers.
+    pub channel_prefix: Option<String>,
+    /// Largest MQTT packet accepted, in bytes.
+    pub max_packet_size: Option<usize>,
+}
+
+/// Prometheus metrics endpoint settings.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct MetricsConfig {
+    /// Serve metrics in the Prometheus text format.
+    #[serde(default)]
+    pub enabled: bool,
+    /// URL path of the endpoint. Defaults to `/_metrics`.
+    pub path: Option<String>,
+    /// Milliseconds between `PING`s of the background Redis latency probe.
+    /// Unset or `0` disables the probe.
+    pub latency_probe_interval_ms: Option<u64>,
+}
+
+/// Operator endpoints under `/_admin`.
+///
+/// Access is granted to clients matching both `ip` and `http_basic_auth`
+/// when set. `enabled` needs at least one of them; startup fails otherwise
+/// rather than opening the endpoints to every client.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AdminConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Credentials (`user:password`) required through HTTP Basic auth.
+    pub http_basic_auth: Option<String>,
+    /// Client subnet (CIDR) allowed to reach the endpoints.
+    pub ip: Option<String>,
+    /// Serves the web console at `/_console`. It talks to `/.json`, so it
+    /// also needs `websockets`.
+    #[serde(default)]
+    pub console: bool,
+}
+
+/// OpenAPI description of the HTTP endpoints.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct OpenApiConfig {
+    /// Serve the document at `/_openapi.json`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Also serve a Swagger UI page for it at `/_openapi`.
+    #[serde(default)]
+    pub swagger_ui: bool,
+}
+
+/// Commands run for requests on `/`, per HTTP method. A method without a
+/// command is not served on `/`. Paths may reference `{ip}` and
+/// `{header:<name>}`.
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct RootCommandsConfig {
+    /// Command path run for `GET /`, e.g. `PING`. Falls back to
+    /// `default_root`.
+    pub get: Option<String>,
+    /// Command path run for `POST /`; the body is appended as its last
+    /// argument.
+    pub post: Option<String>,
+    /// Command path run for `PUT /`; the body is appended as its last
+    /// argument.
+    pub put: Option<String>,
+    /// Runs the body of `POST /` as the command path (`SET/key/value`), as
+    /// Webdis does. Takes precedence over `post`.
+    #[serde(default)]
+    pub post_body_command: bool,
+}
+
+/// Static files served next to the Redis API.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StaticDirConfig {
+    /// Directory the files are read from.
+    pub dir: String,
+    /// URL prefix the directory is mounted at.
+    #[serde(default = "default_static_path_prefix")]
+    pub path_prefix: String,
+    /// `Cache-Control` max-age in seconds. `0` makes clients revalidate
+    /// every time.
+    #[serde(default)]
+    pub max_age_sec: u64,
+}
+
+fn default_static_path_prefix() -> String {
+    DEFAULT_STATIC_PATH_PREFIX.to_string()
+}
+
 /// Configuration for hiredis-compatible session endpoints.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct CompatHiRedisConfig {
@@ -186,7 +850,13 @@ impl Config {
     }
 
     fn from_json_value(mut json: Value) -> Result<Self, ConfigError> {
-        expand_env_vars_in_json(&mut json, JsonPath::root())?;
+        let document_keys = json
+            .as_object()
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default();
+        let mut env_vars = BTreeMap::new();
+        expand_env_vars_in_json(&mut json, JsonPath::root(), &mut env_vars)?;
+        let redis_hosts = take_redis_host_list(&mut json)?;
 
         let expanded = serde_json::to_string(&json)
             .map_err(|e| ConfigError::Message(format!("failed to serialize config: {e}")))?;
@@ -195,10 +865,24 @@ impl Config {
             .build()?;
 
         let mut config: Self = loader.try_deserialize()?;
+        config.redis_hosts = redis_hosts;
         config.apply_legacy_aliases();
+        config.sources = ConfigSources {
+            document_keys,
+            env_vars,
+        };
         Ok(config)
     }
 
+    /// Returns the Redis hosts to try in failover order.
+    pub fn redis_host_candidates(&self) -> Vec<&str> {
+        if self.redis_hosts.is_empty() {
+            vec![self.redis_host.as_str()]
+        } else {
+            self.redis_hosts.iter().map(String::as_str).collect()
+        }
+    }
+
     pub fn get_redis_url(&self) -> String {
         let scheme = if self.ssl.as_ref().map(|s| s.enabled).unwrap_or(false) {
             "rediss"
@@ -238,6 +922,20 @@ impl Config {
         }
     }
 
+    /// The loaded config as JSON with secrets masked: every `redis_auth` and
+    /// `http_basic_auth` value reads `[redacted]`. Unset keys are left out.
+    pub fn redacted_document(&self) -> Value {
+        let mut value = serde_json::to_value(self).expect("config is serializable");
+        redact_secrets(&mut value);
+        match value {
+            Value::Object(mut map) => {
+                map.retain(|_, v| !v.is_null());
+                Value::Object(order_config_map(map, DEFAULT_CONFIG_KEY_ORDER))
+            }
+            other => other,
+        }
+    }
+
     pub fn starter_document(schema_ref: &str) -> Value {
         let mut map = Map::new();
         map.insert(
@@ -258,6 +956,15 @@ impl Config {
             DEFAULT_CONFIG_KEY_ORDER,
         ))
     }
+    /// HTTP concurrency used to size the Redis pools: `http_threads`, or its
+    /// default for the runtime flavor when unset.
+    pub fn effective_http_threads(&self) -> usize {
+        self.http_threads.unwrap_or(match self.runtime_flavor {
+            RuntimeFlavor::MultiThread => DEFAULT_HTTP_THREADS,
+            RuntimeFlavor::CurrentThread => 1,
+        })
+    }
+
     fn apply_legacy_aliases(&mut self) {
         if self.http_threads.is_none() && self.legacy_http_threads.is_some() {
             self.http_threads = self.legacy_http_threads.take();
@@ -273,31 +980,105 @@ impl Config {
     }
 }
 
+impl Serialize for Config {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.redis_hosts.len() < 2 {
+            return Config::serialize(self, serializer);
+        }
+        let mut value = Config::serialize(self, serde_json::value::Serializer)
+            .map_err(serde::ser::Error::custom)?;
+        value["redis_host"] = Value::from(self.redis_hosts.clone());
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Config {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Config::deserialize(deserializer)
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             redis_host: default_redis_host(),
+            redis_hosts: Vec::new(),
             redis_port: default_redis_port(),
             redis_socket: None,
+            redis_cluster: None,
+            wait_for_redis_sec: None,
+            backends: None,
+            backend_health: None,
+            routes: None,
+            shadow: None,
+            shards: None,
+            output_formats: None,
+            downloads: None,
+            json_nil: JsonNil::default(),
+            error_format: None,
             http_host: default_http_host(),
             http_port: default_http_port(),
             transport_mode: TransportMode::default(),
             http_threads: Some(DEFAULT_HTTP_THREADS),
             runtime_worker_threads: None,
+            runtime_flavor: RuntimeFlavor::default(),
             legacy_http_threads: None,
             database: DEFAULT_DATABASE,
             pool_size_per_thread: Some(DEFAULT_POOL_SIZE_PER_THREAD),
             legacy_pool_size_per_thread: None,
+            pool_shards: None,
+            pool_wait_timeout_ms: None,
+            pool_max_waiting: None,
+            redis_connection_mode: RedisConnectionMode::default(),
+            multiplexed_connections: None,
             websockets: false,
+            websocket_connection_idle_sec: None,
+            websocket_ping_interval_sec: None,
+            websocket_idle_timeout_sec: None,
+            websocket_max_message_size: None,
+            websocket_max_frame_size: None,
+            websocket_send_queue_size: None,
+            websocket_max_inflight_commands: None,
+            websocket_require_auth: false,
             ssl: None,
             acl: None,
+            metrics: None,
+            admin: None,
+            openapi: None,
             redis_auth: None,
+            redis_protocol: RedisProtocol::default(),
+            instance_name: None,
             hiredis: None,
+            redis_tcp: None,
+            pubsub_connections: None,
+            pubsub_replay: None,
+            pubsub_filters: None,
+            sse: None,
+            mqtt: None,
             compat_hiredis: None,
             grpc: default_grpc(),
             http_max_request_size: Some(DEFAULT_HTTP_MAX_REQUEST_SIZE),
+            max_value_size: None,
+            value_size_limits: None,
+            http_put_stream_chunk_size: None,
+            http_get_stream_chunk_size: None,
+            http_list_stream_page_size: None,
+            command_timeout_ms: None,
+            max_command_timeout_ms: Some(DEFAULT_MAX_COMMAND_TIMEOUT_MS),
+            command_concurrency: None,
+            quotas: None,
+            audit: None,
+            hooks: None,
             default_root: None,
+            root_commands: None,
+            static_dir: None,
+            favicon: None,
+            robots_txt: false,
+            server_timing: false,
+            ttl_header: false,
+            last_modified: false,
             verbosity: Some(DEFAULT_VERBOSITY),
+            sources: ConfigSources::default(),
         }
     }
 }
@@ -341,8 +1122,27 @@ const DEFAULT_CONFIG_KEY_ORDER: &[&str] = &[
     "redis_host",
     "redis_port",
     "redis_socket",
+    "redis_cluster",
+    "wait_for_redis_sec",
+    "backends",
+    "backend_health",
+    "routes",
+    "shadow",
+    "shards",
+    "output_formats",
+    "downloads",
+    "json_nil",
+    "error_format",
     "redis_auth",
+    "redis_protocol",
+    "instance_name",
     "hiredis",
+    "redis_tcp",
+    "pubsub_connections",
+    "pubsub_replay",
+    "pubsub_filters",
+    "sse",
+    "mqtt",
     "compat_hiredis",
     "transport_mode",
     "grpc",
@@ -350,14 +1150,49 @@ const DEFAULT_CONFIG_KEY_ORDER: &[&str] = &[
     "http_port",
     "http_threads",
     "runtime_worker_threads",
+    "runtime_flavor",
     "pool_size_per_thread",
+    "pool_shards",
+    "pool_wait_timeout_ms",
+    "pool_max_waiting",
+    "redis_connection_mode",
+    "multiplexed_connections",
     "database",
     "websockets",
+    "websocket_connection_idle_sec",
+    "websocket_ping_interval_sec",
+    "websocket_idle_timeout_sec",
+    "websocket_max_message_size",
+    "websocket_max_frame_size",
+    "websocket_send_queue_size",
+    "websocket_max_inflight_commands",
+    "websocket_require_auth",
     "default_root",
+    "root_commands",
+    "static_dir",
+    "favicon",
+    "robots_txt",
+    "server_timing",
+    "ttl_header",
+    "last_modified",
     "http_max_request_size",
+    "max_value_size",
+    "value_size_limits",
+    "http_put_stream_chunk_size",
+    "http_get_stream_chunk_size",
+    "http_list_stream_page_size",
+    "command_timeout_ms",
+    "max_command_timeout_ms",
+    "command_concurrency",
+    "quotas",
+    "audit",
+    "hooks",
     "verbosity",
     "ssl",
     "acl",
+    "metrics",
+    "admin",
+    "openapi",
 ];
 
 fn default_grpc() -> GrpcConfig {
@@ -371,7 +1206,11 @@ fn decorate_config_map(
 ) -> Map<String, Value> {
     map.insert("$schema".to_string(), Value::String(schema_ref.to_string()));
     map.retain(|_, v| !v.is_null());
+    order_config_map(map, key_order)
+}
 
+/// Orders `map` by `key_order`, followed by the remaining keys alphabetically.
+fn order_config_map(map: Map<String, Value>, key_order: &[&str]) -> Map<String, Value> {
     let mut ordered = Map::new();
     let mut remaining: BTreeMap<String, Value> = map.into_iter().collect();
 
@@ -388,6 +1227,53 @@ fn decorate_config_map(
     ordered
 }
 
+/// Config keys holding credentials, masked by [`Config::redacted_document`].
+const SECRET_CONFIG_KEYS: &[&str] = &["redis_auth", "http_basic_auth"];
+
+fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                if SECRET_CONFIG_KEYS.contains(&key.as_str()) && !item.is_null() {
+                    *item = Value::String("[redacted]".to_string());
+                } else {
+                    redact_secrets(item);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// Replaces a list-valued `redis_host` with its first entry and returns the full list.
+///
+/// The typed `Config` keeps `redis_host` as a single string so existing callers
+/// keep working; the failover list lives in `Config::redis_hosts`.
+fn take_redis_host_list(json: &mut Value) -> Result<Vec<String>, ConfigError> {
+    let Some(Value::Array(items)) = json.get("redis_host") else {
+        return Ok(Vec::new());
+    };
+
+    let hosts = items
+        .iter()
+        .map(|item| match item {
+            Value::String(host) if !host.is_empty() => Ok(host.clone()),
+            _ => Err(ConfigError::Message(
+                "redis_host list entries must be non-empty strings".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let Some(first) = hosts.first() else {
+        return Err(ConfigError::Message(
+            "redis_host list must contain at least one host".to_string(),
+        ));
+    };
+
+    json["redis_host"] = Value::String(first.clone());
+    Ok(hosts)
+}
+
 /// A JSON path used for error reporting when expanding `$VARNAME` placeholders.
 ///
 /// This is intentionally kept as a lightweight, allocation-friendly helper so the
@@ -452,7 +1338,13 @@ fn is_valid_env_var_name(name: &str) -> bool {
 /// environment variables. If a referenced environment variable is not set, returns a
 /// `ConfigError` with an actionable message that includes both the missing name and
 /// the JSON key path where it was referenced.
-fn expand_env_vars_in_json(value: &mut Value, path: JsonPath) -> Result<(), ConfigError> {
+///
+/// Each expanded path is recorded in `expanded` with its variable name.
+fn expand_env_vars_in_json(
+    value: &mut Value,
+    path: JsonPath,
+    expanded: &mut BTreeMap<String, String>,
+) -> Result<(), ConfigError> {
     match value {
         Value::String(s) => {
             let Some(var_name) = s.strip_prefix('$') else {
@@ -464,6 +1356,7 @@ fn expand_env_vars_in_json(value: &mut Value, path: JsonPath) -> Result<(), Conf
 
             match std::env::var(var_name) {
                 Ok(env_value) => {
+                    expanded.insert(path.display().to_string(), var_name.to_string());
                     *s = env_value;
                     Ok(())
                 }
@@ -475,13 +1368,13 @@ fn expand_env_vars_in_json(value: &mut Value, path: JsonPath) -> Result<(), Conf
         }
         Value::Array(items) => {
             for (idx, item) in items.iter_mut().enumerate() {
-                expand_env_vars_in_json(item, path.push_index(idx))?;
+                expand_env_vars_in_json(item, path.push_index(idx), expanded)?;
             }
             Ok(())
         }
         Value::Object(map) => {
             for (key, item) in map.iter_mut() {
-                expand_env_vars_in_json(item, path.push_key(key))?;
+                expand_env_vars_in_json(item, path.push_key(key), expanded)?;
             }
             Ok(())
         }
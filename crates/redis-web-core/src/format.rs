@@ -13,9 +13,14 @@ use std::collections::HashMap;
 /// - `jsonp` (preferred)
 /// - `callback` (fallback)
 ///
-/// Per the original Webdis semantics, this performs **minimal validation**:
-/// any non-empty string is accepted and is passed through unchanged.
+/// Names that are not [valid callbacks](is_valid_jsonp_callback) are
+/// dropped, so they are never echoed into a script.
 pub fn select_jsonp_callback(params: &HashMap<String, String>) -> Option<&str> {
+    requested_jsonp_callback(params).filter(|cb| is_valid_jsonp_callback(cb))
+}
+
+/// Returns the non-empty `jsonp` or `callback` parameter, unvalidated.
+pub fn requested_jsonp_callback(params: &HashMap<String, String>) -> Option<&str> {
     params
         .get("jsonp")
         .and_then(|s| (!s.is_empty()).then_some(s.as_str()))
@@ -26,6 +31,23 @@ pub fn select_jsonp_callback(params: &HashMap<String, String>) -> Option<&str> {
         })
 }
 
+/// Longest accepted JSONP callback name.
+pub const MAX_JSONP_CALLBACK_LEN: usize = 128;
+
+/// Whether `name` is safe to emit as a JSONP callback: dot-separated
+/// JavaScript identifiers such as `cb`, `$jq_123` or `app.handlers.onData`,
+/// with ASCII letters, digits, `_` and `$` only.
+pub fn is_valid_jsonp_callback(name: &str) -> bool {
+    name.len() <= MAX_JSONP_CALLBACK_LEN
+        && name.split('.').all(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+        })
+}
+
 /// Formats a JSON value as either plain JSON or JSONP.
 ///
 /// When `jsonp_callback` is set, the payload is wrapped as:
@@ -37,7 +59,7 @@ pub fn json_value_response(
     jsonp_callback: Option<&str>,
 ) -> Response {
     if let Some(cb) = jsonp_callback {
-        let body = format!("{}({})", cb, payload.to_string());
+        let body = format!("{}({})", cb, payload);
         Response::builder()
             .status(status)
             .header(
@@ -73,6 +95,45 @@ pub fn content_type_for_extension(ext: &str) -> Option<&'static str> {
     }
 }
 
+/// `Content-Disposition` value offering a reply as a download named
+/// `filename`.
+///
+/// Only the last path component is kept. `filename` carries an ASCII fallback
+/// with quotes, backslashes and control characters replaced by `_`; a name
+/// that needed replacing is also sent whole as an RFC 5987 `filename*`.
+pub fn content_disposition(filename: &str) -> String {
+    let filename = filename.rsplit(['/', '\\']).next().unwrap_or_default();
+    let filename = if filename.is_empty() {
+        "download"
+    } else {
+        filename
+    };
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if fallback == filename {
+        return format!("attachment; filename=\"{fallback}\"");
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ if byte.is_ascii_alphanumeric() => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect();
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
 /// Output format for HTTP responses.
 ///
 /// This is intentionally *not* a 1:1 mapping to `Content-Type`:
@@ -108,6 +169,12 @@ impl OutputFormat {
     ///
     /// `jsonp_callback` is only applied to JSON output; callers should pass `None` for
     /// non-JSON formats to preserve parity with the original Webdis behavior.
+    ///
+    /// `Raw` encodes the JSON value as RESP, see [`json_to_resp`]. `Raw` and
+    /// `Text` output is better built from the Redis reply itself, as the HTTP
+    /// and WebSocket handlers do with [`crate::resp::value_to_resp`] and the
+    /// reply bytes: going through a JSON value first replaces bytes that are
+    /// not UTF-8 and loses the difference between status and bulk replies.
     pub fn format_response(
         &self,
         command: &str,
@@ -121,36 +188,10 @@ impl OutputFormat {
                 });
                 json_value_response(StatusCode::OK, response, jsonp_callback)
             }
-            OutputFormat::Raw => {
-                // Legacy raw formatter (text/plain).
-                //
-                // Note: the HTTP handler's `.raw` path returns RESP bytes directly. This formatter
-                // is kept for parity with previous code and for any future callers that want a
-                // plain-text representation of the JSON value.
-                let body = match value {
-                    Value::String(s) => s,
-                    Value::Number(n) => n.to_string(),
-                    Value::Bool(b) => b.to_string(),
-                    Value::Null => "".to_string(),
-                    Value::Array(arr) => {
-                        let strings: Vec<String> = arr
-                            .iter()
-                            .map(|v| match v {
-                                Value::String(s) => s.clone(),
-                                Value::Number(n) => n.to_string(),
-                                Value::Bool(b) => b.to_string(),
-                                _ => "".to_string(),
-                            })
-                            .collect();
-                        strings.join("\n")
-                    }
-                    _ => value.to_string(),
-                };
-                Response::builder()
-                    .header(header::CONTENT_TYPE, "text/plain")
-                    .body(Body::from(body))
-                    .unwrap()
-            }
+            OutputFormat::Raw => Response::builder()
+                .header(header::CONTENT_TYPE, "text/plain")
+                .body(Body::from(json_to_resp(&value)))
+                .unwrap(),
             // `Text` responses are built from the raw Redis reply bytes in the handler.
             OutputFormat::Text => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -161,6 +202,37 @@ impl OutputFormat {
     }
 }
 
+/// Encodes a reply already converted to JSON as RESP2: strings as bulk
+/// strings, integers and booleans as integers, `null` as the nil bulk string,
+/// arrays as arrays and objects as flat key/value arrays, the way RESP2
+/// returns `HGETALL`. Other numbers become bulk strings, as Redis returns
+/// floats.
+pub fn json_to_resp(value: &Value) -> Vec<u8> {
+    let bulk = |text: &str| format!("${}\r\n{text}\r\n", text.len()).into_bytes();
+    match value {
+        Value::Null => b"$-1\r\n".to_vec(),
+        Value::Bool(b) => format!(":{}\r\n", u8::from(*b)).into_bytes(),
+        Value::Number(n) if n.is_i64() || n.is_u64() => format!(":{n}\r\n").into_bytes(),
+        Value::Number(n) => bulk(&n.to_string()),
+        Value::String(s) => bulk(s),
+        Value::Array(items) => {
+            let mut res = format!("*{}\r\n", items.len()).into_bytes();
+            for item in items {
+                res.extend_from_slice(&json_to_resp(item));
+            }
+            res
+        }
+        Value::Object(entries) => {
+            let mut res = format!("*{}\r\n", entries.len() * 2).into_bytes();
+            for (key, item) in entries {
+                res.extend_from_slice(&bulk(key));
+                res.extend_from_slice(&json_to_resp(item));
+            }
+            res
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,6 +253,48 @@ mod tests {
         assert_eq!(select_jsonp_callback(&params), None);
     }
 
+    #[test]
+    fn jsonp_callbacks_must_be_dotted_identifiers() {
+        for name in ["cb", "$jq_123", "_", "app.handlers.onData"] {
+            assert!(is_valid_jsonp_callback(name), "{name}");
+        }
+        let too_long = "a".repeat(MAX_JSONP_CALLBACK_LEN + 1);
+        for name in [
+            "",
+            "1cb",
+            "cb()",
+            "alert(1);cb",
+            "a..b",
+            ".cb",
+            "cb[0]",
+            "cb-1",
+            "<script>",
+            "café",
+            too_long.as_str(),
+        ] {
+            assert!(!is_valid_jsonp_callback(name), "{name}");
+        }
+
+        let mut params = HashMap::new();
+        params.insert("jsonp".to_string(), "alert(1)//".to_string());
+        assert_eq!(requested_jsonp_callback(&params), Some("alert(1)//"));
+        assert_eq!(select_jsonp_callback(&params), None);
+    }
+
+    #[test]
+    fn raw_format_encodes_json_as_resp() {
+        assert_eq!(
+            json_to_resp(&json!(["a", null, 3, [], 1.5])),
+            b"*5\r\n$1\r\na\r\n$-1\r\n:3\r\n*0\r\n$3\r\n1.5\r\n"
+        );
+        assert_eq!(
+            json_to_resp(&json!({"f": "v"})),
+            b"*2\r\n$1\r\nf\r\n$1\r\nv\r\n"
+        );
+        assert_eq!(json_to_resp(&json!(true)), b":1\r\n");
+        assert_eq!(json_to_resp(&json!("")), b"$0\r\n\r\n");
+    }
+
     #[test]
     fn extension_maps_to_expected_format_and_content_type() {
         assert_eq!(OutputFormat::from_extension("raw"), Some(OutputFormat::Raw));
@@ -191,4 +305,24 @@ mod tests {
         assert_eq!(OutputFormat::from_extension("msgpack"), None);
         assert_eq!(content_type_for_extension("png"), Some("image/png"));
     }
+
+    #[test]
+    fn content_disposition_keeps_names_safe() {
+        assert_eq!(
+            content_disposition("report.csv"),
+            "attachment; filename=\"report.csv\""
+        );
+        assert_eq!(
+            content_disposition("../etc/passwd"),
+            "attachment; filename=\"passwd\""
+        );
+        assert_eq!(
+            content_disposition("a\"b.txt"),
+            "attachment; filename=\"a_b.txt\"; filename*=UTF-8''a%22b.txt"
+        );
+        assert_eq!(
+            content_disposition("résumé.pdf"),
+            "attachment; filename=\"r_sum_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9.pdf"
+        );
+    }
 }
@@ -1,8 +1,11 @@
 use crate::request::{ParsedRequest, RequestParseError};
+use axum::body::Bytes;
 use redis::Value as RedisValue;
+use std::any::Any;
 use std::future::Future;
 use std::net::IpAddr;
 use std::pin::Pin;
+use std::time::Duration;
 
 /// Input passed from an HTTP adapter to a request parser.
 ///
@@ -11,16 +14,49 @@ use std::pin::Pin;
 pub struct ParseRequestInput<'a> {
     pub command_path: &'a str,
     pub params: &'a std::collections::HashMap<String, String>,
+    /// Values of `?arg=` query parameters, appended in order after the path
+    /// arguments.
+    pub query_args: &'a [String],
     pub default_database: u8,
-    pub body: Option<&'a [u8]>,
+    /// Named backends recognized as a leading path segment, mapped to their
+    /// default database.
+    pub backend_databases: &'a std::collections::HashMap<String, u8>,
+    /// Request body, appended as the last argument. Parsers take ownership so
+    /// the body reaches the executor without being copied.
+    pub body: Option<Bytes>,
     pub etag_enabled: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExecutableCommand {
+    /// Named backend selected by a URL prefix; `None` targets the default Redis.
+    pub backend: Option<String>,
     pub target_database: u8,
     pub command_name: String,
     pub args: Vec<Vec<u8>>,
+    /// Per-request timeout requested by the client (e.g. `?timeout=500ms`).
+    ///
+    /// Executors clamp this to their configured maximum and fall back to their
+    /// default timeout when it is unset.
+    pub timeout: Option<Duration>,
+    /// The top-level `acl` rule whose `redis_auth` the command runs as on the
+    /// top-level Redis; `None` uses the configured `redis_auth`. Entry points
+    /// set it from the caller after the `before` hooks ran.
+    pub credentials: Option<RedisCredentials>,
+}
+
+/// The `acl` rule whose `redis_auth` a command runs as.
+///
+/// Opaque: only [`Acl`](crate::acl::Acl) hands these out, so executors can
+/// compare one and pass it along, but nothing can name another rule's Redis
+/// user or depend on the order of the rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RedisCredentials(usize);
+
+impl RedisCredentials {
+    pub(crate) fn for_rule(index: usize) -> Self {
+        Self(index)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +68,92 @@ pub struct AuthContext {
 pub type ExecutionFuture<'a> =
     Pin<Box<dyn Future<Output = Result<RedisValue, CommandExecutionError>> + Send + 'a>>;
 
+pub type PipelineFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<RedisValue>, CommandExecutionError>> + Send + 'a>>;
+
+/// Connection affinity for a client that sends a series of commands, such as a
+/// WebSocket.
+///
+/// Executors that support it pin a connection here on first use, so
+/// `MULTI`/`EXEC` and `WATCH` see the same connection; others ignore it.
+/// Dropping the session releases the connection.
+///
+/// A connection is kept while a transaction or `WATCH` depends on it, and for
+/// the rest of the session once a command such as `SELECT` or `AUTH` changed
+/// its state, so later commands still see that state.
+#[derive(Default)]
+pub struct CommandSession {
+    connection: Option<Box<dyn Any + Send>>,
+    /// Set while a transaction or `WATCH` depends on the pinned connection.
+    held: bool,
+    /// Set once a command changed connection state, such as the selected
+    /// database or the Redis user.
+    stateful: bool,
+}
+
+impl CommandSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pinned connection, if one of type `T` is pinned.
+    pub fn connection_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.connection.as_mut()?.downcast_mut()
+    }
+
+    pub fn pin<T: Any + Send>(&mut self, connection: T) {
+        self.connection = Some(Box::new(connection));
+        self.held = false;
+        self.stateful = false;
+    }
+
+    /// Drops the pinned connection, for example after it failed.
+    pub fn unpin(&mut self) {
+        self.connection = None;
+        self.held = false;
+        self.stateful = false;
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Whether a transaction or `WATCH` depends on the pinned connection.
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+
+    /// Marks whether the pinned connection carries transaction state.
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+    }
+
+    /// Whether a command changed the state of the pinned connection.
+    pub fn is_stateful(&self) -> bool {
+        self.stateful
+    }
+
+    /// Marks the pinned connection as carrying state of its own.
+    pub fn set_stateful(&mut self) {
+        self.stateful = true;
+    }
+
+    /// Whether [`CommandSession::release_idle`] would release a connection.
+    pub fn is_releasable(&self) -> bool {
+        self.connection.is_some() && !self.held && !self.stateful
+    }
+
+    /// Releases an idle connection unless a transaction, `WATCH` or
+    /// connection state still needs it. Returns whether it was released.
+    pub fn release_idle(&mut self) -> bool {
+        if !self.is_releasable() {
+            return false;
+        }
+        self.connection = None;
+        true
+    }
+}
+
 /// Parser interface that turns transport-level input into a normalized request.
 pub trait RequestParser: Send + Sync {
     fn parse(&self, input: ParseRequestInput<'_>) -> Result<ParsedRequest, RequestParseError>;
@@ -40,12 +162,71 @@ pub trait RequestParser: Send + Sync {
 /// Executor interface that runs a normalized request against a backend.
 pub trait CommandExecutor: Send + Sync {
     fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a>;
+
+    /// Runs `requests` in order and returns one reply per command.
+    ///
+    /// The default runs them one at a time and stops at the first error.
+    /// Executors that can send them to Redis in a single round trip override
+    /// this.
+    fn execute_pipeline<'a>(&'a self, requests: &'a [ExecutableCommand]) -> PipelineFuture<'a> {
+        Box::pin(async move {
+            let mut replies = Vec::with_capacity(requests.len());
+            for request in requests {
+                replies.push(self.execute(request).await?);
+            }
+            Ok(replies)
+        })
+    }
+
+    /// Runs `request` on the connection pinned in `session`, pinning one
+    /// first if needed.
+    ///
+    /// The default ignores the session and calls [`CommandExecutor::execute`].
+    fn execute_in_session<'a>(
+        &'a self,
+        session: &'a mut CommandSession,
+        request: &'a ExecutableCommand,
+    ) -> ExecutionFuture<'a> {
+        let _ = session;
+        self.execute(request)
+    }
+}
+
+/// Plugin hook run on every command a client sends, before its routing and
+/// ACL checks, and on its reply.
+///
+/// `before` may rewrite the command or refuse it; a refusal reaches the
+/// client as a rejected command. `after` may rewrite the reply. Both run
+/// synchronously on the request task, so hooks should not block.
+pub trait CommandHook: Send + Sync {
+    fn before(&self, request: &mut ExecutableCommand) -> Result<(), String> {
+        let _ = request;
+        Ok(())
+    }
+
+    fn after(&self, request: &ExecutableCommand, reply: &mut RedisValue) {
+        let _ = (request, reply);
+    }
 }
 
 #[derive(Debug)]
 pub enum CommandExecutionError {
     ServiceUnavailable(String),
     ExecutionFailed(String),
+    /// The command did not complete within the effective timeout.
+    Timeout(String),
+    /// The command was refused before reaching Redis (e.g. a cross-slot
+    /// multi-key command in cluster mode).
+    Rejected(String),
+    /// The backend already runs as many commands as `command_concurrency`
+    /// allows and no queue slot was free.
+    Overloaded(String),
+    /// No pooled connection became free within `pool_wait_timeout_ms`, or
+    /// `pool_max_waiting` commands were already waiting for one.
+    PoolExhausted(String),
+    /// A write carried a value larger than `max_value_size` or its
+    /// `value_size_limits` rule allows.
+    ValueTooLarge(String),
 }
 
 impl std::fmt::Display for CommandExecutionError {
@@ -53,8 +234,48 @@ impl std::fmt::Display for CommandExecutionError {
         match self {
             CommandExecutionError::ServiceUnavailable(msg) => write!(f, "{msg}"),
             CommandExecutionError::ExecutionFailed(msg) => write!(f, "{msg}"),
+            CommandExecutionError::Timeout(msg) => write!(f, "{msg}"),
+            CommandExecutionError::Rejected(msg) => write!(f, "{msg}"),
+            CommandExecutionError::Overloaded(msg) => write!(f, "{msg}"),
+            CommandExecutionError::PoolExhausted(msg) => write!(f, "{msg}"),
+            CommandExecutionError::ValueTooLarge(msg) => write!(f, "{msg}"),
         }
     }
 }
 
 impl std::error::Error for CommandExecutionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn held_sessions_keep_their_connection() {
+        let mut session = CommandSession::new();
+        assert!(!session.release_idle());
+
+        session.pin(7u32);
+        assert_eq!(session.connection_mut::<u32>(), Some(&mut 7));
+        assert_eq!(session.connection_mut::<String>(), None);
+
+        session.set_held(true);
+        assert!(!session.release_idle());
+        assert!(session.is_pinned());
+
+        session.set_held(false);
+        assert!(session.release_idle());
+        assert!(!session.is_pinned());
+    }
+
+    #[test]
+    fn stateful_sessions_keep_their_connection() {
+        let mut session = CommandSession::new();
+        session.pin(7u32);
+        session.set_stateful();
+        assert!(!session.release_idle());
+        assert!(session.is_pinned());
+
+        session.unpin();
+        assert!(!session.is_stateful());
+    }
+}
@@ -1,7 +1,7 @@
 //! redis-web core modules.
 //!
-//! Shared protocol, parsing, formatting, config, and ACL primitives used by
-//! runtime and compatibility layers.
+//! Shared protocol, parsing, formatting, config, ACL, and routing primitives
+//! used by runtime and compatibility layers.
 
 pub mod acl;
 pub mod config;
@@ -9,3 +9,4 @@ pub mod format;
 pub mod interfaces;
 pub mod request;
 pub mod resp;
+pub mod routing;
@@ -1,5 +1,10 @@
-use crate::format::{content_type_for_extension, select_jsonp_callback, OutputFormat};
+use crate::format::{
+    content_type_for_extension, is_valid_jsonp_callback, requested_jsonp_callback, OutputFormat,
+};
 use crate::interfaces::{ExecutableCommand, ParseRequestInput, RequestParser};
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine as _;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct ParsedRequest {
@@ -7,6 +12,8 @@ pub struct ParsedRequest {
     pub output_format: OutputFormat,
     pub jsonp_callback: Option<String>,
     pub content_type_override: Option<String>,
+    /// `?filename=`: serve a raw or text reply as a download of this name.
+    pub filename: Option<String>,
     pub extension_content_type: Option<&'static str>,
     pub etag_enabled: bool,
 }
@@ -16,6 +23,11 @@ pub enum RequestParseError {
     EmptyCommand,
     InvalidDatabaseIndex,
     MissingCommandAfterDatabasePrefix,
+    MissingCommandAfterBackendPrefix,
+    InvalidTimeout,
+    InvalidSeparator,
+    InvalidBase64Argument,
+    InvalidJsonpCallback,
     InvalidCommand(String),
 }
 
@@ -29,11 +41,17 @@ impl RequestParser for WebdisRequestParser {
     }
 }
 
+/// Every `/` separates two segments, as in Webdis: an empty segment, such as
+/// the last one of `SET/key/` or the middle one of `SET//value`, is an
+/// empty-string argument. Only the command name may not be empty. `?sep=`
+/// picks another separator, so `GET|path/to/key?sep=|` reads the key
+/// `path/to/key`.
 fn parse_http_request(input: ParseRequestInput<'_>) -> Result<ParsedRequest, RequestParseError> {
-    let parts: Vec<&str> = input.command_path.split('/').collect();
-    if parts.is_empty() {
-        return Err(RequestParseError::EmptyCommand);
-    }
+    let separator = match input.params.get("sep") {
+        Some(sep) => parse_separator(sep).ok_or(RequestParseError::InvalidSeparator)?,
+        None => '/',
+    };
+    let parts: Vec<&str> = input.command_path.split(separator).collect();
 
     fn is_decimal_segment(segment: &str) -> bool {
         !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit())
@@ -41,16 +59,26 @@ fn parse_http_request(input: ParseRequestInput<'_>) -> Result<ParsedRequest, Req
 
     let mut target_database = input.default_database;
     let mut command_segment_index = 0usize;
-    if is_decimal_segment(parts[0]) {
-        target_database = parts[0]
+    let mut backend = None;
+    if let Some(&database) = input.backend_databases.get(parts[0]) {
+        if parts.len() < 2 || parts[1].is_empty() {
+            return Err(RequestParseError::MissingCommandAfterBackendPrefix);
+        }
+        backend = Some(parts[0].to_string());
+        target_database = database;
+        command_segment_index = 1;
+    }
+
+    if is_decimal_segment(parts[command_segment_index]) {
+        target_database = parts[command_segment_index]
             .parse::<u8>()
             .map_err(|_| RequestParseError::InvalidDatabaseIndex)?;
 
-        if parts.len() < 2 || parts[1].is_empty() {
+        if parts.len() < command_segment_index + 2 || parts[command_segment_index + 1].is_empty() {
             return Err(RequestParseError::MissingCommandAfterDatabasePrefix);
         }
 
-        command_segment_index = 1;
+        command_segment_index += 1;
     }
 
     let mut raw_cmd_name = parts[command_segment_index].to_string();
@@ -81,12 +109,38 @@ fn parse_http_request(input: ParseRequestInput<'_>) -> Result<ParsedRequest, Req
     }
 
     let command_name = percent_decode_segment_lossy(&raw_cmd_name);
-    let mut args: Vec<Vec<u8>> = raw_args
-        .into_iter()
-        .map(|segment| percent_decode_segment_lossy(&segment).into_bytes())
-        .collect();
+    if command_name.is_empty() {
+        return Err(RequestParseError::EmptyCommand);
+    }
+    // `?b64args=1` sends each argument base64-encoded, for keys and values
+    // that are not valid UTF-8 or are awkward in URLs.
+    let base64_args = matches!(
+        input.params.get("b64args").map(String::as_str),
+        Some("1" | "true")
+    );
+    let mut args: Vec<Vec<u8>> = if base64_args {
+        raw_args
+            .iter()
+            .map(|segment| decode_base64_segment(segment))
+            .collect::<Option<_>>()
+            .ok_or(RequestParseError::InvalidBase64Argument)?
+    } else {
+        // Arguments stay bytes, so `%FF` reaches Redis as the byte 0xFF.
+        raw_args
+            .iter()
+            .map(|segment| percent_decode_segment(segment))
+            .collect()
+    };
+    for value in input.query_args {
+        args.push(if base64_args {
+            decode_base64_segment(value).ok_or(RequestParseError::InvalidBase64Argument)?
+        } else {
+            value.clone().into_bytes()
+        });
+    }
     if let Some(body) = input.body.filter(|body| !body.is_empty()) {
-        args.push(body.to_vec());
+        // Reuses the body's allocation unless it is shared.
+        args.push(Vec::from(body));
     }
 
     let mut output_format = OutputFormat::Json;
@@ -96,36 +150,112 @@ fn parse_http_request(input: ParseRequestInput<'_>) -> Result<ParsedRequest, Req
         }
     }
 
-    let jsonp_callback = if output_format == OutputFormat::Json {
-        select_jsonp_callback(input.params).map(|cb| cb.to_string())
-    } else {
-        None
+    let jsonp_callback = match requested_jsonp_callback(input.params) {
+        Some(_) if output_format != OutputFormat::Json => None,
+        Some(cb) if !is_valid_jsonp_callback(cb) => {
+            return Err(RequestParseError::InvalidJsonpCallback)
+        }
+        cb => cb.map(str::to_string),
+    };
+
+    let timeout = match input.params.get("timeout") {
+        Some(value) => Some(parse_timeout(value).ok_or(RequestParseError::InvalidTimeout)?),
+        None => None,
     };
 
     let content_type_override = input
         .params
         .get("type")
         .and_then(|value| (!value.is_empty()).then_some(value.clone()));
+    let filename = input
+        .params
+        .get("filename")
+        .and_then(|value| (!value.is_empty()).then_some(value.clone()));
     let extension_content_type = extension
         .as_deref()
         .and_then(|ext| content_type_for_extension(ext));
 
     Ok(ParsedRequest {
         command: ExecutableCommand {
+            backend,
             target_database,
             command_name,
             args,
+            timeout,
+            credentials: None,
         },
         output_format,
         jsonp_callback,
         content_type_override,
+        filename,
         extension_content_type,
         etag_enabled: input.etag_enabled,
     })
 }
 
+/// Parses a `?timeout=` value such as `500ms`, `2s`, or a bare millisecond count.
+///
+/// Zero and malformed values are rejected so a typo never disables the timeout.
+pub fn parse_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (digits, unit_ms) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1)
+    } else if let Some(secs) = value.strip_suffix('s') {
+        (secs, 1000)
+    } else {
+        (value, 1)
+    };
+
+    let amount: u64 = digits.parse().ok()?;
+    let millis = amount.checked_mul(unit_ms)?;
+    (millis > 0).then(|| Duration::from_millis(millis))
+}
+
+/// The argument separator of `?sep=`: a single character other than `%`,
+/// which starts escapes, and `.`, which starts the format extension.
+fn parse_separator(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(separator), None) if !matches!(separator, '%' | '.') => Some(separator),
+        _ => None,
+    }
+}
+
+/// Splits a raw (still percent-encoded) URL path into decoded segments.
+///
+/// Empty segments are dropped, and `%2F` stays inside its segment, so
+/// `a/b%2Fc` yields `["a", "b/c"]`.
+pub fn decode_path_segments(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(percent_decode_segment_lossy)
+        .collect()
+}
+
+/// Decodes a base64 argument. Both the URL-safe and the standard alphabet
+/// are accepted, with or without padding.
+fn decode_base64_segment(segment: &str) -> Option<Vec<u8>> {
+    const BASE64: GeneralPurpose = GeneralPurpose::new(
+        &base64::alphabet::URL_SAFE,
+        GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    let mut encoded = percent_decode_segment(segment);
+    for byte in &mut encoded {
+        match byte {
+            b'+' => *byte = b'-',
+            b'/' => *byte = b'_',
+            _ => {}
+        }
+    }
+    BASE64.decode(encoded).ok()
+}
+
 /// Percent-decodes a single URL path segment while preserving slash splitting.
 fn percent_decode_segment_lossy(input: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_segment(input)).into_owned()
+}
+
+fn percent_decode_segment(input: &str) -> Vec<u8> {
     let bytes = input.as_bytes();
     let mut output: Vec<u8> = Vec::with_capacity(bytes.len());
 
@@ -145,13 +275,14 @@ fn percent_decode_segment_lossy(input: &str) -> String {
         index += 1;
     }
 
-    String::from_utf8_lossy(&output).into_owned()
+    output
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::interfaces::ParseRequestInput;
+    use axum::body::Bytes;
     use std::collections::HashMap;
 
     #[test]
@@ -161,8 +292,10 @@ mod tests {
             command_path: "7/GET/key.raw",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .unwrap();
 
@@ -173,6 +306,40 @@ mod tests {
         assert!(parsed.extension_content_type.is_some());
     }
 
+    #[test]
+    fn parser_routes_named_backend_prefix() {
+        let params = HashMap::new();
+        let backends = HashMap::from([("cache".to_string(), 2u8)]);
+        let parse = |command_path| {
+            parse_http_request(ParseRequestInput {
+                command_path,
+                params: &params,
+                default_database: 0,
+                backend_databases: &backends,
+                body: None,
+                etag_enabled: true,
+                query_args: &[],
+            })
+        };
+
+        let parsed = parse("cache/GET/key").unwrap();
+        assert_eq!(parsed.command.backend.as_deref(), Some("cache"));
+        assert_eq!(parsed.command.target_database, 2);
+        assert_eq!(parsed.command.command_name, "GET");
+
+        let parsed = parse("cache/5/GET/key").unwrap();
+        assert_eq!(parsed.command.target_database, 5);
+
+        let parsed = parse("GET/cache").unwrap();
+        assert_eq!(parsed.command.backend, None);
+        assert_eq!(parsed.command.args, vec![b"cache".to_vec()]);
+
+        assert!(matches!(
+            parse("cache/").unwrap_err(),
+            RequestParseError::MissingCommandAfterBackendPrefix
+        ));
+    }
+
     #[test]
     fn parser_decodes_percent_escapes_per_segment() {
         let params = HashMap::new();
@@ -180,14 +347,144 @@ mod tests {
             command_path: "GET/a%2Fb%2Eraw",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .unwrap();
 
         assert_eq!(parsed.command.command_name, "GET");
         assert_eq!(parsed.command.args, vec![b"a/b.raw".to_vec()]);
         assert_eq!(parsed.output_format, OutputFormat::Json);
+
+        // `+` is literal in a path; `%20` is the space. Escapes are
+        // case-insensitive and a `%` not followed by two hex digits is kept.
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "SET/user%3a%C3%A9l%C3%A8ne%20a+b/100%25%zz%",
+            params: &params,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+            query_args: &[],
+        })
+        .unwrap();
+        assert_eq!(
+            parsed.command.args,
+            vec!["user:élène a+b".as_bytes().to_vec(), b"100%%zz%".to_vec()]
+        );
+    }
+
+    #[test]
+    fn parser_keeps_empty_segments_as_empty_arguments() {
+        let params = HashMap::new();
+        let parse = |command_path| {
+            parse_http_request(ParseRequestInput {
+                command_path,
+                params: &params,
+                default_database: 0,
+                backend_databases: &HashMap::new(),
+                body: None,
+                etag_enabled: true,
+                query_args: &[],
+            })
+        };
+
+        let parsed = parse("SET/key/").unwrap();
+        assert_eq!(parsed.command.args, vec![b"key".to_vec(), Vec::new()]);
+        let parsed = parse("SET//value").unwrap();
+        assert_eq!(parsed.command.args, vec![Vec::new(), b"value".to_vec()]);
+        let parsed = parse("SET/key/.txt").unwrap();
+        assert_eq!(parsed.command.args, vec![b"key".to_vec(), Vec::new()]);
+        assert_eq!(parsed.output_format, OutputFormat::Text);
+
+        assert!(matches!(parse(""), Err(RequestParseError::EmptyCommand)));
+        assert!(matches!(
+            parse("/GET/key"),
+            Err(RequestParseError::EmptyCommand)
+        ));
+        assert!(matches!(
+            parse(".json"),
+            Err(RequestParseError::EmptyCommand)
+        ));
+    }
+
+    #[test]
+    fn parser_splits_on_the_sep_parameter() {
+        let params = HashMap::from([("sep".to_string(), "|".to_string())]);
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "3|GET|path/to/key%7Cx.txt",
+            params: &params,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+            query_args: &[],
+        })
+        .unwrap();
+        assert_eq!(parsed.command.target_database, 3);
+        assert_eq!(parsed.command.command_name, "GET");
+        assert_eq!(parsed.command.args, vec![b"path/to/key|x".to_vec()]);
+        assert_eq!(parsed.output_format, OutputFormat::Text);
+
+        for sep in ["", "||", "%", "."] {
+            let params = HashMap::from([("sep".to_string(), sep.to_string())]);
+            let err = parse_http_request(ParseRequestInput {
+                command_path: "GET|key",
+                params: &params,
+                default_database: 0,
+                backend_databases: &HashMap::new(),
+                body: None,
+                etag_enabled: true,
+                query_args: &[],
+            })
+            .unwrap_err();
+            assert!(matches!(err, RequestParseError::InvalidSeparator), "{sep}");
+        }
+    }
+
+    #[test]
+    fn parser_decodes_base64_arguments() {
+        let parse = |command_path, b64args: &str| {
+            let params = HashMap::from([("b64args".to_string(), b64args.to_string())]);
+            parse_http_request(ParseRequestInput {
+                command_path,
+                params: &params,
+                default_database: 0,
+                backend_databases: &HashMap::new(),
+                body: Some(Bytes::from_static(b"body")),
+                etag_enabled: true,
+                query_args: &[],
+            })
+        };
+
+        // "\xff/k y" in the URL-safe alphabet, then in the standard one with
+        // percent-encoded padding.
+        let parsed = parse("SET/_y9rIHk/dg.txt", "1").unwrap();
+        assert_eq!(parsed.command.command_name, "SET");
+        assert_eq!(
+            parsed.command.args,
+            vec![b"\xff/k y".to_vec(), b"v".to_vec(), b"body".to_vec()]
+        );
+        assert_eq!(parsed.output_format, OutputFormat::Text);
+        let parsed = parse("GET/%2Fy9rIHk%3D", "true").unwrap();
+        assert_eq!(parsed.command.args[0], b"\xff/k y");
+
+        assert!(matches!(
+            parse("GET/not*base64", "1"),
+            Err(RequestParseError::InvalidBase64Argument)
+        ));
+        let parsed = parse("GET/dg", "0").unwrap();
+        assert_eq!(parsed.command.args[0], b"dg");
+    }
+
+    #[test]
+    fn path_segments_keep_encoded_slashes() {
+        assert_eq!(
+            decode_path_segments("news/sports%2Flive//weather"),
+            vec!["news", "sports/live", "weather"]
+        );
     }
 
     #[test]
@@ -197,8 +494,10 @@ mod tests {
             command_path: "9999/GET/key",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .expect_err("invalid DB prefix should fail");
 
@@ -212,8 +511,10 @@ mod tests {
             command_path: "7",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .expect_err("db prefix without command should fail");
 
@@ -230,8 +531,10 @@ mod tests {
             command_path: "GET/key.msgpack",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .expect("msgpack suffix should not change parsing");
 
@@ -240,6 +543,65 @@ mod tests {
         assert_eq!(parsed.output_format, OutputFormat::Json);
     }
 
+    #[test]
+    fn parser_keeps_percent_encoded_bytes_of_arguments() {
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "SET/k%FF/%89PNG%0D%0A%1A%0A%00.png",
+            params: &HashMap::new(),
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+            query_args: &[],
+        })
+        .expect("binary arguments should parse");
+
+        assert_eq!(
+            parsed.command.args,
+            vec![b"k\xff".to_vec(), b"\x89PNG\r\n\x1a\n\x00".to_vec()]
+        );
+        assert_eq!(parsed.extension_content_type, Some("image/png"));
+    }
+
+    #[test]
+    fn parser_appends_query_args_after_path_args() {
+        let query_args = ["a/b c".to_string(), "%41".to_string()];
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "RPUSH/list.txt",
+            params: &HashMap::new(),
+            query_args: &query_args,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: Some(Bytes::from_static(b"body")),
+            etag_enabled: false,
+        })
+        .unwrap();
+        assert_eq!(
+            parsed.command.args,
+            vec![
+                b"list".to_vec(),
+                b"a/b c".to_vec(),
+                b"%41".to_vec(),
+                b"body".to_vec()
+            ]
+        );
+
+        let mut params = HashMap::new();
+        params.insert("b64args".to_string(), "1".to_string());
+        let query_args = ["dg".to_string(), "not base64!".to_string()];
+        let err = parse_http_request(ParseRequestInput {
+            command_path: "SET/aw",
+            params: &params,
+            query_args: &query_args,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+        })
+        .unwrap_err();
+        assert!(matches!(err, RequestParseError::InvalidBase64Argument));
+    }
+
     #[test]
     fn parser_disables_jsonp_for_raw_output() {
         let mut params = HashMap::new();
@@ -249,8 +611,10 @@ mod tests {
             command_path: "GET/key.raw",
             params: &params,
             default_database: 0,
+            backend_databases: &HashMap::new(),
             body: None,
             etag_enabled: true,
+            query_args: &[],
         })
         .expect("raw request should parse");
 
@@ -258,6 +622,28 @@ mod tests {
         assert!(parsed.jsonp_callback.is_none());
     }
 
+    #[test]
+    fn parser_rejects_unsafe_jsonp_callbacks() {
+        let parse = |name: &str| {
+            let mut params = HashMap::new();
+            params.insert("callback".to_string(), name.to_string());
+            parse_http_request(ParseRequestInput {
+                command_path: "GET/key",
+                params: &params,
+                default_database: 0,
+                backend_databases: &HashMap::new(),
+                body: None,
+                etag_enabled: true,
+                query_args: &[],
+            })
+        };
+
+        let parsed = parse("app.onData").expect("dotted callback should parse");
+        assert_eq!(parsed.jsonp_callback.as_deref(), Some("app.onData"));
+        let err = parse("alert(document.cookie);cb").unwrap_err();
+        assert!(matches!(err, RequestParseError::InvalidJsonpCallback));
+    }
+
     #[test]
     fn parser_appends_non_empty_body_as_last_argument() {
         let params = HashMap::new();
@@ -265,8 +651,10 @@ mod tests {
             command_path: "SET/key",
             params: &params,
             default_database: 0,
-            body: Some(b"value"),
+            backend_databases: &HashMap::new(),
+            body: Some(Bytes::from_static(b"value")),
             etag_enabled: false,
+            query_args: &[],
         })
         .expect("body-backed request should parse");
 
@@ -275,4 +663,60 @@ mod tests {
             vec![b"key".to_vec(), b"value".to_vec()]
         );
     }
+
+    #[test]
+    fn parser_moves_an_unshared_body_without_copying() {
+        let body = Bytes::from(vec![b'x'; 1024]);
+        let body_ptr = body.as_ptr();
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "SET/key",
+            params: &HashMap::new(),
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: Some(body),
+            etag_enabled: false,
+            query_args: &[],
+        })
+        .expect("body-backed request should parse");
+
+        assert_eq!(parsed.command.args[1].as_ptr(), body_ptr);
+    }
+
+    #[test]
+    fn parser_reads_timeout_query_parameter() {
+        let mut params = HashMap::new();
+        params.insert("timeout".to_string(), "500ms".to_string());
+        let parsed = parse_http_request(ParseRequestInput {
+            command_path: "KEYS/*",
+            params: &params,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+            query_args: &[],
+        })
+        .expect("timeout should parse");
+        assert_eq!(parsed.command.timeout, Some(Duration::from_millis(500)));
+
+        params.insert("timeout".to_string(), "soon".to_string());
+        let err = parse_http_request(ParseRequestInput {
+            command_path: "KEYS/*",
+            params: &params,
+            default_database: 0,
+            backend_databases: &HashMap::new(),
+            body: None,
+            etag_enabled: true,
+            query_args: &[],
+        })
+        .expect_err("malformed timeout should fail");
+        assert!(matches!(err, RequestParseError::InvalidTimeout));
+    }
+
+    #[test]
+    fn timeout_values_accept_units() {
+        assert_eq!(parse_timeout("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("750"), Some(Duration::from_millis(750)));
+        assert_eq!(parse_timeout("0ms"), None);
+        assert_eq!(parse_timeout("-5"), None);
+    }
 }
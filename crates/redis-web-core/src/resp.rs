@@ -17,6 +17,10 @@ pub enum RespError {
     Io(std::io::Error),
 }
 
+/// A parsed RESP command (arguments including the command name) and the number of
+/// bytes consumed from the input buffer.
+pub type ParsedCommand = (Vec<Vec<u8>>, usize);
+
 /// Converts a `redis::Value` into its RESP byte representation.
 ///
 /// This is used to send Redis responses back to the client over a raw WebSocket.
@@ -39,10 +43,89 @@ pub fn value_to_resp(v: &Value) -> Vec<u8> {
         }
         Value::SimpleString(s) => format!("+{}\r\n", s).into_bytes(),
         Value::Okay => b"+OK\r\n".to_vec(),
+        Value::Map(entries) => pairs_to_resp(b'%', entries),
+        Value::Attribute { data, attributes } => {
+            let mut res = pairs_to_resp(b'|', attributes);
+            res.extend_from_slice(&value_to_resp(data));
+            res
+        }
+        Value::Set(items) => aggregate_to_resp(b'~', items),
+        Value::Push { kind, data } => {
+            let mut res = format!(">{}\r\n", data.len() + 1).into_bytes();
+            res.extend_from_slice(&value_to_resp(&Value::BulkString(
+                kind.to_string().into_bytes(),
+            )));
+            for item in data {
+                res.extend_from_slice(&value_to_resp(item));
+            }
+            res
+        }
+        Value::Double(value) => {
+            let encoded = if value.is_nan() {
+                "nan".to_string()
+            } else {
+                value.to_string()
+            };
+            format!(",{encoded}\r\n").into_bytes()
+        }
+        Value::Boolean(value) => {
+            if *value {
+                b"#t\r\n".to_vec()
+            } else {
+                b"#f\r\n".to_vec()
+            }
+        }
+        Value::VerbatimString { format, text } => {
+            let payload = format!("{format}:{text}");
+            format!("={}\r\n{payload}\r\n", payload.len()).into_bytes()
+        }
+        Value::BigNumber(number) => format!("({number}\r\n").into_bytes(),
+        Value::ServerError(error) => simple_error(error.code(), error.details()),
         _ => b"-ERR Unsupported RESP3 type\r\n".to_vec(),
     }
 }
 
+/// Encodes `message` as a RESP error with the generic `ERR` code, as the
+/// `.raw` output reports failures that did not come from Redis itself.
+pub fn error_to_resp(message: &str) -> Vec<u8> {
+    simple_error("ERR", Some(message))
+}
+
+/// A simple error line; line breaks, which would end the frame early, become
+/// spaces.
+pub fn simple_error(code: &str, detail: Option<&str>) -> Vec<u8> {
+    let mut line = format!("-{code}");
+    if let Some(detail) = detail {
+        line.push(' ');
+        line.push_str(detail);
+    }
+    let mut res: Vec<u8> = line
+        .bytes()
+        .map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b })
+        .collect();
+    res.extend_from_slice(b"\r\n");
+    res
+}
+
+fn aggregate_to_resp(prefix: u8, items: &[Value]) -> Vec<u8> {
+    let mut res = vec![prefix];
+    res.extend_from_slice(format!("{}\r\n", items.len()).as_bytes());
+    for item in items {
+        res.extend_from_slice(&value_to_resp(item));
+    }
+    res
+}
+
+fn pairs_to_resp(prefix: u8, entries: &[(Value, Value)]) -> Vec<u8> {
+    let mut res = vec![prefix];
+    res.extend_from_slice(format!("{}\r\n", entries.len()).as_bytes());
+    for (key, value) in entries {
+        res.extend_from_slice(&value_to_resp(key));
+        res.extend_from_slice(&value_to_resp(value));
+    }
+    res
+}
+
 /// Parses a RESP command (Array of Bulk Strings) from a byte buffer.
 ///
 /// Returns `Ok(Some((args, consumed)))` if a full command was parsed,
@@ -50,7 +133,7 @@ pub fn value_to_resp(v: &Value) -> Vec<u8> {
 /// is the number of bytes read from the buffer.
 ///
 /// Returns `Ok(None)` if more data is needed to complete the command.
-pub fn parse_command(buffer: &[u8]) -> Result<Option<(Vec<Vec<u8>>, usize)>, RespError> {
+pub fn parse_command(buffer: &[u8]) -> Result<Option<ParsedCommand>, RespError> {
     let mut cursor = Cursor::new(buffer);
     let mut line = String::new();
 
@@ -124,6 +207,63 @@ mod tests {
             value_to_resp(&Value::Array(vec![Value::Int(1), Value::Okay])),
             b"*2\r\n:1\r\n+OK\r\n"
         );
+        // LRANGE-style reply with a nested nil, as MULTI/EXEC can return.
+        assert_eq!(
+            value_to_resp(&Value::Array(vec![
+                Value::BulkString(b"a".to_vec()),
+                Value::Nil,
+                Value::Array(vec![]),
+                Value::Int(-3),
+            ])),
+            b"*4\r\n$1\r\na\r\n$-1\r\n*0\r\n:-3\r\n"
+        );
+        assert_eq!(value_to_resp(&Value::BulkString(vec![])), b"$0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_error_to_resp() {
+        assert_eq!(error_to_resp("no such key"), b"-ERR no such key\r\n");
+        assert_eq!(
+            error_to_resp("line one\r\nline two"),
+            b"-ERR line one  line two\r\n"
+        );
+        let error = redis::parse_redis_value(b"-WRONGTYPE Operation against a key\r\n").unwrap();
+        assert_eq!(
+            value_to_resp(&error),
+            b"-WRONGTYPE Operation against a key\r\n"
+        );
+    }
+
+    #[test]
+    fn test_value_to_resp3() {
+        assert_eq!(
+            value_to_resp(&Value::Map(vec![(
+                Value::SimpleString("a".into()),
+                Value::Int(1)
+            )])),
+            b"%1\r\n+a\r\n:1\r\n"
+        );
+        assert_eq!(
+            value_to_resp(&Value::Set(vec![Value::Int(1)])),
+            b"~1\r\n:1\r\n"
+        );
+        assert_eq!(value_to_resp(&Value::Double(1.5)), b",1.5\r\n");
+        assert_eq!(value_to_resp(&Value::Double(f64::INFINITY)), b",inf\r\n");
+        assert_eq!(value_to_resp(&Value::Boolean(true)), b"#t\r\n");
+        assert_eq!(
+            value_to_resp(&Value::VerbatimString {
+                format: redis::VerbatimFormat::Text,
+                text: "hi".into(),
+            }),
+            b"=6\r\ntxt:hi\r\n"
+        );
+        assert_eq!(
+            value_to_resp(&Value::Push {
+                kind: redis::PushKind::Message,
+                data: vec![Value::BulkString(b"ch".to_vec())],
+            }),
+            b">2\r\n$7\r\nmessage\r\n$2\r\nch\r\n"
+        );
     }
 
     #[test]
@@ -0,0 +1,348 @@
+//! Command routing rules.
+//!
+//! `routes` entries send matching commands to a named backend, for example to
+//! move `SCAN`/`KEYS` and analytics reads onto a replica. `output_formats`
+//! entries pick the reply format of requests whose URL has no extension, for
+//! example raw bytes for `GET img:*`. `downloads` entries name the file a raw
+//! or text reply is saved as. Rules are evaluated in order against the command
+//! name and its first argument, and the first match wins.
+
+use crate::config::{DownloadRuleConfig, OutputFormatRuleConfig, RouteConfig};
+use crate::interfaces::ExecutableCommand;
+
+pub struct CommandRouter {
+    rules: Vec<(CommandMatch, String)>,
+}
+
+/// Conditions of a rule; unset ones match everything.
+struct CommandMatch {
+    commands: Vec<String>,
+    key_pattern: Option<Vec<u8>>,
+}
+
+impl CommandMatch {
+    fn new(commands: Option<Vec<String>>, key_pattern: Option<String>) -> Self {
+        CommandMatch {
+            commands: commands.unwrap_or_default(),
+            key_pattern: key_pattern.map(String::into_bytes),
+        }
+    }
+
+    fn matches(&self, command: &ExecutableCommand) -> bool {
+        let command_matches = self.commands.is_empty()
+            || self
+                .commands
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&command.command_name));
+        let key_matches = match &self.key_pattern {
+            Some(pattern) => command
+                .args
+                .first()
+                .is_some_and(|key| glob_match(pattern, key)),
+            None => true,
+        };
+        command_matches && key_matches
+    }
+}
+
+impl CommandRouter {
+    pub fn new(config: Option<Vec<RouteConfig>>) -> Self {
+        let rules = config
+            .unwrap_or_default()
+            .into_iter()
+            .map(|route| {
+                let matcher = CommandMatch::new(route.commands, route.key_pattern);
+                (matcher, route.backend)
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Returns the backend of the first rule matching `command`.
+    pub fn backend_for(&self, command: &ExecutableCommand) -> Option<&str> {
+        first_match(&self.rules, command)
+    }
+
+    /// Returns the backend of the first rule matching `command` whose backend
+    /// is `available`.
+    pub fn available_backend_for(
+        &self,
+        command: &ExecutableCommand,
+        available: impl Fn(&str) -> bool,
+    ) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(matcher, backend)| matcher.matches(command) && available(backend))
+            .map(|(_, backend)| backend.as_str())
+    }
+
+    /// Backend names referenced by the rules, in rule order.
+    pub fn backends(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|(_, backend)| backend.as_str())
+    }
+}
+
+/// `output_formats` rules.
+pub struct FormatRouter {
+    rules: Vec<(CommandMatch, String)>,
+}
+
+impl FormatRouter {
+    pub fn new(config: Option<Vec<OutputFormatRuleConfig>>) -> Self {
+        let rules = config
+            .unwrap_or_default()
+            .into_iter()
+            .map(|rule| {
+                let matcher = CommandMatch::new(rule.commands, rule.key_pattern);
+                (matcher, rule.format.to_ascii_lowercase())
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Returns the extension of the first rule matching `command`.
+    pub fn extension_for(&self, command: &ExecutableCommand) -> Option<&str> {
+        first_match(&self.rules, command)
+    }
+
+    /// Extensions referenced by the rules, in rule order.
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|(_, extension)| extension.as_str())
+    }
+}
+
+/// `downloads` rules.
+pub struct DownloadRouter {
+    rules: Vec<(CommandMatch, String)>,
+}
+
+impl DownloadRouter {
+    pub fn new(config: Option<Vec<DownloadRuleConfig>>) -> Self {
+        let rules = config
+            .unwrap_or_default()
+            .into_iter()
+            .map(|rule| {
+                let matcher = CommandMatch::new(rule.commands, rule.key_pattern);
+                (matcher, rule.filename)
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Returns the file name of the first rule matching `command`, with
+    /// `{key}` replaced by its first argument.
+    pub fn filename_for(&self, command: &ExecutableCommand) -> Option<String> {
+        let filename = first_match(&self.rules, command)?;
+        let key = command
+            .args
+            .first()
+            .map(|key| String::from_utf8_lossy(key))
+            .unwrap_or_default();
+        Some(filename.replace("{key}", &key))
+    }
+}
+
+fn first_match<'a>(
+    rules: &'a [(CommandMatch, String)],
+    command: &ExecutableCommand,
+) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|(matcher, _)| matcher.matches(command))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Matches `text` against a Redis-style glob pattern.
+///
+/// Supports `*`, `?`, character classes such as `[abc]`, `[a-z]` and `[^a]`,
+/// and `\` escapes, like `KEYS` and `SCAN MATCH`.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return text.is_empty();
+    };
+    match first {
+        b'*' => (0..=text.len()).any(|skip| glob_match(rest, &text[skip..])),
+        b'?' => !text.is_empty() && glob_match(rest, &text[1..]),
+        b'[' => {
+            let Some((&byte, remaining)) = text.split_first() else {
+                return false;
+            };
+            match class_match(rest, byte) {
+                Some((true, after)) => glob_match(after, remaining),
+                Some((false, _)) => false,
+                // Unterminated class: treat `[` literally.
+                None => byte == b'[' && glob_match(rest, remaining),
+            }
+        }
+        b'\\' if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match(&rest[1..], &text[1..])
+        }
+        literal => text.first() == Some(&literal) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Escapes the glob wildcards of `text`, so a pattern built from it matches
+/// it literally.
+pub fn glob_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Matches `byte` against a character class body (after `[`).
+///
+/// Returns whether it matched and the pattern after the closing `]`.
+fn class_match(class: &[u8], byte: u8) -> Option<(bool, &[u8])> {
+    let (negated, mut body) = match class.split_first() {
+        Some((b'^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    loop {
+        match body {
+            [] => return None,
+            [b']', rest @ ..] => return Some((matched != negated, rest)),
+            [b'\\', escaped, rest @ ..] => {
+                matched |= *escaped == byte;
+                body = rest;
+            }
+            [low, b'-', high, rest @ ..] if *high != b']' => {
+                let (low, high) = if low <= high {
+                    (*low, *high)
+                } else {
+                    (*high, *low)
+                };
+                matched |= (low..=high).contains(&byte);
+                body = rest;
+            }
+            [single, rest @ ..] => {
+                matched |= *single == byte;
+                body = rest;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    #[test]
+    fn glob_matches_redis_patterns() {
+        assert!(glob_match(b"analytics:*", b"analytics:daily"));
+        assert!(!glob_match(b"analytics:*", b"users:1"));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo"));
+        assert!(glob_match(b"a\\*b", b"a*b"));
+        assert!(!glob_match(b"a\\*b", b"axb"));
+        assert!(glob_match(b"*", b""));
+        let literal = glob_escape("q:*[x]?");
+        assert_eq!(literal, "q:\\*\\[x\\]\\?");
+        assert!(glob_match(literal.as_bytes(), b"q:*[x]?"));
+        assert!(!glob_match(literal.as_bytes(), b"q:a[x]b"));
+    }
+
+    #[test]
+    fn first_matching_rule_selects_backend() {
+        let router = CommandRouter::new(Some(vec![
+            RouteConfig {
+                commands: Some(vec!["scan".to_string(), "KEYS".to_string()]),
+                key_pattern: None,
+                backend: "replica".to_string(),
+            },
+            RouteConfig {
+                commands: Some(vec!["GET".to_string()]),
+                key_pattern: Some("analytics:*".to_string()),
+                backend: "analytics".to_string(),
+            },
+        ]));
+
+        assert_eq!(
+            router.backend_for(&command("SCAN", &["0"])),
+            Some("replica")
+        );
+        assert_eq!(
+            router.backend_for(&command("get", &["analytics:daily"])),
+            Some("analytics")
+        );
+        assert_eq!(router.backend_for(&command("GET", &["users:1"])), None);
+        assert_eq!(router.backend_for(&command("SET", &["analytics:x"])), None);
+    }
+
+    #[test]
+    fn unavailable_backends_are_skipped() {
+        let rule = |backend: &str| RouteConfig {
+            commands: Some(vec!["SCAN".to_string()]),
+            key_pattern: None,
+            backend: backend.to_string(),
+        };
+        let router = CommandRouter::new(Some(vec![rule("replica-a"), rule("replica-b")]));
+        let scan = command("SCAN", &["0"]);
+
+        assert_eq!(
+            router.available_backend_for(&scan, |backend| backend != "replica-a"),
+            Some("replica-b")
+        );
+        assert_eq!(router.available_backend_for(&scan, |_| false), None);
+    }
+
+    #[test]
+    fn first_matching_rule_selects_format() {
+        let router = FormatRouter::new(Some(vec![
+            OutputFormatRuleConfig {
+                commands: Some(vec!["GET".to_string()]),
+                key_pattern: Some("img:*".to_string()),
+                format: "RAW".to_string(),
+            },
+            OutputFormatRuleConfig {
+                commands: None,
+                key_pattern: Some("page:*".to_string()),
+                format: "html".to_string(),
+            },
+        ]));
+
+        assert_eq!(
+            router.extension_for(&command("get", &["img:logo"])),
+            Some("raw")
+        );
+        assert_eq!(
+            router.extension_for(&command("HGET", &["page:home", "body"])),
+            Some("html")
+        );
+        assert_eq!(router.extension_for(&command("GET", &["users:1"])), None);
+        assert_eq!(router.extensions().collect::<Vec<_>>(), ["raw", "html"]);
+    }
+
+    #[test]
+    fn first_matching_rule_names_the_download() {
+        let router = DownloadRouter::new(Some(vec![DownloadRuleConfig {
+            commands: Some(vec!["GET".to_string()]),
+            key_pattern: Some("report:*".to_string()),
+            filename: "{key}.csv".to_string(),
+        }]));
+
+        assert_eq!(
+            router.filename_for(&command("GET", &["report:2024"])),
+            Some("report:2024.csv".to_string())
+        );
+        assert_eq!(router.filename_for(&command("GET", &["users:1"])), None);
+    }
+}
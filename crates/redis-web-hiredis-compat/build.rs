@@ -1,5 +1,5 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     println!("cargo:rerun-if-env-changed=REDIS_WEB_HIREDIS_SRC_DIR");
@@ -51,7 +51,7 @@ fn main() {
     build.compile("hiredis_upstream");
 }
 
-fn resolve_hiredis_source_dir(manifest_dir: &PathBuf) -> PathBuf {
+fn resolve_hiredis_source_dir(manifest_dir: &Path) -> PathBuf {
     let mut candidates = Vec::new();
 
     if let Ok(override_dir) = env::var("REDIS_WEB_HIREDIS_SRC_DIR") {
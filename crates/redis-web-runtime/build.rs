@@ -1,8 +1,10 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 fn main() {
     println!("cargo:rerun-if-changed=proto/redis_web/v1/gateway.proto");
+    println!("cargo:rustc-env=REDIS_WEB_GIT_HASH={}", git_hash());
 
     let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc should exist");
     unsafe {
@@ -19,3 +21,36 @@ fn main() {
         .compile_protos(&["proto/redis_web/v1/gateway.proto"], &["proto"])
         .expect("gRPC proto compilation should succeed");
 }
+
+/// Commit the binary is built from, for `GET /_info`: `REDIS_WEB_GIT_HASH`
+/// when set, as in builds from a source archive, else the checkout's `HEAD`.
+fn git_hash() -> String {
+    println!("cargo:rerun-if-env-changed=REDIS_WEB_GIT_HASH");
+    if let Ok(hash) = env::var("REDIS_WEB_GIT_HASH") {
+        return hash;
+    }
+    let git_dir = Path::new("../../.git");
+    let head = git_dir.join("HEAD");
+    if !head.exists() {
+        return "unknown".to_string();
+    }
+    println!("cargo:rerun-if-changed={}", head.display());
+    if let Some(reference) = std::fs::read_to_string(&head)
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        let reference = git_dir.join(reference);
+        if reference.exists() {
+            println!("cargo:rerun-if-changed={}", reference.display());
+        }
+    }
+    Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,339 @@
+//! Operator endpoints under `/_admin`, mounted when `admin.enabled` is set.
+//!
+//! - `GET /_admin/stats`: runtime statistics as JSON, currently the Pub/Sub
+//!   subscriber counts and counters.
+//! - `GET /_admin/ratelimits`: the per-client stream limits of `sse` and the
+//!   streams each client IP holds against them, and with `quotas` set, what
+//!   each identity used in the current windows and whether it is blocked.
+//! - `DELETE /_admin/ratelimits/{identity}`: resets the `quotas` counters of
+//!   a client IP or user name, lifting its block.
+//! - `GET /_admin/config`: the loaded config with credentials masked, and
+//!   whether each top-level key came from the defaults, the file, or an
+//!   environment variable.
+//! - `GET /_console`: a single-page console, when `admin.console` is set, for
+//!   running commands, browsing keys, and watching channels over `/.json`.
+
+use crate::handler::AppState;
+use crate::pubsub::PubSubStats;
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use redis_web_core::acl::Acl;
+use redis_web_core::config::{AclConfig, AdminConfig, Config};
+use serde_json::{json, Map, Value};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+/// Decides which clients may reach the admin endpoints.
+pub struct AdminAccess {
+    /// One rule set per configured condition; a client must pass all of them.
+    checks: Vec<Acl>,
+    /// Whether Basic auth credentials are required, so browsers can be
+    /// prompted for them.
+    basic_auth: bool,
+}
+
+impl AdminAccess {
+    pub fn new(config: &AdminConfig) -> Self {
+        let ip = config.ip.clone().map(|ip| AclConfig {
+            disabled: None,
+            enabled: Some(vec!["*".to_string()]),
+            http_basic_auth: None,
+            ip: Some(ip),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        });
+        let auth = config.http_basic_auth.clone().map(|auth| AclConfig {
+            disabled: None,
+            enabled: Some(vec!["*".to_string()]),
+            http_basic_auth: Some(auth),
+            ip: None,
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        });
+        // Deny everyone, then allow clients matching the condition.
+        let checks = ip
+            .into_iter()
+            .chain(auth)
+            .map(|allow| {
+                let deny = AclConfig {
+                    disabled: Some(vec!["*".to_string()]),
+                    enabled: None,
+                    http_basic_auth: None,
+                    ip: None,
+                    channels: None,
+                    redis_auth: None,
+                    schedule: None,
+                };
+                Acl::new(Some(vec![deny, allow]))
+            })
+            .collect();
+        Self {
+            checks,
+            basic_auth: config.http_basic_auth.is_some(),
+        }
+    }
+
+    /// Whether the client passes every configured condition. Without any,
+    /// nobody does; startup refuses that config anyway.
+    pub fn allows(&self, ip: IpAddr, headers: &HeaderMap) -> bool {
+        let authorization = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        !self.checks.is_empty()
+            && self
+                .checks
+                .iter()
+                .all(|acl| acl.check(ip, "ADMIN", authorization))
+    }
+}
+
+/// Handles `GET /_admin/stats`.
+pub async fn handle_stats(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = check_access(&state, addr, &headers) {
+        return denied;
+    }
+    let pubsub = state.pubsub.stats().await;
+    let sse = state.sse_limits.stats();
+    let backends: Map<String, Value> = state
+        .backend_health
+        .stats()
+        .into_iter()
+        .map(|backend| {
+            let health = json!({
+                "healthy": backend.healthy,
+                "consecutive_failures": backend.consecutive_failures,
+                "consecutive_successes": backend.consecutive_successes,
+                "ejections": backend.ejections,
+            });
+            (backend.name, health)
+        })
+        .collect();
+    Json(json!({
+        "pubsub": pubsub_stats_json(&pubsub),
+        "backends": backends,
+        "sse": {
+            "open": sse.open,
+            "clients": sse.clients,
+            "rejected_total": sse.rejected_total,
+            "rejected_per_ip": sse.rejected_per_ip,
+            "slow_disconnects": sse.slow_disconnects,
+            "websocket_drops": sse.websocket_drops,
+        },
+    }))
+    .into_response()
+}
+
+/// Handles `GET /_admin/ratelimits`.
+pub async fn handle_ratelimits(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = check_access(&state, addr, &headers) {
+        return denied;
+    }
+    let limits = state.sse_limits.state();
+    let clients: Vec<_> = limits
+        .clients
+        .iter()
+        .map(|&(ip, open)| {
+            json!({
+                "ip": ip.to_string(),
+                "open_streams": open,
+                "remaining": limits.max_per_ip.map(|max| max.saturating_sub(open)),
+            })
+        })
+        .collect();
+    let quotas = match &state.quotas {
+        Some(quotas) => match quotas.usage(state.command_executor.as_ref()).await {
+            Ok(usage) => {
+                let clients: Vec<_> = usage
+                    .into_iter()
+                    .map(|usage| {
+                        json!({
+                            "identity": usage.identity,
+                            "commands_today": usage.commands_today,
+                            "commands_this_month": usage.commands_this_month,
+                            "bytes_today": usage.bytes_today,
+                            "bytes_this_month": usage.bytes_this_month,
+                            "blocked_for": usage.blocked_for,
+                        })
+                    })
+                    .collect();
+                json!({"clients": clients})
+            }
+            Err(error) => return state.errors.execution_response(&error, &headers, None),
+        },
+        None => Value::Null,
+    };
+    Json(json!({
+        "streams": {
+            "max_connections": limits.max_total,
+            "max_connections_per_ip": limits.max_per_ip,
+            "clients": clients,
+        },
+        "quotas": quotas,
+    }))
+    .into_response()
+}
+
+/// Handles `DELETE /_admin/ratelimits/{identity}`.
+pub async fn handle_reset_ratelimit(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(identity): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = check_access(&state, addr, &headers) {
+        return denied;
+    }
+    // Only mounted with `quotas` set.
+    let Some(quotas) = &state.quotas else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match quotas
+        .reset(state.command_executor.as_ref(), &identity)
+        .await
+    {
+        Ok(cleared) => Json(json!({"identity": identity, "cleared": cleared})).into_response(),
+        Err(error) => state.errors.execution_response(&error, &headers, None),
+    }
+}
+
+/// Describes the loaded config for `GET /_admin/config`.
+pub fn config_document(config: &Config) -> Value {
+    let document = config.redacted_document();
+    let sources: Map<String, Value> = document
+        .as_object()
+        .into_iter()
+        .flat_map(|map| map.keys())
+        .map(|key| (key.clone(), config.sources.source_of(key).into()))
+        .collect();
+    json!({
+        "config": document,
+        "sources": sources,
+        "env": config.sources.env_vars,
+    })
+}
+
+/// Handles `GET /_admin/config`.
+pub async fn handle_config(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    document: Arc<Value>,
+) -> Response {
+    if let Some(denied) = check_access(&state, addr, &headers) {
+        return denied;
+    }
+    Json(document.as_ref()).into_response()
+}
+
+/// The console page, embedded in the binary.
+const CONSOLE_HTML: &str = include_str!("../assets/console.html");
+
+/// Handles `GET /_console`. Browsers missing the admin credentials get a
+/// `401` challenge so they can prompt for them.
+pub async fn handle_console(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    if let Some(denied) = check_access(&state, addr, &headers) {
+        if state.admin.as_ref().is_some_and(|admin| admin.basic_auth) {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Basic realm="redis-web""#)],
+            )
+                .into_response();
+        }
+        return denied;
+    }
+    Html(CONSOLE_HTML).into_response()
+}
+
+/// Returns the rejection for clients the admin config does not allow.
+fn check_access(state: &AppState, addr: SocketAddr, headers: &HeaderMap) -> Option<Response> {
+    let allowed = state
+        .admin
+        .as_ref()
+        .is_some_and(|admin| admin.allows(addr.ip(), headers));
+    if allowed {
+        return None;
+    }
+    let body = state.errors.body("forbidden", "Forbidden", headers);
+    Some((StatusCode::FORBIDDEN, Json(body)).into_response())
+}
+
+fn pubsub_stats_json(stats: &PubSubStats) -> serde_json::Value {
+    let topics: Vec<_> = stats
+        .topics
+        .iter()
+        .map(|topic| {
+            json!({
+                "kind": topic.topic.kind(),
+                "name": topic.topic.name(),
+                "subscribers": topic.subscribers,
+                "messages_delivered": topic.messages_delivered,
+            })
+        })
+        .collect();
+    json!({
+        "topics": topics,
+        "messages_received": stats.messages_received,
+        "messages_delivered": stats.messages_delivered,
+        "messages_lagged": stats.messages_lagged,
+        "messages_filtered": stats.messages_filtered,
+        "reconnects": stats.reconnects,
+        "shard_reconnects": stats.shard_reconnects,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(encoded: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = format!("Basic {encoded}");
+        headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn access_requires_configured_ip_and_credentials() {
+        let access = AdminAccess::new(&AdminConfig {
+            enabled: true,
+            http_basic_auth: Some("ops:secret".to_string()),
+            ip: Some("10.0.0.0/8".to_string()),
+            console: false,
+        });
+        let inside: IpAddr = "10.1.2.3".parse().unwrap();
+        let outside: IpAddr = "192.168.1.1".parse().unwrap();
+
+        // `ops:secret` and `ops:wrong`.
+        let valid = basic("b3BzOnNlY3JldA==");
+        let wrong = basic("b3BzOndyb25n");
+
+        assert!(access.allows(inside, &valid));
+        assert!(!access.allows(inside, &wrong));
+        assert!(!access.allows(inside, &HeaderMap::new()));
+        assert!(!access.allows(outside, &valid));
+
+        let unguarded = AdminAccess::new(&AdminConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        assert!(!unguarded.allows(inside, &valid));
+    }
+}
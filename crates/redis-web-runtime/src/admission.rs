@@ -0,0 +1,233 @@
+//! Routing, access checks and hooks shared by every entry point that runs
+//! client commands: the command URLs, WebSockets, `POST /_query`,
+//! `POST /_publish`, gRPC and MQTT.
+//!
+//! An entry point runs the `before` hooks ([`AppState::hooks`]) on a command,
+//! then [`route`]s it, which also picks its Redis user, [`authorize`]s it, [`charge`]s it to the caller's
+//! `quotas`, and runs it with [`execute`] or its siblings, which pass the
+//! reply through the `after` hooks. Each outcome goes to the `audit` stream
+//! through [`audit`].
+
+use crate::handler::{execution_error_status, AppState};
+use crate::quota::{QuotaDecision, Quotas};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::Response;
+use redis::Value as RedisValue;
+use redis_web_core::acl::Acl;
+use redis_web_core::interfaces::{
+    AuthContext, CommandExecutionError, CommandSession, ExecutableCommand,
+};
+use std::time::Instant;
+
+/// Picks the Redis user `command` runs as from the `acl` rules matching its
+/// caller, then sends it to the backend its `routes` rule names, unless it
+/// already names one. Ejected backends are skipped, and a command for the
+/// default database moves to the backend's default database.
+///
+/// Runs after the `before` hooks, so no hook picks the Redis user.
+pub(crate) fn route(state: &AppState, auth: &AuthContext, command: &mut ExecutableCommand) {
+    command.credentials = state.acl.credentials_for(auth);
+    if command.backend.is_some() {
+        return;
+    }
+    let healthy = |backend: &str| state.backend_health.is_healthy(backend);
+    let Some(backend) = state.routes.available_backend_for(command, healthy) else {
+        return;
+    };
+    if command.target_database == state.default_database {
+        if let Some(&database) = state.backend_databases.get(backend) {
+            command.target_database = database;
+        }
+    }
+    command.backend = Some(backend.to_string());
+}
+
+/// Whether the `acl` rules, and those of the backend the command runs on,
+/// allow it.
+pub(crate) fn authorize(state: &AppState, auth: &AuthContext, command: &ExecutableCommand) -> bool {
+    check_acls(state, command, |acl| {
+        acl.check_auth(auth, &command.command_name)
+    })
+}
+
+/// Like [`authorize`], for a Pub/Sub command on `channel`, so that rules
+/// with `channels` apply.
+pub(crate) fn authorize_channel(
+    state: &AppState,
+    auth: &AuthContext,
+    command: &ExecutableCommand,
+    channel: &str,
+) -> bool {
+    check_acls(state, command, |acl| {
+        acl.check_channel(auth, &command.command_name, channel)
+    })
+}
+
+fn check_acls(state: &AppState, command: &ExecutableCommand, check: impl Fn(&Acl) -> bool) -> bool {
+    let backend_allowed = command
+        .backend
+        .as_ref()
+        .and_then(|backend| state.backend_acls.get(backend))
+        .is_none_or(&check);
+    backend_allowed && check(&state.acl)
+}
+
+/// What a caller whose commands passed [`charge`] has left of its `quotas`.
+pub(crate) struct Charge {
+    /// The caller's identity, when `quotas` is set.
+    identity: Option<String>,
+    decision: QuotaDecision,
+}
+
+impl Charge {
+    /// Counts `bytes` of reply sent over a WebSocket or gRPC.
+    pub(crate) fn record_bytes(&self, state: &AppState, bytes: u64) {
+        if let (Some(quotas), Some(identity)) = (&state.quotas, &self.identity) {
+            quotas.record_bytes(state.command_executor.clone(), identity.clone(), bytes);
+        }
+    }
+
+    /// Sets the `X-Quota-Remaining` headers of an HTTP response and counts its
+    /// body as it is sent.
+    pub(crate) fn meter(self, state: &AppState, response: Response) -> Response {
+        let (Some(quotas), Some(identity)) = (&state.quotas, self.identity) else {
+            return response;
+        };
+        let (mut parts, body) = response.into_parts();
+        self.decision.add_headers(&mut parts.headers);
+        let body = quotas.meter(state.command_executor.clone(), identity, body);
+        Response::from_parts(parts, body)
+    }
+}
+
+/// Counts `commands` client commands against the `quotas` of their caller,
+/// once they passed the ACL checks. `Err` holds the seconds until the
+/// exhausted window resets.
+pub(crate) async fn charge(
+    state: &AppState,
+    auth: &AuthContext,
+    commands: u64,
+) -> Result<Charge, u64> {
+    let Some(quotas) = &state.quotas else {
+        return Ok(Charge {
+            identity: None,
+            decision: QuotaDecision::Allowed {
+                commands: None,
+                bytes: None,
+            },
+        });
+    };
+    let identity = Quotas::identity(&state.acl, auth);
+    match quotas
+        .admit(state.command_executor.as_ref(), &identity, commands)
+        .await
+    {
+        QuotaDecision::Exceeded { retry_after } => Err(retry_after),
+        decision => Ok(Charge {
+            identity: Some(identity),
+            decision,
+        }),
+    }
+}
+
+/// The HTTP response to a caller over its `quotas`.
+pub(crate) fn quota_exceeded(
+    state: &AppState,
+    headers: &HeaderMap,
+    retry_after: u64,
+    jsonp_callback: Option<&str>,
+) -> Response {
+    let mut response = state.errors.response(
+        StatusCode::TOO_MANY_REQUESTS,
+        "quota_exceeded",
+        "Usage quota exceeded",
+        headers,
+        jsonp_callback,
+    );
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    QuotaDecision::Exceeded { retry_after }.add_headers(response.headers_mut());
+    response
+}
+
+/// Appends `command` to the `audit` stream, when configured and the command
+/// is one it records. `status` is the HTTP status of its outcome, or what a
+/// command URL would have answered on other transports.
+pub(crate) fn audit(
+    state: &AppState,
+    auth: &AuthContext,
+    command: &ExecutableCommand,
+    status: StatusCode,
+    started: Instant,
+) {
+    if let Some(audit) = &state.audit {
+        let identity = Quotas::identity(&state.acl, auth);
+        audit.record(
+            &state.command_executor,
+            &identity,
+            command,
+            status,
+            started.elapsed(),
+        );
+    }
+}
+
+/// The status [`audit`] records for a command that ran: `200`, or the status
+/// of its error.
+pub(crate) fn outcome_status<T>(outcome: &Result<T, CommandExecutionError>) -> StatusCode {
+    match outcome {
+        Ok(_) => StatusCode::OK,
+        Err(error) => execution_error_status(error),
+    }
+}
+
+/// Runs a client command and passes its reply through [`complete`].
+pub(crate) async fn execute(
+    state: &AppState,
+    command: &ExecutableCommand,
+) -> Result<RedisValue, CommandExecutionError> {
+    let mut reply = state.command_executor.execute(command).await?;
+    complete(state, command, &mut reply).await;
+    Ok(reply)
+}
+
+/// Like [`execute`], on a WebSocket's own connection.
+pub(crate) async fn execute_in_session(
+    state: &AppState,
+    session: &mut CommandSession,
+    command: &ExecutableCommand,
+) -> Result<RedisValue, CommandExecutionError> {
+    let mut reply = state
+        .command_executor
+        .execute_in_session(session, command)
+        .await?;
+    complete(state, command, &mut reply).await;
+    Ok(reply)
+}
+
+/// Like [`execute`], for commands sent in one pipeline.
+pub(crate) async fn execute_pipeline(
+    state: &AppState,
+    commands: &[ExecutableCommand],
+) -> Result<Vec<RedisValue>, CommandExecutionError> {
+    let mut replies = state.command_executor.execute_pipeline(commands).await?;
+    for (command, reply) in commands.iter().zip(&mut replies) {
+        complete(state, command, reply).await;
+    }
+    Ok(replies)
+}
+
+/// Follows up a command that succeeded: updates the `last_modified`
+/// timestamps of the keys it wrote, then passes its reply through the
+/// `after` hooks.
+pub(crate) async fn complete(
+    state: &AppState,
+    command: &ExecutableCommand,
+    reply: &mut RedisValue,
+) {
+    if state.last_modified {
+        crate::last_modified::record(state.command_executor.as_ref(), command, reply).await;
+    }
+    state.hooks.after(command, reply);
+}
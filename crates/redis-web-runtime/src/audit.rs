@@ -0,0 +1,376 @@
+//! Audit trail in a Redis Stream.
+//!
+//! With `audit` set, every client command that writes, and every command
+//! refused with `403`, appends a record to a capped stream on the top-level
+//! Redis. Entry points record their commands through `admission::audit`:
+//!
+//! ```text
+//! XADD redis-web:audit MAXLEN ~ 100000 * ts 1718000000123 identity alice
+//!     command SET key user:1 status 200 latency_ms 0.84
+//! ```
+//!
+//! Other consumers can tail the stream with `XREAD` or a consumer group.
+//! Records are written in the background and never delay or fail the command.
+
+use axum::http::StatusCode;
+use redis_web_core::config::Config;
+use redis_web_core::interfaces::{CommandExecutor, ExecutableCommand};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const DEFAULT_STREAM: &str = "redis-web:audit";
+pub const DEFAULT_MAX_LEN: u64 = 100_000;
+
+/// Commands that change data, sorted for binary search.
+const WRITE_COMMANDS: &[&str] = &[
+    "APPEND",
+    "BITFIELD",
+    "BITOP",
+    "BLMOVE",
+    "BLMPOP",
+    "BLPOP",
+    "BRPOP",
+    "BRPOPLPUSH",
+    "BZMPOP",
+    "BZPOPMAX",
+    "BZPOPMIN",
+    "COPY",
+    "DECR",
+    "DECRBY",
+    "DEL",
+    "EVAL",
+    "EVALSHA",
+    "EXEC",
+    "EXPIRE",
+    "EXPIREAT",
+    "FCALL",
+    "FLUSHALL",
+    "FLUSHDB",
+    "GEOADD",
+    "GEORADIUS",
+    "GEORADIUSBYMEMBER",
+    "GEOSEARCHSTORE",
+    "GETDEL",
+    "GETEX",
+    "GETSET",
+    "HDEL",
+    "HINCRBY",
+    "HINCRBYFLOAT",
+    "HMSET",
+    "HSET",
+    "HSETNX",
+    "INCR",
+    "INCRBY",
+    "INCRBYFLOAT",
+    "LINSERT",
+    "LMOVE",
+    "LMPOP",
+    "LPOP",
+    "LPUSH",
+    "LPUSHX",
+    "LREM",
+    "LSET",
+    "LTRIM",
+    "MOVE",
+    "MSET",
+    "MSETNX",
+    "PERSIST",
+    "PEXPIRE",
+    "PEXPIREAT",
+    "PFADD",
+    "PFMERGE",
+    "PSETEX",
+    "RENAME",
+    "RENAMENX",
+    "RESTORE",
+    "RPOP",
+    "RPOPLPUSH",
+    "RPUSH",
+    "RPUSHX",
+    "SADD",
+    "SDIFFSTORE",
+    "SET",
+    "SETBIT",
+    "SETEX",
+    "SETNX",
+    "SETRANGE",
+    "SINTERSTORE",
+    "SMOVE",
+    "SORT",
+    "SPOP",
+    "SREM",
+    "SUNIONSTORE",
+    "SWAPDB",
+    "UNLINK",
+    "XACK",
+    "XADD",
+    "XAUTOCLAIM",
+    "XCLAIM",
+    "XDEL",
+    "XGROUP",
+    "XSETID",
+    "XTRIM",
+    "ZADD",
+    "ZDIFFSTORE",
+    "ZINCRBY",
+    "ZINTERSTORE",
+    "ZMPOP",
+    "ZPOPMAX",
+    "ZPOPMIN",
+    "ZRANGESTORE",
+    "ZREM",
+    "ZREMRANGEBYLEX",
+    "ZREMRANGEBYRANK",
+    "ZREMRANGEBYSCORE",
+    "ZUNIONSTORE",
+];
+
+/// Writes whose first argument is not a key, when they name no other.
+const KEYLESS_FIRST_ARGUMENT: &[&str] = &[
+    "EVAL", "EVALSHA", "EXEC", "FCALL", "FLUSHALL", "FLUSHDB", "SWAPDB",
+];
+
+/// Appends audit records to the configured stream.
+#[derive(Debug)]
+pub struct AuditLog {
+    stream: String,
+    max_len: u64,
+    writes: bool,
+    denied: bool,
+    database: u8,
+}
+
+impl AuditLog {
+    /// The audit log of `config`, or `None` when `audit` is unset.
+    pub fn new(config: &Config) -> Option<Self> {
+        let settings = config.audit.as_ref()?;
+        Some(Self {
+            stream: settings
+                .stream
+                .clone()
+                .unwrap_or_else(|| DEFAULT_STREAM.to_string()),
+            max_len: settings.max_len.unwrap_or(DEFAULT_MAX_LEN),
+            writes: settings.writes.unwrap_or(true),
+            denied: settings.denied.unwrap_or(true),
+            database: config.database,
+        })
+    }
+
+    /// Whether a request for `command` answered with `status` is recorded.
+    fn records(&self, command: &ExecutableCommand, status: StatusCode) -> bool {
+        (self.denied && status == StatusCode::FORBIDDEN)
+            || (self.writes && is_write(&command.command_name))
+    }
+
+    /// The `XADD` of a record, or `None` when the request is not recorded.
+    fn entry(
+        &self,
+        identity: &str,
+        command: &ExecutableCommand,
+        status: StatusCode,
+        latency: Duration,
+        now: SystemTime,
+    ) -> Option<ExecutableCommand> {
+        if !self.records(command, status) {
+            return None;
+        }
+        let timestamp = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let mut args: Vec<Vec<u8>> = vec![
+            self.stream.clone().into_bytes(),
+            b"MAXLEN".to_vec(),
+            b"~".to_vec(),
+            self.max_len.to_string().into_bytes(),
+            b"*".to_vec(),
+        ];
+        let fields = [
+            ("ts", timestamp.to_string().into_bytes()),
+            ("identity", identity.as_bytes().to_vec()),
+            (
+                "command",
+                command.command_name.to_ascii_uppercase().into_bytes(),
+            ),
+            ("key", record_key(command)),
+            ("status", status.as_u16().to_string().into_bytes()),
+            (
+                "latency_ms",
+                format!("{:.2}", latency.as_secs_f64() * 1000.0).into_bytes(),
+            ),
+        ];
+        for (name, value) in fields {
+            args.push(name.as_bytes().to_vec());
+            args.push(value);
+        }
+        Some(ExecutableCommand {
+            backend: None,
+            target_database: self.database,
+            command_name: "XADD".to_string(),
+            args,
+            timeout: None,
+            credentials: None,
+        })
+    }
+
+    /// Appends the record of a request in the background, if it is one that
+    /// is recorded.
+    pub fn record(
+        &self,
+        executor: &Arc<dyn CommandExecutor>,
+        identity: &str,
+        command: &ExecutableCommand,
+        status: StatusCode,
+        latency: Duration,
+    ) {
+        let Some(entry) = self.entry(identity, command, status, latency, SystemTime::now()) else {
+            return;
+        };
+        let executor = executor.clone();
+        tokio::spawn(async move {
+            if let Err(error) = executor.execute(&entry).await {
+                warn!("Cannot append to the audit stream: {error}");
+            }
+        });
+    }
+}
+
+/// The first key `command` names, empty without one.
+fn record_key(command: &ExecutableCommand) -> Vec<u8> {
+    let name = command.command_name.to_ascii_uppercase();
+    let key = match crate::cluster::command_keys(&name, &command.args).first() {
+        Some(key) => Some(*key),
+        None if name == "BITOP" => command.args.get(1).map(Vec::as_slice),
+        None if KEYLESS_FIRST_ARGUMENT.contains(&name.as_str()) => None,
+        None => command.args.first().map(Vec::as_slice),
+    };
+    key.unwrap_or_default().to_vec()
+}
+
+pub(crate) fn is_write(command_name: &str) -> bool {
+    WRITE_COMMANDS
+        .binary_search(&command_name.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    #[test]
+    fn records_writes_and_denied_requests() {
+        assert!(WRITE_COMMANDS.windows(2).all(|pair| pair[0] < pair[1]));
+        let config = Config::from_value(json!({"database": 2, "audit": {"max_len": 500}})).unwrap();
+        let audit = AuditLog::new(&config).unwrap();
+        let now = UNIX_EPOCH + Duration::from_millis(1_718_000_000_123);
+        let latency = Duration::from_micros(840);
+
+        let entry = audit
+            .entry(
+                "alice",
+                &command("set", &["user:1", "x"]),
+                StatusCode::OK,
+                latency,
+                now,
+            )
+            .unwrap();
+        assert_eq!(entry.target_database, 2);
+        let args: Vec<String> = entry
+            .args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "redis-web:audit",
+                "MAXLEN",
+                "~",
+                "500",
+                "*",
+                "ts",
+                "1718000000123",
+                "identity",
+                "alice",
+                "command",
+                "SET",
+                "key",
+                "user:1",
+                "status",
+                "200",
+                "latency_ms",
+                "0.84"
+            ]
+        );
+
+        let get = command("GET", &["user:1"]);
+        assert!(audit
+            .entry("alice", &get, StatusCode::OK, latency, now)
+            .is_none());
+        assert!(audit
+            .entry("alice", &get, StatusCode::FORBIDDEN, latency, now)
+            .is_some());
+
+        let config = Config::from_value(json!({"audit": {"writes": false}})).unwrap();
+        let audit = AuditLog::new(&config).unwrap();
+        let set = command("SET", &["k", "v"]);
+        assert!(audit
+            .entry("bob", &set, StatusCode::OK, latency, now)
+            .is_none());
+    }
+
+    #[test]
+    fn records_the_keys_commands_name() {
+        let config = Config::from_value(json!({"audit": {}})).unwrap();
+        let audit = AuditLog::new(&config).unwrap();
+        let key = |command: &ExecutableCommand| {
+            let entry = audit
+                .entry("alice", command, StatusCode::OK, Duration::ZERO, UNIX_EPOCH)
+                .unwrap();
+            let at = entry.args.iter().position(|arg| arg == b"key").unwrap();
+            String::from_utf8(entry.args[at + 1].clone()).unwrap()
+        };
+
+        let eval = command(
+            "EVAL",
+            &["return redis.call('DEL', KEYS[1])", "1", "user:1"],
+        );
+        assert_eq!(key(&eval), "user:1");
+        assert_eq!(key(&command("EVALSHA", &["a1b2c3", "0"])), "");
+        assert_eq!(key(&command("FCALL", &["purge", "1", "user:2"])), "user:2");
+        assert_eq!(key(&command("LMPOP", &["2", "a", "b", "LEFT"])), "a");
+        assert_eq!(key(&command("BITOP", &["AND", "dest", "a", "b"])), "dest");
+        assert_eq!(key(&command("FLUSHDB", &["ASYNC"])), "");
+    }
+
+    #[test]
+    fn records_transactions_on_exec() {
+        let config = Config::from_value(json!({"audit": {}})).unwrap();
+        let audit = AuditLog::new(&config).unwrap();
+        let record = |name: &str| {
+            audit
+                .entry(
+                    "alice",
+                    &command(name, &[]),
+                    StatusCode::OK,
+                    Duration::ZERO,
+                    UNIX_EPOCH,
+                )
+                .is_some()
+        };
+        assert!(!record("MULTI"));
+        assert!(record("EXEC"));
+    }
+}
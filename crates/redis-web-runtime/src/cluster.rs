@@ -0,0 +1,310 @@
+//! Redis Cluster command routing.
+//!
+//! When `redis_cluster.nodes` is configured, command traffic goes through a
+//! single [`redis::cluster_async::ClusterConnection`] instead of the per-database
+//! pools. The cluster connection discovers the topology from any reachable seed
+//! node, routes each command by hash slot, follows `MOVED`/`ASK` redirects, and
+//! refreshes its slot map when the cluster reshards.
+//!
+//! Multi-key commands are checked up front: if their keys hash to different
+//! slots the request is rejected with a `CROSSSLOT` error instead of being
+//! split or forwarded. Redis Cluster only exposes database `0`, so DB-prefixed
+//! requests for other databases are rejected as well.
+
+use crate::executor::{redis_error_message, CommandTimeouts};
+use crate::server_timing;
+use redis::cluster::{ClusterClient, ClusterClientBuilder};
+use redis::cluster_async::ClusterConnection;
+use redis::cluster_routing::Slot;
+use redis::{cmd, ConnectionAddr, ErrorKind, IntoConnectionInfo};
+use redis_web_core::config::{Config as AppConfig, RedisAuthConfig};
+use redis_web_core::interfaces::{
+    CommandExecutionError, CommandExecutor, ExecutableCommand, ExecutionFuture,
+};
+use std::time::Instant;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+/// Builds a cluster client from `redis_cluster.nodes` and the shared auth/TLS settings.
+pub fn create_cluster_client(config: &AppConfig) -> Result<ClusterClient, redis::RedisError> {
+    cluster_client_builder(config)?.build()
+}
+
+/// Returns a cluster client builder with seeds, protocol, and credentials applied.
+pub fn cluster_client_builder(
+    config: &AppConfig,
+) -> Result<ClusterClientBuilder, redis::RedisError> {
+    let nodes = cluster_node_urls(config)?;
+    info!("Creating Redis cluster client: seeds={}", nodes.join(","));
+
+    let mut builder =
+        ClusterClientBuilder::new(nodes).use_protocol(crate::redis::protocol_version(config));
+    match &config.redis_auth {
+        Some(RedisAuthConfig::Legacy(password)) => builder = builder.password(password),
+        Some(RedisAuthConfig::ACL(creds)) if creds.len() == 2 => {
+            builder = builder.username(&creds[0]).password(&creds[1]);
+        }
+        _ => {}
+    }
+    Ok(builder)
+}
+
+/// Returns a copy of `config` pointing `redis_host` / `redis_port` at the first seed node.
+///
+/// Pub/Sub and hiredis-compat sessions use plain connections. `PUBLISH` is
+/// propagated to every cluster node, so subscribing through one seed is enough.
+pub fn seed_node_config(config: &AppConfig) -> Result<AppConfig, redis::RedisError> {
+    let first = cluster_node_urls(config)?.remove(0);
+    let info = first.as_str().into_connection_info()?;
+    let (host, port) = match info.addr() {
+        ConnectionAddr::Tcp(host, port) | ConnectionAddr::TcpTls { host, port, .. } => {
+            (host.clone(), *port)
+        }
+        _ => return Err(invalid_cluster_config("seed nodes must be TCP addresses")),
+    };
+
+    let mut seed = config.clone();
+    seed.redis_host = host;
+    seed.redis_port = port;
+    seed.redis_hosts = Vec::new();
+    seed.redis_socket = None;
+    seed.database = 0;
+    Ok(seed)
+}
+
+fn cluster_node_urls(config: &AppConfig) -> Result<Vec<String>, redis::RedisError> {
+    let nodes = config
+        .redis_cluster
+        .as_ref()
+        .map(|cluster| cluster.nodes.as_slice())
+        .unwrap_or_default();
+    if nodes.is_empty() {
+        return Err(invalid_cluster_config(
+            "redis_cluster.nodes must list at least one seed node",
+        ));
+    }
+    if config.database != 0 {
+        return Err(invalid_cluster_config(
+            "Redis Cluster only supports database 0",
+        ));
+    }
+
+    let scheme = if config.ssl.as_ref().map(|ssl| ssl.enabled).unwrap_or(false) {
+        "rediss"
+    } else {
+        "redis"
+    };
+    Ok(nodes
+        .iter()
+        .map(|node| {
+            if node.contains("://") {
+                node.clone()
+            } else {
+                format!("{scheme}://{node}")
+            }
+        })
+        .collect())
+}
+
+fn invalid_cluster_config(detail: &str) -> redis::RedisError {
+    redis::RedisError::from((
+        ErrorKind::InvalidClientConfig,
+        "invalid redis_cluster config",
+        detail.to_string(),
+    ))
+}
+
+/// Executes commands against a Redis Cluster.
+///
+/// The cluster connection is established on first use so startup does not
+/// depend on the cluster being reachable, matching the lazy pool behavior.
+pub struct ClusterCommandExecutor {
+    client: ClusterClient,
+    connection: OnceCell<ClusterConnection>,
+    timeouts: CommandTimeouts,
+}
+
+impl ClusterCommandExecutor {
+    pub fn new(client: ClusterClient) -> Self {
+        Self {
+            client,
+            connection: OnceCell::new(),
+            timeouts: CommandTimeouts::default(),
+        }
+    }
+
+    /// Applies a command timeout policy to this executor.
+    pub fn with_timeouts(mut self, timeouts: CommandTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    async fn connection(&self) -> Result<ClusterConnection, CommandExecutionError> {
+        self.connection
+            .get_or_try_init(|| self.client.get_async_connection())
+            .await
+            .cloned()
+            .map_err(|error| CommandExecutionError::ServiceUnavailable(error.to_string()))
+    }
+}
+
+impl CommandExecutor for ClusterCommandExecutor {
+    fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            if let Some(backend) = request.backend.as_deref() {
+                return Err(CommandExecutionError::Rejected(format!(
+                    "named backend {backend} is not available in cluster mode"
+                )));
+            }
+            if request.target_database != 0 {
+                return Err(CommandExecutionError::Rejected(format!(
+                    "Redis Cluster only supports database 0, got database {}",
+                    request.target_database
+                )));
+            }
+            ensure_single_slot(&request.command_name, &request.args)
+                .map_err(CommandExecutionError::Rejected)?;
+
+            let mut connection = self.connection().await?;
+            let mut redis_command = cmd(request.command_name.as_str());
+            for arg in &request.args {
+                redis_command.arg(arg);
+            }
+
+            let started = Instant::now();
+            let result = match self.timeouts.effective(request.timeout) {
+                Some(limit) => {
+                    tokio::time::timeout(limit, redis_command.query_async(&mut connection))
+                        .await
+                        .map_err(|_| limit)
+                }
+                None => Ok(redis_command.query_async(&mut connection).await),
+            };
+            server_timing::record_redis(started.elapsed());
+
+            // The cluster connection is multiplexed, so a late reply is simply
+            // discarded and the connection stays usable.
+            match result {
+                Ok(result) => result.map_err(|error| {
+                    CommandExecutionError::ExecutionFailed(redis_error_message(&error))
+                }),
+                Err(limit) => {
+                    warn!(
+                        "Redis cluster command timed out: command={} timeout_ms={}",
+                        request.command_name,
+                        limit.as_millis()
+                    );
+                    Err(CommandExecutionError::Timeout(format!(
+                        "command timed out after {}ms",
+                        limit.as_millis()
+                    )))
+                }
+            }
+        })
+    }
+}
+
+/// Rejects multi-key commands whose keys hash to different cluster slots.
+pub fn ensure_single_slot(command_name: &str, args: &[Vec<u8>]) -> Result<(), String> {
+    let keys = command_keys(command_name, args);
+    let Some((first, rest)) = keys.split_first() else {
+        return Ok(());
+    };
+
+    let slot = Slot::for_key(first);
+    if rest.iter().all(|key| Slot::for_key(key) == slot) {
+        Ok(())
+    } else {
+        Err(format!(
+            "CROSSSLOT keys in {} don't hash to the same slot; use a hash tag such as {{user1}}:a and {{user1}}:b",
+            command_name.to_ascii_uppercase()
+        ))
+    }
+}
+
+/// Extracts key arguments for multi-key commands.
+///
+/// Single-key commands return an empty list because they can never span slots.
+pub(crate) fn command_keys<'a>(command_name: &str, args: &'a [Vec<u8>]) -> Vec<&'a [u8]> {
+    let all = |args: &'a [Vec<u8>]| args.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    match command_name.to_ascii_uppercase().as_str() {
+        "DEL" | "UNLINK" | "EXISTS" | "TOUCH" | "MGET" | "WATCH" | "SINTER" | "SUNION"
+        | "SDIFF" | "SINTERSTORE" | "SUNIONSTORE" | "SDIFFSTORE" | "PFCOUNT" | "PFMERGE" => {
+            all(args)
+        }
+        "MSET" | "MSETNX" => args.iter().step_by(2).map(Vec::as_slice).collect(),
+        "RENAME" | "RENAMENX" | "RPOPLPUSH" | "BRPOPLPUSH" | "SMOVE" | "LMOVE" | "BLMOVE"
+        | "COPY" => all(&args[..args.len().min(2)]),
+        "BLPOP" | "BRPOP" | "BZPOPMIN" | "BZPOPMAX" => all(&args[..args.len().saturating_sub(1)]),
+        "EVAL" | "EVALSHA" | "EVAL_RO" | "EVALSHA_RO" | "FCALL" | "FCALL_RO" | "BLMPOP"
+        | "BZMPOP" => numkeys_keys(args, 1),
+        "ZUNION" | "ZINTER" | "ZDIFF" | "SINTERCARD" | "ZINTERCARD" | "LMPOP" | "ZMPOP" => {
+            numkeys_keys(args, 0)
+        }
+        "ZUNIONSTORE" | "ZINTERSTORE" | "ZDIFFSTORE" => {
+            let mut keys = numkeys_keys(args, 1);
+            if let Some(destination) = args.first() {
+                keys.push(destination);
+            }
+            keys
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the keys following a `numkeys` argument at `numkeys_index`.
+fn numkeys_keys(args: &[Vec<u8>], numkeys_index: usize) -> Vec<&[u8]> {
+    let count = args
+        .get(numkeys_index)
+        .and_then(|raw| std::str::from_utf8(raw).ok())
+        .and_then(|raw| raw.parse::<usize>().ok())
+        .unwrap_or(0);
+    args.iter()
+        .skip(numkeys_index + 1)
+        .take(count)
+        .map(Vec::as_slice)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis_web_core::config::RedisClusterConfig;
+
+    fn args(values: &[&str]) -> Vec<Vec<u8>> {
+        values
+            .iter()
+            .map(|value| value.as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn multi_key_commands_must_share_a_slot() {
+        assert!(ensure_single_slot("MGET", &args(&["{user1}:a", "{user1}:b"])).is_ok());
+        assert!(ensure_single_slot("GET", &args(&["a"])).is_ok());
+
+        let err = ensure_single_slot("mget", &args(&["a", "b"])).unwrap_err();
+        assert!(err.starts_with("CROSSSLOT"));
+        assert!(err.contains("MGET"));
+
+        // Only keys count for MSET; values may hash anywhere.
+        assert!(ensure_single_slot("MSET", &args(&["{k}1", "a", "{k}2", "b"])).is_ok());
+        assert!(ensure_single_slot("EVAL", &args(&["return 1", "2", "a", "b", "x"])).is_err());
+        assert!(ensure_single_slot("EVAL", &args(&["return 1", "1", "a", "b"])).is_ok());
+        assert!(ensure_single_slot("BLPOP", &args(&["{q}1", "{q}2", "0"])).is_ok());
+    }
+
+    #[test]
+    fn seed_node_config_points_at_first_node() {
+        let mut config = AppConfig::default();
+        config.redis_cluster = Some(RedisClusterConfig {
+            nodes: vec!["10.0.0.5:7000".to_string(), "10.0.0.6:7000".to_string()],
+        });
+        let seed = seed_node_config(&config).unwrap();
+        assert_eq!(seed.redis_host, "10.0.0.5");
+        assert_eq!(seed.redis_port, 7000);
+
+        config.redis_cluster = Some(RedisClusterConfig { nodes: Vec::new() });
+        assert!(seed_node_config(&config).is_err());
+    }
+}
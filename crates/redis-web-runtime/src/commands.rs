@@ -0,0 +1,169 @@
+//! Command introspection on `GET /_commands`.
+//!
+//! Lists the Redis commands the caller may run, so frontends can hide
+//! operations instead of discovering `403`s. The list comes from Redis's
+//! `COMMAND` reply, filtered through the `acl` rules for the caller's IP and
+//! credentials:
+//!
+//! ```json
+//! {"commands": [{"name": "GET", "categories": ["@read", "@string", "@fast"], "readonly": true}]}
+//! ```
+//!
+//! `categories` is empty on Redis versions before 6, which do not report ACL
+//! categories.
+
+use crate::handler::AppState;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use redis::Value as RedisValue;
+use redis_web_core::format::json_value_response;
+use redis_web_core::interfaces::{AuthContext, ExecutableCommand};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Handles `GET /_commands`.
+pub async fn handle_commands(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let auth = AuthContext {
+        client_ip: addr.ip(),
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    };
+    let command = ExecutableCommand {
+        backend: None,
+        target_database: state.default_database,
+        command_name: "COMMAND".to_string(),
+        args: Vec::new(),
+        timeout: None,
+        credentials: state.acl.credentials_for(&auth),
+    };
+    let reply = match state.command_executor.execute(&command).await {
+        Ok(reply) => reply,
+        Err(error) => return state.errors.execution_response(&error, &headers, None),
+    };
+
+    let mut commands: Vec<_> = command_infos(reply)
+        .into_iter()
+        .filter(|info| state.acl.check_auth(&auth, &info.name))
+        .collect();
+    commands.sort_by(|a, b| a.name.cmp(&b.name));
+    let commands: Vec<_> = commands
+        .into_iter()
+        .map(|info| {
+            json!({
+                "name": info.name,
+                "categories": info.categories,
+                "readonly": info.readonly,
+            })
+        })
+        .collect();
+    json_value_response(StatusCode::OK, json!({ "commands": commands }), None)
+}
+
+#[derive(Debug, PartialEq)]
+struct CommandInfo {
+    name: String,
+    categories: Vec<String>,
+    readonly: bool,
+}
+
+/// Reads the entries of a `COMMAND` reply: `[name, arity, flags, first key,
+/// last key, step, categories, ...]`.
+fn command_infos(reply: RedisValue) -> Vec<CommandInfo> {
+    let RedisValue::Array(entries) = reply else {
+        return Vec::new();
+    };
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let RedisValue::Array(fields) = entry else {
+                return None;
+            };
+            let mut fields = fields.into_iter();
+            let name = string(fields.next()?)?.to_ascii_uppercase();
+            let flags = strings(fields.nth(1));
+            let categories = strings(fields.nth(3));
+            Some(CommandInfo {
+                name,
+                readonly: flags.iter().any(|flag| flag == "readonly"),
+                categories,
+            })
+        })
+        .collect()
+}
+
+fn string(value: RedisValue) -> Option<String> {
+    match value {
+        RedisValue::BulkString(bytes) => String::from_utf8(bytes).ok(),
+        RedisValue::SimpleString(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Reads a list of strings, which RESP3 may send as a set.
+fn strings(value: Option<RedisValue>) -> Vec<String> {
+    match value {
+        Some(RedisValue::Array(items) | RedisValue::Set(items)) => {
+            items.into_iter().filter_map(string).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(text: &str) -> RedisValue {
+        RedisValue::SimpleString(text.to_string())
+    }
+
+    #[test]
+    fn reads_names_flags_and_categories() {
+        let reply = RedisValue::Array(vec![
+            RedisValue::Array(vec![
+                RedisValue::BulkString(b"get".to_vec()),
+                RedisValue::Int(2),
+                RedisValue::Set(vec![status("readonly"), status("fast")]),
+                RedisValue::Int(1),
+                RedisValue::Int(1),
+                RedisValue::Int(1),
+                RedisValue::Array(vec![status("@read"), status("@string")]),
+            ]),
+            // Redis 5 stops after the key step.
+            RedisValue::Array(vec![
+                RedisValue::BulkString(b"set".to_vec()),
+                RedisValue::Int(-3),
+                RedisValue::Array(vec![status("write")]),
+                RedisValue::Int(1),
+                RedisValue::Int(1),
+                RedisValue::Int(1),
+            ]),
+            RedisValue::Nil,
+        ]);
+        assert_eq!(
+            command_infos(reply),
+            vec![
+                CommandInfo {
+                    name: "GET".to_string(),
+                    categories: vec!["@read".to_string(), "@string".to_string()],
+                    readonly: true,
+                },
+                CommandInfo {
+                    name: "SET".to_string(),
+                    categories: Vec::new(),
+                    readonly: false,
+                },
+            ]
+        );
+    }
+}
@@ -7,7 +7,7 @@ use axum::response::{IntoResponse, Response};
 use futures::{SinkExt, StreamExt};
 use redis::aio::MultiplexedConnection;
 use redis::Value;
-use redis_web_core::config::{CompatHiRedisConfig, Config};
+use redis_web_core::config::Config;
 use redis_web_core::resp;
 use serde_json::json;
 use std::collections::{HashMap, HashSet};
@@ -31,10 +31,7 @@ pub struct CompatSessionSettings {
 
 impl CompatSessionSettings {
     pub fn from_config(config: &Config) -> Self {
-        let cfg = config
-            .compat_hiredis
-            .clone()
-            .unwrap_or_else(CompatHiRedisConfig::default);
+        let cfg = config.compat_hiredis.clone().unwrap_or_default();
         let path_prefix = normalize_prefix(&cfg.path_prefix);
         Self {
             enabled: cfg.enabled,
@@ -65,6 +62,7 @@ pub struct CompatSessionManager {
     settings: CompatSessionSettings,
     command_client: redis::Client,
     pubsub_client: redis::Client,
+    pubsub_name: Arc<str>,
     sessions: RwLock<HashMap<String, Arc<CompatSession>>>,
 }
 
@@ -83,6 +81,7 @@ impl CompatSessionManager {
             settings,
             command_client,
             pubsub_client,
+            pubsub_name: crate::redis::client_name(config, "pubsub").into(),
             sessions: RwLock::new(HashMap::new()),
         })
     }
@@ -116,6 +115,7 @@ impl CompatSessionManager {
             id.clone(),
             conn,
             self.pubsub_client.clone(),
+            self.pubsub_name.clone(),
         ));
 
         let mut sessions = self.sessions.write().await;
@@ -173,12 +173,18 @@ pub struct CompatSession {
     push_tx: broadcast::Sender<Vec<u8>>,
     pubsub_cmd_tx: Mutex<Option<mpsc::Sender<SessionPubSubCommand>>>,
     pubsub_client: redis::Client,
+    pubsub_name: Arc<str>,
     http_pubsub_warning_emitted: AtomicBool,
     has_pubsub: AtomicBool,
 }
 
 impl CompatSession {
-    fn new(id: String, conn: MultiplexedConnection, pubsub_client: redis::Client) -> Self {
+    fn new(
+        id: String,
+        conn: MultiplexedConnection,
+        pubsub_client: redis::Client,
+        pubsub_name: Arc<str>,
+    ) -> Self {
         let (push_tx, _) = broadcast::channel(1024);
         Self {
             id,
@@ -188,6 +194,7 @@ impl CompatSession {
             push_tx,
             pubsub_cmd_tx: Mutex::new(None),
             pubsub_client,
+            pubsub_name,
             http_pubsub_warning_emitted: AtomicBool::new(false),
             has_pubsub: AtomicBool::new(false),
         }
@@ -308,9 +315,10 @@ impl CompatSession {
         let (tx, mut rx) = mpsc::channel::<SessionPubSubCommand>(64);
         let push_tx = self.push_tx.clone();
         let client = self.pubsub_client.clone();
+        let name = self.pubsub_name.clone();
 
         tokio::spawn(async move {
-            let mut pubsub = match client.get_async_pubsub().await {
+            let mut pubsub = match crate::redis::connect_pubsub(&client, Some(&name)).await {
                 Ok(pubsub) => pubsub,
                 Err(error) => {
                     let _ = push_tx
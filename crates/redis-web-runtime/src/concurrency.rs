@@ -0,0 +1,181 @@
+//! Per-backend caps on concurrent commands.
+//!
+//! With `command_concurrency` set, [`LimitedExecutor`] lets each backend, the
+//! top-level Redis included, run at most `max_in_flight` commands at once,
+//! however large its pool is. Up to `max_queued` more wait for a free slot;
+//! beyond that a command fails at once as overloaded, which clients get as
+//! `503` with `Retry-After`. A stampede of slow commands then queues up in
+//! front of Redis instead of piling onto it.
+
+use redis_web_core::config::{CommandConcurrencyConfig, Config};
+use redis_web_core::interfaces::{
+    CommandExecutionError, CommandExecutor, CommandSession, ExecutableCommand, ExecutionFuture,
+    PipelineFuture,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Executor capping the commands each backend runs at once.
+pub struct LimitedExecutor {
+    inner: Arc<dyn CommandExecutor>,
+    default: BackendLimit,
+    backends: HashMap<String, BackendLimit>,
+}
+
+struct BackendLimit {
+    slots: Semaphore,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+impl BackendLimit {
+    fn new(settings: &CommandConcurrencyConfig) -> Self {
+        Self {
+            slots: Semaphore::new(settings.max_in_flight.max(1)),
+            queued: AtomicUsize::new(0),
+            max_queued: settings.max_queued.unwrap_or(0),
+        }
+    }
+
+    async fn acquire(
+        &self,
+        backend: Option<&str>,
+    ) -> Result<SemaphorePermit<'_>, CommandExecutionError> {
+        if let Ok(permit) = self.slots.try_acquire() {
+            return Ok(permit);
+        }
+        let position = self.queued.fetch_add(1, Ordering::AcqRel);
+        let _queued = QueueSlot(&self.queued);
+        if position >= self.max_queued {
+            return Err(CommandExecutionError::Overloaded(format!(
+                "too many concurrent commands for backend {}",
+                backend.unwrap_or("default")
+            )));
+        }
+        let permit = self.slots.acquire().await;
+        Ok(permit.expect("command slots are never closed"))
+    }
+}
+
+/// A place in a backend's queue, given up when the command gets a slot or
+/// its request is dropped while waiting.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl LimitedExecutor {
+    /// Wraps `inner` when `command_concurrency` is set.
+    pub fn wrap(inner: Arc<dyn CommandExecutor>, config: &Config) -> Arc<dyn CommandExecutor> {
+        let Some(settings) = config.command_concurrency.as_ref() else {
+            return inner;
+        };
+        let backends = config
+            .backends
+            .iter()
+            .flatten()
+            .map(|(name, _)| (name.clone(), BackendLimit::new(settings)))
+            .collect();
+        Arc::new(Self {
+            inner,
+            default: BackendLimit::new(settings),
+            backends,
+        })
+    }
+
+    async fn acquire(
+        &self,
+        backend: Option<&str>,
+    ) -> Result<Option<SemaphorePermit<'_>>, CommandExecutionError> {
+        let limit = match backend {
+            Some(name) => match self.backends.get(name) {
+                Some(limit) => limit,
+                // Unknown backends are refused by the inner executor.
+                None => return Ok(None),
+            },
+            None => &self.default,
+        };
+        limit.acquire(backend).await.map(Some)
+    }
+}
+
+impl CommandExecutor for LimitedExecutor {
+    fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            let _permit = self.acquire(request.backend.as_deref()).await?;
+            self.inner.execute(request).await
+        })
+    }
+
+    /// Takes one slot for a pipeline on a single backend; commands spread
+    /// over several backends run one at a time, each in a slot of its own.
+    fn execute_pipeline<'a>(&'a self, requests: &'a [ExecutableCommand]) -> PipelineFuture<'a> {
+        Box::pin(async move {
+            let Some(first) = requests.first() else {
+                return Ok(Vec::new());
+            };
+            if requests
+                .iter()
+                .any(|request| request.backend != first.backend)
+            {
+                let mut replies = Vec::with_capacity(requests.len());
+                for request in requests {
+                    replies.push(self.execute(request).await?);
+                }
+                return Ok(replies);
+            }
+            let _permit = self.acquire(first.backend.as_deref()).await?;
+            self.inner.execute_pipeline(requests).await
+        })
+    }
+
+    fn execute_in_session<'a>(
+        &'a self,
+        session: &'a mut CommandSession,
+        request: &'a ExecutableCommand,
+    ) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            let _permit = self.acquire(request.backend.as_deref()).await?;
+            self.inner.execute_in_session(session, request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn refuses_commands_beyond_the_cap_and_queue() {
+        let limit = BackendLimit::new(&CommandConcurrencyConfig {
+            max_in_flight: 1,
+            max_queued: Some(1),
+        });
+        let running = limit.acquire(None).await.unwrap();
+
+        let queued = limit.acquire(Some("replica"));
+        tokio::pin!(queued);
+        assert!(futures::poll!(queued.as_mut()).is_pending());
+        assert_eq!(limit.queued.load(Ordering::Acquire), 1);
+        assert!(matches!(
+            limit.acquire(Some("replica")).await,
+            Err(CommandExecutionError::Overloaded(message))
+                if message == "too many concurrent commands for backend replica"
+        ));
+
+        drop(running);
+        let next = queued.await.unwrap();
+        assert_eq!(limit.queued.load(Ordering::Acquire), 0);
+
+        let mut abandoned = Box::pin(limit.acquire(None));
+        assert!(futures::poll!(abandoned.as_mut()).is_pending());
+        drop(abandoned);
+        assert_eq!(limit.queued.load(Ordering::Acquire), 0);
+        drop(next);
+    }
+}
@@ -0,0 +1,431 @@
+//! Streamed replies to `GET <key>` and `LRANGE <key> <start> <stop>`.
+//!
+//! redis-rs reads every reply whole, so a multi-megabyte value would sit in
+//! memory next to its JSON-escaped copy for the life of the request. With
+//! `http_get_stream_chunk_size` set, a `GET` of one key first asks for the
+//! value's `STRLEN`; a longer value than one chunk is then read with
+//! `GETRANGE`, one chunk per command, and each chunk is written to the
+//! response as it arrives. Shorter values, missing keys and keys holding
+//! other types take the usual `GET`.
+//!
+//! With `http_list_stream_page_size` set, a JSON `LRANGE` first asks for the
+//! list's `LLEN`; a range of more elements than one page is then read with
+//! one `LRANGE` per page and written out as one JSON array.
+//!
+//! The chunks are separate commands, so a value rewritten mid-stream can reach
+//! the client as a mix of old and new bytes. Streamed replies carry no `ETag`,
+//! since the body is not known when the headers are sent.
+
+use crate::handler::AppState;
+use axum::body::{Body, Bytes};
+use axum::http::header;
+use axum::response::Response;
+use redis::Value as RedisValue;
+use redis_web_core::format::OutputFormat;
+use redis_web_core::interfaces::{CommandExecutionError, ExecutableCommand};
+use redis_web_core::request::ParsedRequest;
+use std::borrow::Cow;
+use std::sync::Arc;
+use tracing::error;
+
+/// Whether a request may be streamed: a JSON or text `GET` of a single key.
+pub(crate) fn is_streamable(parsed: &ParsedRequest) -> bool {
+    parsed.command.command_name.eq_ignore_ascii_case("GET")
+        && parsed.command.args.len() == 1
+        && matches!(
+            parsed.output_format,
+            OutputFormat::Json | OutputFormat::Text
+        )
+}
+
+/// Whether a request may be streamed by pages: a JSON `LRANGE` of one key.
+pub(crate) fn is_list_streamable(parsed: &ParsedRequest) -> bool {
+    parsed.command.command_name.eq_ignore_ascii_case("LRANGE")
+        && parsed.command.args.len() == 3
+        && parsed.output_format == OutputFormat::Json
+}
+
+/// Streams the elements of a streamable `LRANGE` when the range covers more
+/// than `page_size` of them; `None` leaves the request to the usual `LRANGE`.
+pub(crate) async fn stream_lrange(
+    state: &Arc<AppState>,
+    parsed: &ParsedRequest,
+    page_size: usize,
+) -> Option<Response> {
+    let length = match send(state, &parsed.command, "LLEN", Vec::new()).await {
+        Ok(RedisValue::Int(length)) => length,
+        _ => return None,
+    };
+    let index = |arg: &[u8]| std::str::from_utf8(arg).ok()?.parse::<i64>().ok();
+    let (start, stop) = list_range(
+        index(&parsed.command.args[1])?,
+        index(&parsed.command.args[2])?,
+        length,
+    )?;
+    if stop - start < page_size as i64 {
+        return None;
+    }
+
+    let jsonp = parsed.jsonp_callback.as_deref();
+    let content_type = if jsonp.is_some() {
+        "application/javascript; charset=utf-8"
+    } else {
+        "application/json"
+    };
+    let mut prefix = String::new();
+    if let Some(callback) = jsonp {
+        prefix.push_str(callback);
+        prefix.push('(');
+    }
+    prefix.push('{');
+    prefix.push_str(&serde_json::Value::from(parsed.command.command_name.as_str()).to_string());
+    prefix.push_str(":[");
+    let reader = ListReader {
+        state: state.clone(),
+        command: parsed.command.clone(),
+        prefix: Some(Bytes::from(prefix)),
+        suffix: if jsonp.is_some() { "]})" } else { "]}" },
+        next: start,
+        stop,
+        page_size: page_size as i64,
+        written: false,
+        done: false,
+    };
+    let stream = futures::stream::unfold(reader, |mut reader| async move {
+        let item = reader.next().await?;
+        Some((item, reader))
+    });
+    Some(
+        Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from_stream(stream))
+            .unwrap(),
+    )
+}
+
+/// Resolves `LRANGE` indexes, which may count from the end, against a list
+/// of `length` elements; `None` for an empty range.
+fn list_range(start: i64, stop: i64, length: i64) -> Option<(i64, i64)> {
+    let resolve = |index: i64| if index < 0 { length + index } else { index };
+    let start = resolve(start).max(0);
+    let stop = resolve(stop).min(length - 1);
+    (start <= stop).then_some((start, stop))
+}
+
+/// Streams the value of a streamable `GET` when it is longer than
+/// `chunk_size`; `None` leaves the request to the usual `GET`.
+pub(crate) async fn stream_get(
+    state: &Arc<AppState>,
+    parsed: &ParsedRequest,
+    chunk_size: usize,
+) -> Option<Response> {
+    let length = match send(state, &parsed.command, "STRLEN", Vec::new()).await {
+        Ok(RedisValue::Int(length)) => usize::try_from(length).ok()?,
+        // `GET` reports errors, such as a key of the wrong type, as usual.
+        _ => return None,
+    };
+    if length <= chunk_size {
+        return None;
+    }
+
+    let (json, content_type) = match parsed.output_format {
+        OutputFormat::Text => (None, None),
+        _ => {
+            let jsonp = parsed.jsonp_callback.as_deref();
+            let content_type = if jsonp.is_some() {
+                "application/javascript; charset=utf-8"
+            } else {
+                "application/json"
+            };
+            let json = JsonChunks::new(&parsed.command.command_name, jsonp);
+            (Some(json), Some(content_type))
+        }
+    };
+    let reader = Reader {
+        state: state.clone(),
+        command: parsed.command.clone(),
+        offset: 0,
+        length,
+        chunk_size,
+        json,
+        done: false,
+    };
+    let stream = futures::stream::unfold(reader, |mut reader| async move {
+        let item = reader.next().await?;
+        Some((item, reader))
+    });
+
+    let mut response = Response::builder();
+    if let Some(content_type) = content_type {
+        response = response.header(header::CONTENT_TYPE, content_type);
+    }
+    Some(response.body(Body::from_stream(stream)).unwrap())
+}
+
+/// Reads a value chunk by chunk for the response body.
+struct Reader {
+    state: Arc<AppState>,
+    command: ExecutableCommand,
+    offset: usize,
+    /// Length reported by `STRLEN`; bytes appended later are not sent.
+    length: usize,
+    chunk_size: usize,
+    /// Set for JSON replies, `None` for text.
+    json: Option<JsonChunks>,
+    done: bool,
+}
+
+impl Reader {
+    async fn next(&mut self) -> Option<Result<Bytes, CommandExecutionError>> {
+        if self.done {
+            return None;
+        }
+        if let Some(prefix) = self.json.as_mut().and_then(|json| json.prefix.take()) {
+            return Some(Ok(prefix));
+        }
+        if self.offset >= self.length {
+            self.done = true;
+            return self.json.as_mut().map(|json| Ok(json.finish()));
+        }
+
+        let end = (self.offset + self.chunk_size).min(self.length);
+        let range = vec![
+            self.offset.to_string().into_bytes(),
+            (end - 1).to_string().into_bytes(),
+        ];
+        let chunk = match send(&self.state, &self.command, "GETRANGE", range).await {
+            Ok(RedisValue::BulkString(chunk)) => chunk,
+            Ok(other) => {
+                return self.fail(CommandExecutionError::ExecutionFailed(format!(
+                    "Unexpected GETRANGE reply: {other:?}"
+                )))
+            }
+            Err(error) => return self.fail(error),
+        };
+        // A value that shrank since `STRLEN` ends the stream early.
+        self.offset = if chunk.len() < end - self.offset {
+            self.length
+        } else {
+            end
+        };
+        Some(Ok(match &mut self.json {
+            Some(json) => json.push(&chunk),
+            None => Bytes::from(chunk),
+        }))
+    }
+
+    /// Ends the stream with `error`, which aborts the response.
+    fn fail(
+        &mut self,
+        error: CommandExecutionError,
+    ) -> Option<Result<Bytes, CommandExecutionError>> {
+        error!(
+            "Streamed GET failed: db={} error={}",
+            self.command.target_database, error
+        );
+        self.done = true;
+        Some(Err(error))
+    }
+}
+
+/// Reads a list range page by page for the response body.
+struct ListReader {
+    state: Arc<AppState>,
+    command: ExecutableCommand,
+    prefix: Option<Bytes>,
+    suffix: &'static str,
+    /// Index of the next element to read.
+    next: i64,
+    /// Last index of the range, resolved against `LLEN`.
+    stop: i64,
+    page_size: i64,
+    /// Whether an element was written, so the next one needs a comma.
+    written: bool,
+    done: bool,
+}
+
+impl ListReader {
+    async fn next(&mut self) -> Option<Result<Bytes, CommandExecutionError>> {
+        if self.done {
+            return None;
+        }
+        if let Some(prefix) = self.prefix.take() {
+            return Some(Ok(prefix));
+        }
+        if self.next > self.stop {
+            self.done = true;
+            return Some(Ok(Bytes::from_static(self.suffix.as_bytes())));
+        }
+
+        let last = (self.next + self.page_size - 1).min(self.stop);
+        let range = vec![
+            self.next.to_string().into_bytes(),
+            last.to_string().into_bytes(),
+        ];
+        let elements = match send(&self.state, &self.command, "LRANGE", range).await {
+            Ok(RedisValue::Array(elements)) => elements,
+            Ok(other) => {
+                return self.fail(CommandExecutionError::ExecutionFailed(format!(
+                    "Unexpected LRANGE reply: {other:?}"
+                )))
+            }
+            Err(error) => return self.fail(error),
+        };
+        let mut body = Vec::new();
+        for element in &elements {
+            if self.written {
+                body.push(b',');
+            }
+            self.written = true;
+            let element = crate::handler::redis_value_to_json(element.clone());
+            serde_json::to_writer(&mut body, &element).unwrap();
+        }
+        // A list that shrank since `LLEN` ends the stream early.
+        self.next = if (elements.len() as i64) < last - self.next + 1 {
+            self.stop + 1
+        } else {
+            last + 1
+        };
+        Some(Ok(Bytes::from(body)))
+    }
+
+    /// Ends the stream with `error`, which aborts the response.
+    fn fail(
+        &mut self,
+        error: CommandExecutionError,
+    ) -> Option<Result<Bytes, CommandExecutionError>> {
+        error!(
+            "Streamed LRANGE failed: db={} error={}",
+            self.command.target_database, error
+        );
+        self.done = true;
+        Some(Err(error))
+    }
+}
+
+/// Writes `{"<command>":"<value>"}`, escaping the value a chunk at a time.
+///
+/// A UTF-8 sequence cut by a chunk boundary is carried over to the next chunk,
+/// so the body matches `String::from_utf8_lossy` on the whole value.
+struct JsonChunks {
+    prefix: Option<Bytes>,
+    suffix: &'static str,
+    carry: Vec<u8>,
+}
+
+impl JsonChunks {
+    fn new(command: &str, jsonp: Option<&str>) -> Self {
+        let mut prefix = String::new();
+        if let Some(callback) = jsonp {
+            prefix.push_str(callback);
+            prefix.push('(');
+        }
+        prefix.push('{');
+        prefix.push_str(&serde_json::Value::from(command).to_string());
+        prefix.push_str(":\"");
+        JsonChunks {
+            prefix: Some(Bytes::from(prefix)),
+            suffix: if jsonp.is_some() { "\"})" } else { "\"}" },
+            carry: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) -> Bytes {
+        let bytes: Cow<'_, [u8]> = if self.carry.is_empty() {
+            Cow::Borrowed(chunk)
+        } else {
+            let mut bytes = std::mem::take(&mut self.carry);
+            bytes.extend_from_slice(chunk);
+            Cow::Owned(bytes)
+        };
+        let mut text = String::with_capacity(bytes.len());
+        let mut rest = &bytes[..];
+        while let Err(error) = std::str::from_utf8(rest) {
+            let (valid, invalid) = rest.split_at(error.valid_up_to());
+            text.push_str(std::str::from_utf8(valid).unwrap());
+            match error.error_len() {
+                Some(len) => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    rest = &invalid[len..];
+                }
+                None => {
+                    // Incomplete sequence at the end: wait for the next chunk.
+                    self.carry = invalid.to_vec();
+                    rest = &[];
+                }
+            }
+        }
+        text.push_str(std::str::from_utf8(rest).unwrap());
+        escape(&text)
+    }
+
+    fn finish(&mut self) -> Bytes {
+        let carry = std::mem::take(&mut self.carry);
+        let mut body = escape(&String::from_utf8_lossy(&carry)).to_vec();
+        body.extend_from_slice(self.suffix.as_bytes());
+        Bytes::from(body)
+    }
+}
+
+/// The JSON string literal for `text`, without its quotes.
+fn escape(text: &str) -> Bytes {
+    let quoted = Bytes::from(serde_json::to_vec(text).unwrap());
+    quoted.slice(1..quoted.len() - 1)
+}
+
+/// Runs `name` on the key of `command` with its backend, database and timeout.
+async fn send(
+    state: &AppState,
+    command: &ExecutableCommand,
+    name: &str,
+    args: Vec<Vec<u8>>,
+) -> Result<RedisValue, CommandExecutionError> {
+    let mut command_args = vec![command.args[0].clone()];
+    command_args.extend(args);
+    let command = ExecutableCommand {
+        backend: command.backend.clone(),
+        target_database: command.target_database,
+        command_name: name.to_string(),
+        args: command_args,
+        timeout: command.timeout,
+        credentials: command.credentials,
+    };
+    state.command_executor.execute(&command).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn list_ranges_resolve_like_lrange() {
+        assert_eq!(list_range(0, -1, 10), Some((0, 9)));
+        assert_eq!(list_range(-3, 100, 10), Some((7, 9)));
+        assert_eq!(list_range(-100, 2, 10), Some((0, 2)));
+        assert_eq!(list_range(5, 2, 10), None);
+        assert_eq!(list_range(0, -1, 0), None);
+    }
+
+    #[test]
+    fn json_chunks_match_the_whole_value() {
+        let mut value = "caf\u{e9} \u{1f600} \"quoted\"\n".as_bytes().to_vec();
+        value.extend_from_slice(&[0xff, b'x', 0xe2, 0x82]);
+        let expected = json!({"GET": String::from_utf8_lossy(&value)}).to_string();
+        for chunk_size in 1..value.len() {
+            let mut json = JsonChunks::new("GET", None);
+            let mut body = json.prefix.take().unwrap().to_vec();
+            for chunk in value.chunks(chunk_size) {
+                body.extend_from_slice(&json.push(chunk));
+            }
+            body.extend_from_slice(&json.finish());
+            assert_eq!(String::from_utf8(body).unwrap(), expected, "{chunk_size}");
+        }
+
+        let mut json = JsonChunks::new("GET", Some("cb"));
+        let mut body = json.prefix.take().unwrap().to_vec();
+        body.extend_from_slice(&json.push(b"hi"));
+        body.extend_from_slice(&json.finish());
+        assert_eq!(body, br#"cb({"GET":"hi"})"#);
+    }
+}
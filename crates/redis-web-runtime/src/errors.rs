@@ -0,0 +1,155 @@
+//! JSON error bodies.
+//!
+//! An error is `{"error": "<message>"}` by default. `error_format` renames the
+//! message field, adds a machine-readable code, echoes a request id header,
+//! and can nest the fields under one key, so error bodies follow an
+//! organization's API standard:
+//!
+//! ```json
+//! {"error": {"message": "Forbidden", "code": "forbidden", "request_id": "7f3a"}}
+//! ```
+
+use crate::handler::execution_error_status;
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::Response;
+use redis_web_core::config::ErrorFormatConfig;
+use redis_web_core::format::json_value_response;
+use redis_web_core::interfaces::CommandExecutionError;
+use serde_json::{Map, Value};
+
+/// Builds error bodies in the configured shape.
+#[derive(Debug)]
+pub struct ErrorFormat {
+    message_field: String,
+    code_field: Option<String>,
+    request_id: Option<(HeaderName, String)>,
+    envelope: Option<String>,
+}
+
+impl ErrorFormat {
+    /// An invalid `request_id_header` is ignored here; `build_runtime`
+    /// refuses to start with one.
+    pub fn new(config: Option<&ErrorFormatConfig>) -> Self {
+        let config = config.cloned().unwrap_or_default();
+        let request_id = config
+            .request_id_header
+            .and_then(|name| HeaderName::try_from(name).ok())
+            .map(|name| (name, config.request_id_field));
+        ErrorFormat {
+            message_field: config.message_field,
+            code_field: config.code_field,
+            request_id,
+            envelope: config.envelope,
+        }
+    }
+
+    /// The error body for `message`, with `code` and the request id of
+    /// `headers` when configured.
+    pub fn body(&self, code: &str, message: &str, headers: &HeaderMap) -> Value {
+        let mut fields = Map::new();
+        fields.insert(self.message_field.clone(), Value::from(message));
+        if let Some(field) = &self.code_field {
+            fields.insert(field.clone(), Value::from(code));
+        }
+        if let Some((header, field)) = &self.request_id {
+            if let Some(id) = headers.get(header).and_then(|id| id.to_str().ok()) {
+                fields.insert(field.clone(), Value::from(id));
+            }
+        }
+        match &self.envelope {
+            Some(key) => Value::Object(Map::from_iter([(key.clone(), Value::Object(fields))])),
+            None => Value::Object(fields),
+        }
+    }
+
+    pub fn response(
+        &self,
+        status: StatusCode,
+        code: &str,
+        message: &str,
+        headers: &HeaderMap,
+        jsonp_callback: Option<&str>,
+    ) -> Response {
+        json_value_response(status, self.body(code, message, headers), jsonp_callback)
+    }
+
+    /// The error response for a failed command.
+    pub fn execution_response(
+        &self,
+        error: &CommandExecutionError,
+        headers: &HeaderMap,
+        jsonp_callback: Option<&str>,
+    ) -> Response {
+        let response = self.response(
+            execution_error_status(error),
+            execution_error_code(error),
+            &error.to_string(),
+            headers,
+            jsonp_callback,
+        );
+        with_retry_after(response, error)
+    }
+}
+
+/// Adds `Retry-After` when `error` is an overloaded backend or an exhausted
+/// pool, whose slots usually free up within a second.
+pub(crate) fn with_retry_after(mut response: Response, error: &CommandExecutionError) -> Response {
+    if matches!(
+        error,
+        CommandExecutionError::Overloaded(_) | CommandExecutionError::PoolExhausted(_)
+    ) {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+    }
+    response
+}
+
+/// The machine-readable code of a failed command.
+pub fn execution_error_code(error: &CommandExecutionError) -> &'static str {
+    match error {
+        CommandExecutionError::ServiceUnavailable(_) => "unavailable",
+        CommandExecutionError::ExecutionFailed(_) => "command_failed",
+        CommandExecutionError::Timeout(_) => "timeout",
+        CommandExecutionError::Rejected(_) => "rejected",
+        CommandExecutionError::Overloaded(_) => "overloaded",
+        CommandExecutionError::PoolExhausted(_) => "pool_exhausted",
+        CommandExecutionError::ValueTooLarge(_) => "value_too_large",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn default_shape_is_a_bare_message() {
+        let format = ErrorFormat::new(None);
+        assert_eq!(
+            format.body("forbidden", "Forbidden", &HeaderMap::new()),
+            json!({"error": "Forbidden"})
+        );
+    }
+
+    #[test]
+    fn configured_shape_adds_code_and_request_id() {
+        let format = ErrorFormat::new(Some(&ErrorFormatConfig {
+            message_field: "message".to_string(),
+            code_field: Some("code".to_string()),
+            request_id_header: Some("X-Request-Id".to_string()),
+            request_id_field: "request_id".to_string(),
+            envelope: Some("error".to_string()),
+        }));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "7f3a".parse().unwrap());
+        assert_eq!(
+            format.body("forbidden", "Forbidden", &headers),
+            json!({"error": {"message": "Forbidden", "code": "forbidden", "request_id": "7f3a"}})
+        );
+        assert_eq!(
+            format.body("timeout", "slow", &HeaderMap::new()),
+            json!({"error": {"message": "slow", "code": "timeout"}})
+        );
+    }
+}
@@ -1,44 +1,466 @@
-use crate::redis::DatabasePoolRegistry;
-use redis::cmd;
+use crate::redis::{DatabasePoolRegistry, RedisConnection, RedisConnectionError};
+use crate::server_timing;
+use deadpool::managed::{PoolError, TimeoutType};
+use redis::{cmd, Value};
+use redis_web_core::config::{Config, DEFAULT_MAX_COMMAND_TIMEOUT_MS};
 use redis_web_core::interfaces::{
-    CommandExecutionError, CommandExecutor, ExecutableCommand, ExecutionFuture,
+    CommandExecutionError, CommandExecutor, CommandSession, ExecutableCommand, ExecutionFuture,
+    PipelineFuture, RedisCredentials,
 };
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Timeout policy applied to every command executed against Redis.
+///
+/// `default` applies when the request does not ask for a timeout; it is unset
+/// unless `command_timeout_ms` is, so blocking commands are not cut short.
+/// `max` caps client-requested overrides so `?timeout=` can only tighten or modestly
+/// extend the server policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommandTimeouts {
+    pub default: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl CommandTimeouts {
+    pub fn from_config(config: &Config) -> Self {
+        let millis = |value: Option<u64>| value.filter(|&ms| ms > 0).map(Duration::from_millis);
+        Self {
+            default: millis(config.command_timeout_ms),
+            max: millis(Some(
+                config
+                    .max_command_timeout_ms
+                    .unwrap_or(DEFAULT_MAX_COMMAND_TIMEOUT_MS),
+            )),
+        }
+    }
+
+    /// Resolves the timeout for a single command.
+    pub fn effective(&self, requested: Option<Duration>) -> Option<Duration> {
+        match (requested, self.max) {
+            (Some(requested), Some(max)) => Some(requested.min(max)),
+            (Some(requested), None) => Some(requested),
+            (None, _) => self.default,
+        }
+    }
+}
 
 /// Default Redis-backed executor for parsed Webdis requests.
 pub struct RedisCommandExecutor {
     redis_pools: Arc<DatabasePoolRegistry>,
+    backends: HashMap<String, Arc<DatabasePoolRegistry>>,
+    /// Top-level Redis pools per `acl` rule with its own `redis_auth`.
+    credentials: HashMap<RedisCredentials, Arc<DatabasePoolRegistry>>,
+    timeouts: CommandTimeouts,
 }
 
 impl RedisCommandExecutor {
     pub fn new(redis_pools: Arc<DatabasePoolRegistry>) -> Self {
-        Self { redis_pools }
+        Self {
+            redis_pools,
+            backends: HashMap::new(),
+            credentials: HashMap::new(),
+            timeouts: CommandTimeouts::default(),
+        }
+    }
+
+    /// Registers pools for named backends selected by `ExecutableCommand::backend`.
+    pub fn with_backends(mut self, backends: HashMap<String, Arc<DatabasePoolRegistry>>) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    /// Registers top-level Redis pools selected by `ExecutableCommand::credentials`.
+    pub fn with_credentials(
+        mut self,
+        credentials: HashMap<RedisCredentials, Arc<DatabasePoolRegistry>>,
+    ) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Applies a command timeout policy to this executor.
+    pub fn with_timeouts(mut self, timeouts: CommandTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Pools for `request`: its backend's, or the top-level Redis pools for
+    /// its credentials. Credentials do not apply to named backends.
+    fn registry(
+        &self,
+        request: &ExecutableCommand,
+    ) -> Result<&DatabasePoolRegistry, CommandExecutionError> {
+        match request.backend.as_deref() {
+            Some(name) => {
+                self.backends.get(name).map(Arc::as_ref).ok_or_else(|| {
+                    CommandExecutionError::Rejected(format!("unknown backend: {name}"))
+                })
+            }
+            None => Ok(request
+                .credentials
+                .and_then(|credentials| self.credentials.get(&credentials))
+                .unwrap_or(&self.redis_pools)),
+        }
+    }
+
+    /// Runs one command on `connection`, discarding the connection when the
+    /// failure leaves it unusable.
+    async fn query(
+        &self,
+        mut connection: RedisConnection,
+        request: &ExecutableCommand,
+    ) -> (
+        Option<RedisConnection>,
+        Result<Value, CommandExecutionError>,
+    ) {
+        let mut redis_command = cmd(request.command_name.as_str());
+        for arg in &request.args {
+            redis_command.arg(arg);
+        }
+
+        let started = Instant::now();
+        let result = match self.timeouts.effective(request.timeout) {
+            Some(limit) => tokio::time::timeout(limit, redis_command.query_async(&mut connection))
+                .await
+                .map_err(|_| limit),
+            None => Ok(redis_command.query_async(&mut connection).await),
+        };
+        server_timing::record_redis(started.elapsed());
+
+        match result {
+            Ok(Ok(value)) => (Some(connection), Ok(value)),
+            Ok(Err(error)) => {
+                let connection = if error.is_unrecoverable_error() {
+                    connection.invalidate().await;
+                    None
+                } else {
+                    Some(connection)
+                };
+                let error = CommandExecutionError::ExecutionFailed(redis_error_message(&error));
+                (connection, Err(error))
+            }
+            Err(limit) => {
+                warn!(
+                    "Redis command timed out: command={} db={} timeout_ms={}",
+                    request.command_name,
+                    request.target_database,
+                    limit.as_millis()
+                );
+                // The late reply may still arrive on this connection, so it must never be
+                // handed to another request.
+                connection.detach();
+                let error = CommandExecutionError::Timeout(format!(
+                    "command timed out after {}ms",
+                    limit.as_millis()
+                ));
+                (None, Err(error))
+            }
+        }
+    }
+}
+
+/// The error of a command that got no connection. A full pool is told apart
+/// from an unreachable Redis, since retrying it shortly is likely to work.
+fn connection_error(error: RedisConnectionError) -> CommandExecutionError {
+    match error {
+        RedisConnectionError::Checkout(PoolError::Timeout(TimeoutType::Wait)) => {
+            CommandExecutionError::PoolExhausted("Redis connection pool exhausted".to_string())
+        }
+        error => CommandExecutionError::ServiceUnavailable(error.to_string()),
+    }
+}
+
+/// The message of a failed command. Errors Redis replied with keep their
+/// wire form, `WRONGTYPE Operation against a key ...`, so their code still
+/// leads the message and picks the HTTP status.
+pub(crate) fn redis_error_message(error: &redis::RedisError) -> String {
+    match (error.code(), error.detail()) {
+        (Some(code), Some(detail)) => format!("{code} {detail}"),
+        _ => error.to_string(),
+    }
+}
+
+/// A connection pinned to a [`CommandSession`].
+struct PinnedConnection {
+    backend: Option<String>,
+    credentials: Option<RedisCredentials>,
+    database: u8,
+    connection: Option<RedisConnection>,
+    /// Cleared once a command changed connection state that must not leak
+    /// into other requests, such as the selected database or a `WATCH`.
+    reusable: bool,
+}
+
+impl Drop for PinnedConnection {
+    fn drop(&mut self) {
+        if !self.reusable {
+            if let Some(connection) = self.connection.take() {
+                connection.detach();
+            }
+        }
+    }
+}
+
+/// Commands whose effect outlives the command on its connection.
+const CONNECTION_STATE_COMMANDS: &[&str] = &[
+    "SELECT",
+    "CLIENT",
+    "HELLO",
+    "AUTH",
+    "RESET",
+    "READONLY",
+    "READWRITE",
+    "MONITOR",
+];
+
+/// Whether the connection carries transaction state after `command` ran:
+/// `Some(true)` after `MULTI` or `WATCH`, `Some(false)` once it is cleared.
+fn transaction_state(command: &str) -> Option<bool> {
+    match command {
+        "MULTI" | "WATCH" => Some(true),
+        "EXEC" | "DISCARD" | "UNWATCH" | "RESET" => Some(false),
+        _ => None,
     }
 }
 
+/// Whether `command` changes the state of the connection it runs on, so it
+/// has to run on a session's pinned connection.
+pub(crate) fn changes_session(command: &str) -> bool {
+    let command = command.to_ascii_uppercase();
+    CONNECTION_STATE_COMMANDS.contains(&command.as_str()) || transaction_state(&command).is_some()
+}
+
 impl CommandExecutor for RedisCommandExecutor {
     fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
         Box::pin(async move {
-            let pool = self
-                .redis_pools
-                .pool_for_database(request.target_database)
-                .await
-                .map_err(|error| CommandExecutionError::ServiceUnavailable(error.to_string()))?;
+            let registry = self.registry(request)?;
+            let started = Instant::now();
+            let connection = registry
+                .connection_for_database(request.target_database)
+                .await;
+            server_timing::record_pool(started.elapsed());
+            let connection = connection.map_err(connection_error)?;
+            self.query(connection, request).await.1
+        })
+    }
 
-            let mut connection = pool
-                .get()
-                .await
-                .map_err(|error| CommandExecutionError::ServiceUnavailable(error.to_string()))?;
+    /// Pins a connection for the session's backend and database on first use.
+    /// Commands for another backend or database run on their own connection.
+    fn execute_in_session<'a>(
+        &'a self,
+        session: &'a mut CommandSession,
+        request: &'a ExecutableCommand,
+    ) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            if !session.is_pinned() {
+                let registry = self.registry(request)?;
+                let started = Instant::now();
+                let connection = registry
+                    .session_connection_for_database(request.target_database)
+                    .await;
+                server_timing::record_pool(started.elapsed());
+                let connection = connection.map_err(connection_error)?;
+                session.pin(PinnedConnection {
+                    backend: request.backend.clone(),
+                    credentials: request.credentials,
+                    database: request.target_database,
+                    connection: Some(connection),
+                    reusable: true,
+                });
+            }
+            let Some(pinned) = session
+                .connection_mut::<PinnedConnection>()
+                .filter(|pinned| {
+                    pinned.backend == request.backend
+                        && pinned.credentials == request.credentials
+                        && pinned.database == request.target_database
+                })
+            else {
+                return self.execute(request).await;
+            };
+            let Some(connection) = pinned.connection.take() else {
+                session.unpin();
+                return self.execute(request).await;
+            };
 
-            let mut redis_command = cmd(request.command_name.as_str());
-            for arg in &request.args {
-                redis_command.arg(arg);
+            let command = request.command_name.to_ascii_uppercase();
+            let (connection, result) = self.query(connection, request).await;
+            let Some(connection) = connection else {
+                session.unpin();
+                return result;
+            };
+            pinned.connection = Some(connection);
+            let stateful = CONNECTION_STATE_COMMANDS.contains(&command.as_str());
+            let transaction = transaction_state(&command);
+            // Neither connection state nor a transaction or WATCH left behind by
+            // a socket that closed may reach the next request.
+            if stateful || transaction == Some(true) {
+                pinned.reusable = false;
+            }
+            if stateful {
+                session.set_stateful();
             }
+            if let Some(held) = transaction {
+                // EXEC and DISCARD also clear WATCH, even when they fail.
+                session.set_held(held && result.is_ok());
+            }
+            result
+        })
+    }
 
-            redis_command
-                .query_async(&mut connection)
-                .await
-                .map_err(|error| CommandExecutionError::ExecutionFailed(error.to_string()))
+    /// Sends the commands as one Redis pipeline when they share a backend and
+    /// database; otherwise runs them one at a time. The first command's
+    /// timeout applies to the whole pipeline.
+    fn execute_pipeline<'a>(&'a self, requests: &'a [ExecutableCommand]) -> PipelineFuture<'a> {
+        Box::pin(async move {
+            let Some(first) = requests.first() else {
+                return Ok(Vec::new());
+            };
+            let shared = requests.iter().all(|request| {
+                request.backend == first.backend
+                    && request.credentials == first.credentials
+                    && request.target_database == first.target_database
+            });
+            if !shared {
+                let mut replies = Vec::with_capacity(requests.len());
+                for request in requests {
+                    replies.push(self.execute(request).await?);
+                }
+                return Ok(replies);
+            }
+
+            let registry = self.registry(first)?;
+            let started = Instant::now();
+            let connection = registry
+                .connection_for_database(first.target_database)
+                .await;
+            server_timing::record_pool(started.elapsed());
+            let mut connection = connection.map_err(connection_error)?;
+
+            let mut pipeline = redis::pipe();
+            for request in requests {
+                let redis_command = pipeline.cmd(request.command_name.as_str());
+                for arg in &request.args {
+                    redis_command.arg(arg);
+                }
+            }
+
+            let started = Instant::now();
+            let result = match self.timeouts.effective(first.timeout) {
+                Some(limit) => {
+                    tokio::time::timeout(limit, pipeline.query_async::<Vec<Value>>(&mut connection))
+                        .await
+                        .map_err(|_| limit)
+                }
+                None => Ok(pipeline.query_async(&mut connection).await),
+            };
+            server_timing::record_redis(started.elapsed());
+
+            match result {
+                Ok(Ok(values)) => Ok(values),
+                Ok(Err(error)) => {
+                    if error.is_unrecoverable_error() {
+                        connection.invalidate().await;
+                    }
+                    Err(CommandExecutionError::ExecutionFailed(redis_error_message(
+                        &error,
+                    )))
+                }
+                Err(limit) => {
+                    warn!(
+                        "Redis pipeline timed out: commands={} db={} timeout_ms={}",
+                        requests.len(),
+                        first.target_database,
+                        limit.as_millis()
+                    );
+                    connection.detach();
+                    Err(CommandExecutionError::Timeout(format!(
+                        "pipeline timed out after {}ms",
+                        limit.as_millis()
+                    )))
+                }
+            }
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_timeouts_are_clamped_to_max() {
+        let timeouts = CommandTimeouts {
+            default: Some(Duration::from_secs(30)),
+            max: Some(Duration::from_secs(60)),
+        };
+        assert_eq!(timeouts.effective(None), Some(Duration::from_secs(30)));
+        assert_eq!(
+            timeouts.effective(Some(Duration::from_millis(500))),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            timeouts.effective(Some(Duration::from_secs(600))),
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn redis_errors_keep_their_code_first() {
+        let error = |reply: &[u8]| {
+            redis::parse_redis_value(reply)
+                .unwrap()
+                .extract_error()
+                .unwrap_err()
+        };
+        assert_eq!(
+            redis_error_message(&error(b"-WRONGTYPE Operation against a key\r\n")),
+            "WRONGTYPE Operation against a key"
+        );
+        assert_eq!(
+            redis_error_message(&error(b"-ERR unknown command 'NOPE'\r\n")),
+            "ERR unknown command 'NOPE'"
+        );
+    }
+
+    #[test]
+    fn commands_have_no_default_timeout() {
+        let timeouts = CommandTimeouts::from_config(&Config::default());
+        assert_eq!(timeouts.effective(None), None);
+        assert_eq!(
+            timeouts.effective(Some(Duration::from_secs(600))),
+            Some(Duration::from_millis(DEFAULT_MAX_COMMAND_TIMEOUT_MS))
+        );
+    }
+
+    #[test]
+    fn zero_config_values_disable_timeouts() {
+        let mut config = Config::default();
+        config.command_timeout_ms = Some(0);
+        config.max_command_timeout_ms = None;
+        let timeouts = CommandTimeouts::from_config(&config);
+        assert_eq!(timeouts.default, None);
+        assert_eq!(
+            timeouts.max,
+            Some(Duration::from_millis(DEFAULT_MAX_COMMAND_TIMEOUT_MS))
+        );
+    }
+
+    #[test]
+    fn pool_wait_timeouts_are_reported_as_exhaustion() {
+        let error = connection_error(RedisConnectionError::Checkout(PoolError::Timeout(
+            TimeoutType::Wait,
+        )));
+        assert!(matches!(error, CommandExecutionError::PoolExhausted(_)));
+        let error = connection_error(RedisConnectionError::Checkout(PoolError::Timeout(
+            TimeoutType::Create,
+        )));
+        assert!(matches!(
+            error,
+            CommandExecutionError::ServiceUnavailable(_)
+        ));
+    }
+}
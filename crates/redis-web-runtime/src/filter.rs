@@ -0,0 +1,338 @@
+//! Per-channel Pub/Sub message filters (`pubsub_filters`).
+//!
+//! Each rule names a channel glob and optional conditions on the payload: a
+//! regular expression and a JSONPath lookup with an expected value. Messages
+//! failing the first rule that matches their channel are dropped before they
+//! reach subscribers; passing messages can be rewritten with a template.
+//!
+//! JSONPath support is limited to member and index steps (`$.order.items[0]`
+//! or `$['order']`), which covers picking a field out of an event payload.
+
+use crate::pubsub::PubSubMessage;
+use redis_web_core::config::PubSubFilterConfig;
+use redis_web_core::routing::glob_match;
+use regex::Regex;
+use serde_json::Value;
+
+/// Compiled `pubsub_filters` rules, checked in order.
+#[derive(Default)]
+pub struct MessageFilters {
+    rules: Vec<FilterRule>,
+}
+
+struct FilterRule {
+    channel: Vec<u8>,
+    regex: Option<Regex>,
+    json_path: Option<JsonPath>,
+    equals: Option<String>,
+    template: Option<Vec<TemplatePart>>,
+}
+
+/// One step of a JSONPath.
+#[derive(Debug, PartialEq)]
+enum PathStep {
+    Member(String),
+    Index(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct JsonPath(Vec<PathStep>);
+
+#[derive(Debug, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Channel,
+    Payload,
+    Path(JsonPath),
+}
+
+impl MessageFilters {
+    /// Compiles the rules, reporting the first invalid regex, path, or template.
+    pub fn new(config: &[PubSubFilterConfig]) -> Result<Self, String> {
+        let rules = config
+            .iter()
+            .map(FilterRule::new)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns the message to forward, or `None` when a rule drops it.
+    pub fn apply(&self, mut message: PubSubMessage) -> Option<PubSubMessage> {
+        let Some(rule) = self
+            .rules
+            .iter()
+            .find(|rule| glob_match(&rule.channel, message.channel.as_bytes()))
+        else {
+            return Some(message);
+        };
+        if rule
+            .regex
+            .as_ref()
+            .is_some_and(|regex| !regex.is_match(&message.payload))
+        {
+            return None;
+        }
+        // Parsed once, shared by the JSONPath condition and the template.
+        let needs_json = rule.json_path.is_some()
+            || rule.template.as_ref().is_some_and(|parts| {
+                parts
+                    .iter()
+                    .any(|part| matches!(part, TemplatePart::Path(_)))
+            });
+        let document = needs_json
+            .then(|| serde_json::from_str::<Value>(&message.payload).ok())
+            .flatten();
+        if let Some(path) = &rule.json_path {
+            let found = document
+                .as_ref()
+                .and_then(|document| path.lookup(document))
+                .filter(|value| !value.is_null())?;
+            if rule
+                .equals
+                .as_deref()
+                .is_some_and(|expected| value_text(found) != expected)
+            {
+                return None;
+            }
+        }
+        if let Some(template) = &rule.template {
+            message.payload = render(template, &message, document.as_ref());
+        }
+        Some(message)
+    }
+}
+
+impl FilterRule {
+    fn new(config: &PubSubFilterConfig) -> Result<Self, String> {
+        let regex = config
+            .regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|error| format!("invalid regex: {error}"))?;
+        let json_path = config
+            .json_path
+            .as_deref()
+            .map(JsonPath::parse)
+            .transpose()?;
+        if config.equals.is_some() && json_path.is_none() {
+            return Err("equals requires json_path".to_string());
+        }
+        let template = config.template.as_deref().map(parse_template).transpose()?;
+        Ok(Self {
+            channel: config.channel.clone().into_bytes(),
+            regex,
+            json_path,
+            equals: config.equals.clone(),
+            template,
+        })
+    }
+}
+
+impl JsonPath {
+    fn parse(path: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid JSONPath {path}");
+        let mut rest = path.strip_prefix('$').ok_or_else(invalid)?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid());
+                }
+                steps.push(PathStep::Member(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(invalid)?;
+                let inner = &after[..end];
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|name| name.strip_suffix('\''))
+                    .or_else(|| {
+                        inner
+                            .strip_prefix('"')
+                            .and_then(|name| name.strip_suffix('"'))
+                    });
+                steps.push(match quoted {
+                    Some(name) => PathStep::Member(name.to_string()),
+                    None => PathStep::Index(inner.parse().map_err(|_| invalid())?),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(Self(steps))
+    }
+
+    fn lookup<'a>(&self, document: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(document, |value, step| match step {
+            PathStep::Member(name) => value.get(name),
+            PathStep::Index(index) => value.get(index),
+        })
+    }
+}
+
+/// Splits a template into text and `{channel}`, `{payload}`, or `{$.path}`
+/// placeholders. Other braces are kept as text.
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let placeholder = after
+            .find('}')
+            .map(|end| (&after[..end], &after[end + 1..]));
+        let part = match placeholder {
+            Some(("channel", _)) => Some(TemplatePart::Channel),
+            Some(("payload", _)) => Some(TemplatePart::Payload),
+            Some((path, _)) if path.starts_with('$') => {
+                Some(TemplatePart::Path(JsonPath::parse(path)?))
+            }
+            _ => None,
+        };
+        match (part, placeholder) {
+            (Some(part), Some((_, remaining))) => {
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+                rest = remaining;
+            }
+            _ => {
+                text.push('{');
+                rest = after;
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+fn render(template: &[TemplatePart], message: &PubSubMessage, document: Option<&Value>) -> String {
+    let mut out = String::new();
+    for part in template {
+        match part {
+            TemplatePart::Text(text) => out.push_str(text),
+            TemplatePart::Channel => out.push_str(&message.channel),
+            TemplatePart::Payload => out.push_str(&message.payload),
+            TemplatePart::Path(path) => {
+                if let Some(value) = document.and_then(|document| path.lookup(document)) {
+                    out.push_str(&value_text(value));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Strings as their content, other values as JSON text.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(channel: &str) -> PubSubFilterConfig {
+        PubSubFilterConfig {
+            channel: channel.to_string(),
+            regex: None,
+            json_path: None,
+            equals: None,
+            template: None,
+        }
+    }
+
+    fn message(channel: &str, payload: &str) -> PubSubMessage {
+        PubSubMessage {
+            channel: channel.to_string(),
+            payload: payload.to_string(),
+            pattern: None,
+            sharded: false,
+            id: None,
+        }
+    }
+
+    #[test]
+    fn parses_member_and_index_steps() {
+        assert_eq!(
+            JsonPath::parse("$.order.items[1]['sku']").unwrap(),
+            JsonPath(vec![
+                PathStep::Member("order".to_string()),
+                PathStep::Member("items".to_string()),
+                PathStep::Index(1),
+                PathStep::Member("sku".to_string()),
+            ])
+        );
+        assert!(JsonPath::parse("order.items").is_err());
+        assert!(JsonPath::parse("$..items").is_err());
+        assert!(JsonPath::parse("$[x]").is_err());
+    }
+
+    #[test]
+    fn first_matching_rule_filters_by_regex_and_json_path() {
+        let filters = MessageFilters::new(&[
+            PubSubFilterConfig {
+                regex: Some("^ERROR".to_string()),
+                ..rule("logs.*")
+            },
+            PubSubFilterConfig {
+                json_path: Some("$.status".to_string()),
+                equals: Some("failed".to_string()),
+                ..rule("orders")
+            },
+        ])
+        .unwrap();
+
+        assert!(filters.apply(message("logs.api", "ERROR boom")).is_some());
+        assert!(filters.apply(message("logs.api", "INFO ok")).is_none());
+        assert!(filters
+            .apply(message("orders", r#"{"status":"failed"}"#))
+            .is_some());
+        assert!(filters
+            .apply(message("orders", r#"{"status":"paid"}"#))
+            .is_none());
+        assert!(filters.apply(message("orders", "not json")).is_none());
+        assert!(filters.apply(message("other", "anything")).is_some());
+    }
+
+    #[test]
+    fn templates_substitute_channel_payload_and_paths() {
+        let filters = MessageFilters::new(&[PubSubFilterConfig {
+            template: Some("{channel}: {$.id} {$.total} {unknown} {payload}".to_string()),
+            ..rule("orders")
+        }])
+        .unwrap();
+        let out = filters
+            .apply(message("orders", r#"{"id":"A1","total":9.5}"#))
+            .unwrap();
+        assert_eq!(
+            out.payload,
+            r#"orders: A1 9.5 {unknown} {"id":"A1","total":9.5}"#
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let bad_regex = PubSubFilterConfig {
+            regex: Some("(".to_string()),
+            ..rule("*")
+        };
+        assert!(MessageFilters::new(&[bad_regex]).is_err());
+        let equals_alone = PubSubFilterConfig {
+            equals: Some("x".to_string()),
+            ..rule("*")
+        };
+        assert!(MessageFilters::new(&[equals_alone]).is_err());
+    }
+}
@@ -1,11 +1,16 @@
-use crate::handler::AppState;
+use crate::admission;
+use crate::handler::{execution_error_status, AppState};
+use crate::pubsub::PubSubEvent;
+use axum::http::StatusCode;
 use futures::Stream;
+use prost::Message as _;
 use redis::Value as RedisValue;
 use redis_web_core::config::{Config, DEFAULT_HTTP_MAX_REQUEST_SIZE};
 use redis_web_core::interfaces::{AuthContext, CommandExecutionError, ExecutableCommand};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
@@ -39,20 +44,58 @@ impl proto::redis_gateway_server::RedisGateway for RedisGatewayService {
         &self,
         request: Request<proto::CommandRequest>,
     ) -> Result<Response<proto::CommandReply>, Status> {
+        let started = Instant::now();
         let auth = auth_context(&request);
-        let command = command_from_proto(self.state.default_database, request.into_inner())?;
+        let mut command = command_from_proto(self.state.default_database, request.into_inner())?;
 
-        authorize(&self.state, &auth, command.command_name.as_str())?;
-        let value = self
-            .state
-            .command_executor
-            .execute(&command)
-            .await
-            .map_err(command_error_to_status)?;
+        admit(&self.state, &auth, &mut command, started)?;
+        let charge = charge(&self.state, &auth, 1).await?;
+        let outcome = admission::execute(&self.state, &command).await;
+        let status = admission::outcome_status(&outcome);
+        admission::audit(&self.state, &auth, &command, status, started);
+        let value = outcome.map_err(command_error_to_status)?;
 
-        Ok(Response::new(proto::CommandReply {
+        let reply = proto::CommandReply {
             value: Some(redis_value_to_proto(value)?),
-        }))
+        };
+        charge.record_bytes(&self.state, reply.encoded_len() as u64);
+        Ok(Response::new(reply))
+    }
+
+    #[allow(clippy::result_large_err)]
+    async fn pipeline(
+        &self,
+        request: Request<proto::PipelineRequest>,
+    ) -> Result<Response<proto::PipelineReply>, Status> {
+        let started = Instant::now();
+        let auth = auth_context(&request);
+        let mut commands = request
+            .into_inner()
+            .commands
+            .into_iter()
+            .map(|command| command_from_proto(self.state.default_database, command))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Nothing runs unless every command is allowed.
+        for command in &mut commands {
+            admit(&self.state, &auth, command, started)?;
+        }
+        let charge = charge(&self.state, &auth, commands.len() as u64).await?;
+
+        let outcome = admission::execute_pipeline(&self.state, &commands).await;
+        let status = admission::outcome_status(&outcome);
+        for command in &commands {
+            admission::audit(&self.state, &auth, command, status, started);
+        }
+        let values = outcome.map_err(command_error_to_status)?;
+
+        let reply = proto::PipelineReply {
+            values: values
+                .into_iter()
+                .map(redis_value_to_proto)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        charge.record_bytes(&self.state, reply.encoded_len() as u64);
+        Ok(Response::new(reply))
     }
 
     type ExecuteStreamStream = ResponseStream<proto::StreamCommandReply>;
@@ -81,47 +124,10 @@ impl proto::redis_gateway_server::RedisGateway for RedisGatewayService {
                 let reply = match item.command {
                     Some(command_request) => {
                         match command_from_proto(default_database, command_request) {
-                            Ok(command) => {
-                                if let Err(status) =
-                                    authorize(&state, &auth, command.command_name.as_str())
-                                {
-                                    proto::StreamCommandReply {
-                                        correlation_id,
-                                        result: Some(proto::stream_command_reply::Result::Error(
-                                            status_to_stream_error(&status),
-                                        )),
-                                    }
-                                } else {
-                                    match state.command_executor.execute(&command).await {
-                                        Ok(value) => match redis_value_to_proto(value) {
-                                            Ok(value) => proto::StreamCommandReply {
-                                                correlation_id,
-                                                result: Some(
-                                                    proto::stream_command_reply::Result::Value(
-                                                        value,
-                                                    ),
-                                                ),
-                                            },
-                                            Err(status) => proto::StreamCommandReply {
-                                                correlation_id,
-                                                result: Some(
-                                                    proto::stream_command_reply::Result::Error(
-                                                        status_to_stream_error(&status),
-                                                    ),
-                                                ),
-                                            },
-                                        },
-                                        Err(error) => proto::StreamCommandReply {
-                                            correlation_id,
-                                            result: Some(
-                                                proto::stream_command_reply::Result::Error(
-                                                    command_error_to_stream_error(error),
-                                                ),
-                                            ),
-                                        },
-                                    }
-                                }
-                            }
+                            Ok(command) => proto::StreamCommandReply {
+                                correlation_id,
+                                result: Some(run_streamed(&state, &auth, command).await),
+                            },
                             Err(status) => proto::StreamCommandReply {
                                 correlation_id,
                                 result: Some(proto::stream_command_reply::Result::Error(
@@ -163,18 +169,21 @@ impl proto::redis_gateway_server::RedisGateway for RedisGatewayService {
             return Err(Status::invalid_argument("channel must not be empty"));
         }
 
-        authorize(&self.state, &auth, "SUBSCRIBE")?;
-
         let channel = inner.channel;
+        if !self.state.acl.check_channel(&auth, "SUBSCRIBE", &channel) {
+            return Err(Status::permission_denied("Forbidden"));
+        }
         let mut rx = self.state.pubsub.subscribe(channel.clone()).await;
 
         let stream = async_stream::stream! {
             loop {
                 match rx.recv().await {
-                    Ok(payload) => yield Ok(proto::SubscribeEvent {
-                        channel: channel.as_bytes().to_vec(),
-                        payload: payload.into_bytes(),
+                    Ok(PubSubEvent::Message(message)) => yield Ok(proto::SubscribeEvent {
+                        channel: message.channel.into_bytes(),
+                        payload: message.payload.into_bytes(),
                     }),
+                    // SubscribeEvent has no way to signal a gap yet.
+                    Ok(PubSubEvent::Resubscribed) => continue,
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
                         continue;
                     }
@@ -272,12 +281,71 @@ pub async fn serve_with_listener(
     Ok(())
 }
 
-fn authorize(state: &AppState, auth: &AuthContext, command: &str) -> Result<(), Status> {
-    if state.acl.check_auth(auth, command) {
-        Ok(())
-    } else {
-        Err(Status::permission_denied("Forbidden"))
+/// Runs one command of an `ExecuteStream` call.
+async fn run_streamed(
+    state: &AppState,
+    auth: &AuthContext,
+    mut command: ExecutableCommand,
+) -> proto::stream_command_reply::Result {
+    use proto::stream_command_reply::Result as Reply;
+    let started = Instant::now();
+    let charged = match admit(state, auth, &mut command, started) {
+        Ok(()) => charge(state, auth, 1).await,
+        Err(status) => Err(status),
+    };
+    let charge = match charged {
+        Ok(charge) => charge,
+        Err(status) => return Reply::Error(status_to_stream_error(&status)),
+    };
+    let outcome = admission::execute(state, &command).await;
+    let status = admission::outcome_status(&outcome);
+    admission::audit(state, auth, &command, status, started);
+    let reply = match outcome {
+        Ok(value) => match redis_value_to_proto(value) {
+            Ok(value) => Reply::Value(value),
+            Err(status) => Reply::Error(status_to_stream_error(&status)),
+        },
+        Err(error) => Reply::Error(command_error_to_stream_error(error)),
+    };
+    charge.record_bytes(state, reply.encoded_len() as u64);
+    reply
+}
+
+/// Counts `commands` commands against the caller's `quotas`.
+async fn charge(
+    state: &AppState,
+    auth: &AuthContext,
+    commands: u64,
+) -> Result<admission::Charge, Status> {
+    admission::charge(state, auth, commands)
+        .await
+        .map_err(|retry_after| {
+            Status::resource_exhausted(format!(
+                "Usage quota exceeded, retry after {retry_after} seconds"
+            ))
+        })
+}
+
+/// Runs the `before` hooks on `command`, routes it as its caller, and checks the `acl`
+/// rules of the server and of its backend, auditing a refusal.
+#[allow(clippy::result_large_err)]
+fn admit(
+    state: &AppState,
+    auth: &AuthContext,
+    command: &mut ExecutableCommand,
+    started: Instant,
+) -> Result<(), Status> {
+    if let Err(error) = state.hooks.before(command) {
+        let status = execution_error_status(&error);
+        admission::audit(state, auth, command, status, started);
+        return Err(command_error_to_status(error));
+    }
+    admission::route(state, auth, command);
+    if !admission::authorize(state, auth, command) {
+        admission::audit(state, auth, command, StatusCode::FORBIDDEN, started);
+        return Err(Status::permission_denied("Forbidden"));
     }
+    Ok(())
 }
 
 fn auth_context<T>(request: &Request<T>) -> AuthContext {
@@ -297,6 +365,7 @@ fn authorization_header(metadata: &MetadataMap) -> Option<String> {
         .map(|value| value.to_string())
 }
 
+#[allow(clippy::result_large_err)]
 fn command_from_proto(
     default_database: u8,
     request: proto::CommandRequest,
@@ -311,12 +380,16 @@ fn command_from_proto(
     }
 
     Ok(ExecutableCommand {
+        backend: None,
         target_database: request.database.unwrap_or(default_database as u32) as u8,
         command_name: request.command,
         args: request.args,
+        timeout: None,
+        credentials: None,
     })
 }
 
+#[allow(clippy::result_large_err)]
 pub fn redis_value_to_proto(value: RedisValue) -> Result<proto::RedisValue, Status> {
     use proto::redis_value::Kind;
 
@@ -332,6 +405,35 @@ pub fn redis_value_to_proto(value: RedisValue) -> Result<proto::RedisValue, Stat
         }),
         RedisValue::SimpleString(value) => Kind::SimpleString(value),
         RedisValue::Okay => Kind::SimpleString("OK".to_string()),
+        RedisValue::Map(entries) => Kind::Map(proto::RedisMap {
+            entries: entries
+                .into_iter()
+                .map(|(key, value)| {
+                    Ok(proto::RedisMapEntry {
+                        key: Some(redis_value_to_proto(key)?),
+                        value: Some(redis_value_to_proto(value)?),
+                    })
+                })
+                .collect::<Result<Vec<_>, Status>>()?,
+        }),
+        RedisValue::Set(values) => Kind::Set(proto::RedisArray {
+            values: values
+                .into_iter()
+                .map(redis_value_to_proto)
+                .collect::<Result<Vec<_>, _>>()?,
+        }),
+        RedisValue::Double(value) => Kind::Double(value),
+        RedisValue::Boolean(value) => Kind::Boolean(value),
+        RedisValue::VerbatimString { text, .. } => Kind::VerbatimString(text),
+        RedisValue::BigNumber(number) => Kind::BigNumber(number.to_string()),
+        RedisValue::Attribute { data, .. } => return redis_value_to_proto(*data),
+        RedisValue::Push { kind, data } => Kind::Array(proto::RedisArray {
+            values: std::iter::once(Ok(proto::RedisValue {
+                kind: Some(Kind::BulkBytes(kind.to_string().into_bytes())),
+            }))
+            .chain(data.into_iter().map(redis_value_to_proto))
+            .collect::<Result<Vec<_>, _>>()?,
+        }),
         unsupported => {
             error!(
                 "unsupported Redis value for gRPC mapping: {:?}",
@@ -350,6 +452,11 @@ fn command_error_to_status(error: CommandExecutionError) -> Status {
     match error {
         CommandExecutionError::ServiceUnavailable(message) => Status::unavailable(message),
         CommandExecutionError::ExecutionFailed(message) => Status::internal(message),
+        CommandExecutionError::Timeout(message) => Status::deadline_exceeded(message),
+        CommandExecutionError::Rejected(message) => Status::invalid_argument(message),
+        CommandExecutionError::Overloaded(message)
+        | CommandExecutionError::PoolExhausted(message) => Status::resource_exhausted(message),
+        CommandExecutionError::ValueTooLarge(message) => Status::out_of_range(message),
     }
 }
 
@@ -363,6 +470,26 @@ fn command_error_to_stream_error(error: CommandExecutionError) -> proto::Command
             kind: proto::ErrorKind::ExecutionFailed as i32,
             message,
         },
+        CommandExecutionError::Timeout(message) => proto::CommandError {
+            kind: proto::ErrorKind::Timeout as i32,
+            message,
+        },
+        CommandExecutionError::Rejected(message) => proto::CommandError {
+            kind: proto::ErrorKind::Rejected as i32,
+            message,
+        },
+        CommandExecutionError::Overloaded(message) => proto::CommandError {
+            kind: proto::ErrorKind::Overloaded as i32,
+            message,
+        },
+        CommandExecutionError::PoolExhausted(message) => proto::CommandError {
+            kind: proto::ErrorKind::PoolExhausted as i32,
+            message,
+        },
+        CommandExecutionError::ValueTooLarge(message) => proto::CommandError {
+            kind: proto::ErrorKind::ValueTooLarge as i32,
+            message,
+        },
     }
 }
 
@@ -394,10 +521,32 @@ mod tests {
         assert_eq!(array.values.len(), 4);
     }
 
+    #[test]
+    fn redis_value_mapping_supports_resp3_variants() {
+        let mapped = redis_value_to_proto(RedisValue::Map(vec![(
+            RedisValue::SimpleString("enabled".to_string()),
+            RedisValue::Boolean(true),
+        )]))
+        .expect("mapping should succeed");
+
+        let proto::redis_value::Kind::Map(map) = mapped.kind.expect("map kind expected") else {
+            panic!("expected map kind");
+        };
+        assert_eq!(map.entries.len(), 1);
+        assert_eq!(
+            map.entries[0]
+                .value
+                .as_ref()
+                .and_then(|value| value.kind.clone()),
+            Some(proto::redis_value::Kind::Boolean(true))
+        );
+    }
+
     #[test]
     fn redis_value_mapping_rejects_unsupported_variants() {
-        let error = redis_value_to_proto(RedisValue::Boolean(true))
-            .expect_err("RESP3 boolean should be unsupported in v1");
+        let server_error = redis::parse_redis_value(b"-ERR boom\r\n").expect("valid RESP");
+        let error =
+            redis_value_to_proto(server_error).expect_err("server errors have no reply mapping");
         assert_eq!(error.code(), tonic::Code::Internal);
     }
 }
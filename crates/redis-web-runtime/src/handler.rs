@@ -1,25 +1,42 @@
 use crate::redis::DatabasePoolRegistry;
 use axum::body::Body; // Added Body
-use axum::extract::{ConnectInfo, OriginalUri};
+use axum::extract::{ConnectInfo, FromRequest, OriginalUri, Request};
 use axum::{
     extract::State,
-    http::{header, HeaderMap, StatusCode},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use redis::Value as RedisValue;
 use redis_web_core::acl::Acl;
-use redis_web_core::format::{json_value_response, select_jsonp_callback, OutputFormat};
+use redis_web_core::config::JsonNil;
+use redis_web_core::format::{
+    content_disposition, content_type_for_extension, json_value_response, select_jsonp_callback,
+    OutputFormat,
+};
 use redis_web_core::interfaces::{
-    AuthContext, CommandExecutionError, CommandExecutor, ParseRequestInput, RequestParser,
+    AuthContext, CommandExecutionError, CommandExecutor, ExecutableCommand, ParseRequestInput,
+    RequestParser,
 };
-use redis_web_core::request::RequestParseError;
+use redis_web_core::request::{ParsedRequest, RequestParseError};
 use redis_web_core::resp;
+use redis_web_core::routing::{CommandRouter, DownloadRouter, FormatRouter};
 use serde_json::{json, Value};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tracing::error;
 
+use crate::admission;
+use crate::audit::AuditLog;
+use crate::errors::{with_retry_after, ErrorFormat};
+use crate::health::BackendHealth;
+use crate::hooks::CommandHooks;
+use crate::latency::RedisLatency;
 use crate::pubsub::PubSubManager;
+use crate::quota::Quotas;
+use crate::shadow::Shadow;
+use crate::template::CommandTemplate;
+use crate::value_size::ValueSizeLimits;
 use sha1::{Digest, Sha1};
 
 pub async fn handle_default_root(
@@ -27,20 +44,70 @@ pub async fn handle_default_root(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
-    default_root: String,
+    default_root: Arc<CommandTemplate>,
 ) -> Response {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.to_string());
+    process_request(
+        default_root.render(addr, &headers),
+        params,
+        RequestBody::None,
+        state,
+        addr,
+        Vec::new(),
+        headers,
+    )
+    .await
+}
 
+/// Handles `POST /` and `PUT /` with a command from `root_commands`; the body
+/// is appended as its last argument.
+pub async fn handle_root_with_body(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    command: Arc<CommandTemplate>,
+    body: Bytes,
+) -> Response {
     process_request(
-        default_root,
+        command.render(addr, &headers),
         params,
-        None,
+        RequestBody::Buffered(body),
+        state,
+        addr,
+        Vec::new(),
+        headers,
+    )
+    .await
+}
+
+/// Handles `POST /` with `root_commands.post_body_command`: the body is the
+/// command path, e.g. `SET/key/value`.
+pub async fn handle_root_command_body(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let Ok(command) = std::str::from_utf8(&body) else {
+        return state.errors.response(
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+            "Command body must be UTF-8",
+            &headers,
+            select_jsonp_callback(&params),
+        );
+    };
+    let command = command.trim();
+    let command = command.strip_prefix('/').unwrap_or(command).to_string();
+
+    process_request(
+        command,
+        params,
+        RequestBody::Buffered(Bytes::new()),
         state,
         addr,
-        auth_header,
+        Vec::new(),
         headers,
     )
     .await
@@ -60,10 +127,76 @@ pub struct AppState {
     pub request_parser: Arc<dyn RequestParser>,
     /// Executor used to run normalized requests against Redis or another backend.
     pub command_executor: Arc<dyn CommandExecutor>,
+    /// Run on client commands and their replies; internal commands skip them.
+    pub hooks: CommandHooks,
     pub acl: Acl,
+    /// Named backends recognized as a URL prefix, mapped to their default database.
+    pub backend_databases: HashMap<String, u8>,
+    /// Per-backend ACLs, checked after the top-level `acl`.
+    pub backend_acls: HashMap<String, Acl>,
+    /// `routes` rules applied to requests without a backend prefix.
+    pub routes: CommandRouter,
+    /// Health of the named backends; `routes` skip ejected ones.
+    pub backend_health: Arc<BackendHealth>,
+    /// Round trips of the background Redis latency probe.
+    pub redis_latency: Arc<RedisLatency>,
+    /// Copies read commands to the `shadow` backend when configured.
+    pub shadow: Option<Arc<Shadow>>,
+    /// Usage caps on client commands when `quotas` is configured.
+    pub quotas: Option<Arc<Quotas>>,
+    /// Audit stream of client commands when `audit` is configured.
+    pub audit: Option<AuditLog>,
+    /// Checked while streaming uploads, whose size the executor never sees.
+    pub value_size_limits: ValueSizeLimits,
+    /// `output_formats` rules applied to requests without an extension.
+    pub output_formats: FormatRouter,
+    /// `downloads` rules naming raw and text replies sent as attachments.
+    pub downloads: DownloadRouter,
+    /// Fetches the `PTTL` of single-key reads for the `X-TTL-Ms` header.
+    pub ttl_header: bool,
+    /// Keeps `<key>:__mtime__` timestamps for `Last-Modified` on single-key
+    /// reads.
+    pub last_modified: bool,
+    /// How Nil replies are rendered in JSON bodies.
+    pub json_nil: JsonNil,
+    /// Shape of JSON error bodies.
+    pub errors: ErrorFormat,
     pub pubsub: PubSubManager,
+    /// Open HTTP Pub/Sub streams, capped by the `sse` limits.
+    pub sse_limits: Arc<crate::pubsub::StreamLimits>,
+    /// Idle time after which a WebSocket returns its Redis connection; `None`
+    /// keeps it for the socket's lifetime.
+    pub websocket_connection_idle: Option<Duration>,
+    /// Interval of WebSocket protocol pings; `None` disables them.
+    pub websocket_ping_interval: Option<Duration>,
+    /// Closes WebSockets without data frames for this long.
+    pub websocket_idle_timeout: Option<Duration>,
+    /// Size limits applied on WebSocket upgrade.
+    pub websocket_max_message_size: usize,
+    pub websocket_max_frame_size: usize,
+    /// Capacity of each WebSocket's send queue.
+    pub websocket_send_queue_size: usize,
+    /// Id-tagged commands a `/.json` socket may run concurrently.
+    pub websocket_max_inflight_commands: usize,
+    /// Whether WebSocket commands wait for the client to authenticate.
+    pub websocket_require_auth: bool,
+    /// Set to `true` when the server shuts down, so WebSockets can close with
+    /// `1001` before the listener stops.
+    pub shutdown: tokio::sync::watch::Sender<bool>,
+    /// SSE heartbeat; `None` when `sse.keep_alive_sec` is `0`.
+    pub sse_keep_alive: Option<axum::response::sse::KeepAlive>,
+    /// Access rules for `/_admin` endpoints; `None` when `admin` is disabled.
+    pub admin: Option<crate::admin::AdminAccess>,
     /// Optional hiredis-compat session manager (mounted under `/__compat/*`).
     pub compat_hiredis: Option<Arc<crate::compat::CompatSessionManager>>,
+    /// Largest accepted request body, also enforced on streamed uploads.
+    pub http_max_request_size: usize,
+    /// Chunk size of streamed `PUT /SET/<key>` uploads; `None` buffers bodies.
+    pub put_stream_chunk_size: Option<usize>,
+    /// Chunk size of streamed `GET` replies; `None` reads values whole.
+    pub get_stream_chunk_size: Option<usize>,
+    /// Page size of streamed `LRANGE` replies; `None` reads ranges whole.
+    pub list_stream_page_size: Option<usize>,
 }
 
 use axum::body::Bytes;
@@ -89,27 +222,24 @@ use std::collections::HashMap;
 /// HTTP body is appended as the final Redis argument.
 pub async fn handle_post(
     OriginalUri(uri): OriginalUri,
-    Query(params): Query<HashMap<String, String>>,
+    Query(query): Query<Vec<(String, String)>>,
     State(state): State<Arc<AppState>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.to_string());
     // Use the raw request URI path (percent-encoded) to preserve `%2f` and `%2e` semantics.
     // `Path<String>` would decode many percent-escapes before we can apply Webdis-compatible
     // segment decoding rules.
-    let command = uri.path().trim_start_matches('/').to_string();
+    let command = uri.path().strip_prefix('/').unwrap_or_default().to_string();
+    let (params, query_args) = split_query(query);
     process_request(
         command,
         params,
-        Some(body.to_vec()),
+        RequestBody::Buffered(body),
         state,
         addr,
-        auth_header,
+        query_args,
         headers,
     )
     .await
@@ -117,46 +247,84 @@ pub async fn handle_post(
 
 /// Handles `PUT` command requests where the URI encodes command parts and the
 /// HTTP body is appended as the final Redis argument.
+///
+/// With `http_put_stream_chunk_size` set, bodies without a `Content-Length`
+/// or larger than one chunk are not buffered up front; `PUT /SET/<key>`
+/// streams them into Redis through [`crate::upload::stream_set`].
 pub async fn handle_put(
     OriginalUri(uri): OriginalUri,
-    Query(params): Query<HashMap<String, String>>,
+    Query(query): Query<Vec<(String, String)>>,
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+) -> Response {
+    let headers = request.headers().clone();
+    let command = uri.path().strip_prefix('/').unwrap_or_default().to_string();
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    let body = match (state.put_stream_chunk_size, content_length) {
+        (Some(chunk_size), length) if length.map(|length| length > chunk_size).unwrap_or(true) => {
+            RequestBody::Streamed(request.into_body())
+        }
+        _ => match Bytes::from_request(request, &()).await {
+            Ok(body) => RequestBody::Buffered(body),
+            Err(rejection) => return rejection.into_response(),
+        },
+    };
+    let (params, query_args) = split_query(query);
+    process_request(command, params, body, state, addr, query_args, headers).await
+}
+
+/// Handles `GET` command requests where the full Redis command is encoded in
+/// the request path.
+pub async fn handle_get(
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<Vec<(String, String)>>,
     State(state): State<Arc<AppState>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
-    body: Bytes,
 ) -> Response {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.to_string());
-    let command = uri.path().trim_start_matches('/').to_string();
+    let command = uri.path().strip_prefix('/').unwrap_or_default().to_string();
+    let (params, query_args) = split_query(query);
     process_request(
         command,
         params,
-        Some(body.to_vec()),
+        RequestBody::None,
         state,
         addr,
-        auth_header,
+        query_args,
         headers,
     )
     .await
 }
 
-/// Handles `GET` command requests where the full Redis command is encoded in
-/// the request path.
-pub async fn handle_get(
-    OriginalUri(uri): OriginalUri,
-    Query(params): Query<HashMap<String, String>>,
-    State(state): State<Arc<AppState>>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    headers: HeaderMap,
-) -> Response {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.to_string());
-    let command = uri.path().trim_start_matches('/').to_string();
-    process_request(command, params, None, state, addr, auth_header, headers).await
+/// Splits the query of a command URL into its parameters and the values of
+/// its `arg` parameters, in order. Later duplicates of other parameters win.
+fn split_query(query: Vec<(String, String)>) -> (HashMap<String, String>, Vec<String>) {
+    let (args, params): (Vec<_>, Vec<_>) = query.into_iter().partition(|(name, _)| name == "arg");
+    (
+        params.into_iter().collect(),
+        args.into_iter().map(|(_, value)| value).collect(),
+    )
+}
+
+/// The `Authorization` header of a request.
+fn authorization(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Body of a command request.
+pub(crate) enum RequestBody {
+    None,
+    /// Read in full; appended as the last argument when not empty.
+    Buffered(Bytes),
+    /// Not read yet; a `PUT /SET/<key>` upload streamed into Redis.
+    Streamed(Body),
 }
 
 /// Normalizes an HTTP request into a Redis command and emits a formatted HTTP response.
@@ -165,73 +333,241 @@ pub async fn handle_get(
 /// - optional `/<db>/` prefix parsing with strict numeric range validation,
 /// - per-request DB pool selection without connection state bleed,
 /// - extension-driven response formatting and content type negotiation,
-/// - ACL checks and conditional ETag handling.
+/// - ACL and quota checks and conditional ETag handling.
 async fn process_request(
     command: String,
     params: HashMap<String, String>,
-    body: Option<Vec<u8>>,
+    body: RequestBody,
     state: Arc<AppState>,
     addr: SocketAddr,
-    auth_header: Option<String>,
+    query_args: Vec<String>,
     headers: HeaderMap,
 ) -> Response {
-    let parsed = match state.request_parser.parse(ParseRequestInput {
-        command_path: command.as_str(),
-        params: &params,
-        default_database: state.default_database,
-        body: body.as_deref(),
-        etag_enabled: body.is_none(),
-    }) {
+    let started = Instant::now();
+    let parse = |body: Option<Bytes>, etag_enabled: bool| {
+        state.request_parser.parse(ParseRequestInput {
+            command_path: command.as_str(),
+            params: &params,
+            query_args: &query_args,
+            default_database: state.default_database,
+            backend_databases: &state.backend_databases,
+            etag_enabled,
+            body,
+        })
+    };
+    let (parsed, upload) = match body {
+        RequestBody::None => (parse(None, true), None),
+        RequestBody::Buffered(body) => (parse(Some(body), false), None),
+        RequestBody::Streamed(body) => match parse(None, false) {
+            // Only a single-key SET is streamed; anything else is buffered
+            // and parsed again with its body, as are uploads hooks must see.
+            Ok(parsed)
+                if crate::upload::is_streamable(&parsed.command) && state.hooks.is_empty() =>
+            {
+                (Ok(parsed), Some(body))
+            }
+            Ok(_) => match axum::body::to_bytes(body, state.http_max_request_size).await {
+                Ok(body) => (parse(Some(body), false), None),
+                Err(_) => return payload_too_large(&state, &headers),
+            },
+            Err(error) => (Err(error), None),
+        },
+    };
+    let mut parsed = match parsed {
         Ok(parsed) => parsed,
         Err(error) => {
             let jsonp = select_jsonp_callback(&params);
-            return json_value_response(
+            return state.errors.response(
                 StatusCode::BAD_REQUEST,
-                json!({"error": request_parse_error_message(&error)}),
+                "bad_request",
+                &request_parse_error_message(&error),
+                &headers,
                 jsonp,
             );
         }
     };
 
-    // Check ACL
+    // An extension in the URL wins over `output_formats`; without one the
+    // parser leaves JSON and no extension content type.
+    if parsed.output_format == OutputFormat::Json && parsed.extension_content_type.is_none() {
+        if let Some(extension) = state.output_formats.extension_for(&parsed.command) {
+            if let Some(format) = OutputFormat::from_extension(extension) {
+                parsed.output_format = format;
+                parsed.extension_content_type = content_type_for_extension(extension);
+                if format != OutputFormat::Json {
+                    parsed.jsonp_callback = None;
+                }
+            }
+        }
+    }
+
     let auth = AuthContext {
         client_ip: addr.ip(),
-        authorization: auth_header,
+        authorization: authorization(&headers),
     };
+    // Hooks see the command as the client sent it.
+    if let Err(error) = state.hooks.before(&mut parsed.command) {
+        admission::audit(
+            &state,
+            &auth,
+            &parsed.command,
+            execution_error_status(&error),
+            started,
+        );
+        return execution_error_response(&state, &parsed, &headers, &error);
+    }
 
-    if !state
-        .acl
-        .check_auth(&auth, parsed.command.command_name.as_str())
-    {
-        return json_value_response(
+    // Routing rules only apply when the URL did not name a backend.
+    admission::route(&state, &auth, &mut parsed.command);
+
+    // Check ACL
+    if !admission::authorize(&state, &auth, &parsed.command) {
+        admission::audit(
+            &state,
+            &auth,
+            &parsed.command,
+            StatusCode::FORBIDDEN,
+            started,
+        );
+        return state.errors.response(
             StatusCode::FORBIDDEN,
-            json!({"error": "Forbidden"}),
+            "forbidden",
+            "Forbidden",
+            &headers,
             parsed.jsonp_callback.as_deref(),
         );
     }
 
-    let execution = state.command_executor.execute(&parsed.command).await;
+    let charge = match admission::charge(&state, &auth, 1).await {
+        Ok(charge) => charge,
+        Err(retry_after) => {
+            let jsonp = parsed.jsonp_callback.as_deref();
+            return admission::quota_exceeded(&state, &headers, retry_after, jsonp);
+        }
+    };
+    let response = run_command(state.clone(), parsed, upload, addr, headers, auth, started).await;
+    charge.meter(&state, response)
+}
 
-    let mut response = match execution {
+/// Runs an admitted command and renders its reply.
+async fn run_command(
+    state: Arc<AppState>,
+    parsed: ParsedRequest,
+    upload: Option<Body>,
+    addr: SocketAddr,
+    headers: HeaderMap,
+    auth: AuthContext,
+    started: Instant,
+) -> Response {
+    // Streamed replies would bypass the `after` hooks.
+    let streamable = upload.is_none() && state.hooks.is_empty();
+    if streamable && crate::download::is_streamable(&parsed) {
+        if let Some(chunk_size) = state.get_stream_chunk_size {
+            if let Some(response) = crate::download::stream_get(&state, &parsed, chunk_size).await {
+                let response = with_response_headers(response, &parsed);
+                return with_attachment(response, &state, &parsed, true);
+            }
+        }
+    }
+    if streamable && crate::download::is_list_streamable(&parsed) {
+        if let Some(page_size) = state.list_stream_page_size {
+            if let Some(response) = crate::download::stream_lrange(&state, &parsed, page_size).await
+            {
+                return with_response_headers(response, &parsed);
+            }
+        }
+    }
+
+    let mut metadata = KeyMetadata::default();
+    let mut execution = match upload {
+        Some(body) => {
+            let chunk_size = state.put_stream_chunk_size.unwrap_or(usize::MAX);
+            match crate::upload::stream_set(&state, &parsed.command, body, chunk_size).await {
+                Ok(reply) => Ok(reply),
+                Err(crate::upload::UploadError::TooLarge) => {
+                    return payload_too_large(&state, &headers)
+                }
+                Err(crate::upload::UploadError::Execution(error)) => Err(error),
+            }
+        }
+        None => {
+            let execution =
+                if (state.ttl_header || state.last_modified) && reads_single_key(&parsed.command) {
+                    let (execution, fetched) = execute_with_metadata(&state, &parsed.command).await;
+                    metadata = fetched;
+                    execution
+                } else {
+                    state.command_executor.execute(&parsed.command).await
+                };
+            if let (Some(shadow), Ok(reply)) = (&state.shadow, &execution) {
+                shadow.mirror(&state.command_executor, &parsed.command, reply);
+            }
+            execution
+        }
+    };
+    if let Ok(reply) = &mut execution {
+        admission::complete(&state, &parsed.command, reply).await;
+    }
+
+    // Only an existing key has a modification time.
+    let last_modified = match &execution {
+        Ok(RedisValue::Nil) | Err(_) => None,
+        Ok(_) => metadata.modified,
+    };
+    if let Some(modified) = last_modified {
+        if headers.get(header::IF_NONE_MATCH).is_none() && modified_since(&headers, modified) {
+            let mut response = not_modified_response();
+            set_last_modified(&mut response, modified);
+            return response;
+        }
+    }
+
+    let succeeded = execution.is_ok();
+    let response = match execution {
         Ok(val) => {
             if matches!(parsed.output_format, OutputFormat::Raw) {
                 // Raw mode: convert directly to RESP (Redis Serialization Protocol) bytes.
                 // This bypasses the JSON conversion and returns the exact wire protocol representation
-                // expected by Redis clients.
+                // expected by Redis clients. A nil reply, such as a missing key, is a 404.
                 let bytes = resp::value_to_resp(&val);
-                Response::builder()
+                let status = match val {
+                    RedisValue::Nil => StatusCode::NOT_FOUND,
+                    _ => StatusCode::OK,
+                };
+                let etag = if parsed.etag_enabled && status == StatusCode::OK {
+                    let tag = body_etag(&parsed, &bytes);
+                    if let Some(resp) = not_modified(&headers, &tag) {
+                        return resp;
+                    }
+                    Some(tag)
+                } else {
+                    None
+                };
+                let mut resp = Response::builder()
+                    .status(status)
                     .header(header::CONTENT_TYPE, "text/plain")
                     .body(Body::from(bytes))
-                    .unwrap()
+                    .unwrap();
+                if let Some(tag) = etag {
+                    resp.headers_mut()
+                        .insert(header::ETAG, tag.parse().unwrap());
+                }
+                resp
+            } else if matches!(parsed.output_format, OutputFormat::Text)
+                && matches!(val, RedisValue::Nil)
+            {
+                StatusCode::NOT_FOUND.into_response()
             } else if matches!(parsed.output_format, OutputFormat::Text) {
                 // Text/binary mode: return only the string value bytes with a MIME type
                 // implied by the suffix (.txt, .html, .png, etc.).
                 let bytes = match redis_value_to_bytes(val) {
                     Some(b) => b,
                     None => {
-                        return json_value_response(
+                        return state.errors.response(
                             StatusCode::INTERNAL_SERVER_ERROR,
-                            json!({"error": "Text output supports only string/binary Redis replies"}),
+                            "unsupported_reply",
+                            "Text output supports only string/binary Redis replies",
+                            &headers,
                             None,
                         );
                     }
@@ -239,26 +575,9 @@ async fn process_request(
 
                 // Compute ETag for GET requests (body is None).
                 let etag = if parsed.etag_enabled {
-                    let mut hasher = Sha1::new();
-                    hasher.update(parsed.command.command_name.as_bytes());
-                    for arg in &parsed.command.args {
-                        hasher.update(arg);
-                    }
-                    hasher.update(&bytes);
-                    let tag = format!("\"{:x}\"", hasher.finalize());
-
-                    if let Some(if_none_match) = headers
-                        .get(header::IF_NONE_MATCH)
-                        .and_then(|h| h.to_str().ok())
-                    {
-                        if if_none_match == tag {
-                            let mut resp = StatusCode::NOT_MODIFIED.into_response();
-                            resp.headers_mut()
-                                .insert(header::ETAG, tag.parse().unwrap());
-                            resp.headers_mut()
-                                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
-                            return resp;
-                        }
+                    let tag = body_etag(&parsed, &bytes);
+                    if let Some(resp) = not_modified(&headers, &tag) {
+                        return resp;
                     }
                     Some(tag)
                 } else {
@@ -274,20 +593,47 @@ async fn process_request(
                         .insert(header::ETAG, tag.parse().unwrap());
                 }
                 resp
+            } else if let Some(reply) = (!is_info_command(&parsed.command))
+                .then(|| {
+                    crate::reply::encode(
+                        &parsed.command.command_name,
+                        &val,
+                        parsed.jsonp_callback.as_deref(),
+                        state.json_nil,
+                    )
+                })
+                .flatten()
+            {
+                // Simple replies skip the `serde_json::Value` tree; see `crate::reply`.
+                let etag = if parsed.etag_enabled {
+                    let tag = json_etag(&parsed, reply.value_json());
+                    if let Some(resp) = not_modified(&headers, &tag) {
+                        return resp;
+                    }
+                    Some(tag)
+                } else {
+                    None
+                };
+                let content_type = if parsed.jsonp_callback.is_some() {
+                    "application/javascript; charset=utf-8"
+                } else {
+                    "application/json"
+                };
+                let mut resp = Response::builder()
+                    .header(header::CONTENT_TYPE, content_type)
+                    .body(Body::from(reply.body))
+                    .unwrap();
+                if let Some(tag) = etag {
+                    resp.headers_mut()
+                        .insert(header::ETAG, tag.parse().unwrap());
+                }
+                resp
             } else {
-                let mut json_val = redis_value_to_json(val);
+                let omit_key = state.json_nil == JsonNil::Omit && val == RedisValue::Nil;
+                let mut json_val = redis_value_to_json_with_nil(val, state.json_nil);
 
                 // Special handling for INFO command to return structured JSON
-                if (parsed.command.command_name.eq_ignore_ascii_case("INFO")
-                    || (parsed.command.command_name.eq_ignore_ascii_case("CLUSTER")
-                        && parsed
-                            .command
-                            .args
-                            .get(0)
-                            .map(|a| bytes_eq_ignore_ascii_case(a, b"INFO"))
-                            .unwrap_or(false)))
-                    && json_val.is_string()
-                {
+                if is_info_command(&parsed.command) && json_val.is_string() {
                     if let Some(s) = json_val.as_str() {
                         json_val = parse_info_output(s);
                     }
@@ -295,30 +641,10 @@ async fn process_request(
 
                 // Compute ETag for GET requests (body is None)
                 let etag = if parsed.etag_enabled {
-                    let mut hasher = Sha1::new();
-                    hasher.update(parsed.command.command_name.as_bytes());
-                    for arg in &parsed.command.args {
-                        hasher.update(arg);
-                    }
-                    if let Some(cb) = parsed.jsonp_callback.as_deref() {
-                        hasher.update(cb.as_bytes());
-                    }
                     // Use the string representation of json_val for stable hashing
-                    hasher.update(json_val.to_string().as_bytes());
-                    let tag = format!("\"{:x}\"", hasher.finalize());
-
-                    if let Some(if_none_match) = headers
-                        .get(header::IF_NONE_MATCH)
-                        .and_then(|h| h.to_str().ok())
-                    {
-                        if if_none_match == tag {
-                            let mut resp = StatusCode::NOT_MODIFIED.into_response();
-                            resp.headers_mut()
-                                .insert(header::ETAG, tag.parse().unwrap());
-                            resp.headers_mut()
-                                .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
-                            return resp;
-                        }
+                    let tag = json_etag(&parsed, json_val.to_string().as_bytes());
+                    if let Some(resp) = not_modified(&headers, &tag) {
+                        return resp;
                     }
                     Some(tag)
                 } else {
@@ -326,11 +652,15 @@ async fn process_request(
                 };
 
                 // Note: ETag must vary by JSONP callback, since the response body changes.
-                let mut resp = parsed.output_format.format_response(
-                    parsed.command.command_name.as_str(),
-                    json_val,
-                    parsed.jsonp_callback.as_deref(),
-                );
+                let mut resp = if omit_key {
+                    json_value_response(StatusCode::OK, json!({}), parsed.jsonp_callback.as_deref())
+                } else {
+                    parsed.output_format.format_response(
+                        parsed.command.command_name.as_str(),
+                        json_val,
+                        parsed.jsonp_callback.as_deref(),
+                    )
+                };
                 if let Some(tag) = etag {
                     resp.headers_mut()
                         .insert(header::ETAG, tag.parse().unwrap());
@@ -343,38 +673,187 @@ async fn process_request(
                 "Redis command execution failed: command={} db={} client={} error={}",
                 parsed.command.command_name, parsed.command.target_database, addr, error
             );
-            if matches!(parsed.output_format, OutputFormat::Raw) {
-                // Raw Error: -ERR message
-                let err_msg = format!("-ERR {}\r\n", error);
-                Response::builder()
-                    .header(header::CONTENT_TYPE, "text/plain")
-                    .body(Body::from(err_msg))
-                    .unwrap()
-            } else if matches!(parsed.output_format, OutputFormat::Text) {
-                // Text errors mirror the original Webdis behavior: errors are plain text.
-                let status = match error {
-                    CommandExecutionError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
-                    CommandExecutionError::ExecutionFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
-                };
-                Response::builder()
-                    .status(status)
-                    .header(header::CONTENT_TYPE, "text/plain")
-                    .body(Body::from(error.to_string()))
-                    .unwrap()
-            } else {
-                let status = match error {
-                    CommandExecutionError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
-                    CommandExecutionError::ExecutionFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
-                };
-                json_value_response(
-                    status,
-                    json!({"error": error.to_string()}),
-                    parsed.jsonp_callback.as_deref(),
-                )
-            }
+            execution_error_response(&state, &parsed, &headers, &error)
+        }
+    };
+
+    let response = with_response_headers(response, &parsed);
+    let mut response = with_attachment(response, &state, &parsed, succeeded);
+    if let Some(ttl) = metadata.ttl {
+        response
+            .headers_mut()
+            .insert("x-ttl-ms", HeaderValue::from(ttl));
+    }
+    if let Some(modified) = last_modified.filter(|_| response.status() == StatusCode::OK) {
+        set_last_modified(&mut response, modified);
+    }
+    admission::audit(&state, &auth, &parsed.command, response.status(), started);
+    response
+}
+
+/// The response to a failed command, in the requested output format.
+fn execution_error_response(
+    state: &AppState,
+    parsed: &ParsedRequest,
+    headers: &HeaderMap,
+    error: &CommandExecutionError,
+) -> Response {
+    if matches!(parsed.output_format, OutputFormat::Raw) {
+        let frame = raw_error_frame(&error.to_string());
+        let response = Response::builder()
+            .status(execution_error_status(error))
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(frame))
+            .unwrap();
+        with_retry_after(response, error)
+    } else if matches!(parsed.output_format, OutputFormat::Text) {
+        // Text errors mirror the original Webdis behavior: errors are plain text.
+        let response = Response::builder()
+            .status(execution_error_status(error))
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from(error.to_string()))
+            .unwrap();
+        with_retry_after(response, error)
+    } else {
+        state
+            .errors
+            .execution_response(error, headers, parsed.jsonp_callback.as_deref())
+    }
+}
+
+/// Single-key reads answered with an `X-TTL-Ms` header when `ttl_header` is
+/// set, and with `Last-Modified` when `last_modified` is.
+const SINGLE_KEY_READS: &[&str] = &[
+    "GET",
+    "GETRANGE",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HVALS",
+    "LINDEX",
+    "LLEN",
+    "LRANGE",
+    "SCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "STRLEN",
+    "ZCARD",
+    "ZRANGE",
+    "ZRANGEBYSCORE",
+    "ZREVRANGE",
+    "ZREVRANGEBYSCORE",
+    "ZSCORE",
+];
+
+fn reads_single_key(command: &ExecutableCommand) -> bool {
+    !command.args.is_empty()
+        && SINGLE_KEY_READS
+            .iter()
+            .any(|name| command.command_name.eq_ignore_ascii_case(name))
+}
+
+/// What a single-key read learns about its key besides the reply.
+#[derive(Default)]
+struct KeyMetadata {
+    /// The `PTTL` with `ttl_header`: `None` for a missing key, and `-1` for a
+    /// key without expiry.
+    ttl: Option<i64>,
+    /// The `<key>:__mtime__` timestamp with `last_modified`.
+    modified: Option<SystemTime>,
+}
+
+/// Runs `command` in one pipeline with a `PTTL` of its key and a lookup of
+/// its modification time, as enabled.
+async fn execute_with_metadata(
+    state: &AppState,
+    command: &ExecutableCommand,
+) -> (Result<RedisValue, CommandExecutionError>, KeyMetadata) {
+    let mut requests = vec![command.clone()];
+    if state.ttl_header {
+        requests.push(ExecutableCommand {
+            command_name: "PTTL".to_string(),
+            args: command.args[..1].to_vec(),
+            ..command.clone()
+        });
+    }
+    if state.last_modified {
+        requests.push(crate::last_modified::lookup(command));
+    }
+    let mut metadata = KeyMetadata::default();
+    let replies = match state.command_executor.execute_pipeline(&requests).await {
+        Ok(replies) if replies.len() == requests.len() => replies,
+        Ok(_) => {
+            return (
+                Err(CommandExecutionError::ExecutionFailed(
+                    "unexpected pipeline reply".to_string(),
+                )),
+                metadata,
+            )
         }
+        Err(error) => return (Err(error), metadata),
     };
+    let mut replies = replies.into_iter();
+    let reply = replies.next().unwrap_or(RedisValue::Nil);
+    if state.ttl_header {
+        metadata.ttl = match replies.next() {
+            Some(RedisValue::Int(ttl)) if ttl >= -1 => Some(ttl),
+            _ => None,
+        };
+    }
+    if state.last_modified {
+        metadata.modified = replies
+            .next()
+            .and_then(|reply| crate::last_modified::parse(&reply));
+    }
+    (Ok(reply), metadata)
+}
 
+/// Whether `If-Modified-Since` is not older than `modified`; HTTP dates have
+/// one-second precision.
+fn modified_since(headers: &HeaderMap, modified: SystemTime) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| modified <= since)
+}
+
+fn set_last_modified(response: &mut Response, modified: SystemTime) {
+    if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+}
+
+/// Adds `Content-Disposition: attachment` to a successful raw or text reply
+/// named by `?filename=` or else by the `downloads` rules. JSON replies and
+/// errors are left inline.
+fn with_attachment(
+    mut response: Response,
+    state: &AppState,
+    parsed: &ParsedRequest,
+    succeeded: bool,
+) -> Response {
+    if !succeeded || !response.status().is_success() || parsed.output_format == OutputFormat::Json {
+        return response;
+    }
+    let filename = parsed
+        .filename
+        .clone()
+        .or_else(|| state.downloads.filename_for(&parsed.command));
+    if let Some(value) =
+        filename.and_then(|filename| HeaderValue::from_str(&content_disposition(&filename)).ok())
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+    response
+}
+
+/// Adds the CORS header and the requested content type to a command reply.
+fn with_response_headers(mut response: Response, parsed: &ParsedRequest) -> Response {
     // Add CORS headers to every response
     response
         .headers_mut()
@@ -410,6 +889,153 @@ fn bytes_eq_ignore_ascii_case(lhs: &[u8], rhs: &[u8]) -> bool {
     lhs.eq_ignore_ascii_case(rhs)
 }
 
+/// Completes once the server starts shutting down.
+pub(crate) async fn shut_down(shutdown: &mut tokio::sync::watch::Receiver<bool>) {
+    if shutdown.wait_for(|&down| down).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+pub(crate) fn execution_error_status(error: &CommandExecutionError) -> StatusCode {
+    match error {
+        CommandExecutionError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        CommandExecutionError::ExecutionFailed(message) => {
+            redis_error_status(message).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+        CommandExecutionError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        CommandExecutionError::Rejected(_) => StatusCode::BAD_REQUEST,
+        CommandExecutionError::Overloaded(_) | CommandExecutionError::PoolExhausted(_) => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        CommandExecutionError::ValueTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+    }
+}
+
+/// Status of an error Redis replied with, picked by the code leading its
+/// message, or `None` for other failures. `ERR` is a client error only for
+/// the requests Redis could not make sense of; other `ERR` replies, such as
+/// script errors, stay server-side failures.
+fn redis_error_status(message: &str) -> Option<StatusCode> {
+    let (code, detail) = message.split_once(' ')?;
+    Some(match code {
+        "ERR" if is_request_error(detail) => StatusCode::BAD_REQUEST,
+        "ERR" => StatusCode::INTERNAL_SERVER_ERROR,
+        "WRONGTYPE" | "EXECABORT" | "CROSSSLOT" => StatusCode::BAD_REQUEST,
+        "NOAUTH" | "WRONGPASS" | "NOPERM" => StatusCode::FORBIDDEN,
+        "NOSCRIPT" => StatusCode::NOT_FOUND,
+        "BUSY" | "LOADING" | "MASTERDOWN" | "CLUSTERDOWN" | "TRYAGAIN" | "READONLY" | "MISCONF" => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        "OOM" => StatusCode::INSUFFICIENT_STORAGE,
+        _ => return None,
+    })
+}
+
+/// Raw Error: the Redis error line, keeping its code, or -ERR message.
+pub(crate) fn raw_error_frame(message: &str) -> Vec<u8> {
+    match message.split_once(' ') {
+        Some((code, detail)) if redis_error_status(message).is_some() => {
+            resp::simple_error(code, Some(detail))
+        }
+        _ => resp::error_to_resp(message),
+    }
+}
+
+/// `ERR` details Redis gives a request it could not parse or accept.
+fn is_request_error(detail: &str) -> bool {
+    const REQUEST_ERRORS: &[&str] = &[
+        "unknown command",
+        "unknown subcommand",
+        "wrong number of arguments",
+        "syntax error",
+        "value is not",
+        "invalid",
+    ];
+    REQUEST_ERRORS
+        .iter()
+        .any(|prefix| detail.starts_with(prefix))
+}
+
+/// `INFO` and `CLUSTER INFO`, whose text replies are returned as objects.
+fn is_info_command(command: &ExecutableCommand) -> bool {
+    command.command_name.eq_ignore_ascii_case("INFO")
+        || (command.command_name.eq_ignore_ascii_case("CLUSTER")
+            && command
+                .args
+                .first()
+                .map(|a| bytes_eq_ignore_ascii_case(a, b"INFO"))
+                .unwrap_or(false))
+}
+
+/// ETag of a JSON reply, given the JSON of the reply value.
+fn json_etag(parsed: &ParsedRequest, value_json: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(parsed.command.command_name.as_bytes());
+    for arg in &parsed.command.args {
+        hasher.update(arg);
+    }
+    if let Some(cb) = parsed.jsonp_callback.as_deref() {
+        hasher.update(cb.as_bytes());
+    }
+    hasher.update(value_json);
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+/// ETag of a raw or text reply, given its body.
+fn body_etag(parsed: &ParsedRequest, body: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(parsed.command.command_name.as_bytes());
+    for arg in &parsed.command.args {
+        hasher.update(arg);
+    }
+    hasher.update(body);
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+/// `304 Not Modified` when the client's `If-None-Match` lists `tag` or is
+/// `*`.
+fn not_modified(headers: &HeaderMap, tag: &str) -> Option<Response> {
+    let if_none_match = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())?;
+    if !etag_listed(if_none_match, tag) {
+        return None;
+    }
+    let mut resp = not_modified_response();
+    resp.headers_mut()
+        .insert(header::ETAG, tag.parse().unwrap());
+    Some(resp)
+}
+
+/// An empty `304 Not Modified` with the headers every reply carries, for both
+/// the `If-None-Match` and the `If-Modified-Since` checks.
+fn not_modified_response() -> Response {
+    let mut resp = StatusCode::NOT_MODIFIED.into_response();
+    resp.headers_mut()
+        .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
+    resp
+}
+
+/// Whether an `If-None-Match` value matches `tag`. The comparison is weak,
+/// as RFC 9110 asks for `If-None-Match`: a `W/` prefix is ignored.
+fn etag_listed(if_none_match: &str, tag: &str) -> bool {
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .map(|candidate| candidate.trim())
+            .any(|candidate| candidate.strip_prefix("W/").unwrap_or(candidate) == tag)
+}
+
+fn payload_too_large(state: &AppState, headers: &HeaderMap) -> Response {
+    state.errors.response(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "payload_too_large",
+        "Request body is larger than http_max_request_size",
+        headers,
+        None,
+    )
+}
+
 fn request_parse_error_message(error: &RequestParseError) -> String {
     match error {
         RequestParseError::EmptyCommand => "Empty command".to_string(),
@@ -419,31 +1045,85 @@ fn request_parse_error_message(error: &RequestParseError) -> String {
         RequestParseError::MissingCommandAfterDatabasePrefix => {
             "Missing command after database prefix".to_string()
         }
+        RequestParseError::MissingCommandAfterBackendPrefix => {
+            "Missing command after backend prefix".to_string()
+        }
+        RequestParseError::InvalidTimeout => {
+            "Invalid timeout. Expected a positive duration such as 500ms or 2s".to_string()
+        }
+        RequestParseError::InvalidSeparator => {
+            "Invalid sep. Expected a single character other than % and .".to_string()
+        }
+        RequestParseError::InvalidBase64Argument => {
+            "Invalid argument. b64args expects every argument to be base64-encoded".to_string()
+        }
+        RequestParseError::InvalidJsonpCallback => {
+            "Invalid JSONP callback. Expected a JavaScript identifier such as cb or app.onData"
+                .to_string()
+        }
         RequestParseError::InvalidCommand(message) => message.clone(),
     }
 }
 
 /// Converts a Redis response value (RedisValue) into a JSON Value.
-/// This mapping accounts for Redis 0.32+ variant names and prepares for RESP3 types.
+/// This mapping accounts for Redis 0.32+ variant names and the RESP3 types
+/// returned when `redis_protocol` is `resp3`.
 pub fn redis_value_to_json(v: RedisValue) -> Value {
+    redis_value_to_json_with_nil(v, JsonNil::Null)
+}
+
+/// Like [`redis_value_to_json`], rendering Nil as `json_nil` asks. With
+/// [`JsonNil::Omit`], map entries holding Nil are left out and other Nils,
+/// including a Nil reply, stay `null`; callers drop a Nil reply's key.
+pub fn redis_value_to_json_with_nil(v: RedisValue, nil: JsonNil) -> Value {
+    let to_json = |v| redis_value_to_json_with_nil(v, nil);
     match v {
+        RedisValue::Nil if nil == JsonNil::False => Value::Bool(false),
         RedisValue::Nil => Value::Null,
         RedisValue::Int(i) => Value::Number(i.into()),
         // BulkString replaces the older 'Data' variant in modern redis-rs.
         RedisValue::BulkString(bytes) => Value::String(String::from_utf8_lossy(&bytes).to_string()),
         // Array replaces the older 'Bulk' variant.
-        RedisValue::Array(items) => {
-            Value::Array(items.into_iter().map(redis_value_to_json).collect())
+        RedisValue::Array(items) | RedisValue::Set(items) => {
+            Value::Array(items.into_iter().map(to_json).collect())
         }
         // SimpleString replaces 'Status'.
         RedisValue::SimpleString(s) => Value::String(s),
         RedisValue::Okay => Value::String("OK".to_string()),
-        // Handle new RESP3 types (Map, Set, Attribute, etc.) by defaulting to Null for now.
-        // As Webdis evolves, these can be mapped to more specific JSON structures.
+        // JSON object keys must be strings, so map keys are rendered as text.
+        RedisValue::Map(entries) => Value::Object(
+            entries
+                .into_iter()
+                .filter(|(_, value)| !(nil == JsonNil::Omit && *value == RedisValue::Nil))
+                .map(|(key, value)| (redis_value_to_key(key), to_json(value)))
+                .collect(),
+        ),
+        // JSON has no infinity/NaN, so non-finite doubles fall back to strings.
+        RedisValue::Double(d) => serde_json::Number::from_f64(d)
+            .map(Value::Number)
+            .unwrap_or_else(|| Value::String(d.to_string())),
+        RedisValue::Boolean(b) => Value::Bool(b),
+        RedisValue::VerbatimString { text, .. } => Value::String(text),
+        RedisValue::BigNumber(number) => Value::String(number.to_string()),
+        // Attributes are out-of-band metadata; only the data is part of the reply.
+        RedisValue::Attribute { data, .. } => to_json(*data),
+        RedisValue::Push { kind, data } => Value::Array(
+            std::iter::once(Value::String(kind.to_string()))
+                .chain(data.into_iter().map(to_json))
+                .collect(),
+        ),
+        RedisValue::ServerError(error) => json!({"error": error.to_string()}),
         _ => Value::Null,
     }
 }
 
+fn redis_value_to_key(v: RedisValue) -> String {
+    match redis_value_to_json(v) {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
 /// Parses the textual output of the Redis INFO command into a structured JSON object.
 ///
 /// It splits the output line by line, ignoring comments (starting with #) and empty lines,
@@ -476,6 +1156,10 @@ fn redis_value_to_bytes(v: RedisValue) -> Option<Vec<u8>> {
         RedisValue::BulkString(bytes) => Some(bytes),
         RedisValue::SimpleString(s) => Some(s.into_bytes()),
         RedisValue::Okay => Some(b"OK".to_vec()),
+        RedisValue::Double(d) => Some(d.to_string().into_bytes()),
+        RedisValue::Boolean(b) => Some(b.to_string().into_bytes()),
+        RedisValue::VerbatimString { text, .. } => Some(text.into_bytes()),
+        RedisValue::BigNumber(number) => Some(number.to_string().into_bytes()),
         _ => None,
     }
 }
@@ -0,0 +1,224 @@
+//! Background health checks of named backends.
+//!
+//! With `backend_health` set, each backend in `backends` gets a `PING` every
+//! `interval_sec` on a connection of its own, so a busy pool cannot fail the
+//! check. A backend that fails `unhealthy_threshold` checks in a row is
+//! ejected: `routes` rules naming it are skipped, and routed commands go to
+//! the next matching rule or to the top-level Redis. It is readmitted after
+//! `healthy_threshold` successful checks in a row. Requests that name a
+//! backend in the URL still reach it.
+
+use crate::redis;
+use ::redis::aio::MultiplexedConnection;
+use redis_web_core::config::{BackendHealthConfig, Config};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+const DEFAULT_INTERVAL_SEC: u64 = 5;
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+const DEFAULT_HEALTHY_THRESHOLD: u32 = 2;
+
+/// Health of the named backends. Backends without checks are always healthy.
+#[derive(Debug, Default)]
+pub struct BackendHealth {
+    backends: BTreeMap<String, BackendStatus>,
+    unhealthy_threshold: u32,
+    healthy_threshold: u32,
+}
+
+#[derive(Debug)]
+struct BackendStatus {
+    healthy: AtomicBool,
+    failures: AtomicU32,
+    successes: AtomicU32,
+    ejections: AtomicU64,
+}
+
+/// Snapshot of one backend's health.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendHealthStats {
+    pub name: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub consecutive_successes: u32,
+    pub ejections: u64,
+}
+
+impl BackendHealth {
+    /// Tracks every backend of `config` when `backend_health` is set.
+    pub fn new(config: &Config) -> Self {
+        let Some(settings) = config.backend_health.as_ref() else {
+            return Self::default();
+        };
+        let backends = config
+            .backends
+            .iter()
+            .flatten()
+            .map(|(name, _)| {
+                let status = BackendStatus {
+                    healthy: AtomicBool::new(true),
+                    failures: AtomicU32::new(0),
+                    successes: AtomicU32::new(0),
+                    ejections: AtomicU64::new(0),
+                };
+                (name.clone(), status)
+            })
+            .collect();
+        Self {
+            backends,
+            unhealthy_threshold: settings
+                .unhealthy_threshold
+                .unwrap_or(DEFAULT_UNHEALTHY_THRESHOLD)
+                .max(1),
+            healthy_threshold: settings
+                .healthy_threshold
+                .unwrap_or(DEFAULT_HEALTHY_THRESHOLD)
+                .max(1),
+        }
+    }
+
+    /// Whether `routes` may send commands to `backend`.
+    pub fn is_healthy(&self, backend: &str) -> bool {
+        self.backends
+            .get(backend)
+            .is_none_or(|status| status.healthy.load(Ordering::Relaxed))
+    }
+
+    /// Records the result of one check of `backend`.
+    pub fn record(&self, backend: &str, passed: bool) {
+        let Some(status) = self.backends.get(backend) else {
+            return;
+        };
+        if passed {
+            status.failures.store(0, Ordering::Relaxed);
+            let successes = status.successes.fetch_add(1, Ordering::Relaxed) + 1;
+            if successes >= self.healthy_threshold && !status.healthy.swap(true, Ordering::Relaxed)
+            {
+                info!("Backend {backend} passed {successes} health checks; readmitted");
+            }
+        } else {
+            status.successes.store(0, Ordering::Relaxed);
+            let failures = status.failures.fetch_add(1, Ordering::Relaxed) + 1;
+            if failures >= self.unhealthy_threshold && status.healthy.swap(false, Ordering::Relaxed)
+            {
+                status.ejections.fetch_add(1, Ordering::Relaxed);
+                warn!("Backend {backend} failed {failures} health checks; ejected from routes");
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Vec<BackendHealthStats> {
+        self.backends
+            .iter()
+            .map(|(name, status)| BackendHealthStats {
+                name: name.clone(),
+                healthy: status.healthy.load(Ordering::Relaxed),
+                consecutive_failures: status.failures.load(Ordering::Relaxed),
+                consecutive_successes: status.successes.load(Ordering::Relaxed),
+                ejections: status.ejections.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// Starts one check task per backend of `config`.
+pub fn spawn_checks(health: Arc<BackendHealth>, config: &Config) {
+    let Some(settings) = config.backend_health.as_ref() else {
+        return;
+    };
+    for (name, backend) in config.backends.iter().flatten() {
+        let client = match redis::create_client(&redis::backend_config(config, backend)) {
+            Ok(client) => client,
+            Err(error) => {
+                error!("Cannot health-check backend {name}: {error}");
+                continue;
+            }
+        };
+        tokio::spawn(check_backend(
+            health.clone(),
+            name.clone(),
+            client,
+            settings.clone(),
+        ));
+    }
+}
+
+async fn check_backend(
+    health: Arc<BackendHealth>,
+    name: String,
+    client: ::redis::Client,
+    settings: BackendHealthConfig,
+) {
+    let interval = settings.interval_sec.unwrap_or(DEFAULT_INTERVAL_SEC).max(1);
+    let timeout = Duration::from_millis(settings.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+    let mut ticks = tokio::time::interval(Duration::from_secs(interval));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut connection: Option<MultiplexedConnection> = None;
+    loop {
+        ticks.tick().await;
+        let passed = tokio::time::timeout(timeout, ping(&client, &mut connection))
+            .await
+            .is_ok_and(|result| result.is_ok());
+        if !passed {
+            // Reconnect on the next check instead of reusing a broken connection.
+            connection = None;
+        }
+        health.record(&name, passed);
+    }
+}
+
+async fn ping(
+    client: &::redis::Client,
+    connection: &mut Option<MultiplexedConnection>,
+) -> Result<(), ::redis::RedisError> {
+    let connection = match connection {
+        Some(connection) => connection,
+        None => connection.insert(client.get_multiplexed_async_connection().await?),
+    };
+    ::redis::cmd("PING").query_async(connection).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis_web_core::config::BackendConfig;
+
+    #[test]
+    fn ejects_and_readmits_after_consecutive_checks() {
+        let mut config = Config::default();
+        let backend: BackendConfig = serde_json::from_str("{}").unwrap();
+        config.backends = Some(BTreeMap::from([("replica".to_string(), backend)]));
+        config.backend_health = Some(BackendHealthConfig {
+            unhealthy_threshold: Some(2),
+            healthy_threshold: Some(2),
+            ..Default::default()
+        });
+        let health = BackendHealth::new(&config);
+
+        health.record("replica", false);
+        assert!(health.is_healthy("replica"));
+        health.record("replica", false);
+        assert!(!health.is_healthy("replica"));
+        health.record("replica", true);
+        health.record("replica", false);
+        health.record("replica", true);
+        assert!(!health.is_healthy("replica"));
+        health.record("replica", true);
+        assert!(health.is_healthy("replica"));
+        assert_eq!(
+            health.stats(),
+            vec![BackendHealthStats {
+                name: "replica".to_string(),
+                healthy: true,
+                consecutive_failures: 0,
+                consecutive_successes: 2,
+                ejections: 1,
+            }]
+        );
+        assert!(health.is_healthy("unknown"));
+    }
+}
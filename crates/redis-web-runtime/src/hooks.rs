@@ -0,0 +1,113 @@
+//! Command hooks run on client commands.
+//!
+//! [`CommandHooks`] holds the registered [`CommandHook`]s: the WebAssembly
+//! modules listed in `hooks` (see [`crate::wasm`]) followed by the Rust hooks
+//! an embedder passes to [`crate::server::build_runtime_with_hooks`]. Every
+//! entry point runs `before` on a client command ahead of its routing and ACL
+//! checks, and `after` on its reply. `before` hooks run in registration order
+//! and `after` hooks in reverse, so the first hook sees the final reply.
+//! Commands the server runs for itself, such as quota counters, audit
+//! records, `Last-Modified` timestamps and replay history, skip the hooks.
+
+use redis::Value as RedisValue;
+use redis_web_core::interfaces::{CommandExecutionError, CommandHook, ExecutableCommand};
+use std::sync::Arc;
+
+/// The hooks applied to client commands, in registration order.
+#[derive(Clone, Default)]
+pub struct CommandHooks {
+    hooks: Vec<Arc<dyn CommandHook>>,
+}
+
+impl CommandHooks {
+    pub fn new(hooks: Vec<Arc<dyn CommandHook>>) -> Self {
+        Self { hooks }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Runs the `before` hooks; a refusal stops at the refusing hook.
+    pub fn before(&self, command: &mut ExecutableCommand) -> Result<(), CommandExecutionError> {
+        for hook in &self.hooks {
+            hook.before(command)
+                .map_err(CommandExecutionError::Rejected)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the `after` hooks on the reply to `command`.
+    pub fn after(&self, command: &ExecutableCommand, reply: &mut RedisValue) {
+        for hook in self.hooks.iter().rev() {
+            hook.after(command, reply);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prefixes keys with `tenant:` and refuses FLUSHALL.
+    struct Tenant;
+
+    impl CommandHook for Tenant {
+        fn before(&self, request: &mut ExecutableCommand) -> Result<(), String> {
+            if request.command_name.eq_ignore_ascii_case("FLUSHALL") {
+                return Err("FLUSHALL is disabled".to_string());
+            }
+            if let Some(key) = request.args.first_mut() {
+                key.splice(0..0, b"tenant:".iter().copied());
+            }
+            Ok(())
+        }
+
+        fn after(&self, request: &ExecutableCommand, reply: &mut RedisValue) {
+            if let RedisValue::BulkString(bytes) = reply {
+                bytes.extend_from_slice(format!(" ({})", request.command_name).as_bytes());
+            }
+        }
+    }
+
+    /// Upper-cases bulk string replies.
+    struct Shout;
+
+    impl CommandHook for Shout {
+        fn after(&self, _request: &ExecutableCommand, reply: &mut RedisValue) {
+            if let RedisValue::BulkString(bytes) = reply {
+                bytes.make_ascii_uppercase();
+            }
+        }
+    }
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    #[test]
+    fn hooks_rewrite_commands_and_replies() {
+        let hooks = CommandHooks::new(vec![Arc::new(Tenant), Arc::new(Shout)]);
+
+        let mut get = command("get", &["key"]);
+        hooks.before(&mut get).unwrap();
+        assert_eq!(get.args, [b"tenant:key".to_vec()]);
+
+        // `after` runs in reverse, so `Tenant` sees the upper-cased reply.
+        let mut reply = RedisValue::BulkString(b"value".to_vec());
+        hooks.after(&get, &mut reply);
+        assert_eq!(reply, RedisValue::BulkString(b"VALUE (get)".to_vec()));
+
+        let error = hooks.before(&mut command("FLUSHALL", &[])).unwrap_err();
+        assert!(
+            matches!(error, CommandExecutionError::Rejected(message) if message == "FLUSHALL is disabled")
+        );
+    }
+}
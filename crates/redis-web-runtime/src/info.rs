@@ -0,0 +1,128 @@
+//! Build and feature discovery on `GET /_info`.
+//!
+//! Fleet tooling and client libraries read which optional features an
+//! instance supports here instead of probing for them:
+//!
+//! ```json
+//! {"name": "redis-web", "version": "0.1.0", "git_hash": "3f2c9a1b7d40",
+//!  "features": {"mqtt": false, "simd_json": false},
+//!  "listeners": [{"protocol": "http", "address": "0.0.0.0:7379"}],
+//!  "capabilities": {"websockets": true, "metrics": false, ...}}
+//! ```
+//!
+//! `features` are the Cargo features the binary was compiled with, and
+//! `capabilities` what the loaded configuration turns on. The document holds
+//! no credentials, host names of Redis or paths on disk.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use redis_web_core::config::{
+    Config, RedisProtocol, DEFAULT_HTTP_MAX_REQUEST_SIZE, DEFAULT_MQTT_PORT,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Commit the binary was built from, or `unknown`.
+pub const GIT_HASH: &str = env!("REDIS_WEB_GIT_HASH");
+/// URL extensions picking a reply format.
+const OUTPUT_FORMATS: &[&str] = &[
+    "json", "raw", "txt", "html", "xhtml", "xml", "png", "jpg", "jpeg",
+];
+
+/// The `/_info` document of `config`, built once when the router is.
+pub fn document(config: &Config) -> Value {
+    let mut listeners = vec![json!({
+        "protocol": "http",
+        "address": format!("{}:{}", config.http_host, config.http_port),
+    })];
+    let mqtt = config
+        .mqtt
+        .as_ref()
+        .filter(|mqtt| cfg!(feature = "mqtt") && mqtt.enabled);
+    if let Some(mqtt) = mqtt {
+        listeners.push(json!({
+            "protocol": "mqtt",
+            "address": format!(
+                "{}:{}",
+                mqtt.host.as_deref().unwrap_or(&config.http_host),
+                mqtt.port.unwrap_or(DEFAULT_MQTT_PORT)
+            ),
+        }));
+    }
+    let metrics = config
+        .metrics
+        .as_ref()
+        .is_some_and(|metrics| metrics.enabled);
+    let max_request_size = config
+        .http_max_request_size
+        .unwrap_or(DEFAULT_HTTP_MAX_REQUEST_SIZE);
+    let compat_hiredis = config
+        .compat_hiredis
+        .as_ref()
+        .is_some_and(|compat| compat.enabled);
+    json!({
+        "name": "redis-web",
+        "version": VERSION,
+        "git_hash": GIT_HASH,
+        "features": {
+            "mqtt": cfg!(feature = "mqtt"),
+            "simd_json": cfg!(feature = "simd-json"),
+            "wasm_hooks": cfg!(feature = "wasm-hooks"),
+        },
+        "listeners": listeners,
+        "capabilities": {
+            "websockets": config.websockets,
+            "pubsub": true,
+            "metrics": metrics,
+            "redis_tls": config.ssl.as_ref().is_some_and(|ssl| ssl.enabled),
+            "redis_cluster": config.redis_cluster.is_some(),
+            "resp3": config.redis_protocol == RedisProtocol::Resp3,
+            "compat_hiredis": compat_hiredis,
+            "mqtt": mqtt.is_some(),
+            "streamed_uploads": config.http_put_stream_chunk_size.is_some(),
+            "streamed_replies": config.http_get_stream_chunk_size.is_some()
+                || config.http_list_stream_page_size.is_some(),
+            "server_timing": config.server_timing,
+            "ttl_header": config.ttl_header,
+            "last_modified": config.last_modified,
+            "output_formats": OUTPUT_FORMATS,
+            "max_request_size": max_request_size,
+        },
+    })
+}
+
+/// Handles `GET /_info`.
+pub async fn handle_info(document: Arc<Value>) -> Response {
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "no-cache")],
+        axum::Json(document.as_ref().clone()),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_build_listeners_and_capabilities() {
+        let config = Config::from_value(json!({
+            "http_port": 8080,
+            "websockets": true,
+            "redis_protocol": "resp3"
+        }))
+        .unwrap();
+        let info = document(&config);
+        assert_eq!(info["version"], VERSION);
+        assert!(!GIT_HASH.is_empty());
+        assert_eq!(info["listeners"][0]["address"], "0.0.0.0:8080");
+        assert_eq!(info["capabilities"]["websockets"], true);
+        assert_eq!(info["capabilities"]["resp3"], true);
+        assert_eq!(info["capabilities"]["redis_cluster"], false);
+        assert_eq!(info["features"]["mqtt"], cfg!(feature = "mqtt"));
+    }
+}
@@ -0,0 +1,42 @@
+//! JSON parsing for request paths that see many small documents.
+//!
+//! WebSocket command frames and `/_query` bodies are parsed here. Builds with
+//! the `simd-json` feature use simd-json, which picks the best instruction set
+//! the CPU offers at startup and has a portable fallback; other builds use
+//! serde_json. Both produce the same `serde_json::Value`.
+
+use serde_json::Value;
+
+/// Parses a JSON document; `None` when it is not valid JSON.
+pub(crate) fn parse(bytes: &[u8]) -> Option<Value> {
+    #[cfg(feature = "simd-json")]
+    {
+        // simd-json parses in place, so it works on its own copy.
+        let mut bytes = bytes.to_vec();
+        simd_json::serde::from_slice(&mut bytes).ok()
+    }
+    #[cfg(not(feature = "simd-json"))]
+    {
+        serde_json::from_slice(bytes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_like_serde_json() {
+        let text = r#"{"id":7,"cmd":["SET","k","café"],"n":-1.5,"big":18446744073709551615,"ok":true,"none":null}"#;
+        assert_eq!(
+            parse(text.as_bytes()),
+            serde_json::from_str::<Value>(text).ok()
+        );
+        assert_eq!(
+            parse(b"[\"GET\",\"k\"]"),
+            Some(serde_json::json!(["GET", "k"]))
+        );
+        assert_eq!(parse(b"{\"cmd\":"), None);
+        assert_eq!(parse(b""), None);
+    }
+}
@@ -0,0 +1,306 @@
+//! `Last-Modified` support backed by shadow timestamp keys.
+//!
+//! With `last_modified` set, each successful write from any entry point also
+//! stores the time of the write, in seconds since the Unix epoch, under
+//! `<key>:__mtime__`. Single-key reads on command URLs fetch that timestamp
+//! in the same pipeline as the command, answer with a `Last-Modified` header,
+//! and with `304 Not Modified` when `If-Modified-Since` is not older than it.
+//! A shadow key expires with its key, and writes made by other Redis clients
+//! do not update it.
+
+use redis::Value as RedisValue;
+use redis_web_core::interfaces::{CommandExecutor, ExecutableCommand};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Appended to a key to name its shadow timestamp key.
+pub const MTIME_SUFFIX: &[u8] = b":__mtime__";
+
+/// Commands whose arguments name no key to timestamp.
+const KEYLESS_WRITES: &[&str] = &["EVAL", "EVALSHA", "FCALL", "FLUSHALL", "FLUSHDB", "SWAPDB"];
+
+/// Commands that remove the keys they name.
+const DELETES: &[&str] = &["DEL", "GETDEL", "UNLINK"];
+
+/// Commands that reply `0` when their condition kept them from writing.
+const CONDITIONAL_WRITES: &[&str] = &[
+    "SETNX",
+    "MSETNX",
+    "HSETNX",
+    "RENAMENX",
+    "COPY",
+    "MOVE",
+    "SMOVE",
+    "PERSIST",
+    "EXPIRE",
+    "PEXPIRE",
+    "EXPIREAT",
+    "PEXPIREAT",
+];
+
+/// The shadow timestamp key of `key`.
+pub fn mtime_key(key: &[u8]) -> Vec<u8> {
+    [key, MTIME_SUFFIX].concat()
+}
+
+/// The `GET` of the shadow timestamp key of a single-key read.
+pub(crate) fn lookup(command: &ExecutableCommand) -> ExecutableCommand {
+    ExecutableCommand {
+        command_name: "GET".to_string(),
+        args: vec![mtime_key(&command.args[0])],
+        ..command.clone()
+    }
+}
+
+/// Parses the reply of [`lookup`]; `None` when the key was never written
+/// through redis-web.
+pub(crate) fn parse(reply: &RedisValue) -> Option<SystemTime> {
+    let RedisValue::BulkString(bytes) = reply else {
+        return None;
+    };
+    let seconds = std::str::from_utf8(bytes).ok()?.parse().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Updates the shadow keys of a successful `command` that replied `reply`:
+/// written keys get the current time and their key's expiry, and deleted or
+/// renamed-away keys lose their timestamp. Failures are logged and otherwise
+/// ignored.
+pub(crate) async fn record(
+    executor: &dyn CommandExecutor,
+    command: &ExecutableCommand,
+    reply: &RedisValue,
+) {
+    if !wrote(command, reply) {
+        return;
+    }
+    let updates = updates(command, SystemTime::now());
+    if updates.is_empty() {
+        return;
+    }
+    let lookups = ttl_lookups(&updates);
+    let requests: Vec<_> = updates.iter().chain(&lookups).cloned().collect();
+    let expiries = match executor.execute_pipeline(&requests).await {
+        Ok(replies) => expiries(&lookups, &replies[updates.len()..]),
+        Err(error) => {
+            warn!(
+                "Failed to update Last-Modified timestamps: command={} error={}",
+                command.command_name, error
+            );
+            return;
+        }
+    };
+    if expiries.is_empty() {
+        return;
+    }
+    if let Err(error) = executor.execute_pipeline(&expiries).await {
+        warn!(
+            "Failed to expire Last-Modified timestamps: command={} error={}",
+            command.command_name, error
+        );
+    }
+}
+
+/// Whether `command` changed anything, judging by its reply: a `SET NX` or
+/// `XX` that replied Nil, or a conditional write that replied `0`, did not.
+fn wrote(command: &ExecutableCommand, reply: &RedisValue) -> bool {
+    let name = command.command_name.to_ascii_uppercase();
+    match reply {
+        // With `GET`, Nil is the missing old value of a key that was written.
+        RedisValue::Nil if name == "SET" => command
+            .args
+            .iter()
+            .any(|arg| arg.eq_ignore_ascii_case(b"GET")),
+        RedisValue::Int(0) => !CONDITIONAL_WRITES.contains(&name.as_str()),
+        _ => true,
+    }
+}
+
+/// A `PTTL` of the key behind each timestamp that `updates` sets.
+fn ttl_lookups(updates: &[ExecutableCommand]) -> Vec<ExecutableCommand> {
+    updates
+        .iter()
+        .filter(|update| update.command_name == "SET")
+        .map(|update| ExecutableCommand {
+            command_name: "PTTL".to_string(),
+            args: vec![update.args[0][..update.args[0].len() - MTIME_SUFFIX.len()].to_vec()],
+            ..update.clone()
+        })
+        .collect()
+}
+
+/// A `PEXPIRE` of the shadow key of each looked-up key that expires.
+fn expiries(lookups: &[ExecutableCommand], ttls: &[RedisValue]) -> Vec<ExecutableCommand> {
+    lookups
+        .iter()
+        .zip(ttls)
+        .filter_map(|(lookup, ttl)| match ttl {
+            RedisValue::Int(millis) if *millis > 0 => Some(ExecutableCommand {
+                command_name: "PEXPIRE".to_string(),
+                args: vec![mtime_key(&lookup.args[0]), millis.to_string().into_bytes()],
+                ..lookup.clone()
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn updates(command: &ExecutableCommand, now: SystemTime) -> Vec<ExecutableCommand> {
+    let name = command.command_name.to_ascii_uppercase();
+    if !crate::audit::is_write(&name) || KEYLESS_WRITES.contains(&name.as_str()) {
+        return Vec::new();
+    }
+    let keys = match crate::cluster::command_keys(&name, &command.args) {
+        keys if !keys.is_empty() => keys,
+        _ if name == "BITOP" => command.args.get(1).map(Vec::as_slice).into_iter().collect(),
+        _ => command
+            .args
+            .first()
+            .map(Vec::as_slice)
+            .into_iter()
+            .collect(),
+    };
+    let seconds = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .to_string();
+    let shadow = |command_name: &str, key: &[u8], value: Option<&str>| ExecutableCommand {
+        command_name: command_name.to_string(),
+        args: std::iter::once(mtime_key(key))
+            .chain(value.map(|value| value.as_bytes().to_vec()))
+            .collect(),
+        ..command.clone()
+    };
+    if DELETES.contains(&name.as_str()) {
+        return keys.iter().map(|key| shadow("DEL", key, None)).collect();
+    }
+    if matches!(name.as_str(), "RENAME" | "RENAMENX") {
+        if let [from, to] = keys[..] {
+            return vec![shadow("DEL", from, None), shadow("SET", to, Some(&seconds))];
+        }
+    }
+    keys.iter()
+        .map(|key| shadow("SET", key, Some(&seconds)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    fn summary(command: &ExecutableCommand) -> Vec<String> {
+        updates(command, UNIX_EPOCH + Duration::from_secs(1_718_000_000))
+            .iter()
+            .map(|update| {
+                std::iter::once(update.command_name.clone())
+                    .chain(
+                        update
+                            .args
+                            .iter()
+                            .map(|arg| String::from_utf8_lossy(arg).into_owned()),
+                    )
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn writes_timestamp_their_keys() {
+        assert_eq!(
+            summary(&command("SET", &["k", "v"])),
+            ["SET k:__mtime__ 1718000000"]
+        );
+        assert_eq!(
+            summary(&command("mset", &["a", "1", "b", "2"])),
+            ["SET a:__mtime__ 1718000000", "SET b:__mtime__ 1718000000"]
+        );
+        assert_eq!(
+            summary(&command("BITOP", &["AND", "dest", "a", "b"])),
+            ["SET dest:__mtime__ 1718000000"]
+        );
+        assert_eq!(
+            summary(&command("DEL", &["a", "b"])),
+            ["DEL a:__mtime__", "DEL b:__mtime__"]
+        );
+        assert_eq!(
+            summary(&command("RENAME", &["a", "b"])),
+            ["DEL a:__mtime__", "SET b:__mtime__ 1718000000"]
+        );
+    }
+
+    #[test]
+    fn reads_and_keyless_writes_are_not_timestamped() {
+        assert!(summary(&command("GET", &["k"])).is_empty());
+        assert!(summary(&command("FLUSHDB", &[])).is_empty());
+        assert!(summary(&command("EVAL", &["return 1", "0"])).is_empty());
+    }
+
+    #[test]
+    fn writes_that_did_nothing_are_not_timestamped() {
+        let set_nx = command("SET", &["k", "v", "NX"]);
+        assert!(!wrote(&set_nx, &RedisValue::Nil));
+        assert!(wrote(&set_nx, &RedisValue::Okay));
+        assert!(wrote(
+            &command("SET", &["k", "v", "NX", "GET"]),
+            &RedisValue::Nil
+        ));
+        assert!(!wrote(
+            &command("RENAMENX", &["a", "b"]),
+            &RedisValue::Int(0)
+        ));
+        assert!(wrote(
+            &command("RENAMENX", &["a", "b"]),
+            &RedisValue::Int(1)
+        ));
+        assert!(!wrote(&command("setnx", &["k", "v"]), &RedisValue::Int(0)));
+        // A counter that reached zero still changed.
+        assert!(wrote(&command("INCRBY", &["k", "-1"]), &RedisValue::Int(0)));
+    }
+
+    #[test]
+    fn timestamps_expire_with_their_keys() {
+        let updates = updates(
+            &command("RENAME", &["a", "b"]),
+            UNIX_EPOCH + Duration::from_secs(1_718_000_000),
+        );
+        let lookups = ttl_lookups(&updates);
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].command_name, "PTTL");
+        assert_eq!(lookups[0].args, [b"b".to_vec()]);
+
+        let expiring = expiries(&lookups, &[RedisValue::Int(5_000)]);
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].command_name, "PEXPIRE");
+        assert_eq!(
+            expiring[0].args,
+            [b"b:__mtime__".to_vec(), b"5000".to_vec()]
+        );
+
+        // Keys without expiry, or gone by now, leave the timestamp as it is.
+        assert!(expiries(&lookups, &[RedisValue::Int(-1)]).is_empty());
+        assert!(expiries(&lookups, &[RedisValue::Int(-2)]).is_empty());
+    }
+
+    #[test]
+    fn parses_stored_timestamps() {
+        assert_eq!(
+            parse(&RedisValue::BulkString(b"1718000000".to_vec())),
+            Some(UNIX_EPOCH + Duration::from_secs(1_718_000_000))
+        );
+        assert_eq!(parse(&RedisValue::Nil), None);
+        assert_eq!(parse(&RedisValue::BulkString(b"soon".to_vec())), None);
+    }
+}
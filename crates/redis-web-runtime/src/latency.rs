@@ -0,0 +1,162 @@
+//! Background Redis latency probe.
+//!
+//! With `metrics.latency_probe_interval_ms` set, a task sends Redis a `PING`
+//! at that interval on a connection of its own and records the round trip.
+//! Since the probe bypasses the command pool and the request path, its
+//! latency is Redis and the network alone: when request latency climbs but
+//! the probe stays flat, the time is spent in redis-web.
+
+use crate::redis;
+use ::redis::aio::MultiplexedConnection;
+use redis_web_core::config::Config;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+];
+
+/// Round trips measured by the probe.
+#[derive(Debug, Default)]
+pub struct RedisLatency {
+    last_micros: AtomicU64,
+    /// Probes per bucket of [`LATENCY_BUCKETS`], not cumulative; the last
+    /// entry counts probes slower than every bound.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// Snapshot of [`RedisLatency`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisLatencyStats {
+    /// Latest round trip, in seconds.
+    pub last: f64,
+    /// Cumulative probe counts per bucket of [`LATENCY_BUCKETS`], then the
+    /// `+Inf` bucket.
+    pub buckets: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
+    pub failures: u64,
+}
+
+impl RedisLatency {
+    pub fn record(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.last_micros.store(micros, Ordering::Relaxed);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> RedisLatencyStats {
+        let mut total = 0;
+        let buckets = self
+            .buckets
+            .iter()
+            .map(|bucket| {
+                total += bucket.load(Ordering::Relaxed);
+                total
+            })
+            .collect();
+        RedisLatencyStats {
+            last: micros_to_seconds(self.last_micros.load(Ordering::Relaxed)),
+            buckets,
+            sum: micros_to_seconds(self.sum_micros.load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn micros_to_seconds(micros: u64) -> f64 {
+    micros as f64 / 1_000_000.0
+}
+
+/// Starts the probe when `metrics.latency_probe_interval_ms` is set. `config`
+/// names the Redis to probe: the top-level one, or a seed node in cluster
+/// mode.
+pub fn spawn_probe(latency: Arc<RedisLatency>, config: &Config) {
+    let Some(interval) = config
+        .metrics
+        .as_ref()
+        .filter(|metrics| metrics.enabled)
+        .and_then(|metrics| metrics.latency_probe_interval_ms)
+        .filter(|&ms| ms > 0)
+        .map(Duration::from_millis)
+    else {
+        return;
+    };
+    match redis::create_client(config) {
+        Ok(client) => {
+            tokio::spawn(probe(latency, client, interval));
+        }
+        Err(error) => error!("Cannot start the Redis latency probe: {error}"),
+    }
+}
+
+/// A probe taking longer than the interval counts as failed.
+async fn probe(latency: Arc<RedisLatency>, client: ::redis::Client, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut connection: Option<MultiplexedConnection> = None;
+    loop {
+        ticks.tick().await;
+        match tokio::time::timeout(interval, ping(&client, &mut connection)).await {
+            Ok(Ok(elapsed)) => latency.record(elapsed),
+            _ => {
+                latency.record_failure();
+                // A late reply may still arrive, so the next probe reconnects.
+                connection = None;
+            }
+        }
+    }
+}
+
+/// Times one `PING`, connecting first when needed. Connecting is not timed.
+async fn ping(
+    client: &::redis::Client,
+    connection: &mut Option<MultiplexedConnection>,
+) -> Result<Duration, ::redis::RedisError> {
+    let connection = match connection {
+        Some(connection) => connection,
+        None => connection.insert(client.get_multiplexed_async_connection().await?),
+    };
+    let started = Instant::now();
+    ::redis::cmd("PING").query_async::<()>(connection).await?;
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_round_trips_into_cumulative_buckets() {
+        let latency = RedisLatency::default();
+        latency.record(Duration::from_micros(300));
+        latency.record(Duration::from_millis(3));
+        latency.record(Duration::from_secs(2));
+        latency.record_failure();
+
+        let stats = latency.stats();
+        assert_eq!(stats.last, 2.0);
+        assert_eq!(stats.buckets, vec![1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3]);
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.failures, 1);
+        assert!((stats.sum - 2.0033).abs() < 1e-9);
+    }
+}
@@ -1,10 +1,44 @@
+pub mod admin;
+mod admission;
+pub mod audit;
+pub mod cluster;
+pub mod commands;
 pub mod compat;
+pub mod concurrency;
+mod download;
+pub mod errors;
 pub mod executor;
+pub mod filter;
 pub mod grpc;
 pub mod handler;
+pub mod health;
+pub mod hooks;
+pub mod info;
+mod json;
+pub mod last_modified;
+pub mod latency;
+pub mod metrics;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+pub mod openapi;
+pub mod publish;
 pub mod pubsub;
+pub mod query;
+pub mod quota;
 pub mod redis;
+pub mod replay;
+mod reply;
 pub mod server;
+pub mod server_timing;
+pub mod shadow;
+pub mod sharding;
+pub mod static_files;
+pub mod template;
+pub mod upload;
+pub mod value_size;
+#[cfg(feature = "wasm-hooks")]
+pub mod wasm;
 pub mod websocket;
+pub mod well_known;
 
 pub use redis_web_core::{acl, config, format, interfaces, request, resp};
@@ -0,0 +1,422 @@
+//! Prometheus metrics.
+//!
+//! Subsystems keep their own counters, such as [`PubSubMetrics`]. When
+//! `metrics.enabled` is set, [`handle_metrics`] renders them in the Prometheus
+//! text exposition format on `metrics.path` (`/_metrics` by default).
+
+use crate::handler::AppState;
+use crate::health::BackendHealthStats;
+use crate::latency::{RedisLatencyStats, LATENCY_BUCKETS};
+use crate::pubsub::{PubSubStats, StreamStats};
+use crate::redis::PoolShardStats;
+use crate::shadow::ShadowStats;
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use std::fmt::Write;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+/// Counters for the Pub/Sub fanout path.
+#[derive(Debug, Default)]
+pub struct PubSubMetrics {
+    /// Messages received from Redis.
+    pub messages_received: AtomicU64,
+    /// Message copies handed to local subscribers.
+    pub messages_delivered: AtomicU64,
+    /// Messages skipped by subscribers that fell behind the broadcast buffer.
+    pub messages_lagged: AtomicU64,
+    /// Messages dropped by `pubsub_filters`.
+    pub messages_filtered: AtomicU64,
+    /// Re-established channel and pattern connections.
+    pub reconnects: AtomicU64,
+    /// Re-established sharded connections.
+    pub shard_reconnects: AtomicU64,
+}
+
+/// Axum handler serving the metrics endpoint.
+pub async fn handle_metrics(State(state): State<Arc<AppState>>) -> Response {
+    let mut body = render_pubsub(&state.pubsub.stats().await);
+    body.push_str(&render_streams(&state.sse_limits.stats()));
+    body.push_str(&render_pools(&state.redis_pools.pool_stats().await));
+    body.push_str(&render_backends(&state.backend_health.stats()));
+    body.push_str(&render_latency(&state.redis_latency.stats()));
+    if let Some(shadow) = state.shadow.as_ref() {
+        body.push_str(&render_shadow(&shadow.stats()));
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+/// Renders the Pub/Sub metrics in the Prometheus text format.
+pub fn render_pubsub(pubsub: &PubSubStats) -> String {
+    let mut out = String::new();
+
+    family(
+        &mut out,
+        "redis_web_pubsub_subscribers",
+        "gauge",
+        "Local subscribers per channel, pattern, or sharded channel.",
+    );
+    for topic in &pubsub.topics {
+        let _ = writeln!(
+            out,
+            "redis_web_pubsub_subscribers{{kind=\"{}\",topic=\"{}\"}} {}",
+            topic.topic.kind(),
+            escape_label(topic.topic.name()),
+            topic.subscribers
+        );
+    }
+
+    family(
+        &mut out,
+        "redis_web_pubsub_topic_messages_delivered_total",
+        "counter",
+        "Message copies delivered per channel, pattern, or sharded channel.",
+    );
+    for topic in &pubsub.topics {
+        let _ = writeln!(
+            out,
+            "redis_web_pubsub_topic_messages_delivered_total{{kind=\"{}\",topic=\"{}\"}} {}",
+            topic.topic.kind(),
+            escape_label(topic.topic.name()),
+            topic.messages_delivered
+        );
+    }
+
+    counter(
+        &mut out,
+        "redis_web_pubsub_messages_received_total",
+        "Messages received from Redis.",
+        pubsub.messages_received,
+    );
+    counter(
+        &mut out,
+        "redis_web_pubsub_messages_delivered_total",
+        "Message copies delivered to local subscribers.",
+        pubsub.messages_delivered,
+    );
+    counter(
+        &mut out,
+        "redis_web_pubsub_messages_lagged_total",
+        "Messages dropped because a subscriber fell behind.",
+        pubsub.messages_lagged,
+    );
+    counter(
+        &mut out,
+        "redis_web_pubsub_messages_filtered_total",
+        "Messages dropped by pubsub_filters.",
+        pubsub.messages_filtered,
+    );
+
+    family(
+        &mut out,
+        "redis_web_pubsub_reconnects_total",
+        "counter",
+        "Pub/Sub connections re-established after a disconnect.",
+    );
+    let _ = writeln!(
+        out,
+        "redis_web_pubsub_reconnects_total{{connection=\"pubsub\"}} {}",
+        pubsub.reconnects
+    );
+    let _ = writeln!(
+        out,
+        "redis_web_pubsub_reconnects_total{{connection=\"sharded\"}} {}",
+        pubsub.shard_reconnects
+    );
+
+    out
+}
+
+/// Renders the HTTP subscription stream metrics.
+pub fn render_streams(streams: &StreamStats) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "redis_web_sse_streams",
+        "gauge",
+        "Open HTTP Pub/Sub streams.",
+    );
+    let _ = writeln!(out, "redis_web_sse_streams {}", streams.open);
+    family(
+        &mut out,
+        "redis_web_sse_clients",
+        "gauge",
+        "Client IPs with at least one open HTTP Pub/Sub stream.",
+    );
+    let _ = writeln!(out, "redis_web_sse_clients {}", streams.clients);
+    family(
+        &mut out,
+        "redis_web_sse_streams_rejected_total",
+        "counter",
+        "HTTP Pub/Sub streams refused by the sse connection limits.",
+    );
+    let _ = writeln!(
+        out,
+        "redis_web_sse_streams_rejected_total{{limit=\"global\"}} {}",
+        streams.rejected_total
+    );
+    let _ = writeln!(
+        out,
+        "redis_web_sse_streams_rejected_total{{limit=\"per_ip\"}} {}",
+        streams.rejected_per_ip
+    );
+    counter(
+        &mut out,
+        "redis_web_sse_slow_client_disconnects_total",
+        "SSE streams and WebSockets closed by sse.slow_client_policy.",
+        streams.slow_disconnects,
+    );
+    counter(
+        &mut out,
+        "redis_web_websocket_messages_dropped_total",
+        "Pub/Sub messages dropped because a WebSocket send queue was full.",
+        streams.websocket_drops,
+    );
+    out
+}
+
+/// Renders the command pool usage, one series per database and shard.
+pub fn render_pools(pools: &[(u8, Vec<PoolShardStats>)]) -> String {
+    let mut out = String::new();
+    type Gauge = (&'static str, &'static str, fn(&PoolShardStats) -> usize);
+    let gauges: [Gauge; 4] = [
+        (
+            "redis_web_pool_connections",
+            "Open Redis connections per pool shard.",
+            |shard| shard.size,
+        ),
+        (
+            "redis_web_pool_idle_connections",
+            "Idle Redis connections per pool shard.",
+            |shard| shard.available,
+        ),
+        (
+            "redis_web_pool_max_connections",
+            "Capacity of each pool shard.",
+            |shard| shard.max_size,
+        ),
+        (
+            "redis_web_pool_waiting_checkouts",
+            "Requests waiting for a connection per pool shard.",
+            |shard| shard.waiting,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        family(&mut out, name, "gauge", help);
+        for (database, shards) in pools {
+            for (index, shard) in shards.iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{database=\"{database}\",shard=\"{index}\"}} {}",
+                    value(shard)
+                );
+            }
+        }
+    }
+    out
+}
+
+/// Renders the health of the named backends checked by `backend_health`.
+pub fn render_backends(backends: &[BackendHealthStats]) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "redis_web_backend_healthy",
+        "gauge",
+        "Whether a named backend passes its health checks (1) or is ejected (0).",
+    );
+    for backend in backends {
+        let _ = writeln!(
+            out,
+            "redis_web_backend_healthy{{backend=\"{}\"}} {}",
+            escape_label(&backend.name),
+            u8::from(backend.healthy)
+        );
+    }
+    family(
+        &mut out,
+        "redis_web_backend_ejections_total",
+        "counter",
+        "Times a named backend was ejected after failed health checks.",
+    );
+    for backend in backends {
+        let _ = writeln!(
+            out,
+            "redis_web_backend_ejections_total{{backend=\"{}\"}} {}",
+            escape_label(&backend.name),
+            backend.ejections
+        );
+    }
+    out
+}
+
+/// Renders the round trips of the Redis latency probe.
+pub fn render_latency(latency: &RedisLatencyStats) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "redis_web_redis_ping_latency_seconds",
+        "gauge",
+        "Round trip of the latest Redis latency probe.",
+    );
+    let _ = writeln!(out, "redis_web_redis_ping_latency_seconds {}", latency.last);
+    family(
+        &mut out,
+        "redis_web_redis_ping_duration_seconds",
+        "histogram",
+        "Round trips of the Redis latency probe.",
+    );
+    let bounds = LATENCY_BUCKETS.iter().map(f64::to_string);
+    for (bound, count) in bounds
+        .chain(std::iter::once("+Inf".to_string()))
+        .zip(&latency.buckets)
+    {
+        let _ = writeln!(
+            out,
+            "redis_web_redis_ping_duration_seconds_bucket{{le=\"{bound}\"}} {count}"
+        );
+    }
+    let _ = writeln!(
+        out,
+        "redis_web_redis_ping_duration_seconds_sum {}",
+        latency.sum
+    );
+    let _ = writeln!(
+        out,
+        "redis_web_redis_ping_duration_seconds_count {}",
+        latency.count
+    );
+    counter(
+        &mut out,
+        "redis_web_redis_ping_failures_total",
+        "Redis latency probes that failed or took longer than the interval.",
+        latency.failures,
+    );
+    out
+}
+
+/// Renders the outcomes of the `shadow` copies.
+pub fn render_shadow(shadow: &ShadowStats) -> String {
+    let mut out = String::new();
+    family(
+        &mut out,
+        "redis_web_shadow_commands_total",
+        "counter",
+        "Commands copied to the shadow backend, by how its reply compared.",
+    );
+    for (result, count) in [
+        ("match", shadow.matched),
+        ("mismatch", shadow.mismatched),
+        ("error", shadow.failed),
+    ] {
+        let _ = writeln!(
+            out,
+            "redis_web_shadow_commands_total{{result=\"{result}\"}} {count}"
+        );
+    }
+    out
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    family(out, name, "counter", help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+/// Escapes a label value: backslash, double quote, and line feed.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::{Topic, TopicStats};
+
+    #[test]
+    fn renders_pubsub_metrics() {
+        let stats = PubSubStats {
+            topics: vec![TopicStats {
+                topic: Topic::Pattern("news.\"*\"".to_string()),
+                subscribers: 2,
+                messages_delivered: 6,
+            }],
+            messages_received: 3,
+            messages_delivered: 6,
+            messages_lagged: 1,
+            messages_filtered: 0,
+            reconnects: 1,
+            shard_reconnects: 0,
+        };
+        let text = render_pubsub(&stats);
+        assert!(text.contains(
+            "redis_web_pubsub_subscribers{kind=\"pattern\",topic=\"news.\\\"*\\\"\"} 2\n"
+        ));
+        assert!(text.contains("# TYPE redis_web_pubsub_messages_lagged_total counter\n"));
+        assert!(text.contains("redis_web_pubsub_messages_lagged_total 1\n"));
+        assert!(text.contains("redis_web_pubsub_reconnects_total{connection=\"pubsub\"} 1\n"));
+    }
+
+    #[test]
+    fn renders_pool_metrics_per_shard() {
+        let shard = |size, available| PoolShardStats {
+            max_size: 20,
+            size,
+            available,
+            waiting: 0,
+        };
+        let text = render_pools(&[(0, vec![shard(4, 1), shard(2, 2)]), (3, vec![shard(1, 1)])]);
+        assert!(text.contains("# TYPE redis_web_pool_connections gauge\n"));
+        assert!(text.contains("redis_web_pool_connections{database=\"0\",shard=\"1\"} 2\n"));
+        assert!(text.contains("redis_web_pool_idle_connections{database=\"0\",shard=\"0\"} 1\n"));
+        assert!(text.contains("redis_web_pool_max_connections{database=\"3\",shard=\"0\"} 20\n"));
+    }
+
+    #[test]
+    fn renders_backend_health() {
+        let text = render_backends(&[BackendHealthStats {
+            name: "replica".to_string(),
+            healthy: false,
+            consecutive_failures: 3,
+            consecutive_successes: 0,
+            ejections: 2,
+        }]);
+        assert!(text.contains("redis_web_backend_healthy{backend=\"replica\"} 0\n"));
+        assert!(text.contains("redis_web_backend_ejections_total{backend=\"replica\"} 2\n"));
+    }
+
+    #[test]
+    fn renders_latency_histogram() {
+        let text = render_latency(&RedisLatencyStats {
+            last: 0.0008,
+            buckets: vec![0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2],
+            sum: 1.5008,
+            count: 2,
+            failures: 1,
+        });
+        assert!(text.contains("redis_web_redis_ping_latency_seconds 0.0008\n"));
+        assert!(text.contains("# TYPE redis_web_redis_ping_duration_seconds histogram\n"));
+        assert!(text.contains("redis_web_redis_ping_duration_seconds_bucket{le=\"0.0005\"} 0\n"));
+        assert!(text.contains("redis_web_redis_ping_duration_seconds_bucket{le=\"1\"} 1\n"));
+        assert!(text.contains("redis_web_redis_ping_duration_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(text.contains("redis_web_redis_ping_duration_seconds_count 2\n"));
+        assert!(text.contains("redis_web_redis_ping_failures_total 1\n"));
+    }
+}
@@ -0,0 +1,676 @@
+//! MQTT 3.1.1 listener bridging MQTT clients to Redis Pub/Sub (feature `mqtt`).
+//!
+//! Devices connect to `mqtt.host:mqtt.port` and speak plain MQTT. A PUBLISH
+//! becomes a Redis `PUBLISH` on `<channel_prefix><topic>`, and a SUBSCRIBE
+//! attaches to the shared [`crate::pubsub::PubSubManager`], so MQTT, SSE, and
+//! WebSocket clients see the same messages. Topic filters with `+` or `#`
+//! become a Redis pattern subscription whose messages are then matched level
+//! by level.
+//!
+//! Only what a bridge needs is implemented: QoS 0 delivery to subscribers,
+//! QoS 0 and 1 publishing, and no retained messages, wills, or persistent
+//! sessions. MQTT credentials are checked against the `acl` rules like HTTP
+//! Basic auth, and their PUBLISH packets go to the `audit` stream like the
+//! commands of other entry points.
+
+use crate::handler::{execution_error_status, AppState};
+use crate::pubsub::{PubSubEvent, Topic};
+use axum::http::StatusCode;
+use base64::{engine::general_purpose, Engine as _};
+use redis_web_core::config::{Config, DEFAULT_MQTT_MAX_PACKET_SIZE, DEFAULT_MQTT_PORT};
+use redis_web_core::interfaces::{AuthContext, ExecutableCommand};
+use redis_web_core::routing::glob_escape;
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+/// CONNACK return code for an unsupported protocol level.
+const UNACCEPTABLE_PROTOCOL_VERSION: u8 = 1;
+/// SUBACK return code for a refused topic filter.
+const SUBSCRIBE_FAILURE: u8 = 0x80;
+
+/// Binds the MQTT listener and serves clients until the process exits.
+pub async fn serve(config: &Config, state: Arc<AppState>) -> io::Result<()> {
+    let mqtt = config.mqtt.clone().unwrap_or_default();
+    let host = mqtt.host.as_deref().unwrap_or(&config.http_host);
+    let ip: IpAddr = host.parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid MQTT host {host}"),
+        )
+    })?;
+    let addr = SocketAddr::from((ip, mqtt.port.unwrap_or(DEFAULT_MQTT_PORT)));
+    let listener = TcpListener::bind(addr).await?;
+    info!("MQTT listener bound to {}", listener.local_addr()?);
+    serve_with_listener(config, state, listener).await
+}
+
+/// Serves MQTT clients on an already bound listener.
+pub async fn serve_with_listener(
+    config: &Config,
+    state: Arc<AppState>,
+    listener: TcpListener,
+) -> io::Result<()> {
+    let mqtt = config.mqtt.clone().unwrap_or_default();
+    let settings = Arc::new(Settings {
+        prefix: mqtt.channel_prefix.unwrap_or_default(),
+        max_packet_size: mqtt.max_packet_size.unwrap_or(DEFAULT_MQTT_MAX_PACKET_SIZE),
+    });
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(error) => {
+                warn!("Failed to accept MQTT connection: {}", error);
+                continue;
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        tokio::spawn(handle_connection(
+            stream,
+            peer.ip(),
+            state.clone(),
+            settings.clone(),
+        ));
+    }
+}
+
+struct Settings {
+    prefix: String,
+    max_packet_size: usize,
+}
+
+/// Serves one MQTT client until it disconnects or breaks the protocol.
+async fn handle_connection<S>(stream: S, ip: IpAddr, state: Arc<AppState>, settings: Arc<Settings>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let connect = match read_packet(&mut reader, settings.max_packet_size).await {
+        Ok(Some(packet)) if packet.kind == CONNECT => Connect::parse(&packet.body),
+        _ => return,
+    };
+    let connect = match connect {
+        Ok(connect) => connect,
+        Err(Some(code)) => {
+            let _ = writer.write_all(&encode(CONNACK << 4, &[0, code])).await;
+            return;
+        }
+        Err(None) => return,
+    };
+    if writer
+        .write_all(&encode(CONNACK << 4, &[0, 0]))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    let (outgoing, mut queue) = mpsc::channel::<Vec<u8>>(256);
+    let writer_task = tokio::spawn(async move {
+        while let Some(packet) = queue.recv().await {
+            if writer.write_all(&packet).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session {
+        auth: AuthContext {
+            client_ip: ip,
+            authorization: connect.authorization(),
+        },
+        state,
+        settings,
+        outgoing,
+        subscriptions: HashMap::new(),
+    };
+    // Clients must send something within one and a half keep-alive periods.
+    let idle_limit = (connect.keep_alive > 0)
+        .then(|| Duration::from_millis(u64::from(connect.keep_alive) * 1500));
+    loop {
+        let next = read_packet(&mut reader, session.settings.max_packet_size);
+        let packet = match idle_limit {
+            Some(limit) => match tokio::time::timeout(limit, next).await {
+                Ok(packet) => packet,
+                Err(_) => break,
+            },
+            None => next.await,
+        };
+        let Ok(Some(packet)) = packet else {
+            break;
+        };
+        if !session.handle(packet).await {
+            break;
+        }
+    }
+
+    for (_, forward) in session.subscriptions.drain() {
+        forward.abort();
+    }
+    drop(session);
+    let _ = writer_task.await;
+}
+
+struct Session {
+    auth: AuthContext,
+    state: Arc<AppState>,
+    settings: Arc<Settings>,
+    outgoing: mpsc::Sender<Vec<u8>>,
+    /// Forwarding task per topic filter.
+    subscriptions: HashMap<String, JoinHandle<()>>,
+}
+
+impl Session {
+    /// Handles one packet; returns `false` when the connection should close.
+    async fn handle(&mut self, packet: Packet) -> bool {
+        match packet.kind {
+            PUBLISH => self.publish(packet.flags, &packet.body).await,
+            SUBSCRIBE if packet.flags == 0b0010 => self.subscribe(&packet.body).await,
+            UNSUBSCRIBE if packet.flags == 0b0010 => self.unsubscribe(&packet.body).await,
+            PINGREQ => self.send(encode(PINGRESP << 4, &[])).await,
+            // Subscribers only get QoS 0 messages, so acknowledgements are unexpected but harmless.
+            PUBACK => true,
+            DISCONNECT => false,
+            _ => {
+                warn!(
+                    "MQTT client {} sent unexpected packet type {}",
+                    self.auth.client_ip, packet.kind
+                );
+                false
+            }
+        }
+    }
+
+    async fn send(&self, packet: Vec<u8>) -> bool {
+        self.outgoing.send(packet).await.is_ok()
+    }
+
+    async fn publish(&mut self, flags: u8, body: &[u8]) -> bool {
+        let qos = (flags >> 1) & 0b11;
+        if qos > 1 {
+            warn!(
+                "MQTT client {} sent an unsupported QoS 2 PUBLISH",
+                self.auth.client_ip
+            );
+            return false;
+        }
+        let mut cursor = Cursor(body);
+        let Some(topic) = cursor.string().filter(|topic| valid_topic_name(topic)) else {
+            return false;
+        };
+        let packet_id = if qos == 1 { cursor.u16() } else { Some(0) };
+        let Some(packet_id) = packet_id else {
+            return false;
+        };
+        let started = Instant::now();
+        let channel = format!("{}{}", self.settings.prefix, topic);
+        let mut request = ExecutableCommand {
+            backend: None,
+            target_database: self.state.default_database,
+            command_name: "PUBLISH".to_string(),
+            args: vec![channel.into_bytes(), cursor.0.to_vec()],
+            timeout: None,
+            credentials: None,
+        };
+        // MQTT 3.1.1 cannot refuse a single PUBLISH, so a refused or denied
+        // one, or one over the client's `quotas`, closes the connection.
+        if let Err(error) = self.state.hooks.before(&mut request) {
+            let status = execution_error_status(&error);
+            crate::admission::audit(&self.state, &self.auth, &request, status, started);
+            warn!(
+                "MQTT client {} may not publish to {}: {}",
+                self.auth.client_ip, topic, error
+            );
+            return false;
+        }
+        crate::admission::route(&self.state, &self.auth, &mut request);
+        let channel = request
+            .args
+            .first()
+            .map(|channel| String::from_utf8_lossy(channel));
+        let channel = channel.unwrap_or_default();
+        if !crate::admission::authorize_channel(&self.state, &self.auth, &request, &channel) {
+            let status = StatusCode::FORBIDDEN;
+            crate::admission::audit(&self.state, &self.auth, &request, status, started);
+            warn!(
+                "MQTT client {} may not publish to {}",
+                self.auth.client_ip, channel
+            );
+            return false;
+        }
+        if crate::admission::charge(&self.state, &self.auth, 1)
+            .await
+            .is_err()
+        {
+            warn!(
+                "MQTT client {} is over its usage quota",
+                self.auth.client_ip
+            );
+            return false;
+        }
+        let outcome = crate::admission::execute(&self.state, &request).await;
+        let status = crate::admission::outcome_status(&outcome);
+        crate::admission::audit(&self.state, &self.auth, &request, status, started);
+        if let Err(error) = outcome {
+            // Without a PUBACK, QoS 1 clients retry.
+            warn!("MQTT PUBLISH to {} failed: {}", topic, error);
+            return true;
+        }
+        if qos == 1 {
+            return self
+                .send(encode(PUBACK << 4, &packet_id.to_be_bytes()))
+                .await;
+        }
+        true
+    }
+
+    async fn subscribe(&mut self, body: &[u8]) -> bool {
+        let mut cursor = Cursor(body);
+        let Some(packet_id) = cursor.u16() else {
+            return false;
+        };
+        let mut codes = Vec::new();
+        while !cursor.0.is_empty() {
+            let (Some(filter), Some(_requested_qos)) = (cursor.string(), cursor.u8()) else {
+                return false;
+            };
+            codes.push(self.attach(filter).await);
+        }
+        if codes.is_empty() {
+            return false;
+        }
+        let mut reply = packet_id.to_be_bytes().to_vec();
+        reply.extend(codes);
+        self.send(encode(SUBACK << 4, &reply)).await
+    }
+
+    /// Subscribes to `filter` and returns its SUBACK code: granted QoS 0 or failure.
+    async fn attach(&mut self, filter: String) -> u8 {
+        if !valid_topic_filter(&filter) {
+            return SUBSCRIBE_FAILURE;
+        }
+        let topic = redis_topic(&self.settings.prefix, &filter);
+        if !self
+            .state
+            .acl
+            .check_channel(&self.auth, topic.subscribe_command(), topic.name())
+        {
+            return SUBSCRIBE_FAILURE;
+        }
+        let mut subscription = self.state.pubsub.subscribe_topic(topic).await;
+        let outgoing = self.outgoing.clone();
+        let settings = self.settings.clone();
+        let matcher = filter.clone();
+        let forward = tokio::spawn(async move {
+            loop {
+                match subscription.recv().await {
+                    Ok(PubSubEvent::Message(message)) => {
+                        let Some(topic) = message.channel.strip_prefix(settings.prefix.as_str())
+                        else {
+                            continue;
+                        };
+                        if !topic_matches(&matcher, topic) {
+                            continue;
+                        }
+                        let packet = publish_packet(topic, message.payload.as_bytes());
+                        if outgoing.send(packet).await.is_err() {
+                            break;
+                        }
+                    }
+                    // QoS 0 delivery has no way to report missed messages.
+                    Ok(PubSubEvent::Resubscribed) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        if let Some(previous) = self.subscriptions.insert(filter, forward) {
+            previous.abort();
+        }
+        0
+    }
+
+    async fn unsubscribe(&mut self, body: &[u8]) -> bool {
+        let mut cursor = Cursor(body);
+        let Some(packet_id) = cursor.u16() else {
+            return false;
+        };
+        while !cursor.0.is_empty() {
+            let Some(filter) = cursor.string() else {
+                return false;
+            };
+            if let Some(forward) = self.subscriptions.remove(&filter) {
+                forward.abort();
+            }
+        }
+        self.send(encode(UNSUBACK << 4, &packet_id.to_be_bytes()))
+            .await
+    }
+}
+
+/// The fields of a CONNECT packet the bridge uses.
+#[derive(Debug, PartialEq)]
+struct Connect {
+    keep_alive: u16,
+    username: Option<String>,
+    password: Option<Vec<u8>>,
+}
+
+impl Connect {
+    /// Parses a CONNECT body. Errors carry the CONNACK return code to send, or
+    /// `None` when the connection should just be closed.
+    fn parse(body: &[u8]) -> Result<Self, Option<u8>> {
+        let mut cursor = Cursor(body);
+        let protocol = cursor.string().ok_or(None)?;
+        let level = cursor.u8().ok_or(None)?;
+        // MQTT 3.1 ("MQIsdp", level 3) and 3.1.1 ("MQTT", level 4) share this layout.
+        if !matches!((protocol.as_str(), level), ("MQTT", 4) | ("MQIsdp", 3)) {
+            return Err(Some(UNACCEPTABLE_PROTOCOL_VERSION));
+        }
+        let flags = cursor.u8().ok_or(None)?;
+        let keep_alive = cursor.u16().ok_or(None)?;
+        let _client_id = cursor.string().ok_or(None)?;
+        if flags & 0x04 != 0 {
+            // The will topic and message are read but not used.
+            cursor.string().ok_or(None)?;
+            cursor.bytes().ok_or(None)?;
+        }
+        let username = if flags & 0x80 != 0 {
+            Some(cursor.string().ok_or(None)?)
+        } else {
+            None
+        };
+        let password = if flags & 0x40 != 0 {
+            Some(cursor.bytes().ok_or(None)?.to_vec())
+        } else {
+            None
+        };
+        Ok(Self {
+            keep_alive,
+            username,
+            password,
+        })
+    }
+
+    /// Credentials as an HTTP Basic `Authorization` value, for the `acl` rules.
+    fn authorization(&self) -> Option<String> {
+        let mut credentials = self.username.clone()?.into_bytes();
+        if let Some(password) = &self.password {
+            credentials.push(b':');
+            credentials.extend_from_slice(password);
+        }
+        Some(format!(
+            "Basic {}",
+            general_purpose::STANDARD.encode(credentials)
+        ))
+    }
+}
+
+struct Packet {
+    kind: u8,
+    flags: u8,
+    body: Vec<u8>,
+}
+
+/// Reads one packet; `Ok(None)` means the client closed the connection.
+async fn read_packet<R>(reader: &mut R, max_size: usize) -> io::Result<Option<Packet>>
+where
+    R: AsyncRead + Unpin,
+{
+    let first = match reader.read_u8().await {
+        Ok(byte) => byte,
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let mut length = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = reader.read_u8().await?;
+        length |= usize::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed MQTT remaining length",
+            ));
+        }
+    }
+    if length > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "MQTT packet exceeds mqtt.max_packet_size",
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(Packet {
+        kind: first >> 4,
+        flags: first & 0x0f,
+        body,
+    }))
+}
+
+/// Encodes a packet from its first header byte and body.
+fn encode(first: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![first];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// A QoS 0 PUBLISH of `payload` on `topic`.
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(2 + topic.len() + payload.len());
+    body.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+    body.extend_from_slice(topic.as_bytes());
+    body.extend_from_slice(payload);
+    encode(PUBLISH << 4, &body)
+}
+
+/// Reads MQTT's big-endian integers and length-prefixed strings.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(byte)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let (bytes, rest) = self.0.split_first_chunk::<2>()?;
+        self.0 = rest;
+        Some(u16::from_be_bytes(*bytes))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let length = usize::from(self.u16()?);
+        if self.0.len() < length {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+}
+
+/// Topic names used in PUBLISH are non-empty and have no wildcards.
+fn valid_topic_name(topic: &str) -> bool {
+    !topic.is_empty() && !topic.contains(['+', '#'])
+}
+
+/// `+` must fill a whole level and `#` must be the whole last level.
+fn valid_topic_filter(filter: &str) -> bool {
+    if filter.is_empty() {
+        return false;
+    }
+    let levels: Vec<&str> = filter.split('/').collect();
+    levels
+        .iter()
+        .enumerate()
+        .all(|(index, level)| match *level {
+            "+" => true,
+            "#" => index == levels.len() - 1,
+            level => !level.contains(['+', '#']),
+        })
+}
+
+/// Maps a topic filter to the Redis subscription that carries its messages.
+///
+/// Filters with wildcards become a pattern on the part before the first
+/// wildcard; [`topic_matches`] then drops messages the filter excludes.
+fn redis_topic(prefix: &str, filter: &str) -> Topic {
+    let Some(wildcard) = filter.find(['+', '#']) else {
+        return Topic::Channel(format!("{prefix}{filter}"));
+    };
+    // `a/#` also matches `a` itself, so the pattern stops before the separator.
+    let literal = match &filter[wildcard..] {
+        "#" => filter[..wildcard].strip_suffix('/').unwrap_or(""),
+        _ => &filter[..wildcard],
+    };
+    Topic::Pattern(format!("{}*", glob_escape(&format!("{prefix}{literal}"))))
+}
+
+/// Matches a topic name against a filter with `+` and `#` wildcards.
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    // Wildcards at the first level do not match `$`-prefixed system topics.
+    if topic.starts_with('$') && filter.starts_with(['+', '#']) {
+        return false;
+    }
+    let mut levels = topic.split('/');
+    for pattern in filter.split('/') {
+        match (pattern, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (pattern, Some(level)) if pattern == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn packets_round_trip() {
+        let payload = vec![7u8; 300];
+        let encoded = publish_packet("sensors/1", &payload);
+        // 300 + 2 + 9 bytes needs a two-byte remaining length.
+        assert_eq!(&encoded[..3], &[PUBLISH << 4, 0xb7, 0x02]);
+
+        let mut reader = encoded.as_slice();
+        let packet = read_packet(&mut reader, 1024).await.unwrap().unwrap();
+        assert_eq!((packet.kind, packet.flags), (PUBLISH, 0));
+        let mut cursor = Cursor(&packet.body);
+        assert_eq!(cursor.string().as_deref(), Some("sensors/1"));
+        assert_eq!(cursor.0, payload.as_slice());
+
+        let mut reader = encoded.as_slice();
+        assert!(read_packet(&mut reader, 64).await.is_err());
+        let mut empty: &[u8] = &[];
+        assert!(read_packet(&mut empty, 64).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn parses_connect_with_credentials() {
+        let mut body = vec![0, 4];
+        body.extend_from_slice(b"MQTT");
+        body.extend_from_slice(&[4, 0xc2, 0, 30]);
+        for field in [&b"dev-1"[..], b"ops", b"secret"] {
+            body.extend_from_slice(&(field.len() as u16).to_be_bytes());
+            body.extend_from_slice(field);
+        }
+        let connect = Connect::parse(&body).unwrap();
+        assert_eq!(connect.keep_alive, 30);
+        assert_eq!(
+            connect.authorization().as_deref(),
+            Some("Basic b3BzOnNlY3JldA==")
+        );
+
+        let mut old = vec![0, 4];
+        old.extend_from_slice(b"MQTT");
+        old.extend_from_slice(&[5, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            Connect::parse(&old),
+            Err(Some(UNACCEPTABLE_PROTOCOL_VERSION))
+        );
+        assert_eq!(Connect::parse(&[0, 4]), Err(None));
+    }
+
+    #[test]
+    fn filters_map_to_channels_and_patterns() {
+        assert_eq!(
+            redis_topic("mqtt:", "sensors/1"),
+            Topic::Channel("mqtt:sensors/1".to_string())
+        );
+        assert_eq!(
+            redis_topic("mqtt:", "sensors/+/temp"),
+            Topic::Pattern("mqtt:sensors/*".to_string())
+        );
+        assert_eq!(
+            redis_topic("", "sensors/#"),
+            Topic::Pattern("sensors*".to_string())
+        );
+        assert_eq!(redis_topic("", "#"), Topic::Pattern("*".to_string()));
+        assert_eq!(
+            redis_topic("", "a[1]/+"),
+            Topic::Pattern("a\\[1\\]/*".to_string())
+        );
+    }
+
+    #[test]
+    fn wildcards_match_whole_levels() {
+        assert!(topic_matches("sensors/+/temp", "sensors/1/temp"));
+        assert!(!topic_matches("sensors/+/temp", "sensors/1/2/temp"));
+        assert!(topic_matches("sensors/#", "sensors"));
+        assert!(topic_matches("sensors/#", "sensors/1/temp"));
+        assert!(!topic_matches("sensors/#", "sensorsx"));
+        assert!(!topic_matches("#", "$SYS/uptime"));
+        assert!(topic_matches("a/b", "a/b"));
+        assert!(!topic_matches("a/b", "a/b/c"));
+
+        assert!(valid_topic_filter("a/+/#"));
+        assert!(!valid_topic_filter("a/#/b"));
+        assert!(!valid_topic_filter("a/b+"));
+        assert!(!valid_topic_name("a/+"));
+    }
+}
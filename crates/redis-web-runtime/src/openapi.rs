@@ -0,0 +1,549 @@
+//! OpenAPI description of the HTTP surface, mounted when `openapi.enabled` is
+//! set.
+//!
+//! [`document`] builds the description from the loaded config, so optional
+//! endpoints such as WebSockets, metrics, admin, and the hiredis compat routes
+//! only appear when they are mounted, and each entry in `backends` gets its
+//! own command path. `GET /_openapi.json` serves the document and, with
+//! `openapi.swagger_ui`, `GET /_openapi` serves a Swagger UI page for it.
+
+use axum::{
+    http::header,
+    response::{Html, IntoResponse, Response},
+};
+use redis_web_core::config::{Config, DEFAULT_METRICS_PATH};
+use serde_json::{json, Map, Value};
+
+/// Path of the OpenAPI document.
+pub const DOCUMENT_PATH: &str = "/_openapi.json";
+/// Path of the Swagger UI page.
+pub const SWAGGER_UI_PATH: &str = "/_openapi";
+
+/// Swagger UI page loading the assets from a CDN and rendering
+/// [`DOCUMENT_PATH`].
+const SWAGGER_UI_HTML: &str = include_str!("../assets/swagger-ui.html");
+
+/// Serves a pre-rendered OpenAPI document.
+pub fn document_response(document: &str) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        document.to_string(),
+    )
+        .into_response()
+}
+
+/// Handles `GET /_openapi`.
+pub async fn handle_swagger_ui() -> Response {
+    Html(SWAGGER_UI_HTML).into_response()
+}
+
+/// Describes the endpoints `config` mounts as an OpenAPI 3.1 document.
+pub fn document(config: &Config) -> Value {
+    let mut paths = Map::new();
+    paths.insert("/{command}".into(), command_path(None));
+    paths.insert("/{db}/{command}".into(), command_path(Some(db_param())));
+    for name in config.backends.iter().flat_map(|backends| backends.keys()) {
+        paths.insert(format!("/{name}/{{command}}"), command_path(None));
+        paths.insert(
+            format!("/{name}/{{db}}/{{command}}"),
+            command_path(Some(db_param())),
+        );
+    }
+    if let Some(root) = root_path(config) {
+        paths.insert("/".into(), root);
+    }
+    paths.insert("/_publish".into(), publish_path());
+    paths.insert(
+        "/_query".into(),
+        json!({"post": {
+            "summary": "Run several named commands in one pipeline",
+            "requestBody": {
+                "required": true,
+                "content": {"application/json": {"schema": {
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": {"type": ["string", "number"]},
+                    },
+                    "example": {"user": ["HGETALL", "user:1"], "friends": ["SMEMBERS", "friends:1"]},
+                }}},
+            },
+            "responses": {
+                "200": json_response("Each reply keyed by its name"),
+                "400": error_response("Malformed query"),
+                "403": error_response("The `acl` rules denied a command"),
+                "500": error_response("Redis returned an error"),
+                "503": error_response("Redis is unavailable"),
+            },
+        }}),
+    );
+    paths.insert(
+        "/_commands".into(),
+        json!({"get": {
+            "summary": "Commands the caller may run",
+            "responses": {
+                "200": json_response("`{\"commands\": [{\"name\", \"categories\", \"readonly\"}]}`, filtered by the `acl` rules"),
+                "503": error_response("Redis is unavailable"),
+            },
+        }}),
+    );
+    paths.insert(
+        "/_info".into(),
+        json!({"get": {
+            "summary": "Build and feature discovery",
+            "responses": {
+                "200": json_response("`{\"name\", \"version\", \"git_hash\", \"features\", \"listeners\", \"capabilities\"}`"),
+            },
+        }}),
+    );
+    for (command, param, shape) in [
+        ("SUBSCRIBE", "channel", "message"),
+        ("PSUBSCRIBE", "pattern", "pmessage"),
+        ("SSUBSCRIBE", "channel", "smessage"),
+    ] {
+        paths.insert(
+            format!("/{command}/{{{param}}}"),
+            subscribe_path(command, param, shape),
+        );
+    }
+    if config.websockets {
+        paths.insert(
+            "/.json".into(),
+            websocket_path("JSON commands and replies over a WebSocket"),
+        );
+        paths.insert(
+            "/.raw".into(),
+            websocket_path("RESP commands and replies over a WebSocket"),
+        );
+    }
+    if let Some(metrics) = config.metrics.as_ref().filter(|metrics| metrics.enabled) {
+        let path = metrics.path.as_deref().unwrap_or(DEFAULT_METRICS_PATH);
+        paths.insert(
+            path.into(),
+            json!({"get": {
+                "summary": "Prometheus metrics",
+                "responses": {"200": {
+                    "description": "Metrics in the Prometheus text format",
+                    "content": {"text/plain": {"schema": {"type": "string"}}},
+                }},
+            }}),
+        );
+    }
+    if let Some(admin) = config.admin.as_ref().filter(|admin| admin.enabled) {
+        paths.insert(
+            "/_admin/stats".into(),
+            json!({"get": {
+                "summary": "Runtime statistics",
+                "responses": {
+                    "200": json_response("Pub/Sub and stream statistics"),
+                    "403": error_response("The admin access checks denied the client"),
+                },
+            }}),
+        );
+        paths.insert(
+            "/_admin/ratelimits".into(),
+            json!({"get": {
+                "summary": "Stream limits and quota use of each client",
+                "responses": {
+                    "200": json_response("`{\"streams\": {\"max_connections_per_ip\", \"clients\": [{\"ip\", \"open_streams\", \"remaining\"}]}, \"quotas\": {\"clients\": [{\"identity\", \"commands_today\", \"blocked_for\", ...}]}}`"),
+                    "403": error_response("The admin access checks denied the client"),
+                },
+            }}),
+        );
+        if config.quotas.is_some() {
+            paths.insert(
+                "/_admin/ratelimits/{identity}".into(),
+                json!({"delete": {
+                    "summary": "Reset the quota counters of a client, lifting its block",
+                    "parameters": [{
+                        "name": "identity",
+                        "in": "path",
+                        "required": true,
+                        "description": "Client IP, or Basic auth user name.",
+                        "schema": {"type": "string"},
+                    }],
+                    "responses": {
+                        "200": json_response("`{\"identity\", \"cleared\"}`"),
+                        "403": error_response("The admin access checks denied the client"),
+                    },
+                }}),
+            );
+        }
+        paths.insert(
+            "/_admin/config".into(),
+            json!({"get": {
+                "summary": "Loaded config with credentials masked",
+                "responses": {
+                    "200": json_response("`{\"config\", \"sources\", \"env\"}`"),
+                    "403": error_response("The admin access checks denied the client"),
+                },
+            }}),
+        );
+        if admin.console {
+            paths.insert(
+                "/_console".into(),
+                json!({"get": {
+                    "summary": "Web console",
+                    "responses": {
+                        "200": {"description": "Console page", "content": {"text/html": {}}},
+                        "401": {"description": "Admin credentials are required"},
+                    },
+                }}),
+            );
+        }
+    }
+    if let Some(static_dir) = config.static_dir.as_ref() {
+        paths.insert(
+            format!("{}/{{path}}", static_dir.path_prefix.trim_end_matches('/')),
+            json!({"get": {
+                "summary": "Static file from `static_dir`",
+                "parameters": [{
+                    "name": "path",
+                    "in": "path",
+                    "required": true,
+                    "description": "File path; a directory serves its `index.html`.",
+                    "schema": {"type": "string"},
+                }],
+                "responses": {
+                    "200": {"description": "The file, typed by its extension"},
+                    "304": {"description": "The cached copy is current"},
+                    "404": {"description": "No such file"},
+                },
+            }}),
+        );
+    }
+    if let Some(compat) = config
+        .compat_hiredis
+        .as_ref()
+        .filter(|compat| compat.enabled)
+    {
+        insert_compat_paths(&mut paths, &compat.path_prefix);
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "redis-web",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Redis commands over HTTP. Each path segment after the command is one argument.",
+        },
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "CommandReply": {
+                    "type": "object",
+                    "description": "The reply keyed by the command name, for example `{\"GET\": \"world\"}`.",
+                    "additionalProperties": true,
+                },
+                "Error": {
+                    "type": "object",
+                    "properties": {"error": {"type": "string"}},
+                },
+            },
+            "securitySchemes": {"basicAuth": {"type": "http", "scheme": "basic"}},
+        },
+    })
+}
+
+fn command_param() -> Value {
+    json!({
+        "name": "command",
+        "in": "path",
+        "required": true,
+        "description": "Command name, followed by its arguments as further path segments (`/SET/key/value`). A `.json`, `.raw`, `.txt`, or image suffix selects the reply format.",
+        "schema": {"type": "string"},
+    })
+}
+
+fn db_param() -> Value {
+    json!({
+        "name": "db",
+        "in": "path",
+        "required": true,
+        "description": "Database the command runs in.",
+        "schema": {"type": "integer", "minimum": 0},
+    })
+}
+
+fn command_path(prefix: Option<Value>) -> Value {
+    let mut params: Vec<Value> = prefix.into_iter().collect();
+    params.push(command_param());
+    params.push(json!({
+        "name": "timeout",
+        "in": "query",
+        "description": "Command timeout such as `500ms` or `2s`, clamped to `max_command_timeout_ms`.",
+        "schema": {"type": "string"},
+    }));
+    params.push(json!({
+        "name": "type",
+        "in": "query",
+        "description": "Overrides the `Content-Type` of the reply.",
+        "schema": {"type": "string"},
+    }));
+    params.push(json!({
+        "name": "sep",
+        "in": "query",
+        "description": "Single character that separates the arguments instead of `/`, so arguments may contain slashes.",
+        "schema": {"type": "string", "minLength": 1, "maxLength": 1},
+    }));
+    params.push(json!({
+        "name": "arg",
+        "in": "query",
+        "description": "One more argument, appended after the path arguments; repeat for several, in order.",
+        "schema": {"type": "array", "items": {"type": "string"}},
+        "style": "form",
+        "explode": true,
+    }));
+    params.push(json!({
+        "name": "b64args",
+        "in": "query",
+        "description": "`1` when every argument in the path is base64-encoded (URL-safe or standard alphabet, padding optional).",
+        "schema": {"type": "string", "enum": ["1", "true"]},
+    }));
+    params.push(json!({
+        "name": "jsonp",
+        "in": "query",
+        "description": "Wraps a JSON reply in a call to this function. Dot-separated JavaScript identifiers only; other names are refused with `400`.",
+        "schema": {"type": "string"},
+    }));
+    json!({
+        "parameters": params,
+        "get": {"summary": "Run a command", "responses": command_responses()},
+        "put": {
+            "summary": "Run a command with the request body as its last argument",
+            "requestBody": {
+                "required": true,
+                "content": {"application/octet-stream": {
+                    "schema": {"type": "string", "format": "binary"}
+                }},
+            },
+            "responses": command_responses(),
+        },
+    })
+}
+
+/// The methods `root_commands` and `default_root` mount on `/`.
+fn root_path(config: &Config) -> Option<Value> {
+    let root = config.root_commands.clone().unwrap_or_default();
+    let mut methods = Map::new();
+    if let Some(command) = root.get.or_else(|| config.default_root.clone()) {
+        methods.insert(
+            "get".into(),
+            json!({"summary": format!("Run `{command}`"), "responses": command_responses()}),
+        );
+    }
+    let with_body = |command: String| {
+        json!({
+            "summary": format!("Run `{command}` with the request body as its last argument"),
+            "requestBody": {
+                "required": true,
+                "content": {"application/octet-stream": {
+                    "schema": {"type": "string", "format": "binary"}
+                }},
+            },
+            "responses": command_responses(),
+        })
+    };
+    if root.post_body_command {
+        methods.insert(
+            "post".into(),
+            json!({
+                "summary": "Run the command line in the request body",
+                "requestBody": {
+                    "required": true,
+                    "content": {"text/plain": {
+                        "schema": {"type": "string", "example": "SET/hello/world"}
+                    }},
+                },
+                "responses": command_responses(),
+            }),
+        );
+    } else if let Some(command) = root.post {
+        methods.insert("post".into(), with_body(command));
+    }
+    if let Some(command) = root.put {
+        methods.insert("put".into(), with_body(command));
+    }
+    (!methods.is_empty()).then_some(Value::Object(methods))
+}
+
+fn command_responses() -> Value {
+    json!({
+        "200": {
+            "description": "The Redis reply",
+            "content": {
+                "application/json": {"schema": {"$ref": "#/components/schemas/CommandReply"}},
+                "application/octet-stream": {"schema": {"type": "string", "format": "binary"}},
+            },
+        },
+        "400": error_response("Malformed command, or a command rejected before execution"),
+        "403": error_response("The `acl` rules denied the command"),
+        "500": error_response("Redis returned an error"),
+        "503": error_response("Redis is unavailable"),
+        "504": error_response("The command timed out"),
+    })
+}
+
+fn json_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": {"type": "object"}}},
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Error"}}},
+    })
+}
+
+fn publish_path() -> Value {
+    json!({"post": {
+        "summary": "Publish one message to several channels",
+        "requestBody": {
+            "required": true,
+            "content": {"application/json": {"schema": {
+                "type": "object",
+                "required": ["message"],
+                "properties": {
+                    "message": {"type": "string"},
+                    "channels": {"type": "array", "items": {"type": "string"}},
+                    "pattern": {"type": "string"},
+                },
+            }}},
+        },
+        "responses": {
+            "200": json_response("Receiver count per channel, as `{\"PUBLISH\": {\"news\": 2}}`"),
+            "400": error_response("Neither `channels` nor `pattern` was given"),
+            "403": error_response("The `acl` rules denied a channel"),
+        },
+    }})
+}
+
+fn subscribe_path(command: &str, param: &str, shape: &str) -> Value {
+    json!({"get": {
+        "summary": format!("Stream messages with {command}"),
+        "parameters": [{
+            "name": param,
+            "in": "path",
+            "required": true,
+            "description": "Slash-separated list; append `.json` for newline-delimited JSON.",
+            "schema": {"type": "string"},
+        }],
+        "responses": {
+            "200": {
+                "description": format!("Server-Sent Events, or JSON documents in the `{shape}` shape"),
+                "content": {
+                    "text/event-stream": {"schema": {"type": "string"}},
+                    "application/json": {"schema": {"type": "object"}},
+                },
+            },
+            "403": error_response("The `acl` rules denied a subscription"),
+            "429": error_response("Too many subscription streams"),
+        },
+    }})
+}
+
+fn websocket_path(summary: &str) -> Value {
+    json!({"get": {
+        "summary": summary,
+        "responses": {"101": {"description": "Switching to the WebSocket protocol"}},
+    }})
+}
+
+fn insert_compat_paths(paths: &mut Map<String, Value>, prefix: &str) {
+    let session = json!({
+        "name": "session_id",
+        "in": "path",
+        "required": true,
+        "schema": {"type": "string"},
+    });
+    paths.insert(
+        format!("{prefix}/session"),
+        json!({"post": {
+            "summary": "Open a hiredis compat session",
+            "responses": {
+                "200": json_response("The new `session_id`"),
+                "429": error_response("The session limit is reached"),
+            },
+        }}),
+    );
+    paths.insert(
+        format!("{prefix}/session/{{session_id}}"),
+        json!({"delete": {
+            "summary": "Close a compat session",
+            "parameters": [session],
+            "responses": {
+                "204": {"description": "Session closed"},
+                "404": {"description": "Unknown session"},
+            },
+        }}),
+    );
+    paths.insert(
+        format!("{prefix}/cmd/{{session_id}}.raw"),
+        json!({"post": {
+            "summary": "Run pipelined RESP commands in a session",
+            "parameters": [session],
+            "requestBody": {
+                "required": true,
+                "content": {"application/octet-stream": {}},
+            },
+            "responses": {"200": {"description": "RESP replies", "content": {"application/octet-stream": {}}}},
+        }}),
+    );
+    paths.insert(
+        format!("{prefix}/stream/{{session_id}}.raw"),
+        json!({"get": {
+            "summary": "Stream a session's Pub/Sub replies as RESP",
+            "parameters": [session],
+            "responses": {"200": {"description": "RESP stream", "content": {"application/octet-stream": {}}}},
+        }}),
+    );
+    paths.insert(
+        format!("{prefix}/ws/{{session_id}}"),
+        json!({"get": {
+            "summary": "RESP over a WebSocket in a session",
+            "parameters": [session],
+            "responses": {"101": {"description": "Switching to the WebSocket protocol"}},
+        }}),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_follows_the_mounted_endpoints() {
+        let config = Config::from_value(json!({})).unwrap();
+        let doc = document(&config);
+        assert_eq!(doc["openapi"], "3.1.0");
+        let paths = doc["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/{command}"));
+        assert!(paths.contains_key("/_publish"));
+        assert!(!paths.contains_key("/.json"));
+        assert!(!paths.contains_key("/_admin/stats"));
+        assert!(!paths.contains_key("/"));
+
+        let config = Config::from_value(json!({
+            "websockets": true,
+            "backends": {"cache": {"redis_host": "cache"}},
+            "admin": {"enabled": true},
+            "metrics": {"enabled": true, "path": "/metrics"},
+            "root_commands": {"get": "PING", "post_body_command": true},
+        }))
+        .unwrap();
+        let doc = document(&config);
+        let paths = doc["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/.json"));
+        assert!(paths.contains_key("/cache/{command}"));
+        assert!(paths.contains_key("/_admin/stats"));
+        assert!(paths.contains_key("/metrics"));
+        assert!(!paths.contains_key("/_console"));
+        assert!(paths["/"]["get"].is_object());
+        assert!(paths["/"]["post"].is_object());
+        assert!(paths["/"].get("put").is_none());
+    }
+}
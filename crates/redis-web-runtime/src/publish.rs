@@ -0,0 +1,267 @@
+//! Fan-out publishing on `POST /_publish`.
+//!
+//! The body names a message and either a list of channels or a pattern:
+//!
+//! ```json
+//! {"message": "hello", "channels": ["news.a", "news.b"]}
+//! {"message": "hello", "pattern": "news.*"}
+//! ```
+//!
+//! A pattern is expanded with `PUBSUB CHANNELS`, so it only reaches channels
+//! that currently have subscribers. Every `PUBLISH` is sent in one pipeline
+//! and the response maps each channel to its receiver count:
+//! `{"PUBLISH": {"news.a": 2, "news.b": 0}}`. The `PUBSUB` and each `PUBLISH`
+//! go through the shared admission layer like the command URLs: hooks,
+//! `routes`, the `acl` rules with their `channels`, `quotas` and `audit`.
+
+use crate::admission;
+use crate::handler::{execution_error_status, AppState};
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use redis::Value as RedisValue;
+use redis_web_core::format::json_value_response;
+use redis_web_core::interfaces::{AuthContext, CommandExecutionError, ExecutableCommand};
+use serde_json::{json, Map, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Handles `POST /_publish`.
+pub async fn handle_publish(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let request = match PublishRequest::parse(&body) {
+        Ok(request) => request,
+        Err(message) => {
+            return error_response(
+                &state,
+                &headers,
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                message,
+            )
+        }
+    };
+    let started = Instant::now();
+    let auth = AuthContext {
+        client_ip: addr.ip(),
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    };
+
+    let channels = match request.target {
+        Target::Channels(channels) => channels,
+        Target::Pattern(pattern) => {
+            let mut command = command(&state, "PUBSUB", vec![b"CHANNELS".to_vec(), pattern.into()]);
+            if let Err(error) = state.hooks.before(&mut command) {
+                let status = execution_error_status(&error);
+                admission::audit(&state, &auth, &command, status, started);
+                return execution_error_response(&state, &headers, &error);
+            }
+            admission::route(&state, &auth, &mut command);
+            if !admission::authorize(&state, &auth, &command) {
+                admission::audit(&state, &auth, &command, StatusCode::FORBIDDEN, started);
+                return error_response(
+                    &state,
+                    &headers,
+                    StatusCode::FORBIDDEN,
+                    "forbidden",
+                    "Forbidden",
+                );
+            }
+            let charge = match admission::charge(&state, &auth, 1).await {
+                Ok(charge) => charge,
+                Err(retry_after) => {
+                    return admission::quota_exceeded(&state, &headers, retry_after, None)
+                }
+            };
+            let outcome = admission::execute(&state, &command).await;
+            let status = admission::outcome_status(&outcome);
+            admission::audit(&state, &auth, &command, status, started);
+            match outcome {
+                Ok(reply) => channel_names(reply),
+                Err(error) => {
+                    let response = execution_error_response(&state, &headers, &error);
+                    return charge.meter(&state, response);
+                }
+            }
+        }
+    };
+
+    let mut commands = Vec::with_capacity(channels.len());
+    for channel in &channels {
+        let mut command = command(
+            &state,
+            "PUBLISH",
+            vec![channel.as_bytes().to_vec(), request.message.clone()],
+        );
+        if let Err(error) = state.hooks.before(&mut command) {
+            let status = execution_error_status(&error);
+            admission::audit(&state, &auth, &command, status, started);
+            return execution_error_response(&state, &headers, &error);
+        }
+        admission::route(&state, &auth, &mut command);
+        commands.push(command);
+    }
+
+    // A denied channel publishes nothing.
+    let mut allowed = true;
+    for command in &commands {
+        let channel = command
+            .args
+            .first()
+            .map(|channel| String::from_utf8_lossy(channel));
+        let channel = channel.unwrap_or_default();
+        if !admission::authorize_channel(&state, &auth, command, &channel) {
+            admission::audit(&state, &auth, command, StatusCode::FORBIDDEN, started);
+            allowed = false;
+        }
+    }
+    if !allowed {
+        return error_response(
+            &state,
+            &headers,
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Forbidden",
+        );
+    }
+
+    let charge = match admission::charge(&state, &auth, commands.len() as u64).await {
+        Ok(charge) => charge,
+        Err(retry_after) => return admission::quota_exceeded(&state, &headers, retry_after, None),
+    };
+    let outcome = admission::execute_pipeline(&state, &commands).await;
+    let status = admission::outcome_status(&outcome);
+    for command in &commands {
+        admission::audit(&state, &auth, command, status, started);
+    }
+    let response = match outcome {
+        Ok(replies) => {
+            let mut receivers = Map::new();
+            for (channel, reply) in channels.into_iter().zip(replies) {
+                let count = match reply {
+                    RedisValue::Int(count) => json!(count),
+                    _ => Value::Null,
+                };
+                receivers.insert(channel, count);
+            }
+            json_value_response(StatusCode::OK, json!({"PUBLISH": receivers}), None)
+        }
+        Err(error) => execution_error_response(&state, &headers, &error),
+    };
+    charge.meter(&state, response)
+}
+
+struct PublishRequest {
+    message: Vec<u8>,
+    target: Target,
+}
+
+enum Target {
+    Channels(Vec<String>),
+    Pattern(String),
+}
+
+impl PublishRequest {
+    fn parse(body: &[u8]) -> Result<Self, &'static str> {
+        let Ok(Value::Object(body)) = serde_json::from_slice::<Value>(body) else {
+            return Err("Expected a JSON object body");
+        };
+        let message = match body.get("message") {
+            Some(Value::String(message)) => message.clone().into_bytes(),
+            _ => return Err("\"message\" must be a string"),
+        };
+        let target = match (body.get("channels"), body.get("pattern")) {
+            (Some(Value::Array(channels)), None) => {
+                let channels: Option<Vec<_>> = channels
+                    .iter()
+                    .map(|channel| channel.as_str().map(str::to_string))
+                    .collect();
+                match channels {
+                    Some(channels) if !channels.is_empty() => Target::Channels(channels),
+                    _ => return Err("\"channels\" must be a non-empty array of strings"),
+                }
+            }
+            (None, Some(Value::String(pattern))) => Target::Pattern(pattern.clone()),
+            _ => return Err("Expected either \"channels\" or \"pattern\""),
+        };
+        Ok(Self { message, target })
+    }
+}
+
+fn command(state: &AppState, name: &str, args: Vec<Vec<u8>>) -> ExecutableCommand {
+    ExecutableCommand {
+        backend: None,
+        target_database: state.default_database,
+        command_name: name.to_string(),
+        args,
+        timeout: None,
+        credentials: None,
+    }
+}
+
+/// Reads the channel names from a `PUBSUB CHANNELS` reply.
+fn channel_names(reply: RedisValue) -> Vec<String> {
+    let RedisValue::Array(names) = reply else {
+        return Vec::new();
+    };
+    names
+        .into_iter()
+        .filter_map(|name| match name {
+            RedisValue::BulkString(bytes) => String::from_utf8(bytes).ok(),
+            RedisValue::SimpleString(name) => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn execution_error_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    error: &CommandExecutionError,
+) -> Response {
+    state.errors.execution_response(error, headers, None)
+}
+
+fn error_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+) -> Response {
+    state.errors.response(status, code, message, headers, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_channels_or_pattern() {
+        let request = PublishRequest::parse(br#"{"message":"hi","channels":["a","b"]}"#).unwrap();
+        assert_eq!(request.message, b"hi");
+        assert!(matches!(request.target, Target::Channels(ref c) if c == &["a", "b"]));
+
+        let request = PublishRequest::parse(br#"{"message":"hi","pattern":"news.*"}"#).unwrap();
+        assert!(matches!(request.target, Target::Pattern(ref p) if p == "news.*"));
+
+        assert!(PublishRequest::parse(br#"{"message":"hi"}"#).is_err());
+        assert!(PublishRequest::parse(br#"{"message":"hi","channels":[]}"#).is_err());
+        assert!(PublishRequest::parse(br#"{"message":"hi","channels":[1]}"#).is_err());
+        assert!(
+            PublishRequest::parse(br#"{"message":"hi","channels":["a"],"pattern":"*"}"#).is_err()
+        );
+        assert!(PublishRequest::parse(br#"{"channels":["a"]}"#).is_err());
+    }
+}
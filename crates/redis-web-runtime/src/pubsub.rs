@@ -1,46 +1,465 @@
 //! Redis Pub/Sub fanout for HTTP and WebSocket consumers.
 //!
-//! This module owns a single long-lived Redis Pub/Sub connection and fans incoming
-//! messages out to in-process `broadcast` channels keyed by Redis channel name.
+//! This module owns long-lived Redis Pub/Sub connections (one by default, see
+//! `pubsub_connections`) and fans incoming messages out to in-process
+//! `broadcast` channels keyed by Redis channel name.
 //! HTTP `/SUBSCRIBE/*channel` and WebSocket subscribers then attach to those
-//! broadcast channels.
+//! broadcast channels. Sharded channels (`SSUBSCRIBE`) use a second, RESP3
+//! connection, routed through the cluster client in cluster mode.
 //!
 //! The HTTP endpoint supports:
 //! - SSE (default, modern clients)
 //! - Chunked JSON stream (legacy-friendly Comet mode when JSON is negotiated)
 //! - Chunked JSONP stream (legacy Comet mode when `jsonp`/`callback` is present)
+//!
+//! Clients that fall behind lose the oldest messages and get a `lagged`
+//! notice, or are disconnected when `sse.slow_client_policy` is `disconnect`.
 
-use crate::handler::AppState;
+use crate::filter::MessageFilters;
+use crate::handler::{shut_down, AppState};
+use crate::metrics::PubSubMetrics;
+use crate::replay::{parse_event_id, ReplaySettings};
 use axum::{
     body::{Body, Bytes},
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, StatusCode},
+    extract::{ConnectInfo, OriginalUri, Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
     response::sse::{Event, KeepAlive},
     response::{IntoResponse, Response, Sse},
+    Json,
 };
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
 use futures::stream::StreamExt;
+use redis::aio::{MultiplexedConnection, PubSubSink};
+use redis::cluster_async::ClusterConnection;
+use redis::{AsyncConnectionConfig, Msg, PushInfo, PushKind};
+use redis_web_core::config::{
+    Config as AppConfig, SlowClientPolicy, SseConfig, DEFAULT_SSE_KEEP_ALIVE_SEC,
+    DEFAULT_SSE_SLOW_CLIENT_TIMEOUT_SEC,
+};
 use redis_web_core::format::select_jsonp_callback;
+use redis_web_core::interfaces::AuthContext;
+use redis_web_core::request::decode_path_segments;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
-use std::sync::Arc;
-use tokio::sync::{broadcast, mpsc, RwLock};
-use tracing::{error, info};
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, Mutex};
+use tracing::{error, info, warn};
+
+/// A message received on a subscribed Redis channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PubSubMessage {
+    pub channel: String,
+    pub payload: String,
+    /// The matching pattern when the message was delivered through `PSUBSCRIBE`.
+    pub pattern: Option<String>,
+    /// Whether the message was delivered through sharded Pub/Sub (`SSUBSCRIBE`).
+    pub sharded: bool,
+    /// Event id assigned when `pubsub_replay` is enabled.
+    pub id: Option<String>,
+}
+
+/// An item fanned out to the local subscribers of a topic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PubSubEvent {
+    Message(PubSubMessage),
+    /// The Redis connection dropped and the topic was resubscribed on a new
+    /// one; messages published in between were missed.
+    Resubscribed,
+}
+
+/// A Redis channel or channel pattern that local clients subscribe to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Topic {
+    Channel(String),
+    Pattern(String),
+    /// A sharded channel (Redis 7 `SSUBSCRIBE`).
+    Shard(String),
+}
+
+impl Topic {
+    /// The channel name or pattern.
+    pub fn name(&self) -> &str {
+        match self {
+            Topic::Channel(name) | Topic::Pattern(name) | Topic::Shard(name) => name,
+        }
+    }
+
+    /// `channel`, `pattern`, or `shard`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Topic::Channel(_) => "channel",
+            Topic::Pattern(_) => "pattern",
+            Topic::Shard(_) => "shard",
+        }
+    }
+
+    /// The Redis command that subscribes to this kind of topic.
+    pub fn subscribe_command(&self) -> &'static str {
+        match self {
+            Topic::Channel(_) => "SUBSCRIBE",
+            Topic::Pattern(_) => "PSUBSCRIBE",
+            Topic::Shard(_) => "SSUBSCRIBE",
+        }
+    }
+
+    /// The Redis command that unsubscribes from this kind of topic.
+    pub fn unsubscribe_command(&self) -> &'static str {
+        match self {
+            Topic::Channel(_) => "UNSUBSCRIBE",
+            Topic::Pattern(_) => "PUNSUBSCRIBE",
+            Topic::Shard(_) => "SUNSUBSCRIBE",
+        }
+    }
+}
+
+/// Point-in-time Pub/Sub statistics for metrics and `/_admin/stats`.
+#[derive(Clone, Debug)]
+pub struct PubSubStats {
+    pub topics: Vec<TopicStats>,
+    pub messages_received: u64,
+    pub messages_delivered: u64,
+    pub messages_lagged: u64,
+    pub messages_filtered: u64,
+    pub reconnects: u64,
+    pub shard_reconnects: u64,
+}
+
+/// Statistics of one subscribed channel, pattern, or sharded channel.
+#[derive(Clone, Debug)]
+pub struct TopicStats {
+    pub topic: Topic,
+    /// Local SSE, WebSocket, and gRPC subscribers.
+    pub subscribers: usize,
+    /// Message copies handed to those subscribers.
+    pub messages_delivered: u64,
+}
+
+/// Caps on open HTTP subscription streams, globally and per client IP, and the
+/// slow-client policy shared with WebSocket subscriptions (`sse`).
+pub struct StreamLimits {
+    max_total: Option<usize>,
+    max_per_ip: Option<usize>,
+    /// Set when `sse.slow_client_policy` is `disconnect`.
+    slow_client_timeout: Option<Duration>,
+    open: std::sync::Mutex<OpenStreams>,
+    rejected_total: AtomicU64,
+    rejected_per_ip: AtomicU64,
+    slow_disconnects: AtomicU64,
+    websocket_drops: AtomicU64,
+}
+
+#[derive(Default)]
+struct OpenStreams {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+/// Open-stream figures for metrics and `/_admin/stats`.
+#[derive(Clone, Debug)]
+pub struct StreamStats {
+    pub open: usize,
+    /// Distinct client IPs with at least one open stream.
+    pub clients: usize,
+    /// Streams refused by `sse.max_connections`.
+    pub rejected_total: u64,
+    /// Streams refused by `sse.max_connections_per_ip`.
+    pub rejected_per_ip: u64,
+    /// SSE streams and WebSockets closed by the `disconnect` slow-client policy.
+    pub slow_disconnects: u64,
+    /// Pub/Sub messages dropped because a WebSocket's send queue was full.
+    pub websocket_drops: u64,
+}
+
+/// Per-client view of [`StreamLimits`] for `/_admin/ratelimits`.
+#[derive(Clone, Debug)]
+pub struct StreamLimitState {
+    pub max_total: Option<usize>,
+    pub max_per_ip: Option<usize>,
+    /// Open streams per client IP, ordered by IP.
+    pub clients: Vec<(IpAddr, usize)>,
+}
+
+/// One slot of [`StreamLimits`], released when the stream is dropped.
+struct StreamPermit {
+    limits: Arc<StreamLimits>,
+    ip: IpAddr,
+}
+
+impl StreamLimits {
+    pub fn new(config: Option<&SseConfig>) -> Self {
+        Self {
+            max_total: config.and_then(|sse| sse.max_connections),
+            max_per_ip: config.and_then(|sse| sse.max_connections_per_ip),
+            slow_client_timeout: config
+                .filter(|sse| sse.slow_client_policy == Some(SlowClientPolicy::Disconnect))
+                .map(|sse| {
+                    Duration::from_secs(
+                        sse.slow_client_timeout_sec
+                            .unwrap_or(DEFAULT_SSE_SLOW_CLIENT_TIMEOUT_SEC),
+                    )
+                }),
+            open: std::sync::Mutex::new(OpenStreams::default()),
+            rejected_total: AtomicU64::new(0),
+            rejected_per_ip: AtomicU64::new(0),
+            slow_disconnects: AtomicU64::new(0),
+            websocket_drops: AtomicU64::new(0),
+        }
+    }
+
+    /// How long a client's send queue may stay full before it is disconnected,
+    /// or `None` under the `drop_oldest` policy.
+    pub fn slow_client_timeout(&self) -> Option<Duration> {
+        self.slow_client_timeout
+    }
+
+    /// Counts a stream closed because its client fell behind.
+    pub fn record_slow_disconnect(&self) {
+        self.slow_disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a Pub/Sub message a WebSocket dropped with a full send queue.
+    pub fn record_websocket_drop(&self) {
+        self.websocket_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reserves a stream slot for `ip`, or returns the status to reject with.
+    fn acquire(self: &Arc<Self>, ip: IpAddr) -> Result<StreamPermit, StatusCode> {
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        if self.max_total.is_some_and(|max| open.total >= max) {
+            self.rejected_total.fetch_add(1, Ordering::Relaxed);
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        let for_ip = open.per_ip.get(&ip).copied().unwrap_or_default();
+        if self.max_per_ip.is_some_and(|max| for_ip >= max) {
+            self.rejected_per_ip.fetch_add(1, Ordering::Relaxed);
+            return Err(StatusCode::TOO_MANY_REQUESTS);
+        }
+        open.total += 1;
+        open.per_ip.insert(ip, for_ip + 1);
+        Ok(StreamPermit {
+            limits: self.clone(),
+            ip,
+        })
+    }
+
+    /// Returns the configured caps and the open streams of each client.
+    pub fn state(&self) -> StreamLimitState {
+        let open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        let mut clients: Vec<_> = open.per_ip.iter().map(|(ip, open)| (*ip, *open)).collect();
+        clients.sort();
+        StreamLimitState {
+            max_total: self.max_total,
+            max_per_ip: self.max_per_ip,
+            clients,
+        }
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        let open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        StreamStats {
+            open: open.total,
+            clients: open.per_ip.len(),
+            rejected_total: self.rejected_total.load(Ordering::Relaxed),
+            rejected_per_ip: self.rejected_per_ip.load(Ordering::Relaxed),
+            slow_disconnects: self.slow_disconnects.load(Ordering::Relaxed),
+            websocket_drops: self.websocket_drops.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        let mut open = self.limits.open.lock().unwrap_or_else(|e| e.into_inner());
+        open.total -= 1;
+        if let Some(count) = open.per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Where sharded (`SSUBSCRIBE`) subscriptions are opened.
+///
+/// Sharded messages are delivered as RESP3 push frames, so both sources use a
+/// RESP3 connection rather than the classic Pub/Sub connection.
+pub enum ShardedSource {
+    /// A standalone Redis 7+ server.
+    Standalone(redis::Client),
+    /// A Redis Cluster. Each `SSUBSCRIBE` is sent to the shard owning the channel.
+    Cluster(Box<AppConfig>),
+}
+
+enum ShardConnection {
+    Standalone(MultiplexedConnection),
+    Cluster(ClusterConnection),
+}
+
+impl ShardedSource {
+    async fn connect(
+        &self,
+        push_tx: mpsc::UnboundedSender<PushInfo>,
+        client_name: Option<&str>,
+    ) -> redis::RedisResult<ShardConnection> {
+        match self {
+            ShardedSource::Standalone(client) => {
+                let config = AsyncConnectionConfig::new().set_push_sender(push_tx);
+                let mut connection = client
+                    .get_multiplexed_async_connection_with_config(&config)
+                    .await?;
+                if let Some(name) = client_name {
+                    // Best-effort, as for pooled connections.
+                    if let Err(e) = redis::cmd("CLIENT")
+                        .arg("SETNAME")
+                        .arg(name)
+                        .exec_async(&mut connection)
+                        .await
+                    {
+                        warn!("Failed to set Redis client name {}: {}", name, e);
+                    }
+                }
+                Ok(ShardConnection::Standalone(connection))
+            }
+            ShardedSource::Cluster(config) => {
+                let client = crate::cluster::cluster_client_builder(config)?
+                    .use_protocol(redis::ProtocolVersion::RESP3)
+                    .push_sender(push_tx)
+                    .build()?;
+                client
+                    .get_async_connection()
+                    .await
+                    .map(ShardConnection::Cluster)
+            }
+        }
+    }
+}
+
+impl ShardConnection {
+    async fn run(&mut self, command: &str, channel: &str) -> redis::RedisResult<()> {
+        let mut request = redis::cmd(command);
+        request.arg(channel);
+        match self {
+            ShardConnection::Standalone(connection) => request.exec_async(connection).await,
+            ShardConnection::Cluster(connection) => request.exec_async(connection).await,
+        }
+    }
+}
 
 #[derive(Clone)]
 /// Coordinates Redis channel subscriptions and local fanout receivers.
 ///
-/// `PubSubManager` keeps an in-memory map from channel name to `broadcast::Sender`.
-/// The first local subscriber to a channel enqueues a `Subscribe` command to the
-/// background Redis Pub/Sub loop. Additional local subscribers reuse the same sender.
+/// `PubSubManager` keeps an in-memory map from [`Topic`] to `broadcast::Sender`,
+/// sharded so subscribers to different topics do not contend on one lock.
+/// The first local subscriber to a channel or pattern enqueues a `Subscribe`
+/// command to the background Redis Pub/Sub loop. Additional local subscribers
+/// reuse the same sender. When the last [`Subscription`] for a topic is dropped,
+/// the sender is removed and Redis is sent `UNSUBSCRIBE` / `PUNSUBSCRIBE`.
+///
+/// Channels and patterns can be spread over several connections, each with
+/// its own loop; see [`PubSubManager::with_connections`]. Sharded channels are
+/// handled by a separate loop started with [`PubSubManager::with_sharded`].
 pub struct PubSubManager {
-    cmd_tx: mpsc::Sender<Command>,
-    channels: Arc<RwLock<HashMap<String, broadcast::Sender<String>>>>,
+    /// One command queue per classic connection, indexed by [`connection_index`].
+    cmd_txs: Vec<mpsc::UnboundedSender<Command>>,
+    shard_tx: Option<mpsc::UnboundedSender<Command>>,
+    fanout: Fanout,
+    connect_timeout: Option<Duration>,
+    /// `CLIENT SETNAME` of the Pub/Sub connections, when they are named.
+    client_name: Option<Arc<str>>,
+}
+
+/// Entries are shared out of the map, so no shard lock is held across an
+/// `.await`.
+type Topics = Arc<DashMap<Topic, Arc<TopicEntry>>>;
+type Replay = Arc<OnceLock<ReplaySettings>>;
+type Filters = Arc<OnceLock<MessageFilters>>;
+
+/// State the Pub/Sub loops share with the manager for delivering messages.
+#[derive(Clone)]
+struct Fanout {
+    topics: Topics,
+    replay: Replay,
+    filters: Filters,
+    metrics: Arc<PubSubMetrics>,
+}
+
+struct TopicEntry {
+    sender: broadcast::Sender<PubSubEvent>,
+    /// Recent messages kept for `Last-Event-ID` replay.
+    history: Mutex<VecDeque<PubSubMessage>>,
+    delivered: AtomicU64,
 }
 
 enum Command {
-    Subscribe(String),
-    // Unsubscribe(String), // TODO: Implement unsubscribe cleanup
+    Subscribe(Topic),
+    /// Sent whenever a local subscription ends; only acted on once no receivers remain.
+    Release(Topic),
+}
+
+/// A local subscription to one Redis channel or pattern.
+///
+/// Dropping it releases the topic, so disconnecting SSE, WebSocket, and gRPC
+/// clients unsubscribe automatically.
+pub struct Subscription {
+    /// Taken on drop, ahead of the release, so the Pub/Sub loop never counts
+    /// it among the topic's receivers.
+    receiver: Option<broadcast::Receiver<PubSubEvent>>,
+    topic: Option<Topic>,
+    cmd_tx: mpsc::UnboundedSender<Command>,
+    /// Delay before releasing the topic, so replay history outlives a reconnect.
+    retention: Duration,
+    metrics: Arc<PubSubMetrics>,
+}
+
+impl Subscription {
+    /// Receives the next event; see [`broadcast::Receiver::recv`].
+    pub async fn recv(&mut self) -> Result<PubSubEvent, broadcast::error::RecvError> {
+        let receiver = self
+            .receiver
+            .as_mut()
+            .expect("receiver is only taken on drop");
+        let event = receiver.recv().await;
+        if let Err(broadcast::error::RecvError::Lagged(skipped)) = &event {
+            self.metrics
+                .messages_lagged
+                .fetch_add(*skipped, Ordering::Relaxed);
+        }
+        event
+    }
+
+    pub fn topic(&self) -> &Topic {
+        self.topic.as_ref().expect("topic is only taken on drop")
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let Some(topic) = self.topic.take() else {
+            return;
+        };
+        // Fields drop only after `drop` returns, too late for the release.
+        drop(self.receiver.take());
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) if !self.retention.is_zero() => {
+                let cmd_tx = self.cmd_tx.clone();
+                let retention = self.retention;
+                runtime.spawn(async move {
+                    tokio::time::sleep(retention).await;
+                    let _ = cmd_tx.send(Command::Release(topic));
+                });
+            }
+            _ => {
+                let _ = self.cmd_tx.send(Command::Release(topic));
+            }
+        }
+    }
 }
 
 impl PubSubManager {
@@ -50,81 +469,92 @@ impl PubSubManager {
     /// is required because normal multiplexed Redis connections cannot run the
     /// blocking subscription message loop.
     pub fn new(client: redis::Client) -> Self {
-        let (cmd_tx, mut cmd_rx) = mpsc::channel(100);
-        let channels: Arc<RwLock<HashMap<String, broadcast::Sender<String>>>> =
-            Arc::new(RwLock::new(HashMap::new()));
-        let channels_clone = channels.clone();
-
-        tokio::spawn(async move {
-            loop {
-                info!("Starting Pub/Sub background task...");
-                // Use get_async_pubsub() to get a dedicated connection for subscriptions.
-                // Standard async connections in redis-rs are multiplexed and cannot be used
-                // for blocking subscription loops.
-                let mut pubsub = match client.get_async_pubsub().await {
-                    Ok(pubsub) => pubsub,
-                    Err(e) => {
-                        error!("Failed to get Redis Pub/Sub connection: {}", e);
-                        // Retry with backoff or delay to avoid tight loop on failure
-                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                        continue;
-                    }
-                };
-
-                loop {
-                    // Check for commands first
-                    while let Ok(cmd) = cmd_rx.try_recv() {
-                        match cmd {
-                            Command::Subscribe(channel) => {
-                                if let Err(e) = pubsub.subscribe(&channel).await {
-                                    error!("Failed to subscribe to {}: {}", channel, e);
-                                } else {
-                                    info!("Subscribed to {}", channel);
-                                }
-                            }
-                        }
-                    }
+        Self::with_failover(vec![client], None)
+    }
 
-                    // Listen for messages with a timeout to allow checking commands periodically
-                    // We create a new stream scope here so we can drop it to process commands
-                    {
-                        let mut stream = pubsub.on_message();
-                        match tokio::time::timeout(
-                            std::time::Duration::from_millis(100),
-                            stream.next(),
-                        )
-                        .await
-                        {
-                            Ok(Some(msg)) => {
-                                let channel_name = msg.get_channel_name().to_string();
-                                let payload: String = match msg.get_payload() {
-                                    Ok(p) => p,
-                                    Err(e) => {
-                                        error!("Failed to get payload: {}", e);
-                                        continue;
-                                    }
-                                };
-
-                                let map = channels_clone.read().await;
-                                if let Some(sender) = map.get(&channel_name) {
-                                    let _ = sender.send(payload);
-                                }
-                            }
-                            Ok(None) => {
-                                error!("Pub/Sub stream ended, reconnecting...");
-                                break;
-                            }
-                            Err(_) => {
-                                // Timeout, loop back to check commands
-                                continue;
-                            }
-                        }
-                    }
-                }
-            }
-        });
+    /// Creates a manager that reconnects through `clients` in priority order.
+    ///
+    /// Each reconnect starts from the first client, so the subscription moves
+    /// back to the primary host whenever its connection is re-established.
+    /// `connect_timeout` bounds each connection attempt so an unresponsive host
+    /// does not hold up failover.
+    pub fn with_failover(clients: Vec<redis::Client>, connect_timeout: Option<Duration>) -> Self {
+        Self::with_connections(clients, connect_timeout, 1, None)
+    }
 
-        Self { cmd_tx, channels }
+    /// Like [`PubSubManager::with_failover`], but spreads channels and patterns
+    /// over `connections` Redis connections by hashing each topic, and names
+    /// them `client_name` when given.
+    pub fn with_connections(
+        clients: Vec<redis::Client>,
+        connect_timeout: Option<Duration>,
+        connections: usize,
+        client_name: Option<String>,
+    ) -> Self {
+        assert!(
+            !clients.is_empty(),
+            "at least one Pub/Sub client is required"
+        );
+        let count = connections.max(1);
+        let client_name: Option<Arc<str>> = client_name.map(Into::into);
+        let fanout = Fanout {
+            topics: Arc::new(DashMap::new()),
+            replay: Arc::new(OnceLock::new()),
+            filters: Arc::new(OnceLock::new()),
+            metrics: Arc::new(PubSubMetrics::default()),
+        };
+        let cmd_txs = (0..count)
+            .map(|index| {
+                let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+                tokio::spawn(run_classic(
+                    clients.clone(),
+                    Partition { index, count },
+                    fanout.clone(),
+                    cmd_rx,
+                    connect_timeout,
+                    client_name.clone(),
+                ));
+                cmd_tx
+            })
+            .collect();
+
+        Self {
+            cmd_txs,
+            shard_tx: None,
+            fanout,
+            connect_timeout,
+            client_name,
+        }
+    }
+
+    /// Enables event ids and `Last-Event-ID` replay; see [`crate::replay`].
+    pub fn with_replay(self, settings: ReplaySettings) -> Self {
+        let _ = self.fanout.replay.set(settings);
+        self
+    }
+
+    /// Applies `pubsub_filters` to every message before it is fanned out; see
+    /// [`crate::filter`].
+    pub fn with_filters(self, filters: MessageFilters) -> Self {
+        let _ = self.fanout.filters.set(filters);
+        self
+    }
+
+    /// Starts the sharded Pub/Sub loop, enabling [`Topic::Shard`] subscriptions.
+    ///
+    /// The loop reconnects after a disconnection and restores every sharded
+    /// subscription that still has local subscribers.
+    pub fn with_sharded(mut self, source: ShardedSource) -> Self {
+        let (shard_tx, cmd_rx) = mpsc::unbounded_channel();
+        tokio::spawn(run_sharded(
+            source,
+            self.fanout.clone(),
+            cmd_rx,
+            self.connect_timeout,
+            self.client_name.clone(),
+        ));
+        self.shard_tx = Some(shard_tx);
+        self
     }
 
     /// Subscribes to a Redis channel and returns a local message receiver.
@@ -132,62 +562,787 @@ impl PubSubManager {
     /// If this is the first subscriber for `channel`, a Redis `SUBSCRIBE` command
     /// is sent to the background task. Receivers can observe lag if they fall behind
     /// the broadcast buffer; callers must handle `RecvError::Lagged`.
-    pub async fn subscribe(&self, channel: String) -> broadcast::Receiver<String> {
-        let mut map = self.channels.write().await;
-        if let Some(sender) = map.get(&channel) {
-            sender.subscribe()
+    pub async fn subscribe(&self, channel: String) -> Subscription {
+        self.subscribe_topic(Topic::Channel(channel)).await
+    }
+
+    /// Subscribes to a Redis channel pattern (`PSUBSCRIBE`).
+    ///
+    /// Messages carry the concrete channel they were published to and the pattern.
+    pub async fn psubscribe(&self, pattern: String) -> Subscription {
+        self.subscribe_topic(Topic::Pattern(pattern)).await
+    }
+
+    /// Subscribes to a sharded channel (`SSUBSCRIBE`).
+    pub async fn ssubscribe(&self, channel: String) -> Subscription {
+        self.subscribe_topic(Topic::Shard(channel)).await
+    }
+
+    /// Subscribes to a channel, pattern, or sharded channel.
+    pub async fn subscribe_topic(&self, topic: Topic) -> Subscription {
+        // Without a sharded loop the classic loops report sharded topics as unsupported.
+        let cmd_tx = match (&topic, &self.shard_tx) {
+            (Topic::Shard(_), Some(shard_tx)) => shard_tx,
+            _ => &self.cmd_txs[connection_index(&topic, self.cmd_txs.len())],
+        };
+        // A receiver is attached while the topic's shard is locked, so
+        // `release_idle` cannot remove the entry under a new subscriber. Only
+        // the first subscriber takes the shard's write lock.
+        let existing = self
+            .fanout
+            .topics
+            .get(&topic)
+            .map(|entry| entry.sender.subscribe());
+        let receiver = match existing {
+            Some(receiver) => receiver,
+            None => match self.fanout.topics.entry(topic.clone()) {
+                Entry::Occupied(entry) => entry.get().sender.subscribe(),
+                Entry::Vacant(entry) => {
+                    let (tx, rx) = broadcast::channel(100);
+                    entry.insert(Arc::new(TopicEntry {
+                        sender: tx,
+                        history: Mutex::new(VecDeque::new()),
+                        delivered: AtomicU64::new(0),
+                    }));
+                    let _ = cmd_tx.send(Command::Subscribe(topic.clone()));
+                    rx
+                }
+            },
+        };
+        Subscription {
+            receiver: Some(receiver),
+            topic: Some(topic),
+            cmd_tx: cmd_tx.clone(),
+            retention: self
+                .fanout
+                .replay
+                .get()
+                .map(|replay| replay.retention)
+                .unwrap_or_default(),
+            metrics: self.fanout.metrics.clone(),
+        }
+    }
+
+    /// Returns the messages of `topics` published after `last_event_id`,
+    /// oldest first. Empty when replay is disabled or the id is unknown.
+    pub async fn replay_since(&self, topics: &[Topic], last_event_id: &str) -> Vec<PubSubMessage> {
+        let (Some(replay), Some(last)) = (self.fanout.replay.get(), parse_event_id(last_event_id))
+        else {
+            return Vec::new();
+        };
+        let mut messages = Vec::new();
+        if replay.stream.is_some() {
+            for topic in topics {
+                messages.extend(replay.stream_since(topic, last_event_id).await);
+            }
         } else {
-            let (tx, rx) = broadcast::channel(100);
-            map.insert(channel.clone(), tx);
-            let _ = self.cmd_tx.send(Command::Subscribe(channel)).await;
-            rx
+            let map = &self.fanout.topics;
+            let entries: Vec<_> = topics
+                .iter()
+                .filter_map(|topic| map.get(topic).map(|entry| entry.clone()))
+                .collect();
+            for entry in entries {
+                let history = entry.history.lock().await;
+                messages.extend(
+                    history
+                        .iter()
+                        .filter(|message| message_event_id(message).is_some_and(|id| id > last))
+                        .cloned(),
+                );
+            }
+        }
+        messages.sort_by_key(message_event_id);
+        messages
+    }
+
+    /// Returns the channels and patterns with at least one local subscriber.
+    pub async fn active_topics(&self) -> Vec<Topic> {
+        let mut topics: Vec<Topic> = self
+            .fanout
+            .topics
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        topics.sort();
+        topics
+    }
+
+    /// Returns per-topic subscriber counts and the Pub/Sub counters.
+    pub async fn stats(&self) -> PubSubStats {
+        let mut topics: Vec<TopicStats> = self
+            .fanout
+            .topics
+            .iter()
+            .map(|entry| TopicStats {
+                topic: entry.key().clone(),
+                subscribers: entry.sender.receiver_count(),
+                messages_delivered: entry.delivered.load(Ordering::Relaxed),
+            })
+            .collect();
+        topics.sort_by(|a, b| a.topic.cmp(&b.topic));
+        let metrics = &self.fanout.metrics;
+        PubSubStats {
+            topics,
+            messages_received: metrics.messages_received.load(Ordering::Relaxed),
+            messages_delivered: metrics.messages_delivered.load(Ordering::Relaxed),
+            messages_lagged: metrics.messages_lagged.load(Ordering::Relaxed),
+            messages_filtered: metrics.messages_filtered.load(Ordering::Relaxed),
+            reconnects: metrics.reconnects.load(Ordering::Relaxed),
+            shard_reconnects: metrics.shard_reconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Applies the message filters, assigns an event id when replay is enabled,
+/// records the message in the topic history, and fans it out to local
+/// subscribers.
+async fn deliver(fanout: &Fanout, topic: &Topic, message: PubSubMessage) {
+    let Fanout {
+        topics,
+        replay,
+        filters,
+        metrics,
+    } = fanout;
+    metrics.messages_received.fetch_add(1, Ordering::Relaxed);
+    let message = match filters.get() {
+        Some(filters) => filters.apply(message),
+        None => Some(message),
+    };
+    let Some(mut message) = message else {
+        metrics.messages_filtered.fetch_add(1, Ordering::Relaxed);
+        return;
+    };
+    let replay = replay.get();
+    if let Some(replay) = replay {
+        replay.assign_id(topic, &mut message).await;
+    }
+    let Some(entry) = topics.get(topic).map(|entry| entry.clone()) else {
+        return;
+    };
+    if let Some(replay) = replay {
+        replay.record(&mut *entry.history.lock().await, &message);
+    }
+    if let Ok(receivers) = entry.sender.send(PubSubEvent::Message(message)) {
+        entry
+            .delivered
+            .fetch_add(receivers as u64, Ordering::Relaxed);
+        metrics
+            .messages_delivered
+            .fetch_add(receivers as u64, Ordering::Relaxed);
+    }
+}
+
+/// Tells the local subscribers of `resubscribed` that messages published while
+/// the connection was down were missed.
+async fn notify_resubscribed(topics: &Topics, resubscribed: &[Topic]) {
+    for entry in resubscribed.iter().filter_map(|topic| topics.get(topic)) {
+        let _ = entry.sender.send(PubSubEvent::Resubscribed);
+    }
+}
+
+fn message_event_id(message: &PubSubMessage) -> Option<(u64, u64)> {
+    message.id.as_deref().and_then(parse_event_id)
+}
+
+/// Removes `topic` once its last local subscriber is gone.
+///
+/// Returns whether Redis should be unsubscribed. `subscribe_topic` holds the
+/// topic's shard lock while attaching a receiver, so a zero count here cannot
+/// race with a new subscriber.
+async fn release_idle(topics: &Topics, topic: &Topic) -> bool {
+    topics
+        .remove_if(topic, |_, entry| entry.sender.receiver_count() == 0)
+        .is_some()
+}
+
+/// Waits `delay` before the next connection attempt while still handling
+/// commands, so topics left during an outage are released right away. Returns
+/// `false` once every sender is gone.
+async fn wait_for_retry(
+    topics: &Topics,
+    cmd_rx: &mut mpsc::UnboundedReceiver<Command>,
+    delay: Duration,
+) -> bool {
+    let retry = tokio::time::sleep(delay);
+    tokio::pin!(retry);
+    loop {
+        tokio::select! {
+            _ = &mut retry => return true,
+            cmd = cmd_rx.recv() => match cmd {
+                // Every remaining topic is subscribed once connected.
+                Some(Command::Subscribe(_)) => {}
+                Some(Command::Release(topic)) => {
+                    release_idle(topics, &topic).await;
+                }
+                None => return false,
+            },
+        }
+    }
+}
+
+async fn with_connect_timeout<T>(
+    limit: Option<Duration>,
+    connect: impl std::future::Future<Output = redis::RedisResult<T>>,
+) -> redis::RedisResult<T> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, connect)
+            .await
+            .unwrap_or_else(|_| {
+                Err(redis::RedisError::from(std::io::Error::from(
+                    std::io::ErrorKind::TimedOut,
+                )))
+            }),
+        None => connect.await,
+    }
+}
+
+async fn subscribe_topic(pubsub: &mut PubSubSink, topic: &Topic) -> redis::RedisResult<()> {
+    match topic {
+        Topic::Channel(channel) => pubsub.subscribe(channel).await,
+        Topic::Pattern(pattern) => pubsub.psubscribe(pattern).await,
+        Topic::Shard(_) => Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "sharded Pub/Sub is not enabled",
+        ))),
+    }
+}
+
+async fn unsubscribe_topic(pubsub: &mut PubSubSink, topic: &Topic) -> redis::RedisResult<()> {
+    match topic {
+        Topic::Channel(channel) => pubsub.unsubscribe(channel).await,
+        Topic::Pattern(pattern) => pubsub.punsubscribe(pattern).await,
+        Topic::Shard(_) => Ok(()),
+    }
+}
+
+/// The share of channels and patterns carried by one classic connection.
+#[derive(Clone, Copy, Debug)]
+struct Partition {
+    index: usize,
+    count: usize,
+}
+
+impl Partition {
+    fn owns(&self, topic: &Topic) -> bool {
+        !matches!(topic, Topic::Shard(_)) && connection_index(topic, self.count) == self.index
+    }
+}
+
+/// Picks the classic connection that carries `topic`.
+fn connection_index(topic: &Topic, count: usize) -> usize {
+    if count <= 1 {
+        return 0;
+    }
+    let mut hasher = std::hash::DefaultHasher::new();
+    topic.hash(&mut hasher);
+    (hasher.finish() % count as u64) as usize
+}
+
+/// Background loop for channel and pattern subscriptions.
+///
+/// The connection is split so subscription commands go out through the sink
+/// while messages are awaited on the stream; `select!` wakes on whichever is
+/// ready, so neither waits on the other.
+async fn run_classic(
+    clients: Vec<redis::Client>,
+    partition: Partition,
+    fanout: Fanout,
+    mut cmd_rx: mpsc::UnboundedReceiver<Command>,
+    connect_timeout: Option<Duration>,
+    client_name: Option<Arc<str>>,
+) {
+    let Fanout {
+        topics, metrics, ..
+    } = &fanout;
+    let mut client_index = 0;
+    // Set once a connection is lost, so only real reconnects report a gap.
+    let mut reconnecting = false;
+    loop {
+        info!("Starting Pub/Sub background task...");
+        // Use a dedicated Pub/Sub connection for subscriptions. Standard async
+        // connections in redis-rs are multiplexed and cannot be used for
+        // blocking subscription loops.
+        let connect = crate::redis::connect_pubsub(&clients[client_index], client_name.as_deref());
+        let (mut sink, mut stream) = match with_connect_timeout(connect_timeout, connect).await {
+            Ok(pubsub) => pubsub.split(),
+            Err(e) => {
+                error!("Failed to get Redis Pub/Sub connection: {}", e);
+                client_index = (client_index + 1) % clients.len();
+                // Retry with backoff or delay to avoid tight loop on failure,
+                // but try every failover host once before sleeping.
+                if client_index == 0
+                    && !wait_for_retry(topics, &mut cmd_rx, Duration::from_secs(5)).await
+                {
+                    return;
+                }
+                continue;
+            }
+        };
+        client_index = 0;
+
+        // Restore subscriptions that outlived the previous connection.
+        let active: Vec<Topic> = topics
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|topic| partition.owns(topic))
+            .collect();
+        let mut resubscribed = Vec::with_capacity(active.len());
+        for topic in active {
+            match subscribe_topic(&mut sink, &topic).await {
+                Ok(()) => resubscribed.push(topic),
+                Err(e) => error!("Failed to resubscribe to {}: {}", topic.name(), e),
+            }
+        }
+        if std::mem::take(&mut reconnecting) {
+            metrics.reconnects.fetch_add(1, Ordering::Relaxed);
+            info!(
+                "Resubscribed to {} topics after reconnect",
+                resubscribed.len()
+            );
+            notify_resubscribed(topics, &resubscribed).await;
+        }
+
+        loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(Command::Subscribe(topic)) => {
+                        match subscribe_topic(&mut sink, &topic).await {
+                            Ok(()) => info!("Subscribed to {}", topic.name()),
+                            Err(e) => error!("Failed to subscribe to {}: {}", topic.name(), e),
+                        }
+                    }
+                    Some(Command::Release(topic)) => {
+                        if !release_idle(topics, &topic).await {
+                            continue;
+                        }
+                        match unsubscribe_topic(&mut sink, &topic).await {
+                            Ok(()) => info!("Unsubscribed from {}", topic.name()),
+                            Err(e) => error!("Failed to unsubscribe from {}: {}", topic.name(), e),
+                        }
+                    }
+                    // The manager and every subscription are gone.
+                    None => return,
+                },
+                msg = stream.next() => {
+                    let Some(msg) = msg else {
+                        error!("Pub/Sub stream ended, reconnecting...");
+                        reconnecting = true;
+                        break;
+                    };
+                    let channel_name = msg.get_channel_name().to_string();
+                    let payload: String = match msg.get_payload() {
+                        Ok(p) => p,
+                        Err(e) => {
+                            error!("Failed to get payload: {}", e);
+                            continue;
+                        }
+                    };
+                    let pattern = if msg.from_pattern() {
+                        msg.get_pattern::<String>().ok()
+                    } else {
+                        None
+                    };
+                    let topic = match &pattern {
+                        Some(pattern) => Topic::Pattern(pattern.clone()),
+                        None => Topic::Channel(channel_name.clone()),
+                    };
+                    let message = PubSubMessage {
+                        channel: channel_name,
+                        payload,
+                        pattern,
+                        sharded: false,
+                        id: None,
+                    };
+                    deliver(&fanout, &topic, message).await;
+                }
+            }
+        }
+    }
+}
+
+/// Background loop for sharded subscriptions.
+///
+/// The connection is opened when the first sharded subscription arrives.
+/// Messages arrive as `smessage` push frames on the RESP3 connection. A
+/// `disconnection` push, or the push channel closing, triggers a reconnect.
+async fn run_sharded(
+    source: ShardedSource,
+    fanout: Fanout,
+    mut cmd_rx: mpsc::UnboundedReceiver<Command>,
+    connect_timeout: Option<Duration>,
+    client_name: Option<Arc<str>>,
+) {
+    let Fanout {
+        topics, metrics, ..
+    } = &fanout;
+    let mut reconnecting = false;
+    loop {
+        // Connect lazily so deployments that never use sharded channels (or run
+        // Redis < 7) do not hold an extra connection.
+        let idle = !topics
+            .iter()
+            .any(|entry| matches!(entry.key(), Topic::Shard(_)));
+        if idle {
+            // Topics subscribed from here on were never on the lost connection.
+            reconnecting = false;
+            match cmd_rx.recv().await {
+                // The topic is already in the map and is subscribed below.
+                Some(Command::Subscribe(_)) => {}
+                Some(Command::Release(topic)) => {
+                    release_idle(topics, &topic).await;
+                    continue;
+                }
+                None => return,
+            }
+        }
+
+        info!("Starting sharded Pub/Sub background task...");
+        let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+        let mut connection = match with_connect_timeout(
+            connect_timeout,
+            source.connect(push_tx, client_name.as_deref()),
+        )
+        .await
+        {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to get sharded Pub/Sub connection: {}", e);
+                if !wait_for_retry(topics, &mut cmd_rx, Duration::from_secs(5)).await {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        let active: Vec<Topic> = topics
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|topic| matches!(topic, Topic::Shard(_)))
+            .collect();
+        let mut resubscribed = Vec::with_capacity(active.len());
+        for topic in active {
+            match connection.run("SSUBSCRIBE", topic.name()).await {
+                Ok(()) => resubscribed.push(topic),
+                Err(e) => error!("Failed to resubscribe to {}: {}", topic.name(), e),
+            }
+        }
+        if std::mem::take(&mut reconnecting) {
+            metrics.shard_reconnects.fetch_add(1, Ordering::Relaxed);
+            info!(
+                "Resubscribed to {} shard channels after reconnect",
+                resubscribed.len()
+            );
+            notify_resubscribed(topics, &resubscribed).await;
+        }
+
+        loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(Command::Subscribe(topic)) => {
+                        match connection.run("SSUBSCRIBE", topic.name()).await {
+                            Ok(()) => info!("Subscribed to shard channel {}", topic.name()),
+                            Err(e) => error!("Failed to subscribe to {}: {}", topic.name(), e),
+                        }
+                    }
+                    Some(Command::Release(topic)) => {
+                        if !release_idle(topics, &topic).await {
+                            continue;
+                        }
+                        match connection.run("SUNSUBSCRIBE", topic.name()).await {
+                            Ok(()) => info!("Unsubscribed from shard channel {}", topic.name()),
+                            Err(e) => error!("Failed to unsubscribe from {}: {}", topic.name(), e),
+                        }
+                    }
+                    // Every sender is gone, so nobody can subscribe again.
+                    None => return,
+                },
+                push = push_rx.recv() => match push {
+                    Some(push) if push.kind == PushKind::SMessage => {
+                        let Some(msg) = Msg::from_push_info(push) else {
+                            continue;
+                        };
+                        let channel = msg.get_channel_name().to_string();
+                        let payload: String = match msg.get_payload() {
+                            Ok(payload) => payload,
+                            Err(e) => {
+                                error!("Failed to get payload: {}", e);
+                                continue;
+                            }
+                        };
+                        let topic = Topic::Shard(channel.clone());
+                        let message = PubSubMessage {
+                            channel,
+                            payload,
+                            pattern: None,
+                            sharded: true,
+                            id: None,
+                        };
+                        deliver(&fanout, &topic, message).await;
+                    }
+                    Some(push) if push.kind == PushKind::Disconnection => {
+                        error!("Sharded Pub/Sub connection lost, reconnecting...");
+                        reconnecting = true;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => {
+                        reconnecting = true;
+                        break;
+                    }
+                },
+            }
         }
     }
 }
 
 /// Handles HTTP Pub/Sub subscriptions on `/SUBSCRIBE/{*channel}`.
 ///
+/// Each path segment names a channel, so `/SUBSCRIBE/a/b` listens to both `a`
+/// and `b` on one stream; use `%2F` for a slash inside a channel name.
+///
 /// Mode selection:
 /// - If `jsonp` or `callback` query parameter is present: stream chunked JSONP
 ///   chunks as `<callback>(<json>);\n`.
-/// - Else if request `Accept` explicitly negotiates JSON and does not request SSE:
+/// - Else if the last channel has a `.json` extension (`/SUBSCRIBE/news.json`), or
+///   request `Accept` explicitly negotiates JSON and does not request SSE:
 ///   stream chunked JSON chunks (newline-delimited JSON documents).
 /// - Else: default to SSE for compatibility with existing clients.
 ///
 /// All modes keep the connection open and emit messages as they arrive.
 pub async fn handle_subscribe(
-    Path(channel): Path<String>,
+    OriginalUri(uri): OriginalUri,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Query(params): Query<HashMap<String, String>>,
     headers: HeaderMap,
     State(state): State<Arc<AppState>>,
 ) -> Response {
-    let mut rx = state.pubsub.subscribe(channel.clone()).await;
-    let jsonp_callback = select_jsonp_callback(&params);
+    let (names, json_extension) = path_names(&uri);
+    let topics = names.into_iter().map(Topic::Channel).collect();
+    stream_topics(topics, json_extension, addr, &params, &headers, &state).await
+}
 
-    if let Some(callback) = jsonp_callback {
-        let channel_name = channel.clone();
-        let callback_name = callback.to_string();
-        let stream = async_stream::stream! {
-            loop {
-                match rx.recv().await {
-                    Ok(msg) => {
-                        let payload = json!({
-                            "SUBSCRIBE": ["message", channel_name.as_str(), msg]
-                        });
-                        let chunk = format!("{callback_name}({payload});\n");
-                        yield Ok::<Bytes, Infallible>(Bytes::from(chunk));
-                    }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        let payload = json!({
-                            "SUBSCRIBE": ["error", channel_name.as_str(), "lagged"]
-                        });
-                        let chunk = format!("{callback_name}({payload});\n");
-                        yield Ok(Bytes::from(chunk));
-                    }
-                    Err(broadcast::error::RecvError::Closed) => break,
+/// Handles HTTP pattern subscriptions on `/PSUBSCRIBE/{*pattern}`.
+///
+/// Response modes match [`handle_subscribe`]. JSON messages use the
+/// `{"PSUBSCRIBE": ["pmessage", "<pattern>", "<channel>", "<payload>"]}` shape
+/// and SSE events are named after the concrete channel.
+pub async fn handle_psubscribe(
+    OriginalUri(uri): OriginalUri,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let (names, json_extension) = path_names(&uri);
+    let topics = names.into_iter().map(Topic::Pattern).collect();
+    stream_topics(topics, json_extension, addr, &params, &headers, &state).await
+}
+
+/// Handles HTTP sharded subscriptions on `/SSUBSCRIBE/{*channel}`.
+///
+/// Response modes match [`handle_subscribe`]; JSON messages use the
+/// `{"SSUBSCRIBE": ["smessage", "<channel>", "<payload>"]}` shape.
+pub async fn handle_ssubscribe(
+    OriginalUri(uri): OriginalUri,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let (names, json_extension) = path_names(&uri);
+    let topics = names.into_iter().map(Topic::Shard).collect();
+    stream_topics(topics, json_extension, addr, &params, &headers, &state).await
+}
+
+/// Returns the decoded channel or pattern names following the command segment,
+/// and whether the last one carried a `.json` extension (which is stripped).
+///
+/// Names are separated by slashes or commas (`/SUBSCRIBE/a,b/c`); a literal
+/// comma in a name is sent as `%2C`.
+fn path_names(uri: &Uri) -> (Vec<String>, bool) {
+    let mut names = decode_path_segments(&uri.path().replace(',', "/"));
+    names.remove(0);
+    let mut json_extension = false;
+    if let Some(last) = names.last_mut() {
+        if let Some(idx) = last.rfind('.') {
+            if last[idx + 1..].eq_ignore_ascii_case("json") {
+                last.truncate(idx);
+                json_extension = true;
+            }
+        }
+    }
+    names.retain(|name| !name.is_empty());
+    (names, json_extension)
+}
+
+/// A message or a gap notice from one of the merged subscriptions.
+pub(crate) enum StreamItem {
+    Message(PubSubMessage),
+    /// The subscriber fell behind the broadcast buffer.
+    Lagged(Topic),
+    /// Redis was reconnected and the topic resubscribed.
+    Resubscribed(Topic),
+    /// The client's send queue stayed full too long; the stream ends here.
+    TooSlow,
+}
+
+/// Turns one subscription into a stream of messages and gap notices. Dropping
+/// the stream releases the subscription.
+pub(crate) fn subscription_items(
+    mut rx: Subscription,
+) -> std::pin::Pin<Box<dyn futures::Stream<Item = StreamItem> + Send>> {
+    Box::pin(async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(PubSubEvent::Message(msg)) => yield StreamItem::Message(msg),
+                Ok(PubSubEvent::Resubscribed) => {
+                    yield StreamItem::Resubscribed(rx.topic().clone())
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    yield StreamItem::Lagged(rx.topic().clone())
                 }
+                Err(broadcast::error::RecvError::Closed) => break,
             }
-        };
+        }
+    })
+}
+
+/// Merges the receivers of several subscriptions into one stream.
+fn merged_subscriptions(
+    subscriptions: Vec<Subscription>,
+) -> impl futures::Stream<Item = StreamItem> + Send {
+    futures::stream::select_all(subscriptions.into_iter().map(subscription_items))
+}
+
+pub(crate) fn stream_item_json(item: &StreamItem) -> serde_json::Value {
+    match item {
+        StreamItem::Message(msg) => subscribe_message_json(msg),
+        StreamItem::Lagged(topic) => gap_notice_json(topic, "lagged"),
+        StreamItem::Resubscribed(topic) => gap_notice_json(topic, "reconnected"),
+        StreamItem::TooSlow => json!({"error": "slow_client"}),
+    }
+}
+
+/// Size of the send queue of an SSE stream under the `disconnect` policy.
+const CLIENT_QUEUE_SIZE: usize = 100;
+
+/// Moves `items` into a bounded send queue drained by the client. When the
+/// queue stays full for `timeout`, the subscriptions are released and the
+/// stream ends with [`StreamItem::TooSlow`] instead of the queued items.
+fn disconnect_slow_client(
+    items: impl futures::Stream<Item = StreamItem> + Send + 'static,
+    timeout: Duration,
+    limits: Arc<StreamLimits>,
+) -> impl futures::Stream<Item = StreamItem> + Send {
+    let (tx, mut rx) = mpsc::channel(CLIENT_QUEUE_SIZE);
+    let too_slow = Arc::new(AtomicBool::new(false));
+    let flag = too_slow.clone();
+    tokio::spawn(async move {
+        let mut items = std::pin::pin!(items);
+        loop {
+            // Stop as soon as the client goes away, even on a quiet channel.
+            let item = tokio::select! {
+                item = items.next() => item,
+                _ = tx.closed() => None,
+            };
+            let Some(item) = item else {
+                break;
+            };
+            match tx.send_timeout(item, timeout).await {
+                Ok(()) => {}
+                Err(mpsc::error::SendTimeoutError::Timeout(_)) => {
+                    flag.store(true, Ordering::Relaxed);
+                    limits.record_slow_disconnect();
+                    break;
+                }
+                Err(mpsc::error::SendTimeoutError::Closed(_)) => break,
+            }
+        }
+    });
+    async_stream::stream! {
+        while let Some(item) = rx.recv().await {
+            if too_slow.load(Ordering::Relaxed) {
+                break;
+            }
+            yield item;
+        }
+        if too_slow.load(Ordering::Relaxed) {
+            yield StreamItem::TooSlow;
+        }
+    }
+}
+
+/// Renders a notice that messages of `topic` were missed, for example
+/// `{"SUBSCRIBE": ["error", "<channel>", "reconnected"]}`.
+pub fn gap_notice_json(topic: &Topic, reason: &str) -> serde_json::Value {
+    json!({ topic.subscribe_command(): ["error", topic.name(), reason] })
+}
+
+async fn stream_topics(
+    topics: Vec<Topic>,
+    json_extension: bool,
+    addr: SocketAddr,
+    params: &HashMap<String, String>,
+    headers: &HeaderMap,
+    state: &AppState,
+) -> Response {
+    if topics.is_empty() {
+        return with_cors((StatusCode::BAD_REQUEST, "missing channel name").into_response());
+    }
+    // Check every topic before attaching, so a denied one subscribes nothing.
+    let auth = AuthContext {
+        client_ip: addr.ip(),
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    };
+    let allowed = topics.iter().all(|topic| {
+        state
+            .acl
+            .check_channel(&auth, topic.subscribe_command(), topic.name())
+    });
+    if !allowed {
+        let body = Json(state.errors.body("forbidden", "Forbidden", headers));
+        return with_cors((StatusCode::FORBIDDEN, body).into_response());
+    }
+    let permit = match state.sse_limits.acquire(addr.ip()) {
+        Ok(permit) => permit,
+        Err(status) => {
+            let message = "too many subscription streams";
+            let body = Json(state.errors.body("too_many_streams", message, headers));
+            return with_cors((status, body).into_response());
+        }
+    };
+    let mut subscriptions = Vec::with_capacity(topics.len());
+    for topic in &topics {
+        subscriptions.push(state.pubsub.subscribe_topic(topic.clone()).await);
+    }
+    let items = merged_subscriptions(subscriptions);
+    let items = match state.sse_limits.slow_client_timeout() {
+        Some(timeout) => {
+            disconnect_slow_client(items, timeout, state.sse_limits.clone()).left_stream()
+        }
+        None => items.right_stream(),
+    };
+    // Streams end when the server shuts down, so clients reconnect elsewhere
+    // instead of waiting for the connection to time out.
+    let mut shutdown = state.shutdown.subscribe();
+    let stopped = shutdown.clone();
+    let items = items.take_until(async move { shut_down(&mut shutdown).await });
+    // The permit lives as long as the response stream.
+    let items = items.map(move |item| {
+        let _permit = &permit;
+        item
+    });
+    let jsonp_callback = select_jsonp_callback(params);
+
+    if let Some(callback) = jsonp_callback {
+        let callback_name = callback.to_string();
+        let stream = items.map(move |item| {
+            let payload = stream_item_json(&item);
+            Ok::<Bytes, Infallible>(Bytes::from(format!("{callback_name}({payload});\n")))
+        });
 
         let response = Response::builder()
             .status(StatusCode::OK)
@@ -200,29 +1355,11 @@ pub async fn handle_subscribe(
         return with_cors(response);
     }
 
-    if wants_chunked_json(&headers) {
-        let channel_name = channel.clone();
-        let stream = async_stream::stream! {
-            loop {
-                match rx.recv().await {
-                    Ok(msg) => {
-                        let payload = json!({
-                            "SUBSCRIBE": ["message", channel_name.as_str(), msg]
-                        });
-                        let chunk = format!("{payload}\n");
-                        yield Ok::<Bytes, Infallible>(Bytes::from(chunk));
-                    }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        let payload = json!({
-                            "SUBSCRIBE": ["error", channel_name.as_str(), "lagged"]
-                        });
-                        let chunk = format!("{payload}\n");
-                        yield Ok(Bytes::from(chunk));
-                    }
-                    Err(broadcast::error::RecvError::Closed) => break,
-                }
-            }
-        };
+    if json_extension || wants_chunked_json(headers) {
+        let stream = items.map(|item| {
+            let payload = stream_item_json(&item);
+            Ok::<Bytes, Infallible>(Bytes::from(format!("{payload}\n")))
+        });
 
         let response = Response::builder()
             .status(StatusCode::OK)
@@ -232,23 +1369,119 @@ pub async fn handle_subscribe(
         return with_cors(response);
     }
 
-    let stream = async_stream::stream! {
-        loop {
-            match rx.recv().await {
-                Ok(msg) => yield Ok::<_, Infallible>(Event::default().data(msg)),
-                Err(broadcast::error::RecvError::Lagged(_)) => {
-                    yield Ok(Event::default().event("error").data("lagged"))
-                }
-                Err(broadcast::error::RecvError::Closed) => break,
+    // Subscribing first means nothing published during the replay lookup is
+    // lost; live copies of replayed messages are skipped by id.
+    let replayed = match last_event_id(params, headers) {
+        Some(last) => state.pubsub.replay_since(&topics, last).await,
+        None => Vec::new(),
+    };
+    let replayed_until = replayed.last().and_then(message_event_id);
+    let live = items.filter(move |item| {
+        let fresh = match (item, replayed_until) {
+            (StreamItem::Message(msg), Some(until)) => {
+                message_event_id(msg).is_none_or(|id| id > until)
             }
-        }
+            _ => true,
+        };
+        std::future::ready(fresh)
+    });
+    let stream = futures::stream::iter(replayed.into_iter().map(StreamItem::Message))
+        .chain(live)
+        .map(|item| {
+            Ok::<_, Infallible>(match item {
+                StreamItem::Message(msg) => sse_message_event(msg),
+                StreamItem::Lagged(_) => Event::default().event("error").data("lagged"),
+                StreamItem::Resubscribed(_) => Event::default().event("error").data("reconnected"),
+                StreamItem::TooSlow => Event::default().event("error").data("slow_client"),
+            })
+        })
+        .chain(
+            futures::stream::once(async move { *stopped.borrow() })
+                .filter(|&stopped| std::future::ready(stopped))
+                .map(|_| {
+                    Ok::<_, Infallible>(Event::default().event("shutdown").data("server_shutdown"))
+                }),
+        );
+
+    let sse = Sse::new(stream);
+    with_cors(match state.sse_keep_alive.clone() {
+        Some(keep_alive) => sse.keep_alive(keep_alive).into_response(),
+        None => sse.into_response(),
+    })
+}
+
+/// Builds the SSE keep-alive from `sse.keep_alive_sec` and `sse.keep_alive_text`.
+///
+/// Returns `None` when the interval is `0`. Line breaks in the text would end
+/// the comment early, so text containing them is ignored.
+pub fn sse_keep_alive(config: Option<&SseConfig>) -> Option<KeepAlive> {
+    let interval = config
+        .and_then(|sse| sse.keep_alive_sec)
+        .unwrap_or(DEFAULT_SSE_KEEP_ALIVE_SEC);
+    if interval == 0 {
+        return None;
+    }
+    let keep_alive = KeepAlive::new().interval(Duration::from_secs(interval));
+    match config.and_then(|sse| sse.keep_alive_text.as_deref()) {
+        Some(text) if !text.contains(['\r', '\n']) => Some(keep_alive.text(text)),
+        _ => Some(keep_alive),
+    }
+}
+
+/// Renders a message in the original Webdis shape:
+/// `{"SUBSCRIBE": ["message", "<channel>", "<payload>"]}`, or
+/// `{"PSUBSCRIBE": ["pmessage", "<pattern>", "<channel>", "<payload>"]}` for
+/// pattern subscriptions and `{"SSUBSCRIBE": ["smessage", "<channel>", "<payload>"]}`
+/// for sharded ones.
+pub fn subscribe_message_json(message: &PubSubMessage) -> serde_json::Value {
+    if message.sharded {
+        return json!({
+            "SSUBSCRIBE": ["smessage", message.channel.as_str(), message.payload.as_str()]
+        });
+    }
+    match &message.pattern {
+        Some(pattern) => json!({
+            "PSUBSCRIBE": [
+                "pmessage",
+                pattern.as_str(),
+                message.channel.as_str(),
+                message.payload.as_str()
+            ]
+        }),
+        None => json!({
+            "SUBSCRIBE": ["message", message.channel.as_str(), message.payload.as_str()]
+        }),
+    }
+}
+
+/// Builds an SSE event named after the message channel.
+///
+/// SSE field values cannot contain line breaks, so channels with CR/LF fall back
+/// to the default `message` event.
+fn sse_message_event(message: PubSubMessage) -> Event {
+    let mut event = if message.channel.contains(['\r', '\n']) {
+        Event::default()
+    } else {
+        Event::default().event(&message.channel)
     };
+    if let Some(id) = &message.id {
+        event = event.id(id);
+    }
+    event.data(message.payload)
+}
 
-    with_cors(
-        Sse::new(stream)
-            .keep_alive(KeepAlive::default())
-            .into_response(),
-    )
+/// Reads the resume point from the `Last-Event-ID` header, which browsers send
+/// when `EventSource` reconnects, or from `?last_event_id=` for the first
+/// connection of a page.
+fn last_event_id<'a>(
+    params: &'a HashMap<String, String>,
+    headers: &'a HeaderMap,
+) -> Option<&'a str> {
+    headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| params.get("last_event_id").map(String::as_str))
+        .filter(|id| !id.is_empty())
 }
 
 /// Returns true when the request explicitly negotiates JSON streaming.
@@ -275,3 +1508,280 @@ fn with_cors(mut response: Response) -> Response {
         .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*".parse().unwrap());
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(pattern: Option<&str>, sharded: bool) -> PubSubMessage {
+        PubSubMessage {
+            channel: "news.sports".to_string(),
+            payload: "hello".to_string(),
+            pattern: pattern.map(str::to_string),
+            sharded,
+            id: None,
+        }
+    }
+
+    #[test]
+    fn json_extension_selects_chunked_json() {
+        let uri: Uri = "/SUBSCRIBE/news/sports%2Flive.JSON".parse().unwrap();
+        assert_eq!(
+            path_names(&uri),
+            (vec!["news".to_string(), "sports/live".to_string()], true)
+        );
+
+        let uri: Uri = "/SUBSCRIBE/news.txt".parse().unwrap();
+        assert_eq!(path_names(&uri), (vec!["news.txt".to_string()], false));
+    }
+
+    #[test]
+    fn commas_separate_names() {
+        let uri: Uri = "/SUBSCRIBE/a,b,,c%2Cd/e.json".parse().unwrap();
+        let names = ["a", "b", "c,d", "e"].map(String::from).to_vec();
+        assert_eq!(path_names(&uri), (names, true));
+    }
+
+    #[tokio::test]
+    async fn deliver_drops_and_rewrites_filtered_messages() {
+        let fanout = Fanout {
+            topics: Arc::new(DashMap::new()),
+            replay: Arc::new(OnceLock::new()),
+            filters: Arc::new(OnceLock::new()),
+            metrics: Arc::new(PubSubMetrics::default()),
+        };
+        let rule = redis_web_core::config::PubSubFilterConfig {
+            channel: "orders".to_string(),
+            regex: None,
+            json_path: Some("$.status".to_string()),
+            equals: Some("failed".to_string()),
+            template: Some("{$.id} failed".to_string()),
+        };
+        let _ = fanout.filters.set(MessageFilters::new(&[rule]).unwrap());
+        let topic = Topic::Channel("orders".to_string());
+        let (sender, mut receiver) = broadcast::channel(4);
+        fanout.topics.insert(
+            topic.clone(),
+            Arc::new(TopicEntry {
+                sender,
+                history: Mutex::new(VecDeque::new()),
+                delivered: AtomicU64::new(0),
+            }),
+        );
+        let message = |payload: &str| PubSubMessage {
+            channel: "orders".to_string(),
+            payload: payload.to_string(),
+            pattern: None,
+            sharded: false,
+            id: None,
+        };
+
+        deliver(&fanout, &topic, message(r#"{"id":"A1","status":"paid"}"#)).await;
+        deliver(&fanout, &topic, message(r#"{"id":"A2","status":"failed"}"#)).await;
+
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(PubSubEvent::Message(message("A2 failed")))
+        );
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(fanout.metrics.messages_filtered.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn subscribers_share_a_topic_until_the_last_is_released() {
+        let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+        let manager = PubSubManager {
+            cmd_txs: vec![cmd_tx],
+            shard_tx: None,
+            fanout: Fanout {
+                topics: Arc::new(DashMap::new()),
+                replay: Arc::new(OnceLock::new()),
+                filters: Arc::new(OnceLock::new()),
+                metrics: Arc::new(PubSubMetrics::default()),
+            },
+            connect_timeout: None,
+            client_name: None,
+        };
+        let topic = Topic::Channel("news".to_string());
+
+        let first = manager.subscribe("news".to_string()).await;
+        let second = manager.subscribe("news".to_string()).await;
+        assert!(
+            matches!(cmd_rx.try_recv(), Ok(Command::Subscribe(subscribed)) if subscribed == topic)
+        );
+        assert!(cmd_rx.try_recv().is_err());
+
+        drop(first);
+        assert!(!release_idle(&manager.fanout.topics, &topic).await);
+        assert_eq!(manager.active_topics().await, vec![topic.clone()]);
+        drop(second);
+        assert!(release_idle(&manager.fanout.topics, &topic).await);
+        assert!(manager.active_topics().await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn dropped_subscriptions_release_without_receivers_left() {
+        let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel();
+        let manager = PubSubManager {
+            cmd_txs: vec![cmd_tx],
+            shard_tx: None,
+            fanout: Fanout {
+                topics: Arc::new(DashMap::new()),
+                replay: Arc::new(OnceLock::new()),
+                filters: Arc::new(OnceLock::new()),
+                metrics: Arc::new(PubSubMetrics::default()),
+            },
+            connect_timeout: None,
+            client_name: None,
+        };
+        // Handles each release on its own thread the moment it is sent, as
+        // the Pub/Sub loop may on another worker.
+        let topics = manager.fanout.topics.clone();
+        let (released_tx, released_rx) = std::sync::mpsc::channel();
+        let loop_thread = std::thread::spawn(move || loop {
+            match cmd_rx.try_recv() {
+                Ok(Command::Release(topic)) => {
+                    let released = futures::executor::block_on(release_idle(&topics, &topic));
+                    released_tx.send(released).unwrap();
+                }
+                Ok(Command::Subscribe(_)) | Err(mpsc::error::TryRecvError::Empty) => {
+                    std::hint::spin_loop()
+                }
+                Err(mpsc::error::TryRecvError::Disconnected) => return,
+            }
+        });
+
+        for _ in 0..50_000 {
+            drop(manager.subscribe("news".to_string()).await);
+            assert!(released_rx.recv().unwrap(), "the topic leaked");
+        }
+        drop(manager);
+        loop_thread.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn resubscribe_notifies_only_listed_topics() {
+        let topics: Topics = Arc::new(DashMap::new());
+        let mut receivers = Vec::new();
+        for name in ["news", "alerts"] {
+            let (sender, receiver) = broadcast::channel(4);
+            topics.insert(
+                Topic::Channel(name.to_string()),
+                Arc::new(TopicEntry {
+                    sender,
+                    history: Mutex::new(VecDeque::new()),
+                    delivered: AtomicU64::new(0),
+                }),
+            );
+            receivers.push(receiver);
+        }
+
+        notify_resubscribed(&topics, &[Topic::Channel("news".to_string())]).await;
+
+        assert_eq!(receivers[0].try_recv(), Ok(PubSubEvent::Resubscribed));
+        assert!(receivers[1].try_recv().is_err());
+        assert_eq!(
+            gap_notice_json(&Topic::Pattern("news.*".to_string()), "reconnected"),
+            json!({"PSUBSCRIBE": ["error", "news.*", "reconnected"]})
+        );
+    }
+
+    #[test]
+    fn each_topic_belongs_to_one_connection() {
+        let partitions: Vec<Partition> =
+            (0..4).map(|index| Partition { index, count: 4 }).collect();
+        let mut used = [false; 4];
+        for name in (0..64).map(|i| format!("channel-{i}")) {
+            let topic = Topic::Channel(name);
+            let owners: Vec<usize> = partitions
+                .iter()
+                .filter(|partition| partition.owns(&topic))
+                .map(|partition| partition.index)
+                .collect();
+            assert_eq!(owners, vec![connection_index(&topic, 4)]);
+            used[owners[0]] = true;
+        }
+        assert_eq!(used, [true; 4]);
+        assert!(!partitions[connection_index(&Topic::Shard("a".into()), 4)]
+            .owns(&Topic::Shard("a".into())));
+    }
+
+    #[test]
+    fn stream_limits_cap_totals_and_clients() {
+        let limits = Arc::new(StreamLimits::new(Some(&SseConfig {
+            max_connections: Some(2),
+            max_connections_per_ip: Some(1),
+            ..SseConfig::default()
+        })));
+        let first: IpAddr = "10.0.0.1".parse().unwrap();
+        let second: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let held = limits.acquire(first).unwrap();
+        assert_eq!(
+            limits.acquire(first).err(),
+            Some(StatusCode::TOO_MANY_REQUESTS)
+        );
+        let _other = limits.acquire(second).unwrap();
+        assert_eq!(
+            limits.acquire("10.0.0.3".parse().unwrap()).err(),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+
+        drop(held);
+        let stats = limits.stats();
+        assert_eq!((stats.open, stats.clients), (1, 1));
+        assert_eq!((stats.rejected_total, stats.rejected_per_ip), (1, 1));
+        assert!(limits.acquire(first).is_ok());
+    }
+
+    #[tokio::test]
+    async fn slow_clients_are_cut_off_after_the_timeout() {
+        let limits = Arc::new(StreamLimits::new(Some(&SseConfig {
+            slow_client_policy: Some(SlowClientPolicy::Disconnect),
+            slow_client_timeout_sec: Some(1),
+            ..SseConfig::default()
+        })));
+        let timeout = limits.slow_client_timeout().unwrap();
+        assert_eq!(timeout, Duration::from_secs(1));
+
+        let burst = || {
+            futures::stream::iter(
+                (0..CLIENT_QUEUE_SIZE + 1).map(|_| StreamItem::Message(message(None, false))),
+            )
+        };
+        // A client that reads keeps every message.
+        let kept = disconnect_slow_client(burst(), timeout, limits.clone())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(kept.len(), CLIENT_QUEUE_SIZE + 1);
+
+        // One that stops reading loses the queue and gets the final notice.
+        let mut stalled = Box::pin(disconnect_slow_client(
+            burst().chain(futures::stream::pending()),
+            Duration::from_millis(20),
+            limits.clone(),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(matches!(stalled.next().await, Some(StreamItem::TooSlow)));
+        assert!(stalled.next().await.is_none());
+        assert_eq!(limits.stats().slow_disconnects, 1);
+
+        assert!(StreamLimits::new(None).slow_client_timeout().is_none());
+    }
+
+    #[test]
+    fn message_json_matches_subscription_kind() {
+        assert_eq!(
+            subscribe_message_json(&message(None, false)),
+            json!({"SUBSCRIBE": ["message", "news.sports", "hello"]})
+        );
+        assert_eq!(
+            subscribe_message_json(&message(Some("news.*"), false)),
+            json!({"PSUBSCRIBE": ["pmessage", "news.*", "news.sports", "hello"]})
+        );
+        assert_eq!(
+            subscribe_message_json(&message(None, true)),
+            json!({"SSUBSCRIBE": ["smessage", "news.sports", "hello"]})
+        );
+    }
+}
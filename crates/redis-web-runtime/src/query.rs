@@ -0,0 +1,283 @@
+//! Batched reads on `POST /_query`.
+//!
+//! The body maps result names to commands, each given as an array of its
+//! name and arguments:
+//!
+//! ```json
+//! {"user": ["HGETALL", "user:1"], "friends": ["SMEMBERS", "friends:1"]}
+//! ```
+//!
+//! The commands are sent in one pipeline and the response keys each reply by
+//! its name: `{"user": {"name": "Ada"}, "friends": ["2", "3"]}`. Only the
+//! read-only commands in [`READ_COMMANDS`] are accepted; a body naming any
+//! other command gets `400`. Hooks, `routes`, the `acl` rules and `quotas`
+//! apply to every command as they do on the command URLs; if any command is
+//! refused or denied, nothing runs. Refused and denied commands go to the
+//! `audit` stream, and so does each command once the pipeline ran.
+
+use crate::admission;
+use crate::handler::{execution_error_status, redis_value_to_json_with_nil, AppState};
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use redis_web_core::config::JsonNil;
+use redis_web_core::format::json_value_response;
+use redis_web_core::interfaces::{AuthContext, ExecutableCommand};
+use serde_json::{Map, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Commands `/_query` runs: reads that never change data or block, sorted
+/// for binary search.
+const READ_COMMANDS: &[&str] = &[
+    "BITCOUNT",
+    "BITFIELD_RO",
+    "BITPOS",
+    "DBSIZE",
+    "EXISTS",
+    "EXPIRETIME",
+    "GEODIST",
+    "GEOHASH",
+    "GEOPOS",
+    "GEORADIUSBYMEMBER_RO",
+    "GEORADIUS_RO",
+    "GEOSEARCH",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HRANDFIELD",
+    "HSCAN",
+    "HSTRLEN",
+    "HVALS",
+    "LCS",
+    "LINDEX",
+    "LLEN",
+    "LPOS",
+    "LRANGE",
+    "MGET",
+    "PEXPIRETIME",
+    "PFCOUNT",
+    "PTTL",
+    "SCARD",
+    "SDIFF",
+    "SINTER",
+    "SINTERCARD",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SMISMEMBER",
+    "SORT_RO",
+    "SRANDMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "SUNION",
+    "TTL",
+    "TYPE",
+    "XLEN",
+    "XPENDING",
+    "XRANGE",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZDIFF",
+    "ZINTER",
+    "ZINTERCARD",
+    "ZLEXCOUNT",
+    "ZMSCORE",
+    "ZRANDMEMBER",
+    "ZRANGE",
+    "ZRANGEBYLEX",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANGEBYLEX",
+    "ZREVRANGEBYSCORE",
+    "ZREVRANK",
+    "ZSCAN",
+    "ZSCORE",
+    "ZUNION",
+];
+
+/// Handles `POST /_query`.
+pub async fn handle_query(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let reads = match parse(&body) {
+        Ok(reads) => reads,
+        Err(message) => {
+            return state.errors.response(
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                &message,
+                &headers,
+                None,
+            )
+        }
+    };
+    let started = Instant::now();
+    let auth = AuthContext {
+        client_ip: addr.ip(),
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    };
+
+    let mut names = Vec::with_capacity(reads.len());
+    let mut commands = Vec::with_capacity(reads.len());
+    for (name, mut args) in reads {
+        let command_name = String::from_utf8_lossy(&args.remove(0)).into_owned();
+        let mut command = ExecutableCommand {
+            backend: None,
+            target_database: state.default_database,
+            command_name,
+            args,
+            timeout: None,
+            credentials: None,
+        };
+        if let Err(error) = state.hooks.before(&mut command) {
+            let status = execution_error_status(&error);
+            admission::audit(&state, &auth, &command, status, started);
+            return state.errors.execution_response(&error, &headers, None);
+        }
+        if !is_read(&command.command_name) {
+            return state.errors.response(
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+                &format!(
+                    "\"{name}\" runs {}, which is not a read-only command",
+                    command.command_name
+                ),
+                &headers,
+                None,
+            );
+        }
+        admission::route(&state, &auth, &mut command);
+        if !admission::authorize(&state, &auth, &command) {
+            admission::audit(&state, &auth, &command, StatusCode::FORBIDDEN, started);
+            return state.errors.response(
+                StatusCode::FORBIDDEN,
+                "forbidden",
+                "Forbidden",
+                &headers,
+                None,
+            );
+        }
+        names.push(name);
+        commands.push(command);
+    }
+
+    let charge = match admission::charge(&state, &auth, commands.len() as u64).await {
+        Ok(charge) => charge,
+        Err(retry_after) => return admission::quota_exceeded(&state, &headers, retry_after, None),
+    };
+    let outcome = admission::execute_pipeline(&state, &commands).await;
+    let status = admission::outcome_status(&outcome);
+    for command in &commands {
+        admission::audit(&state, &auth, command, status, started);
+    }
+    let response = match outcome {
+        Ok(replies) => {
+            let nil = state.json_nil;
+            let results: Map<String, Value> = names
+                .into_iter()
+                .zip(replies)
+                .filter(|(_, reply)| !(nil == JsonNil::Omit && *reply == redis::Value::Nil))
+                .map(|(name, reply)| (name, redis_value_to_json_with_nil(reply, nil)))
+                .collect();
+            json_value_response(StatusCode::OK, Value::Object(results), None)
+        }
+        Err(error) => state.errors.execution_response(&error, &headers, None),
+    };
+    charge.meter(&state, response)
+}
+
+fn is_read(command_name: &str) -> bool {
+    READ_COMMANDS
+        .binary_search(&command_name.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+/// A result name and its command line, command name first.
+type NamedCommand = (String, Vec<Vec<u8>>);
+
+/// Reads the named commands of a query body. Arguments may be strings or
+/// numbers.
+fn parse(body: &[u8]) -> Result<Vec<NamedCommand>, String> {
+    let Some(Value::Object(body)) = crate::json::parse(body) else {
+        return Err("Expected a JSON object body".to_string());
+    };
+    if body.is_empty() {
+        return Err("Expected at least one named command".to_string());
+    }
+    body.into_iter()
+        .map(|(name, command)| {
+            let args = match &command {
+                Value::Array(items) if !items.is_empty() => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(text) => Some(text.clone().into_bytes()),
+                        Value::Number(number) => Some(number.to_string().into_bytes()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            };
+            match args {
+                Some(args) if !args[0].is_empty() => Ok((name, args)),
+                _ => Err(format!(
+                    "\"{name}\" must be a non-empty array of a command name and its arguments"
+                )),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_named_commands() {
+        let reads =
+            parse(br#"{"user":["HGETALL","user:1"],"top":["ZRANGE","scores",0,-1]}"#).unwrap();
+        let reads: Vec<_> = reads
+            .iter()
+            .map(|(name, args)| {
+                let args: Vec<_> = args.iter().map(|arg| arg.as_slice()).collect();
+                (name.as_str(), args)
+            })
+            .collect();
+        assert!(reads.contains(&("user", vec![&b"HGETALL"[..], b"user:1"])));
+        assert!(reads.contains(&("top", vec![&b"ZRANGE"[..], b"scores", b"0", b"-1"])));
+
+        assert!(parse(b"[]").is_err());
+        assert!(parse(b"{}").is_err());
+        assert!(parse(br#"{"a":[]}"#).is_err());
+        assert!(parse(br#"{"a":["GET",null]}"#).is_err());
+        assert!(parse(br#"{"a":"GET key"}"#).is_err());
+    }
+
+    #[test]
+    fn accepts_only_reads() {
+        assert!(READ_COMMANDS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(is_read("GET"));
+        assert!(is_read("zrange"));
+        assert!(!is_read("SET"));
+        assert!(!is_read("BLPOP"));
+        assert!(!is_read("EVAL"));
+        assert!(!is_read("SUBSCRIBE"));
+    }
+}
@@ -0,0 +1,751 @@
+//! Usage quotas per client identity.
+//!
+//! With `quotas` set, every client command that passes the `acl` checks
+//! counts against the daily and monthly caps of its identity: the Basic auth
+//! user name when an `acl` rule accepts its password, or else the client IP,
+//! whichever entry point it came through (see [`crate::admission::charge`]).
+//! Replies count against the byte caps as they are sent. The counters live
+//! in Redis, so all redis-web instances in front of the same Redis share
+//! them. A request over a cap gets `429` with `Retry-After` set to the time
+//! left in the window, and allowed HTTP responses carry `X-Quota-Remaining`.
+//! `/_admin/ratelimits` lists the counters of the current windows and can
+//! reset those of one identity.
+
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue};
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, Months, NaiveTime, Utc};
+use redis::Value as RedisValue;
+use redis_web_core::acl::{basic_credentials, Acl};
+use redis_web_core::config::{Config, QuotaLimits};
+use redis_web_core::interfaces::{
+    AuthContext, CommandExecutionError, CommandExecutor, ExecutableCommand,
+};
+use redis_web_core::routing::glob_escape;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tracing::warn;
+
+pub const DEFAULT_KEY_PREFIX: &str = "redis-web:quota:";
+/// Commands left in the tightest command cap.
+pub const QUOTA_REMAINING_HEADER: &str = "x-quota-remaining";
+/// Response bytes left in the tightest byte cap.
+pub const QUOTA_REMAINING_BYTES_HEADER: &str = "x-quota-remaining-bytes";
+
+/// Quota limits and counters.
+#[derive(Debug)]
+pub struct Quotas {
+    defaults: QuotaLimits,
+    identities: BTreeMap<String, QuotaLimits>,
+    key_prefix: String,
+    database: u8,
+}
+
+/// Outcome of counting a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaDecision {
+    /// Within every cap, with what is left of the tightest command and byte
+    /// caps, when there are any.
+    Allowed {
+        commands: Option<u64>,
+        bytes: Option<u64>,
+    },
+    /// Over a cap whose window resets in `retry_after` seconds.
+    Exceeded { retry_after: u64 },
+}
+
+impl QuotaDecision {
+    /// Sets `X-Quota-Remaining` and `X-Quota-Remaining-Bytes`.
+    pub fn add_headers(&self, headers: &mut HeaderMap) {
+        let (commands, bytes) = match *self {
+            QuotaDecision::Allowed { commands, bytes } => (commands, bytes),
+            QuotaDecision::Exceeded { .. } => (Some(0), None),
+        };
+        if let Some(commands) = commands {
+            headers.insert(QUOTA_REMAINING_HEADER, HeaderValue::from(commands));
+        }
+        if let Some(bytes) = bytes {
+            headers.insert(QUOTA_REMAINING_BYTES_HEADER, HeaderValue::from(bytes));
+        }
+    }
+}
+
+/// What one identity used in the current windows, for `/_admin/ratelimits`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub identity: String,
+    pub commands_today: u64,
+    pub commands_this_month: u64,
+    pub bytes_today: u64,
+    pub bytes_this_month: u64,
+    /// Seconds until the identity may run commands again, while it is over a
+    /// cap.
+    pub blocked_for: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Window {
+    Day,
+    Month,
+}
+
+impl Window {
+    fn period(self, now: DateTime<Utc>) -> String {
+        match self {
+            Window::Day => now.format("%Y-%m-%d").to_string(),
+            Window::Month => now.format("%Y-%m").to_string(),
+        }
+    }
+
+    /// Lifetime of a counter, past the end of its window.
+    fn ttl(self) -> u64 {
+        match self {
+            Window::Day => 2 * 86_400,
+            Window::Month => 32 * 86_400,
+        }
+    }
+
+    /// Seconds until the window after the one holding `now` starts.
+    fn seconds_left(self, now: DateTime<Utc>) -> u64 {
+        let today = now.date_naive();
+        let next = match self {
+            Window::Day => today.succ_opt(),
+            Window::Month => today
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(1))),
+        };
+        next.map_or(0, |next| {
+            let start = next.and_time(NaiveTime::MIN).and_utc();
+            u64::try_from((start - now).num_seconds()).unwrap_or(0)
+        })
+    }
+}
+
+impl Quotas {
+    /// The quotas of `config`, or `None` when `quotas` is unset.
+    pub fn new(config: &Config) -> Option<Self> {
+        let settings = config.quotas.as_ref()?;
+        Some(Self {
+            defaults: QuotaLimits {
+                commands_per_day: settings.commands_per_day,
+                commands_per_month: settings.commands_per_month,
+                bytes_per_day: settings.bytes_per_day,
+                bytes_per_month: settings.bytes_per_month,
+            },
+            identities: settings.identities.clone().unwrap_or_default(),
+            key_prefix: settings
+                .key_prefix
+                .clone()
+                .unwrap_or_else(|| DEFAULT_KEY_PREFIX.to_string()),
+            database: config.database,
+        })
+    }
+
+    /// The identity a request is counted under: its Basic auth user name when
+    /// an `acl` rule accepts the whole `user:password`, or the client IP.
+    /// Unknown credentials count under the IP, so a client cannot dodge its
+    /// quota, or spend another user's, by naming a user.
+    pub fn identity(acl: &Acl, auth: &AuthContext) -> String {
+        auth.authorization
+            .as_deref()
+            .and_then(basic_credentials)
+            .filter(|creds| acl.knows_credentials(creds))
+            .and_then(|creds| creds.split_once(':').map(|(user, _)| user.to_string()))
+            .unwrap_or_else(|| auth.client_ip.to_string())
+    }
+
+    fn limits(&self, identity: &str) -> &QuotaLimits {
+        self.identities.get(identity).unwrap_or(&self.defaults)
+    }
+
+    fn key(&self, identity: &str, window: Window, now: DateTime<Utc>, counter: &str) -> Vec<u8> {
+        format!(
+            "{}{identity}:{}:{counter}",
+            self.key_prefix,
+            window.period(now)
+        )
+        .into_bytes()
+    }
+
+    fn command(&self, name: &str, args: Vec<Vec<u8>>) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: self.database,
+            command_name: name.to_string(),
+            args,
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    /// Counts `commands` commands of `identity` and decides whether they may
+    /// run. When Redis cannot be reached, they are allowed.
+    pub async fn admit(
+        &self,
+        executor: &dyn CommandExecutor,
+        identity: &str,
+        commands: u64,
+    ) -> QuotaDecision {
+        self.admit_at(executor, identity, commands, Utc::now())
+            .await
+    }
+
+    async fn admit_at(
+        &self,
+        executor: &dyn CommandExecutor,
+        identity: &str,
+        count: u64,
+        now: DateTime<Utc>,
+    ) -> QuotaDecision {
+        let limits = self.limits(identity);
+        let command_caps = caps(limits.commands_per_day, limits.commands_per_month);
+        let byte_caps = caps(limits.bytes_per_day, limits.bytes_per_month);
+        if command_caps.is_empty() && byte_caps.is_empty() {
+            return QuotaDecision::Allowed {
+                commands: None,
+                bytes: None,
+            };
+        }
+
+        let mut commands = Vec::new();
+        for &(window, _) in &command_caps {
+            let key = self.key(identity, window, now, "commands");
+            commands.push(self.command("INCRBY", vec![key.clone(), count.to_string().into()]));
+            commands.push(self.command("EXPIRE", vec![key, window.ttl().to_string().into()]));
+        }
+        for &(window, _) in &byte_caps {
+            commands.push(self.command("GET", vec![self.key(identity, window, now, "bytes")]));
+        }
+        let replies = match executor.execute_pipeline(&commands).await {
+            Ok(replies) => replies,
+            Err(error) => {
+                warn!("Quota check failed for {identity}, allowing the request: {error}");
+                return QuotaDecision::Allowed {
+                    commands: None,
+                    bytes: None,
+                };
+            }
+        };
+
+        let used = |index: usize| replies.get(index).map_or(0, counter_value);
+        let mut exceeded: Option<u64> = None;
+        let mut remaining = |window: Window, limit: u64, used: u64, over: bool| {
+            if over {
+                let left = window.seconds_left(now);
+                exceeded = Some(exceeded.map_or(left, |other| other.max(left)));
+            }
+            limit.saturating_sub(used)
+        };
+        // The command counters already include these commands.
+        let commands = command_caps
+            .iter()
+            .enumerate()
+            .map(|(index, &(window, limit))| {
+                let used = used(index * 2);
+                remaining(window, limit, used, used > limit)
+            })
+            .min();
+        let bytes = byte_caps
+            .iter()
+            .enumerate()
+            .map(|(index, &(window, limit))| {
+                let used = used(command_caps.len() * 2 + index);
+                remaining(window, limit, used, used >= limit)
+            })
+            .min();
+        match exceeded {
+            Some(retry_after) => QuotaDecision::Exceeded { retry_after },
+            None => QuotaDecision::Allowed { commands, bytes },
+        }
+    }
+
+    /// The identities with counters in the current windows, ordered by
+    /// identity. The counters are found with `SCAN`, so in cluster mode only
+    /// those on the seed node are listed.
+    pub async fn usage(
+        &self,
+        executor: &dyn CommandExecutor,
+    ) -> Result<Vec<QuotaUsage>, CommandExecutionError> {
+        self.usage_at(executor, Utc::now()).await
+    }
+
+    async fn usage_at(
+        &self,
+        executor: &dyn CommandExecutor,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<QuotaUsage>, CommandExecutionError> {
+        let pattern = format!("{}*", glob_escape(&self.key_prefix));
+        let mut keys = Vec::new();
+        let mut cursor = b"0".to_vec();
+        loop {
+            let args = vec![cursor, b"MATCH".to_vec(), pattern.clone().into_bytes()];
+            let reply = executor.execute(&self.command("SCAN", args)).await?;
+            let RedisValue::Array(mut page) = reply else {
+                return Err(CommandExecutionError::ExecutionFailed(
+                    "unexpected SCAN reply".to_string(),
+                ));
+            };
+            if let Some(RedisValue::Array(found)) = page.pop() {
+                keys.extend(found.into_iter().filter_map(|key| match key {
+                    RedisValue::BulkString(key) => self.parse_key(&key, now),
+                    _ => None,
+                }));
+            }
+            cursor = match page.pop() {
+                Some(RedisValue::BulkString(next)) if next != b"0" => next,
+                _ => break,
+            };
+        }
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let reads: Vec<_> = keys
+            .iter()
+            .map(|(identity, window, counter)| {
+                self.command("GET", vec![self.key(identity, *window, now, counter)])
+            })
+            .collect();
+        let replies = executor.execute_pipeline(&reads).await?;
+        let mut usage: BTreeMap<String, QuotaUsage> = BTreeMap::new();
+        for ((identity, window, counter), reply) in keys.into_iter().zip(&replies) {
+            let entry = usage.entry(identity.clone()).or_insert_with(|| QuotaUsage {
+                identity,
+                ..QuotaUsage::default()
+            });
+            let used = counter_value(reply);
+            match (window, counter.as_str()) {
+                (Window::Day, "commands") => entry.commands_today = used,
+                (Window::Month, "commands") => entry.commands_this_month = used,
+                (Window::Day, _) => entry.bytes_today = used,
+                (Window::Month, _) => entry.bytes_this_month = used,
+            }
+        }
+        Ok(usage
+            .into_values()
+            .map(|mut entry| {
+                let limits = self.limits(&entry.identity);
+                // The next request is refused once a counter reaches its cap.
+                entry.blocked_for = [
+                    (Window::Day, limits.commands_per_day, entry.commands_today),
+                    (
+                        Window::Month,
+                        limits.commands_per_month,
+                        entry.commands_this_month,
+                    ),
+                    (Window::Day, limits.bytes_per_day, entry.bytes_today),
+                    (
+                        Window::Month,
+                        limits.bytes_per_month,
+                        entry.bytes_this_month,
+                    ),
+                ]
+                .into_iter()
+                .filter(|&(_, limit, used)| limit.is_some_and(|limit| used >= limit))
+                .map(|(window, _, _)| window.seconds_left(now))
+                .max();
+                entry
+            })
+            .collect())
+    }
+
+    /// Deletes the counters of `identity` in the current windows, lifting any
+    /// block, and returns how many there were.
+    pub async fn reset(
+        &self,
+        executor: &dyn CommandExecutor,
+        identity: &str,
+    ) -> Result<u64, CommandExecutionError> {
+        self.reset_at(executor, identity, Utc::now()).await
+    }
+
+    async fn reset_at(
+        &self,
+        executor: &dyn CommandExecutor,
+        identity: &str,
+        now: DateTime<Utc>,
+    ) -> Result<u64, CommandExecutionError> {
+        // One `DEL` per key: the keys hash to different Redis Cluster slots.
+        let deletes = [Window::Day, Window::Month]
+            .into_iter()
+            .flat_map(|window| {
+                ["commands", "bytes"].map(|counter| self.key(identity, window, now, counter))
+            })
+            .map(|key| self.command("DEL", vec![key]));
+        let mut cleared = 0;
+        for delete in deletes {
+            cleared += counter_value(&executor.execute(&delete).await?);
+        }
+        Ok(cleared)
+    }
+
+    /// The identity, window and counter of a counter key of the current
+    /// windows.
+    fn parse_key(&self, key: &[u8], now: DateTime<Utc>) -> Option<(String, Window, String)> {
+        let key = std::str::from_utf8(key)
+            .ok()?
+            .strip_prefix(&self.key_prefix)?;
+        // Identities may hold colons, such as IPv6 addresses.
+        let mut parts = key.rsplitn(3, ':');
+        let counter = parts
+            .next()
+            .filter(|c| matches!(*c, "commands" | "bytes"))?;
+        let period = parts.next()?;
+        let identity = parts.next()?;
+        let window = [Window::Day, Window::Month]
+            .into_iter()
+            .find(|window| window.period(now) == period)?;
+        Some((identity.to_string(), window, counter.to_string()))
+    }
+
+    /// Wraps a response body so the bytes sent of it are added to the byte
+    /// counters of `identity` once it ends or the client goes away.
+    pub fn meter(
+        self: &Arc<Self>,
+        executor: Arc<dyn CommandExecutor>,
+        identity: String,
+        body: Body,
+    ) -> Body {
+        let limits = self.limits(&identity);
+        if limits.bytes_per_day.is_none() && limits.bytes_per_month.is_none() {
+            return body;
+        }
+        Body::new(MeteredBody {
+            inner: body,
+            quotas: self.clone(),
+            executor,
+            identity,
+            sent: 0,
+        })
+    }
+
+    /// Adds `bytes` of reply to the byte counters of `identity` in the
+    /// background.
+    pub fn record_bytes(
+        self: &Arc<Self>,
+        executor: Arc<dyn CommandExecutor>,
+        identity: String,
+        bytes: u64,
+    ) {
+        let limits = self.limits(&identity);
+        let byte_caps = caps(limits.bytes_per_day, limits.bytes_per_month);
+        if byte_caps.is_empty() || bytes == 0 {
+            return;
+        }
+        let now = Utc::now();
+        let commands: Vec<_> = byte_caps
+            .iter()
+            .flat_map(|&(window, _)| {
+                let key = self.key(&identity, window, now, "bytes");
+                [
+                    self.command("INCRBY", vec![key.clone(), bytes.to_string().into()]),
+                    self.command("EXPIRE", vec![key, window.ttl().to_string().into()]),
+                ]
+            })
+            .collect();
+        tokio::spawn(async move {
+            if let Err(error) = executor.execute_pipeline(&commands).await {
+                warn!("Cannot record quota bytes for {identity}: {error}");
+            }
+        });
+    }
+}
+
+/// A response body counting the bytes it sends; see [`Quotas::meter`].
+struct MeteredBody {
+    inner: Body,
+    quotas: Arc<Quotas>,
+    executor: Arc<dyn CommandExecutor>,
+    identity: String,
+    sent: u64,
+}
+
+impl http_body::Body for MeteredBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body::Frame<Bytes>, axum::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &polled {
+            if let Some(data) = frame.data_ref() {
+                self.sent += data.len() as u64;
+            }
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for MeteredBody {
+    fn drop(&mut self) {
+        let identity = std::mem::take(&mut self.identity);
+        self.quotas
+            .record_bytes(self.executor.clone(), identity, self.sent);
+    }
+}
+
+/// The configured caps, daily first.
+fn caps(per_day: Option<u64>, per_month: Option<u64>) -> Vec<(Window, u64)> {
+    [(Window::Day, per_day), (Window::Month, per_month)]
+        .into_iter()
+        .filter_map(|(window, limit)| limit.map(|limit| (window, limit)))
+        .collect()
+}
+
+/// A counter read from Redis; a missing key counts as zero.
+fn counter_value(value: &RedisValue) -> u64 {
+    match value {
+        RedisValue::Int(count) => u64::try_from(*count).unwrap_or(0),
+        RedisValue::BulkString(bytes) => std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+    use redis_web_core::interfaces::ExecutionFuture;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Keeps counters for `INCRBY` and `GET`, lists them all on
+    /// `SCAN` and removes them on `DEL`; `EXPIRE` is a no-op.
+    #[derive(Default)]
+    struct Counters(Mutex<HashMap<Vec<u8>, i64>>);
+
+    impl CommandExecutor for Counters {
+        fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
+            let mut counters = self.0.lock().unwrap();
+            if request.command_name == "SCAN" {
+                let keys = counters
+                    .keys()
+                    .map(|key| RedisValue::BulkString(key.clone()))
+                    .collect();
+                let reply = RedisValue::Array(vec![
+                    RedisValue::BulkString(b"0".to_vec()),
+                    RedisValue::Array(keys),
+                ]);
+                return Box::pin(async move { Ok(reply) });
+            }
+            if request.command_name == "DEL" {
+                if request.args.len() > 1 {
+                    // Counter keys live in different cluster slots.
+                    return Box::pin(async {
+                        Err(CommandExecutionError::ExecutionFailed(
+                            "CROSSSLOT Keys in request don't hash to the same slot".to_string(),
+                        ))
+                    });
+                }
+                let removed = request
+                    .args
+                    .iter()
+                    .filter(|key| counters.remove(*key).is_some())
+                    .count();
+                return Box::pin(async move { Ok(RedisValue::Int(removed as i64)) });
+            }
+            let counter = counters.entry(request.args[0].clone()).or_default();
+            let reply = match request.command_name.as_str() {
+                "INCRBY" => {
+                    *counter += std::str::from_utf8(&request.args[1])
+                        .unwrap()
+                        .parse::<i64>()
+                        .unwrap();
+                    RedisValue::Int(*counter)
+                }
+                "GET" => RedisValue::BulkString(counter.to_string().into_bytes()),
+                _ => RedisValue::Int(1),
+            };
+            Box::pin(async move { Ok(reply) })
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_requests_over_the_daily_cap_until_midnight() {
+        let config = Config::from_value(json!({"quotas": {
+            "commands_per_day": 2,
+            "commands_per_month": 100,
+            "identities": {"partner": {"commands_per_day": 5}}
+        }}))
+        .unwrap();
+        let quotas = Quotas::new(&config).unwrap();
+        let counters = Counters::default();
+        let now = "2026-10-16T23:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+        let mut decisions = Vec::new();
+        for _ in 0..3 {
+            decisions.push(quotas.admit_at(&counters, "10.0.0.1", 1, now).await);
+        }
+        assert_eq!(
+            decisions[..2],
+            [
+                QuotaDecision::Allowed {
+                    commands: Some(1),
+                    bytes: None
+                },
+                QuotaDecision::Allowed {
+                    commands: Some(0),
+                    bytes: None
+                }
+            ]
+        );
+        assert_eq!(decisions[2], QuotaDecision::Exceeded { retry_after: 3600 });
+        assert_eq!(
+            quotas.admit_at(&counters, "partner", 1, now).await,
+            QuotaDecision::Allowed {
+                commands: Some(4),
+                bytes: None
+            }
+        );
+        let counters = counters.0.into_inner().unwrap();
+        assert_eq!(
+            counters[&b"redis-web:quota:10.0.0.1:2026-10-16:commands"[..]],
+            3
+        );
+        assert_eq!(
+            counters[&b"redis-web:quota:10.0.0.1:2026-10:commands"[..]],
+            3
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_and_resets_the_counters_of_the_current_windows() {
+        let config = Config::from_value(json!({"quotas": {"commands_per_day": 2}})).unwrap();
+        let quotas = Quotas::new(&config).unwrap();
+        let counters = Counters::default();
+        let now = "2026-10-16T23:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for _ in 0..2 {
+            quotas.admit_at(&counters, "2001:db8::1", 1, now).await;
+        }
+        quotas.admit_at(&counters, "alice", 1, now).await;
+        // Yesterday's counter is left out.
+        let yesterday = "2026-10-15T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        quotas.admit_at(&counters, "bob", 1, yesterday).await;
+
+        let usage = quotas.usage_at(&counters, now).await.unwrap();
+        assert_eq!(
+            usage,
+            [
+                QuotaUsage {
+                    identity: "2001:db8::1".to_string(),
+                    commands_today: 2,
+                    blocked_for: Some(3600),
+                    ..QuotaUsage::default()
+                },
+                QuotaUsage {
+                    identity: "alice".to_string(),
+                    commands_today: 1,
+                    ..QuotaUsage::default()
+                },
+            ]
+        );
+
+        assert_eq!(
+            quotas
+                .reset_at(&counters, "2001:db8::1", now)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            quotas.admit_at(&counters, "2001:db8::1", 1, now).await,
+            QuotaDecision::Allowed {
+                commands: Some(1),
+                bytes: None
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_count_every_command_and_bodies_count_the_bytes_sent() {
+        let config = Config::from_value(json!({"quotas": {
+            "commands_per_day": 5,
+            "bytes_per_day": 100
+        }}))
+        .unwrap();
+        let quotas = Arc::new(Quotas::new(&config).unwrap());
+        let counters = Arc::new(Counters::default());
+        let now = Utc::now();
+        assert_eq!(
+            quotas.admit_at(counters.as_ref(), "alice", 4, now).await,
+            QuotaDecision::Allowed {
+                commands: Some(1),
+                bytes: Some(100)
+            }
+        );
+        assert_eq!(
+            quotas.admit_at(counters.as_ref(), "alice", 2, now).await,
+            QuotaDecision::Exceeded {
+                retry_after: Window::Day.seconds_left(now)
+            }
+        );
+
+        // A streamed body has no exact size; its bytes count as they go out.
+        let chunks = futures::stream::iter(
+            ["abc", "defg"]
+                .map(|chunk| Ok::<_, std::io::Error>(Bytes::from_static(chunk.as_bytes()))),
+        );
+        let body = quotas.meter(
+            counters.clone(),
+            "alice".to_string(),
+            Body::from_stream(chunks),
+        );
+        let sent = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(sent, "abcdefg");
+        let key = quotas.key("alice", Window::Day, now, "bytes");
+        for _ in 0..50 {
+            if counters.0.lock().unwrap().get(&key) == Some(&7) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("the streamed bytes were not recorded");
+    }
+
+    #[test]
+    fn identifies_clients_by_user_name_or_address() {
+        let acl = Acl::new(Some(vec![redis_web_core::config::AclConfig {
+            http_basic_auth: Some("alice:secret".to_string()),
+            ip: None,
+            enabled: None,
+            disabled: None,
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        }]));
+        let identity = |authorization: Option<&str>| {
+            let auth = AuthContext {
+                client_ip: "192.0.2.7".parse().unwrap(),
+                authorization: authorization.map(str::to_string),
+            };
+            Quotas::identity(&acl, &auth)
+        };
+        let basic = |creds: &str| format!("Basic {}", general_purpose::STANDARD.encode(creds));
+        assert_eq!(identity(Some(&basic("alice:secret"))), "alice");
+        // A user name without its password is not trusted.
+        assert_eq!(identity(Some(&basic("alice:guess"))), "192.0.2.7");
+        assert_eq!(identity(Some(&basic("mallory:x"))), "192.0.2.7");
+        assert_eq!(identity(Some("Bearer token")), "192.0.2.7");
+        assert_eq!(identity(None), "192.0.2.7");
+
+        let now = "2026-12-31T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(Window::Day.seconds_left(now), 12 * 3600);
+        assert_eq!(Window::Month.seconds_left(now), 12 * 3600);
+    }
+}
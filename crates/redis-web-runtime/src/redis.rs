@@ -20,56 +20,259 @@
 //!   platform allows.
 //!
 //! UNIX-domain socket connections (`redis_socket`) are unaffected.
+//!
+//! ## Socket options (`redis_tcp`)
+//!
+//! Pool and Pub/Sub TCP/TLS connections set `TCP_NODELAY` unless
+//! `redis_tcp.nodelay` is `false`, and apply `write_timeout_ms` as
+//! `TCP_USER_TIMEOUT` on Linux. `connect_timeout_ms` bounds every connection
+//! attempt (UNIX sockets included) and `read_timeout_ms` bounds replies on
+//! pooled connections. Pub/Sub connections are idle between messages, so they
+//! have no read timeout.
+//!
+//! ## Host failover
+//!
+//! When `redis_host` is a list, the pool connects to the first reachable host
+//! in order. Connections opened before a host switch are discarded on their
+//! next checkout. While a standby is active, checkouts trigger a background
+//! probe of the primary (at most every [`FAILBACK_PROBE_INTERVAL`]) and the pool
+//! fails back once it answers `PING`.
+//!
+//! ## Multiplexed mode
+//!
+//! With `redis_connection_mode: "multiplexed"`, command traffic skips the
+//! checkout pool and pipelines every request over a few shared connections per
+//! database (`multiplexed_connections`, round-robin). Blocking commands such as
+//! `BLPOP` stall every request sharing that connection, so this mode suits
+//! small instances with short commands.
+//!
+//! ## Connection identification
+//!
+//! Every connection announces itself with `CLIENT SETINFO lib-name redis-web`
+//! and the crate version. Pooled command connections are additionally named
+//! `redis-web:<instance>:<purpose>` via `CLIENT SETNAME` so they can be told
+//! apart in `CLIENT LIST`, and Pub/Sub connections of standalone servers as
+//! `redis-web:<instance>:pubsub`. The async Pub/Sub connection type does not
+//! accept arbitrary commands, so [`connect_pubsub`] opens the socket itself
+//! and names it before handing it over.
 
-use deadpool::managed::{CreatePoolError, Object, Pool, PoolConfig, RecycleError, RecycleResult};
+use deadpool::managed::{
+    CreatePoolError, Manager, Object, Pool, PoolConfig, PoolError, RecycleError, RecycleResult,
+    TimeoutType, Timeouts,
+};
 use deadpool::Runtime;
 use redis::aio::ConnectionLike;
 use redis::aio::MultiplexedConnection;
 use redis::io::tcp::{socket2, TcpSettings};
 use redis::{
-    ConnectionAddr, ConnectionInfo, ErrorKind, IntoConnectionInfo, Pipeline, ProtocolVersion,
-    RedisConnectionInfo, RedisFuture, Value,
+    AsyncConnectionConfig, ConnectionAddr, ConnectionInfo, ErrorKind, IntoConnectionInfo, Pipeline,
+    ProtocolVersion, PushInfo, RedisConnectionInfo, RedisFuture, Value,
 };
 use redis_web_core::config::{
-    Config as AppConfig, RedisAuthConfig, DEFAULT_HTTP_THREADS, DEFAULT_POOL_SIZE_PER_THREAD,
+    BackendConfig, Config as AppConfig, RedisAuthConfig, RedisConnectionMode, RedisProtocol,
+    RedisTcpConfig, DEFAULT_MULTIPLEXED_CONNECTIONS, DEFAULT_POOL_SHARDS,
+    DEFAULT_POOL_SIZE_PER_THREAD,
 };
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{broadcast, Mutex as AsyncMutex, RwLock};
+use tracing::{debug, error, info, warn};
 
 #[cfg(unix)]
 use std::os::unix::fs::FileTypeExt;
 
+/// Buffered RESP3 push frames per pool before slow receivers start lagging.
+const PUSH_CHANNEL_CAPACITY: usize = 256;
+
+/// Connection timeout used when `redis_tcp.connect_timeout_ms` is unset.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Minimum delay between fail-back probes of the primary Redis host.
+pub const FAILBACK_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Clone, Debug)]
 #[doc(hidden)]
 pub struct WebdisRedisManager {
+    endpoints: Vec<RedisEndpoint>,
+    client_name: String,
+    failover: Arc<FailoverState>,
+    push_tx: Option<broadcast::Sender<PushInfo>>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+}
+
+#[derive(Clone, Debug)]
+struct RedisEndpoint {
+    label: String,
     client: redis::Client,
 }
 
+/// Tracks which configured host a pool is currently using.
+#[derive(Debug, Default)]
+struct FailoverState {
+    active: AtomicUsize,
+    switched_at: Mutex<Option<Instant>>,
+    probing: AtomicBool,
+}
+
+impl FailoverState {
+    fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    fn switch_to(&self, index: usize) {
+        self.active.store(index, Ordering::Release);
+        *self.switched_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Returns true when the active host changed after `created`.
+    fn switched_since(&self, created: Instant) -> bool {
+        self.switched_at
+            .lock()
+            .unwrap()
+            .is_some_and(|switched_at| switched_at > created)
+    }
+}
+
 impl WebdisRedisManager {
-    fn new(info: ConnectionInfo) -> Result<Self, redis::RedisError> {
+    fn new(config: &AppConfig, infos: Vec<ConnectionInfo>) -> Result<Self, redis::RedisError> {
+        let endpoints = infos
+            .into_iter()
+            .map(|info| {
+                Ok(RedisEndpoint {
+                    label: info.addr().to_string(),
+                    client: redis::Client::open(info)?,
+                })
+            })
+            .collect::<Result<Vec<_>, redis::RedisError>>()?;
+        // RESP3 connections may receive out-of-band push frames (e.g. client-side
+        // caching invalidations). They are fanned out to `push_messages` receivers.
+        let push_tx = (config.redis_protocol == RedisProtocol::Resp3)
+            .then(|| broadcast::channel(PUSH_CHANNEL_CAPACITY).0);
         Ok(Self {
-            client: redis::Client::open(info)?,
+            endpoints,
+            client_name: client_name(config, "pool"),
+            failover: Arc::new(FailoverState::default()),
+            push_tx,
+            connect_timeout: connect_timeout(config),
+            read_timeout: tcp_timeout(config, |tcp| tcp.read_timeout_ms),
         })
     }
+
+    /// Probes the primary host in the background and fails back when it answers.
+    fn spawn_failback_probe(&self) {
+        if self.failover.probing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let primary = self.endpoints[0].clone();
+        let failover = self.failover.clone();
+        tokio::spawn(async move {
+            let healthy = match primary.client.get_multiplexed_async_connection().await {
+                Ok(mut conn) => redis::cmd("PING")
+                    .query_async::<String>(&mut conn)
+                    .await
+                    .is_ok(),
+                Err(_) => false,
+            };
+            if healthy {
+                info!(
+                    "Primary Redis host {} is reachable again; failing back",
+                    primary.label
+                );
+                failover.switch_to(0);
+            } else {
+                tokio::time::sleep(FAILBACK_PROBE_INTERVAL).await;
+            }
+            failover.probing.store(false, Ordering::Release);
+        });
+    }
 }
 
-impl deadpool::managed::Manager for WebdisRedisManager {
+impl Manager for WebdisRedisManager {
     type Type = MultiplexedConnection;
     type Error = redis::RedisError;
 
     async fn create(&self) -> Result<Self::Type, Self::Error> {
         debug!("Creating multiplexed Redis connection");
-        self.client.get_multiplexed_async_connection().await
+        // Command timeouts are enforced per request by the executor, so the
+        // client-level response timeout stays off unless `redis_tcp.read_timeout_ms`
+        // asks for a socket-level limit.
+        let mut config = AsyncConnectionConfig::new()
+            .set_connection_timeout(self.connect_timeout)
+            .set_response_timeout(self.read_timeout);
+        if let Some(push_tx) = &self.push_tx {
+            config = config.set_push_sender(push_tx.clone());
+        }
+        let start = self.failover.active();
+        let mut last_error = None;
+        let mut connected = None;
+        for offset in 0..self.endpoints.len() {
+            let index = (start + offset) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+            match endpoint
+                .client
+                .get_multiplexed_async_connection_with_config(&config)
+                .await
+            {
+                Ok(conn) => {
+                    if index != start {
+                        warn!(
+                            "Redis host {} unreachable; failing over to {}",
+                            self.endpoints[start].label, endpoint.label
+                        );
+                        self.failover.switch_to(index);
+                    }
+                    connected = Some(conn);
+                    break;
+                }
+                Err(e) => {
+                    if self.endpoints.len() > 1 {
+                        warn!("Failed to connect to Redis host {}: {}", endpoint.label, e);
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+        let Some(mut conn) = connected else {
+            return Err(last_error.expect("pool manager has at least one Redis host"));
+        };
+
+        // Naming is best-effort: ACL users without `CLIENT` access must still get a
+        // working connection.
+        if let Err(e) = redis::cmd("CLIENT")
+            .arg("SETNAME")
+            .arg(&self.client_name)
+            .query_async::<()>(&mut conn)
+            .await
+        {
+            warn!(
+                "Failed to set Redis client name {}: {}",
+                self.client_name, e
+            );
+        }
+        Ok(conn)
     }
 
     async fn recycle(
         &self,
         conn: &mut Self::Type,
-        _: &deadpool::managed::Metrics,
+        metrics: &deadpool::managed::Metrics,
     ) -> RecycleResult<Self::Error> {
+        if self.failover.switched_since(metrics.created) {
+            return Err(RecycleError::message(
+                "Redis host changed after failover; discarding connection",
+            ));
+        }
+        if self.failover.active() != 0 {
+            self.spawn_failback_probe();
+        }
+
         // A lightweight health-check to avoid handing out stale connections.
         let pong: String = redis::cmd("PING")
             .query_async(conn)
@@ -95,6 +298,16 @@ impl deadpool::managed::Manager for WebdisRedisManager {
 /// `redis::Cmd::query_async`, mirroring the ergonomics of a Redis-specific pool wrapper.
 pub struct PooledConnection(Object<WebdisRedisManager>);
 
+impl PooledConnection {
+    /// Removes this connection from the pool permanently.
+    ///
+    /// Used when a connection may still carry an in-flight reply (e.g. after a
+    /// command timeout) and must not be recycled for another request.
+    pub fn detach(self) {
+        drop(Object::take(self.0));
+    }
+}
+
 impl From<Object<WebdisRedisManager>> for PooledConnection {
     fn from(obj: Object<WebdisRedisManager>) -> Self {
         Self(obj)
@@ -139,9 +352,255 @@ impl ConnectionLike for PooledConnection {
     }
 }
 
-pub type RedisPool = Pool<WebdisRedisManager, PooledConnection>;
+type PoolShard = Pool<WebdisRedisManager, PooledConnection>;
 pub type RedisCreatePoolError = CreatePoolError<redis::RedisError>;
 
+/// The command pool of one database, split into `pool_shards` sub-pools.
+///
+/// A checkout starts at the calling thread's shard, so worker threads mostly
+/// take connections from their own sub-pool instead of all contending on one.
+/// When that shard has no idle connection and is at capacity, the others are
+/// tried before waiting on it. The shards share one connection manager, and
+/// with it the failover state and RESP3 push channel.
+#[derive(Clone, Debug)]
+pub struct RedisPool {
+    shards: Arc<[PoolShard]>,
+    max_waiting: Option<usize>,
+}
+
+/// Usage of one pool shard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolShardStats {
+    pub max_size: usize,
+    /// Open connections, idle or checked out.
+    pub size: usize,
+    /// Idle connections.
+    pub available: usize,
+    /// Checkouts waiting for a connection.
+    pub waiting: usize,
+}
+
+/// Hands worker threads consecutive numbers, which pick their home shard.
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static THREAD_NUMBER: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+impl RedisPool {
+    /// Checks out a connection, preferring the calling thread's shard.
+    ///
+    /// Fails with a wait timeout after `pool_wait_timeout_ms`, or at once
+    /// when `pool_max_waiting` checkouts are already waiting.
+    pub async fn get(&self) -> Result<PooledConnection, PoolError<redis::RedisError>> {
+        let count = self.shards.len();
+        let home = THREAD_NUMBER.with(|number| number % count);
+        // Idle connections are taken before the queue limit is checked, so
+        // the limit only refuses checkouts that would have to wait.
+        if count > 1 || self.max_waiting.is_some() {
+            let no_wait = Timeouts {
+                wait: Some(Duration::ZERO),
+                ..Timeouts::default()
+            };
+            for offset in 0..count {
+                match self.shards[(home + offset) % count]
+                    .timeout_get(&no_wait)
+                    .await
+                {
+                    Err(PoolError::Timeout(TimeoutType::Wait)) => continue,
+                    result => return result,
+                }
+            }
+        }
+        if let Some(max_waiting) = self.max_waiting {
+            let waiting: usize = self.shards.iter().map(|shard| shard.status().waiting).sum();
+            if waiting >= max_waiting {
+                return Err(PoolError::Timeout(TimeoutType::Wait));
+            }
+        }
+        self.shards[home].get().await
+    }
+
+    /// Usage of each shard, in shard order.
+    pub fn shard_stats(&self) -> Vec<PoolShardStats> {
+        self.shards
+            .iter()
+            .map(|shard| {
+                let status = shard.status();
+                PoolShardStats {
+                    max_size: status.max_size,
+                    size: status.size,
+                    available: status.available,
+                    waiting: status.waiting,
+                }
+            })
+            .collect()
+    }
+
+    fn manager(&self) -> &WebdisRedisManager {
+        self.shards[0].manager()
+    }
+}
+
+/// A few multiplexed connections shared by every request for one database.
+///
+/// Requests are spread round-robin across the slots. A slot connects lazily,
+/// reconnects after a failover switch, and is cleared when a request observes
+/// an unrecoverable error on it.
+pub struct SharedConnections {
+    manager: WebdisRedisManager,
+    slots: Vec<AsyncMutex<Option<SharedSlot>>>,
+    next: AtomicUsize,
+}
+
+#[derive(Clone)]
+struct SharedSlot {
+    connection: MultiplexedConnection,
+    created: Instant,
+}
+
+impl SharedConnections {
+    fn new(manager: WebdisRedisManager, count: usize) -> Self {
+        Self {
+            manager,
+            slots: (0..count.max(1)).map(|_| AsyncMutex::new(None)).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a handle to the next shared connection, connecting it if needed.
+    pub async fn get(self: &Arc<Self>) -> Result<SharedConnection, redis::RedisError> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        let failover = &self.manager.failover;
+        let mut slot = self.slots[index].lock().await;
+        if let Some(current) = slot.as_ref() {
+            if !failover.switched_since(current.created) {
+                if failover.active() != 0 {
+                    self.manager.spawn_failback_probe();
+                }
+                return Ok(self.handle(index, current.clone()));
+            }
+        }
+
+        let current = SharedSlot {
+            connection: self.manager.create().await?,
+            created: Instant::now(),
+        };
+        *slot = Some(current.clone());
+        Ok(self.handle(index, current))
+    }
+
+    fn handle(self: &Arc<Self>, index: usize, slot: SharedSlot) -> SharedConnection {
+        SharedConnection {
+            connection: slot.connection,
+            created: slot.created,
+            index,
+            owner: self.clone(),
+        }
+    }
+}
+
+/// A clone of one of the [`SharedConnections`] slots.
+pub struct SharedConnection {
+    connection: MultiplexedConnection,
+    created: Instant,
+    index: usize,
+    owner: Arc<SharedConnections>,
+}
+
+impl SharedConnection {
+    /// Clears the slot so the next request reconnects, unless it was already replaced.
+    pub async fn invalidate(self) {
+        let mut slot = self.owner.slots[self.index].lock().await;
+        if slot
+            .as_ref()
+            .is_some_and(|current| current.created == self.created)
+        {
+            *slot = None;
+        }
+    }
+}
+
+/// A connection used for one command, from either the pool or the shared set.
+pub enum RedisConnection {
+    Pooled(PooledConnection),
+    Shared(SharedConnection),
+    /// A connection opened for one client session in `multiplexed` mode.
+    Dedicated(MultiplexedConnection),
+}
+
+impl RedisConnection {
+    /// Drops the connection after a timeout so a late reply cannot leak into
+    /// another request.
+    ///
+    /// Shared connections match replies to requests, so they stay usable.
+    pub fn detach(self) {
+        if let Self::Pooled(connection) = self {
+            connection.detach();
+        }
+    }
+
+    /// Discards the connection after an unrecoverable error.
+    pub async fn invalidate(self) {
+        match self {
+            Self::Pooled(connection) => connection.detach(),
+            Self::Shared(connection) => connection.invalidate().await,
+            Self::Dedicated(_) => {}
+        }
+    }
+
+    fn multiplexed(&mut self) -> &mut MultiplexedConnection {
+        match self {
+            Self::Pooled(connection) => connection,
+            Self::Shared(connection) => &mut connection.connection,
+            Self::Dedicated(connection) => connection,
+        }
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> RedisFuture<'a, Value> {
+        self.multiplexed().req_packed_command(cmd)
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        self.multiplexed().req_packed_commands(cmd, offset, count)
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            Self::Pooled(connection) => connection.get_db(),
+            Self::Shared(connection) => connection.connection.get_db(),
+            Self::Dedicated(connection) => connection.get_db(),
+        }
+    }
+}
+
+/// Errors raised while obtaining a connection for a command.
+#[derive(Debug)]
+pub enum RedisConnectionError {
+    CreatePool(RedisCreatePoolError),
+    Checkout(PoolError<redis::RedisError>),
+    Connect(redis::RedisError),
+}
+
+impl std::fmt::Display for RedisConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CreatePool(error) => write!(f, "{error}"),
+            Self::Checkout(error) => write!(f, "{error}"),
+            Self::Connect(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for RedisConnectionError {}
+
 /// Lazily creates and caches Redis pools per logical database index.
 ///
 /// The default database pool is reused as-is, and non-default pools are created
@@ -152,6 +611,7 @@ pub struct DatabasePoolRegistry {
     default_database: u8,
     default_pool: RedisPool,
     pools_by_database: RwLock<HashMap<u8, RedisPool>>,
+    shared_by_database: RwLock<HashMap<u8, Arc<SharedConnections>>>,
 }
 
 impl DatabasePoolRegistry {
@@ -163,6 +623,7 @@ impl DatabasePoolRegistry {
             default_database,
             default_pool,
             pools_by_database: RwLock::new(HashMap::new()),
+            shared_by_database: RwLock::new(HashMap::new()),
         }
     }
 
@@ -184,6 +645,99 @@ impl DatabasePoolRegistry {
         let pooled = pools.entry(database).or_insert_with(|| new_pool.clone());
         Ok(pooled.clone())
     }
+
+    /// Usage of each pool shard by database, for the databases with a pool.
+    /// Empty in `multiplexed` mode, where the pools are not used.
+    pub async fn pool_stats(&self) -> Vec<(u8, Vec<PoolShardStats>)> {
+        if self.base_config.redis_connection_mode != RedisConnectionMode::Pool {
+            return Vec::new();
+        }
+        let mut stats = vec![(self.default_database, self.default_pool.shard_stats())];
+        stats.extend(
+            self.pools_by_database
+                .read()
+                .await
+                .iter()
+                .map(|(&database, pool)| (database, pool.shard_stats())),
+        );
+        stats.sort_by_key(|&(database, _)| database);
+        stats
+    }
+
+    /// Returns a connection for one command on `database`.
+    ///
+    /// Uses a pool checkout in `pool` mode and a shared multiplexed connection in
+    /// `multiplexed` mode.
+    pub async fn connection_for_database(
+        &self,
+        database: u8,
+    ) -> Result<RedisConnection, RedisConnectionError> {
+        if self.base_config.redis_connection_mode == RedisConnectionMode::Pool {
+            let pool = self
+                .pool_for_database(database)
+                .await
+                .map_err(RedisConnectionError::CreatePool)?;
+            let connection = pool.get().await.map_err(RedisConnectionError::Checkout)?;
+            return Ok(RedisConnection::Pooled(connection));
+        }
+
+        let shared = self
+            .shared_for_database(database)
+            .await
+            .map_err(RedisConnectionError::Connect)?;
+        let connection = shared.get().await.map_err(RedisConnectionError::Connect)?;
+        Ok(RedisConnection::Shared(connection))
+    }
+
+    /// Returns a connection one client keeps for a series of commands.
+    ///
+    /// Uses a pool checkout in `pool` mode. In `multiplexed` mode it opens a
+    /// new connection, since a transaction on a shared one would take in other
+    /// requests' commands.
+    pub async fn session_connection_for_database(
+        &self,
+        database: u8,
+    ) -> Result<RedisConnection, RedisConnectionError> {
+        if self.base_config.redis_connection_mode == RedisConnectionMode::Pool {
+            return self.connection_for_database(database).await;
+        }
+        let shared = self
+            .shared_for_database(database)
+            .await
+            .map_err(RedisConnectionError::Connect)?;
+        let connection = shared
+            .manager
+            .create()
+            .await
+            .map_err(RedisConnectionError::Connect)?;
+        Ok(RedisConnection::Dedicated(connection))
+    }
+
+    async fn shared_for_database(
+        &self,
+        database: u8,
+    ) -> Result<Arc<SharedConnections>, redis::RedisError> {
+        {
+            let shared = self.shared_by_database.read().await;
+            if let Some(connections) = shared.get(&database) {
+                return Ok(connections.clone());
+            }
+        }
+
+        let mut db_config = self.base_config.clone();
+        db_config.database = database;
+        let count = db_config
+            .multiplexed_connections
+            .unwrap_or(DEFAULT_MULTIPLEXED_CONNECTIONS);
+        info!(
+            "Creating shared multiplexed Redis connections: endpoint={}, connections={}",
+            redis_endpoint_summary(&db_config),
+            count
+        );
+        let connections = Arc::new(SharedConnections::new(pool_manager(&db_config)?, count));
+        let mut shared = self.shared_by_database.write().await;
+        Ok(shared.entry(database).or_insert(connections).clone())
+    }
 }
 
 /// Creates a Redis connection pool configured for the current Webdis config.
@@ -200,26 +754,79 @@ impl DatabasePoolRegistry {
 /// socket before returning successfully. This makes misconfiguration fail on
 /// startup rather than on the first request.
 pub fn create_pool(config: &AppConfig) -> Result<RedisPool, RedisCreatePoolError> {
-    let info = pool_connection_info(config).map_err(CreatePoolError::Config)?;
-    let manager = WebdisRedisManager::new(info).map_err(CreatePoolError::Config)?;
+    let manager = pool_manager(config).map_err(CreatePoolError::Config)?;
 
     let pool_size = config
         .pool_size_per_thread
         .unwrap_or(DEFAULT_POOL_SIZE_PER_THREAD)
-        * config.http_threads.unwrap_or(DEFAULT_HTTP_THREADS);
+        * config.effective_http_threads();
+    let shard_count = config
+        .pool_shards
+        .unwrap_or(DEFAULT_POOL_SHARDS)
+        .clamp(1, pool_size.max(1));
     info!(
-        "Creating Redis connection pool: endpoint={}, max_size={}",
+        "Creating Redis connection pool: endpoint={}, max_size={}, shards={}",
         redis_endpoint_summary(config),
-        pool_size
+        pool_size,
+        shard_count
     );
 
-    let pool = Pool::builder(manager)
-        .config(PoolConfig::new(pool_size))
-        .runtime(Runtime::Tokio1)
-        .build()
-        .map_err(CreatePoolError::Build)?;
+    // Spread the capacity so the shards add up to `pool_size`.
+    let shards = (0..shard_count)
+        .map(|index| {
+            let shard_size = pool_size / shard_count + usize::from(index < pool_size % shard_count);
+            let mut pool_config = PoolConfig::new(shard_size);
+            pool_config.timeouts.wait = config.pool_wait_timeout_ms.map(Duration::from_millis);
+            Pool::builder(manager.clone())
+                .config(pool_config)
+                .runtime(Runtime::Tokio1)
+                .build()
+                .map_err(CreatePoolError::Build)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    Ok(pool)
+    Ok(RedisPool {
+        shards: shards.into(),
+        max_waiting: config.pool_max_waiting,
+    })
+}
+
+fn pool_manager(config: &AppConfig) -> Result<WebdisRedisManager, redis::RedisError> {
+    WebdisRedisManager::new(config, failover_connection_infos(config)?)
+}
+
+/// Resolves the connection timeout from `redis_tcp.connect_timeout_ms`.
+///
+/// Unset falls back to [`DEFAULT_CONNECT_TIMEOUT`]; `0` disables the limit.
+pub fn connect_timeout(config: &AppConfig) -> Option<Duration> {
+    match config
+        .redis_tcp
+        .as_ref()
+        .and_then(|tcp| tcp.connect_timeout_ms)
+    {
+        None => Some(DEFAULT_CONNECT_TIMEOUT),
+        Some(_) => tcp_timeout(config, |tcp| tcp.connect_timeout_ms),
+    }
+}
+
+/// Reads an optional millisecond setting from `redis_tcp`; `0` means no limit.
+fn tcp_timeout(config: &AppConfig, field: fn(&RedisTcpConfig) -> Option<u64>) -> Option<Duration> {
+    config
+        .redis_tcp
+        .as_ref()
+        .and_then(field)
+        .filter(|&ms| ms > 0)
+        .map(Duration::from_millis)
+}
+
+/// Subscribes to RESP3 push frames received on any connection of `pool`.
+///
+/// Returns `None` when the pool negotiates RESP2, which has no push frames.
+pub fn push_messages(pool: &RedisPool) -> Option<broadcast::Receiver<PushInfo>> {
+    pool.manager()
+        .push_tx
+        .as_ref()
+        .map(broadcast::Sender::subscribe)
 }
 
 /// Creates a Redis pool bound to a specific logical database index.
@@ -235,6 +842,21 @@ pub fn create_pool_for_database(
     create_pool(&db_config)
 }
 
+/// Derives the connection config for a named backend.
+///
+/// Address, database, and credentials come from the backend entry; TLS,
+/// keep-alive, pool sizing, and naming are inherited from `base`.
+pub fn backend_config(base: &AppConfig, backend: &BackendConfig) -> AppConfig {
+    let mut config = base.clone();
+    config.redis_host = backend.redis_host.clone();
+    config.redis_hosts = Vec::new();
+    config.redis_port = backend.redis_port;
+    config.redis_socket = backend.redis_socket.clone();
+    config.database = backend.database;
+    config.redis_auth = backend.redis_auth.clone();
+    config
+}
+
 /// Creates a dedicated Redis client for Pub/Sub subscriptions.
 ///
 /// This is separate from the pool because Pub/Sub uses long-lived connections.
@@ -250,7 +872,7 @@ pub fn create_pubsub_client(config: &AppConfig) -> Result<redis::Client, redis::
             socket, config.database
         );
         let info = connection_info_for_unix_socket_redis(config, socket)?;
-        redis::Client::open(info)
+        redis::Client::open(with_resp2(info))
     } else {
         info!(
             "Creating Redis pub/sub client over TCP/TLS: {}:{} (db={}, tls={})",
@@ -259,8 +881,132 @@ pub fn create_pubsub_client(config: &AppConfig) -> Result<redis::Client, redis::
             config.database,
             ssl_enabled(config)
         );
-        redis::Client::open(config.get_redis_url())
+        let info = config.get_redis_url().into_connection_info()?;
+        let redis_settings = with_lib_info(info.redis_settings().clone());
+        let info = apply_socket_options(config, info.set_redis_settings(redis_settings));
+        redis::Client::open(info)
+    }
+}
+
+/// Creates the RESP3 client used for sharded Pub/Sub (`SSUBSCRIBE`).
+///
+/// Sharded messages arrive as RESP3 push frames on a multiplexed connection,
+/// so this client always negotiates RESP3 regardless of `redis_protocol`.
+pub fn create_sharded_pubsub_client(
+    config: &AppConfig,
+) -> Result<redis::Client, redis::RedisError> {
+    let info = if let Some(socket) = config.redis_socket.as_deref() {
+        connection_info_for_unix_socket_redis(config, socket)?
+    } else {
+        let info = config.get_redis_url().into_connection_info()?;
+        let redis_settings = with_lib_info(info.redis_settings().clone());
+        apply_socket_options(config, info.set_redis_settings(redis_settings))
+    };
+    let redis_settings = info
+        .redis_settings()
+        .clone()
+        .set_protocol(ProtocolVersion::RESP3);
+    redis::Client::open(info.set_redis_settings(redis_settings))
+}
+
+/// Opens a Pub/Sub connection through `client`, named `name`, when given, via
+/// `CLIENT SETNAME`.
+///
+/// `aio::PubSub` only sends subscription commands, so the socket is opened
+/// here, authenticated and named, then set up again by `aio::PubSub`. As for
+/// pooled connections, naming is best-effort. Addresses this cannot open
+/// itself, such as TLS with custom certificates, connect unnamed.
+pub async fn connect_pubsub(
+    client: &redis::Client,
+    name: Option<&str>,
+) -> Result<redis::aio::PubSub, redis::RedisError> {
+    let Some(name) = name else {
+        return client.get_async_pubsub().await;
+    };
+    let info = client.get_connection_info();
+    let mut stream: Pin<Box<dyn redis::aio::AsyncStream + Send + Sync>> = match info.addr() {
+        ConnectionAddr::Tcp(host, port) => {
+            Box::pin(connect_tcp(host, *port, info.tcp_settings()).await?)
+        }
+        ConnectionAddr::TcpTls {
+            host,
+            port,
+            insecure,
+            tls_params: None,
+        } => {
+            let tcp = connect_tcp(host, *port, info.tcp_settings()).await?;
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(*insecure)
+                .danger_accept_invalid_hostnames(*insecure)
+                .build()?;
+            Box::pin(
+                tokio_native_tls::TlsConnector::from(connector)
+                    .connect(host, tcp)
+                    .await?,
+            )
+        }
+        #[cfg(unix)]
+        ConnectionAddr::Unix(path) => Box::pin(tokio::net::UnixStream::connect(path).await?),
+        _ => return client.get_async_pubsub().await,
+    };
+
+    let settings = info.redis_settings();
+    let mut handshake = redis::pipe();
+    if let Some(password) = settings.password() {
+        handshake.cmd("AUTH");
+        if let Some(username) = settings.username() {
+            handshake.arg(username);
+        }
+        handshake.arg(password);
+    }
+    handshake.cmd("CLIENT").arg("SETNAME").arg(name);
+    stream.write_all(&handshake.get_packed_pipeline()).await?;
+    // Both replies are single lines: a status or an error.
+    let mut reply = Vec::new();
+    for _ in 0..handshake.len() {
+        reply.clear();
+        while !reply.ends_with(b"\r\n") {
+            reply.push(stream.read_u8().await?);
+        }
+    }
+    if reply.starts_with(b"-") {
+        warn!(
+            "Failed to set Redis client name {}: {}",
+            name,
+            String::from_utf8_lossy(&reply[1..]).trim_end()
+        );
     }
+    redis::aio::PubSub::new(settings, stream).await
+}
+
+/// Connects to `host:port` with the socket options of `settings`.
+async fn connect_tcp(
+    host: &str,
+    port: u16,
+    settings: &TcpSettings,
+) -> std::io::Result<tokio::net::TcpStream> {
+    let stream = tokio::net::TcpStream::connect((host, port)).await?;
+    stream.set_nodelay(settings.nodelay())?;
+    let socket = socket2::SockRef::from(&stream);
+    if let Some(keepalive) = settings.keepalive() {
+        socket.set_tcp_keepalive(keepalive)?;
+    }
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    socket.set_tcp_user_timeout(settings.user_timeout())?;
+    Ok(stream)
+}
+
+/// Creates one Pub/Sub client per `redis_host` candidate, in failover order.
+pub fn create_pubsub_clients(config: &AppConfig) -> Result<Vec<redis::Client>, redis::RedisError> {
+    if config.redis_socket.is_some() {
+        return Ok(vec![create_pubsub_client(config)?]);
+    }
+
+    config
+        .redis_host_candidates()
+        .into_iter()
+        .map(|candidate| create_pubsub_client(&host_config(config, candidate)))
+        .collect()
 }
 
 /// Creates a Redis client using the same address/credential precedence as the pool.
@@ -268,15 +1014,109 @@ pub fn create_pubsub_client(config: &AppConfig) -> Result<redis::Client, redis::
 /// This is used by compatibility session paths that need dedicated, stateful
 /// connections instead of pooled multiplexed connections.
 pub fn create_client(config: &AppConfig) -> Result<redis::Client, redis::RedisError> {
+    // Compat sessions relay replies to hiredis clients that expect RESP2 unless
+    // they negotiate RESP3 themselves.
     if let Some(socket) = config.redis_socket.as_deref() {
         let info = connection_info_for_unix_socket_redis(config, socket)?;
-        redis::Client::open(info)
+        redis::Client::open(with_resp2(info))
     } else {
         let info = pool_connection_info(config)?;
-        redis::Client::open(info)
+        redis::Client::open(with_resp2(info))
     }
 }
 
+fn with_resp2(info: ConnectionInfo) -> ConnectionInfo {
+    let redis_settings = info
+        .redis_settings()
+        .clone()
+        .set_protocol(ProtocolVersion::RESP2);
+    info.set_redis_settings(redis_settings)
+}
+
+pub(crate) fn protocol_version(config: &AppConfig) -> ProtocolVersion {
+    match config.redis_protocol {
+        RedisProtocol::Resp2 => ProtocolVersion::RESP2,
+        RedisProtocol::Resp3 => ProtocolVersion::RESP3,
+    }
+}
+
+/// Builds the `CLIENT SETNAME` value for connections opened for `purpose`.
+///
+/// The instance part comes from `instance_name`, falling back to the host name
+/// and finally the process id. Redis rejects names containing spaces or
+/// newlines, so any whitespace or control character is replaced with `-`.
+pub fn client_name(config: &AppConfig, purpose: &str) -> String {
+    let instance = config
+        .instance_name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .or_else(local_hostname)
+        .unwrap_or_else(|| format!("pid{}", std::process::id()));
+    let sanitized: String = instance
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c.is_control() {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("redis-web:{sanitized}:{purpose}")
+}
+
+fn local_hostname() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+fn with_lib_info(settings: RedisConnectionInfo) -> RedisConnectionInfo {
+    settings.set_lib_name("redis-web", env!("CARGO_PKG_VERSION"))
+}
+
+/// Builds one connection info per `redis_host` candidate, in failover order.
+///
+/// UNIX sockets take precedence over the host list, so a configured
+/// `redis_socket` always yields a single endpoint.
+fn failover_connection_infos(config: &AppConfig) -> Result<Vec<ConnectionInfo>, redis::RedisError> {
+    if config.redis_socket.is_some() {
+        return Ok(vec![pool_connection_info(config)?]);
+    }
+
+    config
+        .redis_host_candidates()
+        .into_iter()
+        .map(|candidate| pool_connection_info(&host_config(config, candidate)))
+        .collect()
+}
+
+/// Returns a copy of `config` targeting one failover host entry (`host` or `host:port`).
+fn host_config(config: &AppConfig, candidate: &str) -> AppConfig {
+    let mut host_config = config.clone();
+    let (host, port) = split_host_port(candidate, config.redis_port);
+    host_config.redis_host = host.to_string();
+    host_config.redis_port = port;
+    host_config.redis_hosts = Vec::new();
+    host_config
+}
+
+/// Splits `host:port`, leaving bare IPv6 addresses and hosts without a port intact.
+fn split_host_port(candidate: &str, default_port: u16) -> (&str, u16) {
+    if let Some((host, port)) = candidate.rsplit_once(':') {
+        let bracketed = host.starts_with('[') && host.ends_with(']');
+        if bracketed || !host.contains(':') {
+            if let Ok(port) = port.parse() {
+                return (host, port);
+            }
+        }
+    }
+    (candidate, default_port)
+}
+
 fn ssl_enabled(config: &AppConfig) -> bool {
     config.ssl.as_ref().map(|ssl| ssl.enabled).unwrap_or(false)
 }
@@ -317,17 +1157,39 @@ fn pool_connection_info(config: &AppConfig) -> Result<ConnectionInfo, redis::Red
     } else {
         let mut info = config.get_redis_url().into_connection_info()?;
 
-        // Webdis assumes RESP2 everywhere else; keep the default explicit.
-        let redis_settings = info
-            .redis_settings()
-            .clone()
-            .set_protocol(ProtocolVersion::RESP2);
+        // RESP2 unless `redis_protocol` opts into RESP3; keep the choice explicit.
+        let redis_settings =
+            with_lib_info(info.redis_settings().clone()).set_protocol(protocol_version(config));
         info = info.set_redis_settings(redis_settings);
 
+        let info = apply_socket_options(config, info);
         maybe_apply_tcp_keepalive(config, info)
     }
 }
 
+/// Applies `redis_tcp.nodelay` and `redis_tcp.write_timeout_ms` to TCP/TLS connections.
+fn apply_socket_options(config: &AppConfig, info: ConnectionInfo) -> ConnectionInfo {
+    if !matches!(
+        info.addr(),
+        ConnectionAddr::Tcp(_, _) | ConnectionAddr::TcpTls { .. }
+    ) {
+        return info;
+    }
+
+    let nodelay = config
+        .redis_tcp
+        .as_ref()
+        .and_then(|tcp| tcp.nodelay)
+        .unwrap_or(true);
+    let tcp_settings = info.tcp_settings().clone().set_nodelay(nodelay);
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    let tcp_settings = match tcp_timeout(config, |tcp| tcp.write_timeout_ms) {
+        Some(timeout) => tcp_settings.set_user_timeout(timeout),
+        None => tcp_settings,
+    };
+    info.set_tcp_settings(tcp_settings)
+}
+
 fn connection_info_for_unix_socket_redis(
     config: &AppConfig,
     socket_path: &str,
@@ -340,9 +1202,9 @@ fn connection_info_for_unix_socket_redis(
     validate_unix_socket_path(&socket_path)?;
 
     let (username, password) = redis_username_password(config);
-    let mut redis_settings = RedisConnectionInfo::default()
+    let mut redis_settings = with_lib_info(RedisConnectionInfo::default())
         .set_db(i64::from(config.database))
-        .set_protocol(ProtocolVersion::RESP2);
+        .set_protocol(protocol_version(config));
     if let Some(u) = username {
         redis_settings = redis_settings.set_username(u);
     }
@@ -436,6 +1298,13 @@ fn maybe_apply_tcp_keepalive(
 fn redis_endpoint_summary(config: &AppConfig) -> String {
     if let Some(socket) = config.redis_socket.as_deref() {
         format!("unix:{} db={}", socket, config.database)
+    } else if !config.redis_hosts.is_empty() {
+        format!(
+            "tcp:[{}] db={} tls={}",
+            config.redis_hosts.join(","),
+            config.database,
+            ssl_enabled(config)
+        )
     } else {
         format!(
             "tcp:{}:{} db={} tls={}",
@@ -461,7 +1330,7 @@ fn keepalive_time_and_interval(keep_alive_sec: u64) -> (Duration, Duration) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use redis_web_core::config::HiRedisConfig;
+    use redis_web_core::config::{HiRedisConfig, RuntimeFlavor};
 
     #[test]
     fn test_keepalive_time_and_interval_derivation() {
@@ -491,6 +1360,142 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_split_host_port_handles_ports_and_ipv6() {
+        assert_eq!(split_host_port("redis-b:6380", 6379), ("redis-b", 6380));
+        assert_eq!(split_host_port("redis-a", 6379), ("redis-a", 6379));
+        assert_eq!(split_host_port("::1", 6379), ("::1", 6379));
+        assert_eq!(split_host_port("[::1]:6381", 6379), ("[::1]", 6381));
+    }
+
+    #[test]
+    fn test_failover_connection_infos_follow_host_order() {
+        let mut config = AppConfig::default();
+        config.redis_hosts = vec!["redis-a".to_string(), "redis-b:6380".to_string()];
+        let infos = failover_connection_infos(&config).unwrap();
+        let labels: Vec<String> = infos.iter().map(|info| info.addr().to_string()).collect();
+        assert_eq!(labels, vec!["redis-a:6379", "redis-b:6380"]);
+    }
+
+    #[test]
+    fn test_pool_shards_split_the_capacity() {
+        let mut config = AppConfig::default();
+        config.pool_size_per_thread = Some(5);
+        config.http_threads = Some(2);
+        let capacities = |config: &AppConfig| -> Vec<usize> {
+            create_pool(config)
+                .unwrap()
+                .shard_stats()
+                .iter()
+                .map(|shard| shard.max_size)
+                .collect()
+        };
+        assert_eq!(capacities(&config), vec![10]);
+
+        config.pool_shards = Some(3);
+        assert_eq!(capacities(&config), vec![4, 3, 3]);
+
+        config.pool_shards = Some(64);
+        assert_eq!(capacities(&config), vec![1; 10]);
+
+        // A current-thread runtime sizes for one thread unless told otherwise.
+        config.pool_shards = None;
+        config.http_threads = None;
+        config.runtime_flavor = RuntimeFlavor::CurrentThread;
+        assert_eq!(capacities(&config), vec![5]);
+        config.http_threads = Some(2);
+        assert_eq!(capacities(&config), vec![10]);
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_connections_authenticate_then_name_themselves() {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        // Answers `+OK` to every command and hands back the lines it read.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let mut seen = Vec::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.starts_with('*') {
+                    write.write_all(b"+OK\r\n").await.unwrap();
+                } else if !line.starts_with('$') {
+                    seen.push(line);
+                }
+            }
+            seen
+        });
+
+        let client = redis::Client::open(format!("redis://:secret@127.0.0.1:{port}/0")).unwrap();
+        let pubsub = connect_pubsub(&client, Some("redis-web:edge-01:pubsub"))
+            .await
+            .unwrap();
+        drop(pubsub);
+        let seen = server.await.unwrap();
+        assert_eq!(
+            seen[..5],
+            [
+                "AUTH",
+                "secret",
+                "CLIENT",
+                "SETNAME",
+                "redis-web:edge-01:pubsub"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failover_state_tracks_switch_time() {
+        let state = FailoverState::default();
+        let before = Instant::now();
+        assert!(!state.switched_since(before));
+        state.switch_to(1);
+        assert_eq!(state.active(), 1);
+        assert!(state.switched_since(before));
+        assert!(!state.switched_since(Instant::now()));
+    }
+
+    #[test]
+    fn test_client_name_uses_sanitized_instance_name() {
+        let mut config = AppConfig::default();
+        config.instance_name = Some(" edge 01\n".to_string());
+        assert_eq!(client_name(&config, "pool"), "redis-web:edge-01:pool");
+
+        config.instance_name = Some("   ".to_string());
+        let fallback = client_name(&config, "pubsub");
+        assert!(fallback.starts_with("redis-web:"));
+        assert!(fallback.ends_with(":pubsub"));
+        assert!(!fallback.contains(' '));
+    }
+
+    #[test]
+    fn test_socket_options_default_to_nodelay() {
+        let mut config = AppConfig::default();
+        let tcp_info = "redis://127.0.0.1:6379/0".into_connection_info().unwrap();
+        assert!(apply_socket_options(&config, tcp_info.clone())
+            .tcp_settings()
+            .nodelay());
+        assert_eq!(connect_timeout(&config), Some(DEFAULT_CONNECT_TIMEOUT));
+
+        config.redis_tcp = Some(RedisTcpConfig {
+            nodelay: Some(false),
+            connect_timeout_ms: Some(0),
+            read_timeout_ms: None,
+            write_timeout_ms: Some(2_000),
+        });
+        let tcp_info = apply_socket_options(&config, tcp_info);
+        assert!(!tcp_info.tcp_settings().nodelay());
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            tcp_info.tcp_settings().user_timeout(),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(connect_timeout(&config), None);
+    }
+
     #[test]
     fn test_maybe_apply_tcp_keepalive_only_for_tcp_addrs() {
         let mut config = AppConfig::default();
@@ -0,0 +1,278 @@
+//! Pub/Sub message ids and `Last-Event-ID` replay.
+//!
+//! When `pubsub_replay.enabled` is set, every message fanned out by
+//! [`crate::pubsub::PubSubManager`] gets an id that SSE streams send as the
+//! event `id:`. A client that reconnects with `Last-Event-ID` first receives
+//! the messages it missed, then live delivery resumes.
+//!
+//! History is kept in memory per channel or pattern. With
+//! `redis_stream_prefix`, messages are also mirrored into capped Redis Streams
+//! (`XADD ... MAXLEN ~`) and the stream entry id becomes the event id, so
+//! replay survives restarts of redis-web.
+
+use crate::pubsub::{PubSubMessage, Topic};
+use redis::Value;
+use redis_web_core::config::{
+    Config as AppConfig, DEFAULT_PUBSUB_REPLAY_HISTORY_SIZE, DEFAULT_PUBSUB_REPLAY_RETENTION_MS,
+};
+use redis_web_core::interfaces::{CommandExecutor, ExecutableCommand};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Replay policy shared by the Pub/Sub loops and the SSE handlers.
+pub struct ReplaySettings {
+    pub history_size: usize,
+    /// How long a topic stays subscribed after its last local subscriber leaves.
+    pub retention: Duration,
+    pub stream: Option<StreamMirror>,
+    next_id: AtomicU64,
+}
+
+/// Mirrors messages into capped Redis Streams through the command executor.
+pub struct StreamMirror {
+    pub prefix: String,
+    pub executor: Arc<dyn CommandExecutor>,
+    pub database: u8,
+}
+
+impl ReplaySettings {
+    /// Builds settings from `pubsub_replay`; returns `None` when replay is disabled.
+    pub fn from_config(config: &AppConfig, executor: Arc<dyn CommandExecutor>) -> Option<Self> {
+        let replay = config
+            .pubsub_replay
+            .as_ref()
+            .filter(|replay| replay.enabled)?;
+        let stream = replay
+            .redis_stream_prefix
+            .clone()
+            .map(|prefix| StreamMirror {
+                prefix,
+                executor,
+                database: config.database,
+            });
+        Some(Self::new(
+            replay
+                .history_size
+                .unwrap_or(DEFAULT_PUBSUB_REPLAY_HISTORY_SIZE)
+                .max(1),
+            Duration::from_millis(
+                replay
+                    .retention_ms
+                    .unwrap_or(DEFAULT_PUBSUB_REPLAY_RETENTION_MS),
+            ),
+            stream,
+        ))
+    }
+
+    pub fn new(history_size: usize, retention: Duration, stream: Option<StreamMirror>) -> Self {
+        // Seeding from the clock keeps in-memory ids increasing across restarts.
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            history_size,
+            retention,
+            stream,
+            next_id: AtomicU64::new(seed),
+        }
+    }
+
+    /// Assigns the next event id to `message`, mirroring it first when a
+    /// stream is configured. A failed mirror write leaves the message without
+    /// an id rather than delaying delivery.
+    pub async fn assign_id(&self, topic: &Topic, message: &mut PubSubMessage) {
+        message.id = match &self.stream {
+            Some(stream) => stream.append(topic, message, self.history_size).await,
+            None => Some(self.next_id.fetch_add(1, Ordering::Relaxed).to_string()),
+        };
+    }
+
+    /// Records `message` in the in-memory history of its topic.
+    ///
+    /// Mirrored messages are read back from the stream instead.
+    pub fn record(&self, history: &mut VecDeque<PubSubMessage>, message: &PubSubMessage) {
+        if self.stream.is_some() || message.id.is_none() {
+            return;
+        }
+        if history.len() == self.history_size {
+            history.pop_front();
+        }
+        history.push_back(message.clone());
+    }
+
+    /// Reads mirrored messages of `topic` published after `last_event_id`.
+    pub async fn stream_since(&self, topic: &Topic, last_event_id: &str) -> Vec<PubSubMessage> {
+        match &self.stream {
+            Some(stream) => stream.range_after(topic, last_event_id).await,
+            None => Vec::new(),
+        }
+    }
+}
+
+impl StreamMirror {
+    fn key(&self, topic: &Topic) -> String {
+        format!("{}{}:{}", self.prefix, topic.kind(), topic.name())
+    }
+
+    async fn append(
+        &self,
+        topic: &Topic,
+        message: &PubSubMessage,
+        max_len: usize,
+    ) -> Option<String> {
+        let key = self.key(topic);
+        let max_len = max_len.to_string();
+        let args = [
+            key.as_str(),
+            "MAXLEN",
+            "~",
+            &max_len,
+            "*",
+            "channel",
+            &message.channel,
+            "payload",
+            &message.payload,
+        ];
+        match self.execute("XADD", &args).await {
+            Some(Value::BulkString(id)) => String::from_utf8(id).ok(),
+            Some(Value::SimpleString(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    async fn range_after(&self, topic: &Topic, last_event_id: &str) -> Vec<PubSubMessage> {
+        let start = format!("({last_event_id}");
+        let key = self.key(topic);
+        let Some(Value::Array(entries)) = self.execute("XRANGE", &[&key, &start, "+"]).await else {
+            return Vec::new();
+        };
+        entries
+            .into_iter()
+            .filter_map(|entry| stream_entry_message(topic, entry))
+            .collect()
+    }
+
+    async fn execute(&self, command: &str, args: &[&str]) -> Option<Value> {
+        let request = ExecutableCommand {
+            backend: None,
+            target_database: self.database,
+            command_name: command.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        };
+        match self.executor.execute(&request).await {
+            Ok(value) => Some(value),
+            Err(error) => {
+                warn!("Pub/Sub replay {} failed: {}", command, error);
+                None
+            }
+        }
+    }
+}
+
+/// Converts an `XRANGE` entry (`[id, [field, value, ...]]`) back into a message.
+fn stream_entry_message(topic: &Topic, entry: Value) -> Option<PubSubMessage> {
+    let Value::Array(mut parts) = entry else {
+        return None;
+    };
+    let Value::Array(fields) = parts.pop()? else {
+        return None;
+    };
+    let id = bulk_string(parts.pop()?)?;
+    let mut channel = None;
+    let mut payload = None;
+    let mut fields = fields.into_iter();
+    while let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+        match bulk_string(name)?.as_str() {
+            "channel" => channel = bulk_string(value),
+            "payload" => payload = bulk_string(value),
+            _ => {}
+        }
+    }
+    Some(PubSubMessage {
+        channel: channel?,
+        payload: payload?,
+        pattern: match topic {
+            Topic::Pattern(pattern) => Some(pattern.clone()),
+            _ => None,
+        },
+        sharded: matches!(topic, Topic::Shard(_)),
+        id: Some(id),
+    })
+}
+
+fn bulk_string(value: Value) -> Option<String> {
+    match value {
+        Value::BulkString(bytes) => String::from_utf8(bytes).ok(),
+        Value::SimpleString(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Parses an event id into a comparable key.
+///
+/// In-memory ids are plain counters (`"42"`) and stream ids are
+/// `<ms>-<seq>`; both order correctly as `(major, minor)` pairs.
+pub fn parse_event_id(id: &str) -> Option<(u64, u64)> {
+    match id.split_once('-') {
+        Some((major, minor)) => Some((major.parse().ok()?, minor.parse().ok()?)),
+        None => Some((id.parse().ok()?, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str) -> PubSubMessage {
+        PubSubMessage {
+            channel: "news".to_string(),
+            payload: "hello".to_string(),
+            pattern: None,
+            sharded: false,
+            id: Some(id.to_string()),
+        }
+    }
+
+    #[test]
+    fn event_ids_order_counters_and_stream_ids() {
+        assert!(parse_event_id("9") < parse_event_id("10"));
+        assert!(parse_event_id("1700000000000-1") > parse_event_id("1700000000000-0"));
+        assert_eq!(parse_event_id("abc"), None);
+        assert_eq!(parse_event_id("1-x"), None);
+    }
+
+    #[test]
+    fn in_memory_history_is_capped() {
+        let settings = ReplaySettings::new(2, Duration::ZERO, None);
+        let mut history = VecDeque::new();
+        for id in ["1", "2", "3"] {
+            settings.record(&mut history, &message(id));
+        }
+        let ids: Vec<_> = history.iter().map(|m| m.id.as_deref().unwrap()).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[test]
+    fn stream_entries_map_back_to_messages() {
+        let entry = Value::Array(vec![
+            Value::BulkString(b"5-0".to_vec()),
+            Value::Array(vec![
+                Value::BulkString(b"channel".to_vec()),
+                Value::BulkString(b"news.sports".to_vec()),
+                Value::BulkString(b"payload".to_vec()),
+                Value::BulkString(b"goal".to_vec()),
+            ]),
+        ]);
+        let message = stream_entry_message(&Topic::Pattern("news.*".to_string()), entry).unwrap();
+        assert_eq!(message.channel, "news.sports");
+        assert_eq!(message.payload, "goal");
+        assert_eq!(message.pattern.as_deref(), Some("news.*"));
+        assert_eq!(message.id.as_deref(), Some("5-0"));
+    }
+}
@@ -0,0 +1,159 @@
+//! Fast path for JSON replies to simple values.
+//!
+//! Most replies on GET-heavy workloads are `+OK`, integers, nil or short bulk
+//! strings. For those the body `{"<command>":<value>}` is written straight
+//! into a per-thread buffer instead of building a `serde_json::Value` tree and
+//! serializing it into a fresh `String`. Each body is a frozen slice of the
+//! buffer's allocation, which is reused once the earlier responses have been
+//! sent, so a steady stream of small replies does not allocate per request.
+//! The bytes are the same as the general path produces.
+
+use bytes::{BufMut, Bytes, BytesMut};
+use redis::Value as RedisValue;
+use redis_web_core::config::JsonNil;
+use std::cell::RefCell;
+use std::io::Write;
+use std::ops::Range;
+
+/// Size of each thread's buffer.
+const BUFFER_CAPACITY: usize = 64 * 1024;
+/// Longest bulk string taken by the fast path.
+const MAX_BULK_LEN: usize = 1024;
+
+thread_local! {
+    static BUFFER: RefCell<BytesMut> = RefCell::new(BytesMut::with_capacity(BUFFER_CAPACITY));
+}
+
+/// A JSON or JSONP reply body written by [`encode`].
+pub(crate) struct SimpleReply {
+    pub body: Bytes,
+    /// Position of the reply value's JSON within `body`, hashed for ETags.
+    value: Range<usize>,
+}
+
+impl SimpleReply {
+    pub fn value_json(&self) -> &[u8] {
+        &self.body[self.value.clone()]
+    }
+}
+
+/// Writes the reply body for `value` when it is simple enough for the fast
+/// path; `None` leaves the reply to the general formatter.
+pub(crate) fn encode(
+    command: &str,
+    value: &RedisValue,
+    jsonp: Option<&str>,
+    nil: JsonNil,
+) -> Option<SimpleReply> {
+    let is_simple = match value {
+        RedisValue::Nil => nil != JsonNil::Omit,
+        RedisValue::Okay | RedisValue::Int(_) | RedisValue::SimpleString(_) => true,
+        RedisValue::BulkString(bytes) => bytes.len() <= MAX_BULK_LEN,
+        _ => false,
+    };
+    if !is_simple {
+        return None;
+    }
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        // Reclaims the allocation when every body split off it has been
+        // dropped, and allocates a fresh one otherwise.
+        buffer.reserve(BUFFER_CAPACITY / 2);
+        match write(&mut buffer, command, value, jsonp, nil) {
+            Ok(value) => Some(SimpleReply {
+                body: buffer.split().freeze(),
+                value,
+            }),
+            Err(_) => {
+                buffer.clear();
+                None
+            }
+        }
+    })
+}
+
+/// Appends the body to `buffer` and returns the position of the value.
+fn write(
+    buffer: &mut BytesMut,
+    command: &str,
+    value: &RedisValue,
+    jsonp: Option<&str>,
+    nil: JsonNil,
+) -> std::io::Result<Range<usize>> {
+    let mut writer = buffer.writer();
+    if let Some(callback) = jsonp {
+        writer.write_all(callback.as_bytes())?;
+        writer.write_all(b"(")?;
+    }
+    writer.write_all(b"{")?;
+    serde_json::to_writer(&mut writer, command)?;
+    writer.write_all(b":")?;
+    let start = writer.get_ref().len();
+    match value {
+        RedisValue::Okay => writer.write_all(b"\"OK\"")?,
+        RedisValue::Nil if nil == JsonNil::False => writer.write_all(b"false")?,
+        RedisValue::Nil => writer.write_all(b"null")?,
+        RedisValue::Int(number) => serde_json::to_writer(&mut writer, number)?,
+        RedisValue::SimpleString(text) => serde_json::to_writer(&mut writer, text)?,
+        RedisValue::BulkString(bytes) => {
+            serde_json::to_writer(&mut writer, &String::from_utf8_lossy(bytes))?
+        }
+        _ => unreachable!("only simple values are encoded"),
+    }
+    let end = writer.get_ref().len();
+    writer.write_all(b"}")?;
+    if jsonp.is_some() {
+        writer.write_all(b")")?;
+    }
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::{redis_value_to_json, redis_value_to_json_with_nil};
+    use serde_json::json;
+
+    #[test]
+    fn matches_the_general_formatter() {
+        let values = [
+            RedisValue::Okay,
+            RedisValue::Nil,
+            RedisValue::Int(-42),
+            RedisValue::SimpleString("PONG".to_string()),
+            RedisValue::BulkString(b"line\n\"quoted\" caf\xc3\xa9 \x01".to_vec()),
+            RedisValue::BulkString(vec![0xff, b'a']),
+        ];
+        for value in values {
+            let reply = encode("GET", &value, None, JsonNil::Null).unwrap();
+            let json = redis_value_to_json(value.clone());
+            assert_eq!(reply.body, json!({"GET": json}).to_string().as_bytes());
+            assert_eq!(reply.value_json(), json.to_string().as_bytes());
+
+            let reply = encode("GET", &value, Some("cb"), JsonNil::Null).unwrap();
+            assert_eq!(
+                reply.body,
+                format!("cb({})", json!({"GET": json})).as_bytes()
+            );
+
+            let reply = encode("GET", &value, None, JsonNil::False).unwrap();
+            let json = redis_value_to_json_with_nil(value.clone(), JsonNil::False);
+            assert_eq!(reply.body, json!({"GET": json}).to_string().as_bytes());
+        }
+    }
+
+    #[test]
+    fn leaves_other_replies_to_the_general_path() {
+        assert!(encode(
+            "GET",
+            &RedisValue::BulkString(vec![b'a'; MAX_BULK_LEN + 1]),
+            None,
+            JsonNil::Null
+        )
+        .is_none());
+        let nil = JsonNil::Null;
+        assert!(encode("MGET", &RedisValue::Array(vec![RedisValue::Nil]), None, nil).is_none());
+        assert!(encode("HELLO", &RedisValue::Map(Vec::new()), None, nil).is_none());
+        assert!(encode("GET", &RedisValue::Nil, None, JsonNil::Omit).is_none());
+    }
+}
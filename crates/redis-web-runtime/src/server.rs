@@ -1,25 +1,50 @@
-use crate::executor::RedisCommandExecutor;
+use crate::audit::AuditLog;
+use crate::cluster::{self, ClusterCommandExecutor};
+use crate::concurrency::LimitedExecutor;
+use crate::errors::ErrorFormat;
+use crate::executor::{CommandTimeouts, RedisCommandExecutor};
+use crate::filter::MessageFilters;
 use crate::handler::{self, AppState};
+use crate::health::BackendHealth;
+use crate::hooks::CommandHooks;
 use crate::pubsub::{self, PubSubManager};
+use crate::quota::Quotas;
 use crate::redis::{self, DatabasePoolRegistry};
+use crate::replay::ReplaySettings;
+use crate::shadow::Shadow;
+use crate::sharding::ShardedExecutor;
+use crate::template::CommandTemplate;
+use crate::value_size::{ValueSizeExecutor, ValueSizeLimits};
 use crate::websocket;
-use axum::extract::DefaultBodyLimit;
+use axum::extract::{DefaultBodyLimit, Path};
+use axum::response::Redirect;
 use axum::{
     routing::{delete, get, options, post},
     Router,
 };
 use redis_web_core::acl;
-use redis_web_core::config::{Config, TransportMode, DEFAULT_HTTP_MAX_REQUEST_SIZE};
-use redis_web_core::interfaces::{CommandExecutor, RequestParser};
+use redis_web_core::config::{
+    AclConfig, Config, TransportMode, DEFAULT_HTTP_MAX_REQUEST_SIZE, DEFAULT_METRICS_PATH,
+    DEFAULT_PUBSUB_CONNECTIONS, DEFAULT_WEBSOCKET_CONNECTION_IDLE_SEC,
+    DEFAULT_WEBSOCKET_MAX_FRAME_SIZE, DEFAULT_WEBSOCKET_MAX_INFLIGHT_COMMANDS,
+    DEFAULT_WEBSOCKET_PING_INTERVAL_SEC, DEFAULT_WEBSOCKET_SEND_QUEUE_SIZE,
+};
+use redis_web_core::format::OutputFormat;
+use redis_web_core::interfaces::{CommandExecutor, CommandHook, RequestParser};
 use redis_web_core::request::WebdisRequestParser;
+use redis_web_core::routing::{CommandRouter, DownloadRouter, FormatRouter};
+use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
 
 /// Injectable dependencies for embedding Webdis with custom parser/executor implementations.
 pub struct ServerDependencies {
     pub request_parser: Arc<dyn RequestParser>,
     pub command_executor: Arc<dyn CommandExecutor>,
+    /// Run on client commands and their replies; see [`crate::hooks`].
+    pub hooks: Vec<Arc<dyn CommandHook>>,
 }
 
 pub struct RuntimeComponents {
@@ -60,9 +85,84 @@ pub fn build_runtime_with_dependencies(
         default_database: config.database,
         request_parser: dependencies.request_parser,
         command_executor: dependencies.command_executor,
+        hooks: CommandHooks::new(dependencies.hooks),
         acl: acl::Acl::new(config.acl.clone()),
+        backend_databases: config
+            .backends
+            .iter()
+            .flatten()
+            .map(|(name, backend)| (name.clone(), backend.database))
+            .collect(),
+        backend_acls: config
+            .backends
+            .iter()
+            .flatten()
+            .filter(|(_, backend)| backend.acl.is_some())
+            .map(|(name, backend)| (name.clone(), acl::Acl::new(backend.acl.clone())))
+            .collect(),
+        routes: CommandRouter::new(config.routes.clone()),
+        backend_health: Arc::new(BackendHealth::new(config)),
+        redis_latency: Arc::default(),
+        shadow: Shadow::new(config).map(Arc::new),
+        quotas: Quotas::new(config).map(Arc::new),
+        audit: AuditLog::new(config),
+        value_size_limits: ValueSizeLimits::new(config),
+        output_formats: FormatRouter::new(config.output_formats.clone()),
+        downloads: DownloadRouter::new(config.downloads.clone()),
+        ttl_header: config.ttl_header,
+        last_modified: config.last_modified,
+        json_nil: config.json_nil,
+        errors: ErrorFormat::new(config.error_format.as_ref()),
         pubsub: pubsub_manager,
+        sse_limits: Arc::new(pubsub::StreamLimits::new(config.sse.as_ref())),
+        websocket_connection_idle: Some(
+            config
+                .websocket_connection_idle_sec
+                .unwrap_or(DEFAULT_WEBSOCKET_CONNECTION_IDLE_SEC),
+        )
+        .filter(|&sec| sec > 0)
+        .map(Duration::from_secs),
+        websocket_ping_interval: Some(
+            config
+                .websocket_ping_interval_sec
+                .unwrap_or(DEFAULT_WEBSOCKET_PING_INTERVAL_SEC),
+        )
+        .filter(|&sec| sec > 0)
+        .map(Duration::from_secs),
+        websocket_idle_timeout: config
+            .websocket_idle_timeout_sec
+            .filter(|&sec| sec > 0)
+            .map(Duration::from_secs),
+        websocket_max_message_size: config
+            .websocket_max_message_size
+            .or(config.http_max_request_size)
+            .unwrap_or(DEFAULT_HTTP_MAX_REQUEST_SIZE),
+        websocket_max_frame_size: config
+            .websocket_max_frame_size
+            .unwrap_or(DEFAULT_WEBSOCKET_MAX_FRAME_SIZE),
+        websocket_send_queue_size: config
+            .websocket_send_queue_size
+            .unwrap_or(DEFAULT_WEBSOCKET_SEND_QUEUE_SIZE)
+            .max(1),
+        websocket_max_inflight_commands: config
+            .websocket_max_inflight_commands
+            .unwrap_or(DEFAULT_WEBSOCKET_MAX_INFLIGHT_COMMANDS)
+            .max(1),
+        websocket_require_auth: config.websocket_require_auth,
+        shutdown: tokio::sync::watch::Sender::new(false),
+        sse_keep_alive: pubsub::sse_keep_alive(config.sse.as_ref()),
+        admin: config
+            .admin
+            .as_ref()
+            .filter(|admin| admin.enabled)
+            .map(crate::admin::AdminAccess::new),
         compat_hiredis: compat_hiredis.clone(),
+        http_max_request_size: config
+            .http_max_request_size
+            .unwrap_or(DEFAULT_HTTP_MAX_REQUEST_SIZE),
+        put_stream_chunk_size: config.http_put_stream_chunk_size.map(|size| size.max(1)),
+        get_stream_chunk_size: config.http_get_stream_chunk_size.map(|size| size.max(1)),
+        list_stream_page_size: config.http_list_stream_page_size.map(|size| size.max(1)),
     });
 
     RuntimeComponents {
@@ -83,7 +183,12 @@ pub fn build_router_from_components(config: &Config, components: RuntimeComponen
                 .put(handler::handle_put)
                 .options(handler::handle_options),
         )
-        .route("/SUBSCRIBE/{*channel}", get(pubsub::handle_subscribe));
+        .route("/_publish", post(crate::publish::handle_publish))
+        .route("/_query", post(crate::query::handle_query))
+        .route("/_commands", get(crate::commands::handle_commands))
+        .route("/SUBSCRIBE/{*channel}", get(pubsub::handle_subscribe))
+        .route("/PSUBSCRIBE/{*pattern}", get(pubsub::handle_psubscribe))
+        .route("/SSUBSCRIBE/{*channel}", get(pubsub::handle_ssubscribe));
 
     if let Some(compat) = compat_hiredis {
         let prefix = compat.settings().path_prefix.clone();
@@ -104,16 +209,108 @@ pub fn build_router_from_components(config: &Config, components: RuntimeComponen
             .route(&ws_path, get(crate::compat::ws_raw));
     }
 
-    if let Some(default_root) = config.default_root.clone() {
+    let root_commands = config.root_commands.clone().unwrap_or_default();
+    let mut root = options(handler::handle_options);
+    let root_commands_get = root_commands.get.or_else(|| config.default_root.clone());
+    if let Some(default_root) = root_commands_get.as_deref().map(CommandTemplate::parse) {
+        let default_root = Arc::new(default_root);
+        root = root.get(move |state, addr, headers, query| {
+            handler::handle_default_root(state, addr, headers, query, default_root)
+        });
+    }
+    if root_commands.post_body_command {
+        root = root.post(handler::handle_root_command_body);
+    } else if let Some(command) = root_commands.post.as_deref().map(CommandTemplate::parse) {
+        let command = Arc::new(command);
+        root = root.post(move |state, addr, headers, query, body| {
+            handler::handle_root_with_body(state, addr, headers, query, command, body)
+        });
+    }
+    if let Some(command) = root_commands.put.as_deref().map(CommandTemplate::parse) {
+        let command = Arc::new(command);
+        root = root.put(move |state, addr, headers, query, body| {
+            handler::handle_root_with_body(state, addr, headers, query, command, body)
+        });
+    }
+    app = app.route("/", root);
+
+    let favicon = config
+        .favicon
+        .as_ref()
+        .map(|path| Arc::new(std::path::PathBuf::from(path)));
+    app = app.route(
+        "/favicon.ico",
+        get(move || crate::well_known::handle_favicon(favicon)),
+    );
+    let info = Arc::new(crate::info::document(config));
+    app = app.route("/_info", get(move || crate::info::handle_info(info)));
+    if config.robots_txt {
+        app = app.route("/robots.txt", get(crate::well_known::handle_robots));
+    }
+
+    if let Some(metrics) = config.metrics.as_ref().filter(|metrics| metrics.enabled) {
+        let path = metrics.path.as_deref().unwrap_or(DEFAULT_METRICS_PATH);
+        app = app.route(path, get(crate::metrics::handle_metrics));
+    }
+
+    if app_state.admin.is_some() {
+        let document = Arc::new(crate::admin::config_document(config));
+        app = app
+            .route("/_admin/stats", get(crate::admin::handle_stats))
+            .route("/_admin/ratelimits", get(crate::admin::handle_ratelimits))
+            .route(
+                "/_admin/config",
+                get(move |state, addr, headers| {
+                    crate::admin::handle_config(state, addr, headers, document)
+                }),
+            );
+        if app_state.quotas.is_some() {
+            app = app.route(
+                "/_admin/ratelimits/{identity}",
+                delete(crate::admin::handle_reset_ratelimit),
+            );
+        }
+        if config.admin.as_ref().is_some_and(|admin| admin.console) {
+            app = app.route("/_console", get(crate::admin::handle_console));
+        }
+    }
+
+    if let Some(openapi) = config.openapi.as_ref().filter(|openapi| openapi.enabled) {
+        let document = crate::openapi::document(config).to_string();
         app = app.route(
-            "/",
-            get(move |state, addr, headers, query| {
-                handler::handle_default_root(state, addr, headers, query, default_root)
-            })
-            .options(handler::handle_options),
+            crate::openapi::DOCUMENT_PATH,
+            get(move || std::future::ready(crate::openapi::document_response(&document))),
         );
-    } else {
-        app = app.route("/", options(handler::handle_options));
+        if openapi.swagger_ui {
+            app = app.route(
+                crate::openapi::SWAGGER_UI_PATH,
+                get(crate::openapi::handle_swagger_ui),
+            );
+        }
+    }
+
+    if let Some(static_dir) = config.static_dir.as_ref() {
+        let files = Arc::new(crate::static_files::StaticDir::new(static_dir));
+        let prefix = files.path_prefix().to_string();
+        let index_files = files.clone();
+        let index = format!("{prefix}/");
+        app = app
+            .route(
+                &format!("{prefix}/{{*path}}"),
+                get(move |Path(path): Path<String>, headers| {
+                    crate::static_files::handle_static(files, path, headers)
+                }),
+            )
+            .route(
+                &format!("{prefix}/"),
+                get(move |headers| {
+                    crate::static_files::handle_static(index_files, String::new(), headers)
+                }),
+            )
+            .route(
+                &prefix,
+                get(move || std::future::ready(Redirect::permanent(&index))),
+            );
     }
 
     if config.websockets {
@@ -121,6 +318,10 @@ pub fn build_router_from_components(config: &Config, components: RuntimeComponen
         app = app.route("/.raw", get(websocket::ws_handler_raw));
     }
 
+    if config.server_timing {
+        app = app.layer(axum::middleware::from_fn(crate::server_timing::middleware));
+    }
+
     app.layer(DefaultBodyLimit::max(
         config
             .http_max_request_size
@@ -136,40 +337,220 @@ pub fn build_router(config: &Config) -> Result<Router, ServerBuildError> {
 }
 
 pub fn build_runtime(config: &Config) -> Result<RuntimeComponents, ServerBuildError> {
+    build_runtime_with_hooks(config, Vec::new())
+}
+
+/// Builds the default runtime with `hooks` run on every client command and
+/// its reply, after the modules listed in the `hooks` config.
+pub fn build_runtime_with_hooks(
+    config: &Config,
+    hooks: Vec<Arc<dyn CommandHook>>,
+) -> Result<RuntimeComponents, ServerBuildError> {
+    validate_routes(config)?;
+    validate_output_formats(config)?;
+    validate_error_format(config)?;
+    validate_favicon(config)?;
+    validate_static_dir(config)?;
+    validate_shadow(config)?;
+    validate_shards(config)?;
+    validate_acl(config)?;
+    validate_admin(config)?;
+    let hooks = load_hooks(config)?.into_iter().chain(hooks).collect();
+
+    // In cluster mode, the pools, Pub/Sub, and compat sessions talk to the first
+    // seed node while command traffic is routed by the cluster executor.
+    let cluster_seed = match config.redis_cluster {
+        Some(_) => Some(cluster::seed_node_config(config).map_err(ServerBuildError::Cluster)?),
+        None => None,
+    };
+    let node_config = cluster_seed.as_ref().unwrap_or(config);
+
     info!("Initializing Redis command pool");
-    let redis_pool = redis::create_pool(config).map_err(ServerBuildError::RedisPool)?;
-    let redis_pools = DatabasePoolRegistry::new(config.clone(), redis_pool);
+    let redis_pool = redis::create_pool(node_config).map_err(ServerBuildError::RedisPool)?;
+    let redis_pools = DatabasePoolRegistry::new(node_config.clone(), redis_pool);
     let redis_pools_shared = Arc::new(redis_pools);
 
     info!("Initializing Redis pub/sub client");
-    let pubsub_client = redis::create_pubsub_client(config).map_err(ServerBuildError::PubSub)?;
-    let pubsub_manager = pubsub::PubSubManager::new(pubsub_client);
+    let pubsub_clients =
+        redis::create_pubsub_clients(node_config).map_err(ServerBuildError::PubSub)?;
+    let sharded_source = match config.redis_cluster {
+        Some(_) => pubsub::ShardedSource::Cluster(Box::new(config.clone())),
+        None => pubsub::ShardedSource::Standalone(
+            redis::create_sharded_pubsub_client(node_config).map_err(ServerBuildError::PubSub)?,
+        ),
+    };
+    let pubsub_manager = pubsub::PubSubManager::with_connections(
+        pubsub_clients,
+        redis::connect_timeout(node_config),
+        config
+            .pubsub_connections
+            .unwrap_or(DEFAULT_PUBSUB_CONNECTIONS),
+        Some(redis::client_name(node_config, "pubsub")),
+    )
+    .with_sharded(sharded_source);
 
     let compat_hiredis = match (config.transport_mode, config.compat_hiredis.as_ref()) {
         (TransportMode::Rest, Some(cfg)) if cfg.enabled => {
-            let manager = crate::compat::CompatSessionManager::new(config)
+            let manager = crate::compat::CompatSessionManager::new(node_config)
                 .map_err(ServerBuildError::Compat)?;
             Some(Arc::new(manager))
         }
         _ => None,
     };
 
+    let timeouts = CommandTimeouts::from_config(config);
+    let command_executor: Arc<dyn CommandExecutor> = if cluster_seed.is_some() {
+        let client = cluster::create_cluster_client(config).map_err(ServerBuildError::Cluster)?;
+        Arc::new(ClusterCommandExecutor::new(client).with_timeouts(timeouts))
+    } else {
+        let mut backends = HashMap::new();
+        for (name, backend) in config.backends.iter().flatten() {
+            let backend_config = redis::backend_config(config, backend);
+            let pool = redis::create_pool(&backend_config).map_err(ServerBuildError::RedisPool)?;
+            backends.insert(
+                name.clone(),
+                Arc::new(DatabasePoolRegistry::new(backend_config, pool)),
+            );
+        }
+        let mut credentials = HashMap::new();
+        let rules = config.acl.as_deref().unwrap_or_default();
+        for (rule, redis_auth) in acl::Acl::redis_credentials(rules) {
+            let mut rule_config = node_config.clone();
+            rule_config.redis_auth = Some(redis_auth.clone());
+            let pool = redis::create_pool(&rule_config).map_err(ServerBuildError::RedisPool)?;
+            credentials.insert(rule, Arc::new(DatabasePoolRegistry::new(rule_config, pool)));
+        }
+        Arc::new(
+            RedisCommandExecutor::new(redis_pools_shared.clone())
+                .with_backends(backends)
+                .with_credentials(credentials)
+                .with_timeouts(timeouts),
+        )
+    };
+
+    // Mirrored replay history is written through the same executor as commands.
+    let pubsub_manager = match ReplaySettings::from_config(config, command_executor.clone()) {
+        Some(replay) => pubsub_manager.with_replay(replay),
+        None => pubsub_manager,
+    };
+    let pubsub_manager = match config.pubsub_filters.as_deref() {
+        Some(rules) => pubsub_manager
+            .with_filters(MessageFilters::new(rules).map_err(ServerBuildError::PubSubFilter)?),
+        None => pubsub_manager,
+    };
+
+    let command_executor = LimitedExecutor::wrap(command_executor, config);
+    let command_executor = ShardedExecutor::wrap(command_executor, config);
+    let command_executor = ValueSizeExecutor::wrap(command_executor, config);
+
     let dependencies = ServerDependencies {
         request_parser: Arc::new(WebdisRequestParser),
-        command_executor: Arc::new(RedisCommandExecutor::new(redis_pools_shared.clone())),
+        command_executor,
+        hooks,
     };
 
-    Ok(build_runtime_with_dependencies(
+    let components = build_runtime_with_dependencies(
         config,
         dependencies,
         redis_pools_shared,
         pubsub_manager,
         compat_hiredis,
-    ))
+    );
+    if cluster_seed.is_none() {
+        crate::health::spawn_checks(components.app_state.backend_health.clone(), config);
+    }
+    crate::latency::spawn_probe(components.app_state.redis_latency.clone(), node_config);
+    Ok(components)
+}
+
+/// First and largest delay between startup checks of `wait_for_redis_sec`.
+const WAIT_FOR_REDIS_BACKOFF: (Duration, Duration) =
+    (Duration::from_millis(250), Duration::from_secs(5));
+
+/// Waits for Redis to answer `PING` on the command pool and on a Pub/Sub
+/// connection, retrying with backoff for up to `wait_for_redis_sec`, so the
+/// server can start before Redis does. Returns at once when the option is
+/// unset or `0`.
+pub async fn wait_for_redis(
+    config: &Config,
+    components: &RuntimeComponents,
+) -> Result<(), ServerBuildError> {
+    let window = match config.wait_for_redis_sec {
+        Some(seconds) if seconds > 0 => Duration::from_secs(seconds),
+        _ => return Ok(()),
+    };
+    let node_config = match config.redis_cluster {
+        Some(_) => cluster::seed_node_config(config).map_err(ServerBuildError::Cluster)?,
+        None => config.clone(),
+    };
+    let pubsub_client =
+        redis::create_pubsub_client(&node_config).map_err(ServerBuildError::PubSub)?;
+
+    let started = tokio::time::Instant::now();
+    let (mut delay, max_delay) = WAIT_FOR_REDIS_BACKOFF;
+    let mut retried = false;
+    loop {
+        let error = match ping_redis(&components.app_state, &pubsub_client).await {
+            Ok(()) => {
+                if retried {
+                    info!("Redis is reachable after {:.1?}", started.elapsed());
+                }
+                return Ok(());
+            }
+            Err(error) => error,
+        };
+        let remaining = window.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return Err(ServerBuildError::RedisUnavailable(format!(
+                "no reply within {}s: {error}",
+                window.as_secs()
+            )));
+        }
+        let wait = delay.min(remaining);
+        info!("Waiting for Redis ({error}); retrying in {wait:.1?}, {remaining:.1?} left");
+        tokio::time::sleep(wait).await;
+        delay = (delay * 2).min(max_delay);
+        retried = true;
+    }
+}
+
+/// Sends `PING` over a pooled command connection and a Pub/Sub connection.
+async fn ping_redis(state: &AppState, pubsub_client: &::redis::Client) -> Result<(), String> {
+    let mut connection = state
+        .redis_pools
+        .connection_for_database(state.default_database)
+        .await
+        .map_err(|error| error.to_string())?;
+    ::redis::cmd("PING")
+        .query_async::<()>(&mut connection)
+        .await
+        .map_err(|error| error.to_string())?;
+    let mut pubsub = pubsub_client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|error| format!("Pub/Sub: {error}"))?;
+    ::redis::cmd("PING")
+        .query_async::<()>(&mut pubsub)
+        .await
+        .map_err(|error| format!("Pub/Sub: {error}"))
 }
 
 /// Serves a pre-built Axum router on the configured host/port.
 pub async fn serve(config: &Config, app: Router) -> Result<(), std::io::Error> {
+    serve_with_shutdown(config, app, tokio::sync::watch::Sender::new(false)).await
+}
+
+/// How long a shutdown waits for open connections, such as SSE streams that
+/// never end on their own, before returning anyway.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Serves like [`serve`] until SIGINT or SIGTERM, then sets `shutdown` (the
+/// [`AppState::shutdown`] flag WebSockets close on) and drains connections.
+pub async fn serve_with_shutdown(
+    config: &Config,
+    app: Router,
+    shutdown: tokio::sync::watch::Sender<bool>,
+) -> Result<(), std::io::Error> {
     let ip: IpAddr = config.http_host.parse().map_err(|_| {
         let err = std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
@@ -182,11 +563,249 @@ pub async fn serve(config: &Config, app: Router) -> Result<(), std::io::Error> {
     info!("Binding HTTP listener to {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await?;
     info!("HTTP listener bound to {}", addr);
-    axum::serve(
+    serve_listener(listener, app, shutdown).await
+}
+
+/// Serves `app` on an already-bound listener, shutting down like
+/// [`serve_with_shutdown`].
+pub async fn serve_listener(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    shutdown: tokio::sync::watch::Sender<bool>,
+) -> Result<(), std::io::Error> {
+    let mut stopping = shutdown.subscribe();
+    let server = axum::serve(
         listener,
         app.into_make_service_with_connect_info::<SocketAddr>(),
     )
-    .await
+    .with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        info!("Shutting down HTTP listener");
+        shutdown.send_replace(true);
+    });
+    tokio::select! {
+        result = std::future::IntoFuture::into_future(server) => result,
+        _ = async {
+            let _ = stopping.wait_for(|&down| down).await;
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } => Ok(()),
+    }
+}
+
+/// Completes on SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+/// Rejects `routes` entries that name a backend missing from `backends`.
+fn validate_routes(config: &Config) -> Result<(), ServerBuildError> {
+    let router = CommandRouter::new(config.routes.clone());
+    let unknown = router.backends().find(|name| {
+        !config
+            .backends
+            .as_ref()
+            .is_some_and(|backends| backends.contains_key(*name))
+    });
+    match unknown {
+        Some(name) => Err(ServerBuildError::Routing(format!(
+            "route targets unknown backend {name}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Rejects `output_formats` entries with an unknown format.
+fn validate_output_formats(config: &Config) -> Result<(), ServerBuildError> {
+    let router = FormatRouter::new(config.output_formats.clone());
+    let unknown = router
+        .extensions()
+        .find(|extension| OutputFormat::from_extension(extension).is_none());
+    match unknown {
+        Some(extension) => Err(ServerBuildError::OutputFormat(format!(
+            "unknown format {extension}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Rejects an `error_format.request_id_header` that is not a header name.
+fn validate_error_format(config: &Config) -> Result<(), ServerBuildError> {
+    let header = config
+        .error_format
+        .as_ref()
+        .and_then(|format| format.request_id_header.as_deref());
+    match header {
+        Some(name) if axum::http::HeaderName::try_from(name).is_err() => Err(
+            ServerBuildError::ErrorFormat(format!("invalid request_id_header {name}")),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects a `favicon` that is not a file.
+fn validate_favicon(config: &Config) -> Result<(), ServerBuildError> {
+    match config.favicon.as_deref() {
+        Some(path) if !std::path::Path::new(path).is_file() => {
+            Err(ServerBuildError::Favicon(format!("{path} is not a file")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects a `static_dir.path_prefix` that is not a path below the root.
+fn validate_static_dir(config: &Config) -> Result<(), ServerBuildError> {
+    match config.static_dir.as_ref() {
+        Some(static_dir)
+            if !static_dir
+                .path_prefix
+                .trim_end_matches('/')
+                .starts_with('/') =>
+        {
+            Err(ServerBuildError::StaticDir(
+                "path_prefix must start with / and name a path below the root".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects a `shadow` naming an unknown backend or a share outside 0–100.
+fn validate_shadow(config: &Config) -> Result<(), ServerBuildError> {
+    let Some(shadow) = config.shadow.as_ref() else {
+        return Ok(());
+    };
+    let known = config
+        .backends
+        .as_ref()
+        .is_some_and(|backends| backends.contains_key(&shadow.backend));
+    if !known {
+        return Err(ServerBuildError::Shadow(format!(
+            "unknown backend {}",
+            shadow.backend
+        )));
+    }
+    match shadow.percent {
+        Some(percent) if !(0.0..=100.0).contains(&percent) => Err(ServerBuildError::Shadow(
+            format!("percent must be between 0 and 100, got {percent}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects `shards` naming unknown backends, or combined with Redis Cluster.
+fn validate_shards(config: &Config) -> Result<(), ServerBuildError> {
+    let Some(shards) = config.shards.as_ref() else {
+        return Ok(());
+    };
+    if config.redis_cluster.is_some() && !shards.is_empty() {
+        return Err(ServerBuildError::Shards(
+            "shards cannot be combined with redis_cluster".to_string(),
+        ));
+    }
+    let unknown = shards.iter().find(|name| {
+        !config
+            .backends
+            .as_ref()
+            .is_some_and(|backends| backends.contains_key(*name))
+    });
+    match unknown {
+        Some(name) => Err(ServerBuildError::Shards(format!("unknown backend {name}"))),
+        None => Ok(()),
+    }
+}
+
+/// Rejects `admin.enabled` without `ip` or `http_basic_auth`, which would
+/// open the admin endpoints to every client.
+fn validate_admin(config: &Config) -> Result<(), ServerBuildError> {
+    match config.admin.as_ref() {
+        Some(admin) if admin.enabled && admin.ip.is_none() && admin.http_basic_auth.is_none() => {
+            Err(ServerBuildError::Admin(
+                "enabled needs ip or http_basic_auth".to_string(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects invalid rule schedules, and `redis_auth` on `acl` rules where it
+/// would be ignored: in cluster mode and on a backend's rules.
+fn validate_acl(config: &Config) -> Result<(), ServerBuildError> {
+    let backend_rules = config
+        .backends
+        .iter()
+        .flatten()
+        .flat_map(|(_, backend)| backend.acl.iter().flatten());
+    for rule in config.acl.iter().flatten().chain(backend_rules) {
+        if let Some(schedule) = &rule.schedule {
+            acl::AclSchedule::parse(schedule).map_err(ServerBuildError::Acl)?;
+        }
+    }
+    let has_basic_auth = config
+        .acl
+        .iter()
+        .flatten()
+        .any(|rule| rule.http_basic_auth.is_some());
+    if config.websocket_require_auth && !has_basic_auth {
+        return Err(ServerBuildError::Acl(
+            "websocket_require_auth needs an acl rule with http_basic_auth".to_string(),
+        ));
+    }
+    let has_credentials = |rules: Option<&Vec<AclConfig>>| {
+        rules
+            .into_iter()
+            .flatten()
+            .any(|rule| rule.redis_auth.is_some())
+    };
+    if config.redis_cluster.is_some() && has_credentials(config.acl.as_ref()) {
+        return Err(ServerBuildError::Acl(
+            "redis_auth in acl rules is not supported with redis_cluster".to_string(),
+        ));
+    }
+    let backend = config
+        .backends
+        .iter()
+        .flatten()
+        .find(|(_, backend)| has_credentials(backend.acl.as_ref()));
+    match backend {
+        Some((name, _)) => Err(ServerBuildError::Acl(format!(
+            "redis_auth is only supported in top-level acl rules, not in backend {name}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Compiles the WebAssembly modules listed in `hooks`.
+#[cfg(feature = "wasm-hooks")]
+fn load_hooks(config: &Config) -> Result<Vec<Arc<dyn CommandHook>>, ServerBuildError> {
+    crate::wasm::load(config).map_err(ServerBuildError::Hooks)
+}
+
+/// Refuses a `hooks` list, which this build cannot run.
+#[cfg(not(feature = "wasm-hooks"))]
+fn load_hooks(config: &Config) -> Result<Vec<Arc<dyn CommandHook>>, ServerBuildError> {
+    match config.hooks.as_deref() {
+        Some([_, ..]) => Err(ServerBuildError::Hooks(
+            "this build does not include the wasm-hooks feature".to_string(),
+        )),
+        _ => Ok(Vec::new()),
+    }
 }
 
 #[derive(Debug)]
@@ -194,6 +813,19 @@ pub enum ServerBuildError {
     RedisPool(redis::RedisCreatePoolError),
     PubSub(::redis::RedisError),
     Compat(::redis::RedisError),
+    Cluster(::redis::RedisError),
+    Routing(String),
+    OutputFormat(String),
+    ErrorFormat(String),
+    Favicon(String),
+    StaticDir(String),
+    Shadow(String),
+    Shards(String),
+    Acl(String),
+    Admin(String),
+    Hooks(String),
+    PubSubFilter(String),
+    RedisUnavailable(String),
 }
 
 impl std::fmt::Display for ServerBuildError {
@@ -209,6 +841,29 @@ impl std::fmt::Display for ServerBuildError {
                     "failed to create hiredis-compat session manager: {error}"
                 )
             }
+            ServerBuildError::Cluster(error) => {
+                write!(f, "failed to create Redis cluster client: {error}")
+            }
+            ServerBuildError::Routing(error) => write!(f, "invalid routes config: {error}"),
+            ServerBuildError::OutputFormat(error) => {
+                write!(f, "invalid output_formats config: {error}")
+            }
+            ServerBuildError::ErrorFormat(error) => {
+                write!(f, "invalid error_format config: {error}")
+            }
+            ServerBuildError::Favicon(error) => write!(f, "invalid favicon config: {error}"),
+            ServerBuildError::StaticDir(error) => write!(f, "invalid static_dir config: {error}"),
+            ServerBuildError::Shadow(error) => write!(f, "invalid shadow config: {error}"),
+            ServerBuildError::Shards(error) => write!(f, "invalid shards config: {error}"),
+            ServerBuildError::Acl(error) => write!(f, "invalid acl config: {error}"),
+            ServerBuildError::Admin(error) => write!(f, "invalid admin config: {error}"),
+            ServerBuildError::Hooks(error) => write!(f, "invalid hooks config: {error}"),
+            ServerBuildError::PubSubFilter(error) => {
+                write!(f, "invalid pubsub_filters config: {error}")
+            }
+            ServerBuildError::RedisUnavailable(error) => {
+                write!(f, "Redis did not become reachable: {error}")
+            }
         }
     }
 }
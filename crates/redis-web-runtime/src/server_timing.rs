@@ -0,0 +1,92 @@
+//! `Server-Timing` response header.
+//!
+//! With `server_timing` set, HTTP responses carry a header such as
+//! `pool;dur=0.3, redis;dur=1.2, total;dur=2.9`, in milliseconds, so browser
+//! devtools and APM agents show where a request's time went. The executors
+//! report pool checkouts and Redis round trips to a task-local record that
+//! only exists while [`measure`] runs the request, so background work such as
+//! shadow copies is not counted.
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::cell::Cell;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+tokio::task_local! {
+    static TIMINGS: Timings;
+}
+
+/// Time a request spent waiting for pooled connections and on Redis.
+#[derive(Debug, Default)]
+struct Timings {
+    pool: Cell<Duration>,
+    redis: Cell<Duration>,
+}
+
+/// Adds a connection checkout to the current request, if it is measured.
+pub(crate) fn record_pool(elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| timings.pool.set(timings.pool.get() + elapsed));
+}
+
+/// Adds a Redis round trip to the current request, if it is measured.
+pub(crate) fn record_redis(elapsed: Duration) {
+    let _ = TIMINGS.try_with(|timings| timings.redis.set(timings.redis.get() + elapsed));
+}
+
+/// Middleware adding the `Server-Timing` header to every response.
+pub async fn middleware(request: Request, next: Next) -> Response {
+    measure(next.run(request)).await
+}
+
+/// Runs `request` and adds its `Server-Timing` header to the response.
+pub async fn measure(request: impl Future<Output = Response>) -> Response {
+    let started = Instant::now();
+    let (pool, redis, mut response) = TIMINGS
+        .scope(Timings::default(), async {
+            let response = request.await;
+            let (pool, redis) = TIMINGS.with(|timings| (timings.pool.get(), timings.redis.get()));
+            (pool, redis, response)
+        })
+        .await;
+    let value = header_value(pool, redis, started.elapsed());
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert("server-timing", value);
+    }
+    response
+}
+
+fn header_value(pool: Duration, redis: Duration, total: Duration) -> String {
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    format!(
+        "pool;dur={:.1}, redis;dur={:.1}, total;dur={:.1}",
+        millis(pool),
+        millis(redis),
+        millis(total)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    #[tokio::test]
+    async fn reports_recorded_time_of_the_measured_request_only() {
+        record_redis(Duration::from_secs(1));
+        let response = measure(async {
+            record_pool(Duration::from_micros(300));
+            record_redis(Duration::from_micros(1200));
+            record_redis(Duration::from_micros(20));
+            Response::new(Body::empty())
+        })
+        .await;
+        let value = response.headers()["server-timing"].to_str().unwrap();
+        assert!(
+            value.starts_with("pool;dur=0.3, redis;dur=1.2, total;dur="),
+            "{value}"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Shadow traffic to a named backend.
+//!
+//! With `shadow` set, a share of the read commands sent to the top-level Redis
+//! is copied to a backend from `backends` once the client has its reply. The
+//! copy's reply is compared with the one the client got and thrown away, so a
+//! new Redis or cache layer can be checked against live traffic before cutover
+//! without clients noticing.
+//!
+//! ```json
+//! {"backends": {"next": {"redis_host": "10.0.0.9"}}, "shadow": {"backend": "next", "percent": 10}}
+//! ```
+
+use redis::Value as RedisValue;
+use redis_web_core::config::Config;
+use redis_web_core::interfaces::{CommandExecutor, ExecutableCommand};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Read commands copied when `shadow.commands` is unset. Commands whose
+/// replies differ between two servers holding the same data, such as
+/// `SMEMBERS` or `TTL`, are left out.
+const DEFAULT_COMMANDS: &[&str] = &[
+    "BITCOUNT",
+    "EXISTS",
+    "GEODIST",
+    "GEOPOS",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HLEN",
+    "HMGET",
+    "HSTRLEN",
+    "LINDEX",
+    "LLEN",
+    "LRANGE",
+    "MGET",
+    "PFCOUNT",
+    "SCARD",
+    "SISMEMBER",
+    "SMISMEMBER",
+    "STRLEN",
+    "TYPE",
+    "XLEN",
+    "XRANGE",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZMSCORE",
+    "ZRANGE",
+    "ZRANGEBYLEX",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANGEBYSCORE",
+    "ZREVRANK",
+    "ZSCORE",
+];
+
+/// Copies sampled commands to the shadow backend and counts the outcomes.
+#[derive(Debug)]
+pub struct Shadow {
+    backend: String,
+    /// Database of the shadow backend used for requests on the default one.
+    backend_database: u8,
+    default_database: u8,
+    percent: f64,
+    commands: HashSet<String>,
+    eligible: AtomicU64,
+    matched: AtomicU64,
+    mismatched: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Outcomes of the shadow copies so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShadowStats {
+    pub matched: u64,
+    pub mismatched: u64,
+    pub failed: u64,
+}
+
+impl Shadow {
+    /// The shadow of `config`, or `None` when `shadow` is unset.
+    pub fn new(config: &Config) -> Option<Self> {
+        let settings = config.shadow.as_ref()?;
+        let backend_database = config
+            .backends
+            .as_ref()
+            .and_then(|backends| backends.get(&settings.backend))
+            .map_or(config.database, |backend| backend.database);
+        let commands = match &settings.commands {
+            Some(commands) => commands.iter().map(|name| name.to_uppercase()).collect(),
+            None => DEFAULT_COMMANDS
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        };
+        Some(Self {
+            backend: settings.backend.clone(),
+            backend_database,
+            default_database: config.database,
+            percent: settings.percent.unwrap_or(100.0).clamp(0.0, 100.0),
+            commands,
+            eligible: AtomicU64::new(0),
+            matched: AtomicU64::new(0),
+            mismatched: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        })
+    }
+
+    /// Copies `command` to the shadow backend in the background when it is
+    /// sampled, and compares the copy's reply with `reply`.
+    pub fn mirror(
+        self: &Arc<Self>,
+        executor: &Arc<dyn CommandExecutor>,
+        command: &ExecutableCommand,
+        reply: &RedisValue,
+    ) {
+        if command.backend.is_some()
+            || !self
+                .commands
+                .contains(&command.command_name.to_ascii_uppercase())
+            || !self.sample()
+        {
+            return;
+        }
+        let mut copy = command.clone();
+        copy.backend = Some(self.backend.clone());
+        if copy.target_database == self.default_database {
+            copy.target_database = self.backend_database;
+        }
+        let shadow = self.clone();
+        let executor = executor.clone();
+        let expected = reply.clone();
+        tokio::spawn(async move {
+            let counter = match executor.execute(&copy).await {
+                Ok(reply) if reply == expected => &shadow.matched,
+                Ok(_) => {
+                    debug!(
+                        "Shadow reply differs: backend={} command={}",
+                        shadow.backend, copy.command_name
+                    );
+                    &shadow.mismatched
+                }
+                Err(error) => {
+                    debug!(
+                        "Shadow command failed: backend={} command={} error={}",
+                        shadow.backend, copy.command_name, error
+                    );
+                    &shadow.failed
+                }
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Picks `percent` of the eligible requests, spread evenly.
+    fn sample(&self) -> bool {
+        let n = self.eligible.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.percent / 100.0).floor() > (n * self.percent / 100.0).floor()
+    }
+
+    pub fn stats(&self) -> ShadowStats {
+        ShadowStats {
+            matched: self.matched.load(Ordering::Relaxed),
+            mismatched: self.mismatched.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn samples_the_configured_share() {
+        let config = Config::from_value(json!({
+            "backends": {"next": {"database": 4}},
+            "shadow": {"backend": "next", "percent": 25}
+        }))
+        .unwrap();
+        let shadow = Shadow::new(&config).unwrap();
+        assert_eq!(shadow.backend_database, 4);
+        assert!(shadow.commands.contains("HMGET"));
+        assert!(!shadow.commands.contains("SET"));
+        let sampled = (0..100).filter(|_| shadow.sample()).count();
+        assert_eq!(sampled, 25);
+    }
+}
@@ -0,0 +1,299 @@
+//! Client-side sharding across standalone Redis servers.
+//!
+//! With `shards` set, commands that neither the URL nor `routes` sent to a
+//! backend are spread over the listed backends by consistent hashing of their
+//! key, as twemproxy does, so a keyspace too large for one server can be split
+//! without running Redis Cluster. Adding or removing a shard only moves the
+//! keys of its neighbours on the ring.
+//!
+//! A key containing `{...}` is hashed on the text between the braces, so keys
+//! sharing a hash tag stay together. Multi-key commands whose keys land on
+//! different shards are rejected. Commands without a key, such as `PING` or
+//! `INFO`, go to the top-level Redis.
+
+use crate::cluster::command_keys;
+use redis_web_core::config::Config;
+use redis_web_core::interfaces::{
+    CommandExecutionError, CommandExecutor, CommandSession, ExecutableCommand, ExecutionFuture,
+    PipelineFuture,
+};
+use std::sync::Arc;
+
+/// Points each shard places on the ring; more spread the keys more evenly.
+const POINTS_PER_SHARD: usize = 160;
+
+/// Commands whose first argument is not a key.
+const KEYLESS_COMMANDS: &[&str] = &[
+    "ACL",
+    "AUTH",
+    "BGREWRITEAOF",
+    "BGSAVE",
+    "CLIENT",
+    "CLUSTER",
+    "COMMAND",
+    "CONFIG",
+    "DBSIZE",
+    "DEBUG",
+    "DISCARD",
+    "ECHO",
+    "EXEC",
+    "FLUSHALL",
+    "FLUSHDB",
+    "FUNCTION",
+    "HELLO",
+    "INFO",
+    "KEYS",
+    "LASTSAVE",
+    "LATENCY",
+    "MEMORY",
+    "MODULE",
+    "MULTI",
+    "PING",
+    "PUBLISH",
+    "PUBSUB",
+    "QUIT",
+    "RANDOMKEY",
+    "RESET",
+    "ROLE",
+    "SAVE",
+    "SCAN",
+    "SCRIPT",
+    "SELECT",
+    "SLOWLOG",
+    "SPUBLISH",
+    "SWAPDB",
+    "TIME",
+    "UNWATCH",
+    "WAIT",
+];
+
+/// Consistent-hash ring over the `shards` backends.
+#[derive(Debug)]
+pub struct ShardRing {
+    /// Ring points, sorted, with the index of their shard.
+    points: Vec<(u64, usize)>,
+    /// Shard backend names with their default database.
+    shards: Vec<(String, u8)>,
+}
+
+impl ShardRing {
+    /// The ring of `config`, or `None` when `shards` is unset or empty.
+    pub fn new(config: &Config) -> Option<Self> {
+        let names = config.shards.as_ref().filter(|names| !names.is_empty())?;
+        let shards: Vec<(String, u8)> = names
+            .iter()
+            .map(|name| {
+                let database = config
+                    .backends
+                    .as_ref()
+                    .and_then(|backends| backends.get(name))
+                    .map_or(config.database, |backend| backend.database);
+                (name.clone(), database)
+            })
+            .collect();
+        let mut points: Vec<(u64, usize)> = shards
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (name, _))| {
+                (0..POINTS_PER_SHARD)
+                    .map(move |point| (ring_hash(format!("{name}-{point}").as_bytes()), index))
+            })
+            .collect();
+        points.sort_unstable();
+        Some(Self { points, shards })
+    }
+
+    /// The shard owning `key`.
+    fn shard_for_key(&self, key: &[u8]) -> usize {
+        let hash = ring_hash(hash_tag(key));
+        let index = self.points.partition_point(|&(point, _)| point < hash);
+        self.points[index % self.points.len()].1
+    }
+
+    /// The shard of `command`, `None` for a command without a key, or an
+    /// error when its keys span shards.
+    fn shard_for(&self, command: &ExecutableCommand) -> Result<Option<usize>, String> {
+        let name = command.command_name.to_ascii_uppercase();
+        if KEYLESS_COMMANDS.contains(&name.as_str()) {
+            return Ok(None);
+        }
+        let mut keys = command_keys(&name, &command.args);
+        if keys.is_empty() {
+            keys.extend(command.args.first().map(Vec::as_slice));
+        }
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(None);
+        };
+        let shard = self.shard_for_key(first);
+        if rest.iter().all(|key| self.shard_for_key(key) == shard) {
+            Ok(Some(shard))
+        } else {
+            Err(format!(
+                "keys in {name} map to different shards; use a hash tag such as {{user1}}:a and {{user1}}:b"
+            ))
+        }
+    }
+}
+
+/// Executor sending commands without a backend to their shard.
+pub struct ShardedExecutor {
+    inner: Arc<dyn CommandExecutor>,
+    ring: ShardRing,
+    default_database: u8,
+}
+
+impl ShardedExecutor {
+    /// Wraps `inner` when `shards` is set.
+    pub fn wrap(inner: Arc<dyn CommandExecutor>, config: &Config) -> Arc<dyn CommandExecutor> {
+        match ShardRing::new(config) {
+            Some(ring) => Arc::new(Self {
+                inner,
+                ring,
+                default_database: config.database,
+            }),
+            None => inner,
+        }
+    }
+
+    /// `request` addressed to its shard. A request for the default database
+    /// moves to the shard's database.
+    fn route(
+        &self,
+        request: &ExecutableCommand,
+    ) -> Result<ExecutableCommand, CommandExecutionError> {
+        let mut request = request.clone();
+        if request.backend.is_some() {
+            return Ok(request);
+        }
+        let shard = self
+            .ring
+            .shard_for(&request)
+            .map_err(CommandExecutionError::Rejected)?;
+        if let Some(index) = shard {
+            let (name, database) = &self.ring.shards[index];
+            request.backend = Some(name.clone());
+            if request.target_database == self.default_database {
+                request.target_database = *database;
+            }
+        }
+        Ok(request)
+    }
+}
+
+impl CommandExecutor for ShardedExecutor {
+    fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            let request = self.route(request)?;
+            self.inner.execute(&request).await
+        })
+    }
+
+    fn execute_pipeline<'a>(&'a self, requests: &'a [ExecutableCommand]) -> PipelineFuture<'a> {
+        Box::pin(async move {
+            let requests = requests
+                .iter()
+                .map(|request| self.route(request))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.inner.execute_pipeline(&requests).await
+        })
+    }
+
+    fn execute_in_session<'a>(
+        &'a self,
+        session: &'a mut CommandSession,
+        request: &'a ExecutableCommand,
+    ) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            let request = self.route(request)?;
+            self.inner.execute_in_session(session, &request).await
+        })
+    }
+}
+
+/// The part of `key` that is hashed: the text of a non-empty `{...}` tag, or
+/// the whole key.
+fn hash_tag(key: &[u8]) -> &[u8] {
+    if let Some(open) = key.iter().position(|&byte| byte == b'{') {
+        if let Some(length) = key[open + 1..].iter().position(|&byte| byte == b'}') {
+            if length > 0 {
+                return &key[open + 1..open + 1 + length];
+            }
+        }
+    }
+    key
+}
+
+/// 64-bit FNV-1a hash, finished with the splitmix64 mixer so that short keys
+/// spread over the whole ring.
+fn ring_hash(bytes: &[u8]) -> u64 {
+    let hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    let hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    fn ring(shards: &[&str]) -> ShardRing {
+        let backends: serde_json::Map<_, _> = shards
+            .iter()
+            .map(|name| (name.to_string(), json!({})))
+            .collect();
+        let config = Config::from_value(json!({"backends": backends, "shards": shards})).unwrap();
+        ShardRing::new(&config).unwrap()
+    }
+
+    #[test]
+    fn keys_spread_over_shards_and_mostly_stay_when_one_is_added() {
+        let three = ring(&["a", "b", "c"]);
+        let four = ring(&["a", "b", "c", "d"]);
+        let keys: Vec<String> = (0..3000).map(|n| format!("user:{n}")).collect();
+
+        let mut counts = [0; 3];
+        let mut moved = 0;
+        for key in &keys {
+            let before = three.shard_for_key(key.as_bytes());
+            counts[before] += 1;
+            let after = four.shard_for_key(key.as_bytes());
+            if after != before {
+                assert_eq!(after, 3, "keys only move to the new shard");
+                moved += 1;
+            }
+        }
+        assert!(counts.iter().all(|&count| count > 700), "{counts:?}");
+        assert!(moved > 400 && moved < 1200, "{moved}");
+    }
+
+    #[test]
+    fn multi_key_commands_must_share_a_shard() {
+        let ring = ring(&["a", "b", "c"]);
+        assert_eq!(ring.shard_for(&command("PING", &[])), Ok(None));
+        assert_eq!(ring.shard_for(&command("INFO", &["memory"])), Ok(None));
+        assert!(ring.shard_for(&command("GET", &["k"])).unwrap().is_some());
+        assert!(ring
+            .shard_for(&command("MGET", &["{u1}:a", "{u1}:b"]))
+            .is_ok());
+        let spread = (0..20).map(|n| format!("k{n}")).collect::<Vec<_>>();
+        let spread: Vec<&str> = spread.iter().map(String::as_str).collect();
+        let error = ring.shard_for(&command("mget", &spread)).unwrap_err();
+        assert!(error.starts_with("keys in MGET map to different shards"));
+        assert_eq!(hash_tag(b"{}x"), b"{}x");
+        assert_eq!(hash_tag(b"a{tag}b"), b"tag");
+    }
+}
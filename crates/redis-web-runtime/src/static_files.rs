@@ -0,0 +1,218 @@
+//! Static files served from `static_dir`.
+//!
+//! Lets a small app ship its HTML/JS frontend from the same process as the
+//! Redis API. Files under `static_dir.dir` are served at
+//! `static_dir.path_prefix`, with the `Content-Type` taken from the file
+//! extension, a weak `ETag` and `Last-Modified` for revalidation, and a
+//! `Cache-Control` max-age from `static_dir.max_age_sec`. A request for a
+//! directory serves its `index.html`. Paths that try to leave the directory
+//! are answered with `404`.
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use redis_web_core::config::StaticDirConfig;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A mounted static directory.
+pub struct StaticDir {
+    root: PathBuf,
+    path_prefix: String,
+    cache_control: HeaderValue,
+}
+
+impl StaticDir {
+    pub fn new(config: &StaticDirConfig) -> Self {
+        let cache_control = match config.max_age_sec {
+            0 => HeaderValue::from_static("no-cache"),
+            max_age => HeaderValue::from_str(&format!("public, max-age={max_age}"))
+                .expect("max-age is a valid header value"),
+        };
+        Self {
+            root: PathBuf::from(&config.dir),
+            path_prefix: config.path_prefix.trim_end_matches('/').to_string(),
+            cache_control,
+        }
+    }
+
+    /// URL prefix the directory is mounted at, without a trailing slash.
+    pub fn path_prefix(&self) -> &str {
+        &self.path_prefix
+    }
+}
+
+/// Handles `GET <path_prefix>/<path>`; `path` is empty for the prefix itself.
+pub async fn handle_static(files: Arc<StaticDir>, path: String, headers: HeaderMap) -> Response {
+    let mut file = files.root.clone();
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        if segment == "." || segment == ".." || segment.contains('\\') {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        file.push(segment);
+    }
+
+    let Ok(mut metadata) = tokio::fs::metadata(&file).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if metadata.is_dir() {
+        // Relative links in the index resolve against the directory only
+        // when its URL ends with a slash.
+        if !path.is_empty() && !path.ends_with('/') {
+            return Redirect::permanent(&format!("{}/{path}/", files.path_prefix)).into_response();
+        }
+        file.push("index.html");
+        metadata = match tokio::fs::metadata(&file).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+
+    let modified = metadata.modified().ok();
+    let etag = etag(metadata.len(), modified);
+    let last_modified = modified.map(httpdate::fmt_http_date);
+    if is_fresh(&headers, &etag, modified) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        set_cache_headers(&mut response, &files, &etag, last_modified.as_deref());
+        return response;
+    }
+
+    let Ok(contents) = tokio::fs::read(&file).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let mut response = Response::new(Body::from(contents));
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(content_type(extension)),
+    );
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    set_cache_headers(&mut response, &files, &etag, last_modified.as_deref());
+    response
+}
+
+fn etag(len: u64, modified: Option<SystemTime>) -> String {
+    let mtime = modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |mtime| mtime.as_nanos());
+    format!("W/\"{len:x}-{mtime:x}\"")
+}
+
+/// Whether the client's cached copy is current. `If-None-Match` takes
+/// precedence over `If-Modified-Since`.
+fn is_fresh(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|tags| {
+            tags.split(',').map(str::trim).any(|tag| {
+                tag == "*" || tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+            })
+        });
+    }
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    match (since, modified) {
+        // HTTP dates have one-second precision.
+        (Some(since), Some(modified)) => modified
+            .duration_since(since)
+            .map_or(true, |newer| newer.as_secs() == 0),
+        _ => false,
+    }
+}
+
+fn set_cache_headers(
+    response: &mut Response,
+    files: &StaticDir,
+    etag: &str,
+    last_modified: Option<&str>,
+) {
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, files.cache_control.clone());
+    if let Ok(etag) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    if let Some(last_modified) = last_modified.and_then(|value| HeaderValue::from_str(value).ok()) {
+        headers.insert(header::LAST_MODIFIED, last_modified);
+    }
+}
+
+/// `Content-Type` for a file extension; unknown extensions are served as
+/// `application/octet-stream`.
+fn content_type(extension: &str) -> &'static str {
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn content_types_follow_the_extension() {
+        assert_eq!(content_type("HTML"), "text/html; charset=utf-8");
+        assert_eq!(content_type("js"), "text/javascript; charset=utf-8");
+        assert_eq!(content_type("wasm"), "application/wasm");
+        assert_eq!(content_type(""), "application/octet-stream");
+    }
+
+    #[test]
+    fn freshness_checks_etag_before_modification_time() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let tag = etag(10, Some(modified));
+        let mut headers = HeaderMap::new();
+        assert!(!is_fresh(&headers, &tag, Some(modified)));
+
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_str(&httpdate::fmt_http_date(modified)).unwrap(),
+        );
+        assert!(is_fresh(&headers, &tag, Some(modified)));
+        assert!(!is_fresh(
+            &headers,
+            &tag,
+            Some(modified + Duration::from_secs(5))
+        ));
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!is_fresh(&headers, &tag, Some(modified)));
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(&tag).unwrap());
+        assert!(is_fresh(&headers, &tag, Some(modified)));
+    }
+}
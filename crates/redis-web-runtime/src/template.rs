@@ -0,0 +1,121 @@
+//! Request placeholders in configured command paths.
+//!
+//! The commands of `default_root` and `root_commands` may reference the
+//! client's address and request headers, so `/` can enrich what it stores
+//! without custom code:
+//!
+//! ```json
+//! {"root_commands": {"get": "INCR/visits:{ip}", "post": "LPUSH/audit/{header:User-Agent}"}}
+//! ```
+//!
+//! `{ip}` is the client IP and `{header:<name>}` the first value of a request
+//! header, empty when the header is missing. Values are percent-encoded into
+//! the path, so a `/` or `.` in them stays inside one argument. Other braces
+//! are kept as text.
+
+use axum::http::{HeaderMap, HeaderName};
+use std::fmt::Write;
+use std::net::SocketAddr;
+
+/// A command path with its placeholders parsed.
+#[derive(Debug)]
+pub struct CommandTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    Ip,
+    Header(HeaderName),
+}
+
+impl CommandTemplate {
+    pub fn parse(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let (before, after) = rest.split_at(start);
+            text.push_str(before);
+            let Some(end) = after.find('}') else {
+                rest = after;
+                break;
+            };
+            let part = match &after[1..end] {
+                "ip" => Some(Part::Ip),
+                placeholder => placeholder
+                    .strip_prefix("header:")
+                    .and_then(|name| HeaderName::try_from(name).ok())
+                    .map(Part::Header),
+            };
+            match part {
+                Some(part) => {
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(part);
+                }
+                None => text.push_str(&after[..=end]),
+            }
+            rest = &after[end + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        CommandTemplate { parts }
+    }
+
+    /// The command path for a request from `addr` with `headers`.
+    pub fn render(&self, addr: SocketAddr, headers: &HeaderMap) -> String {
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => path.push_str(text),
+                Part::Ip => encode_into(&mut path, addr.ip().to_string().as_bytes()),
+                Part::Header(name) => {
+                    if let Some(value) = headers.get(name) {
+                        encode_into(&mut path, value.as_bytes());
+                    }
+                }
+            }
+        }
+        path
+    }
+}
+
+/// Percent-encodes everything but ASCII letters, digits, `-`, `_` and `~`.
+fn encode_into(path: &mut String, value: &[u8]) {
+    for &byte in value {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'~') {
+            path.push(byte as char);
+        } else {
+            let _ = write!(path, "%{byte:02X}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis_web_core::request::decode_path_segments;
+
+    #[test]
+    fn renders_ip_and_headers_as_single_arguments() {
+        let template =
+            CommandTemplate::parse("LPUSH/audit:{ip}/{header:User-Agent}/{header:X-Missing}/{x}");
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", "curl/8.5.0".parse().unwrap());
+        let path = template.render("[::1]:4000".parse().unwrap(), &headers);
+        assert_eq!(path, "LPUSH/audit:%3A%3A1/curl%2F8%2E5%2E0//{x}");
+        assert_eq!(
+            decode_path_segments(&path),
+            vec!["LPUSH", "audit:::1", "curl/8.5.0", "{x}"]
+        );
+
+        let template = CommandTemplate::parse("GET/{header:bad name}/{unclosed");
+        let path = template.render("127.0.0.1:4000".parse().unwrap(), &headers);
+        assert_eq!(path, "GET/{header:bad name}/{unclosed");
+    }
+}
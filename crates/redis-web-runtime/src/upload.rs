@@ -0,0 +1,156 @@
+//! Streamed `PUT /SET/<key>` uploads.
+//!
+//! With `http_put_stream_chunk_size` set, a large `PUT` body is written to
+//! Redis as it arrives instead of being buffered first: the first chunk is
+//! `SET` on a temporary key, the following ones are `APPEND`ed, and a final
+//! `RENAME` moves the value onto the target key. Memory use stays around one
+//! chunk per upload and readers never see a partial value. A failed upload
+//! deletes its temporary key; one cut short by a crash leaves it behind as
+//! `<key>.upload.<id>`.
+
+use crate::handler::AppState;
+use axum::body::Body;
+use futures::StreamExt;
+use redis::Value as RedisValue;
+use redis_web_core::interfaces::{CommandExecutionError, ExecutableCommand};
+
+/// Why a streamed upload did not complete.
+pub(crate) enum UploadError {
+    /// The body exceeded `http_max_request_size`.
+    TooLarge,
+    Execution(CommandExecutionError),
+}
+
+impl From<CommandExecutionError> for UploadError {
+    fn from(error: CommandExecutionError) -> Self {
+        UploadError::Execution(error)
+    }
+}
+
+/// Whether a command parsed without its body can be streamed: a `SET` whose
+/// only argument is the key.
+pub(crate) fn is_streamable(command: &ExecutableCommand) -> bool {
+    command.command_name.eq_ignore_ascii_case("SET") && command.args.len() == 1
+}
+
+/// Streams `body` into the key of a streamable `SET`, `chunk_size` bytes per
+/// command, and returns the reply of the final `RENAME`.
+pub(crate) async fn stream_set(
+    state: &AppState,
+    command: &ExecutableCommand,
+    body: Body,
+    chunk_size: usize,
+) -> Result<RedisValue, UploadError> {
+    let mut upload = Upload {
+        state,
+        command,
+        temp_key: temp_key(&command.args[0]),
+        started: false,
+    };
+    let result = match upload.write(body, chunk_size).await {
+        Ok(()) => upload
+            .send("RENAME", vec![command.args[0].clone()])
+            .await
+            .map_err(UploadError::from),
+        Err(error) => Err(error),
+    };
+    if result.is_err() && upload.started {
+        // Best effort: a failure here leaves the temporary key behind.
+        let _ = upload.send("DEL", Vec::new()).await;
+    }
+    result
+}
+
+struct Upload<'a> {
+    state: &'a AppState,
+    command: &'a ExecutableCommand,
+    temp_key: Vec<u8>,
+    /// Whether the temporary key has been written.
+    started: bool,
+}
+
+impl Upload<'_> {
+    async fn write(&mut self, body: Body, chunk_size: usize) -> Result<(), UploadError> {
+        let mut stream = body.into_data_stream();
+        let mut pending = Vec::new();
+        let mut total = 0usize;
+        while let Some(data) = stream.next().await {
+            let data = data.map_err(|error| {
+                CommandExecutionError::Rejected(format!("Failed to read request body: {error}"))
+            })?;
+            total += data.len();
+            if total > self.state.http_max_request_size {
+                return Err(UploadError::TooLarge);
+            }
+            self.state
+                .value_size_limits
+                .check_size(&self.command.args[0], total)?;
+            let mut data = &data[..];
+            while !data.is_empty() {
+                let take = (chunk_size - pending.len()).min(data.len());
+                pending.extend_from_slice(&data[..take]);
+                data = &data[take..];
+                if pending.len() == chunk_size {
+                    self.write_chunk(std::mem::take(&mut pending)).await?;
+                }
+            }
+        }
+        if !pending.is_empty() || !self.started {
+            self.write_chunk(pending).await?;
+        }
+        Ok(())
+    }
+
+    async fn write_chunk(&mut self, chunk: Vec<u8>) -> Result<(), CommandExecutionError> {
+        let name = if self.started { "APPEND" } else { "SET" };
+        self.started = true;
+        self.send(name, vec![chunk]).await.map(drop)
+    }
+
+    /// Runs `name` on the temporary key with the target's backend, database
+    /// and timeout.
+    async fn send(
+        &self,
+        name: &str,
+        args: Vec<Vec<u8>>,
+    ) -> Result<RedisValue, CommandExecutionError> {
+        let mut command_args = vec![self.temp_key.clone()];
+        command_args.extend(args);
+        let command = ExecutableCommand {
+            backend: self.command.backend.clone(),
+            target_database: self.command.target_database,
+            command_name: name.to_string(),
+            args: command_args,
+            timeout: self.command.timeout,
+            credentials: self.command.credentials,
+        };
+        self.state.command_executor.execute(&command).await
+    }
+}
+
+/// Temporary key for an upload to `key`. It hashes to the same cluster slot,
+/// so `RENAME` works on Redis Cluster: a key without braces becomes the hash
+/// tag, and one with braces keeps its own.
+fn temp_key(key: &[u8]) -> Vec<u8> {
+    let mut temp = if key.contains(&b'{') || key.contains(&b'}') {
+        key.to_vec()
+    } else {
+        [&b"{"[..], key, b"}"].concat()
+    };
+    temp.extend_from_slice(format!(".upload.{}", uuid::Uuid::new_v4().simple()).as_bytes());
+    temp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_keys_share_the_target_hash_slot() {
+        let temp = temp_key(b"photo:1");
+        assert!(temp.starts_with(b"{photo:1}.upload."));
+        let temp = temp_key(b"{user:1}:photo");
+        assert!(temp.starts_with(b"{user:1}:photo.upload."));
+        assert_ne!(temp_key(b"photo:1"), temp_key(b"photo:1"));
+    }
+}
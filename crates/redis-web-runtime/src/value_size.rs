@@ -0,0 +1,191 @@
+//! Size guard on written values.
+//!
+//! `http_max_request_size` bounds a whole request, which cannot tell a large
+//! batch from one oversized value. With `max_value_size` or
+//! `value_size_limits` set, [`ValueSizeExecutor`] checks each value carried by
+//! a string or hash write before it reaches Redis and refuses the command as
+//! `413` when one is over the limit of its key.
+
+use redis_web_core::config::Config;
+use redis_web_core::interfaces::{
+    CommandExecutionError, CommandExecutor, CommandSession, ExecutableCommand, ExecutionFuture,
+    PipelineFuture,
+};
+use redis_web_core::routing::glob_match;
+use std::sync::Arc;
+
+/// Value size limits, per key.
+#[derive(Debug, Default)]
+pub struct ValueSizeLimits {
+    default: Option<usize>,
+    /// Key patterns with their limit, in rule order.
+    rules: Vec<(Vec<u8>, usize)>,
+}
+
+impl ValueSizeLimits {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            default: config.max_value_size,
+            rules: config
+                .value_size_limits
+                .iter()
+                .flatten()
+                .map(|rule| (rule.key_pattern.clone().into_bytes(), rule.max_bytes))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.rules.is_empty()
+    }
+
+    /// The limit of the first rule matching `key`, or `max_value_size`.
+    pub fn limit_for(&self, key: &[u8]) -> Option<usize> {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, key))
+            .map(|&(_, limit)| limit)
+            .or(self.default)
+    }
+
+    /// Refuses `command` when one of its values is over its key's limit.
+    pub fn check(&self, command: &ExecutableCommand) -> Result<(), CommandExecutionError> {
+        for (key, value) in written_values(command) {
+            self.check_size(key, value.len())?;
+        }
+        Ok(())
+    }
+
+    /// Refuses a value of `size` bytes for `key` when it is over the limit.
+    pub fn check_size(&self, key: &[u8], size: usize) -> Result<(), CommandExecutionError> {
+        match self.limit_for(key) {
+            Some(limit) if size > limit => Err(CommandExecutionError::ValueTooLarge(format!(
+                "value for key {} is {size} bytes, over the {limit}-byte limit",
+                String::from_utf8_lossy(key)
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The keys and values a write command stores. `APPEND` and `SETRANGE` count
+/// the bytes they add, not the resulting length.
+fn written_values(command: &ExecutableCommand) -> Vec<(&[u8], &[u8])> {
+    let args = &command.args;
+    let pair = |key: usize, value: usize| match (args.get(key), args.get(value)) {
+        (Some(key), Some(value)) => vec![(key.as_slice(), value.as_slice())],
+        _ => Vec::new(),
+    };
+    match command.command_name.to_ascii_uppercase().as_str() {
+        "SET" | "SETNX" | "GETSET" | "APPEND" => pair(0, 1),
+        "SETEX" | "PSETEX" | "SETRANGE" | "HSETNX" => pair(0, 2),
+        "MSET" | "MSETNX" => args
+            .chunks_exact(2)
+            .map(|pair| (pair[0].as_slice(), pair[1].as_slice()))
+            .collect(),
+        "HSET" | "HMSET" => match args.split_first() {
+            Some((key, fields)) => fields
+                .chunks_exact(2)
+                .map(|field| (key.as_slice(), field[1].as_slice()))
+                .collect(),
+            None => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Executor refusing writes with oversized values.
+pub struct ValueSizeExecutor {
+    inner: Arc<dyn CommandExecutor>,
+    limits: ValueSizeLimits,
+}
+
+impl ValueSizeExecutor {
+    /// Wraps `inner` when `max_value_size` or `value_size_limits` is set.
+    pub fn wrap(inner: Arc<dyn CommandExecutor>, config: &Config) -> Arc<dyn CommandExecutor> {
+        let limits = ValueSizeLimits::new(config);
+        if limits.is_empty() {
+            return inner;
+        }
+        Arc::new(Self { inner, limits })
+    }
+}
+
+impl CommandExecutor for ValueSizeExecutor {
+    fn execute<'a>(&'a self, request: &'a ExecutableCommand) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            self.limits.check(request)?;
+            self.inner.execute(request).await
+        })
+    }
+
+    /// Nothing runs when any command of the pipeline is refused.
+    fn execute_pipeline<'a>(&'a self, requests: &'a [ExecutableCommand]) -> PipelineFuture<'a> {
+        Box::pin(async move {
+            for request in requests {
+                self.limits.check(request)?;
+            }
+            self.inner.execute_pipeline(requests).await
+        })
+    }
+
+    fn execute_in_session<'a>(
+        &'a self,
+        session: &'a mut CommandSession,
+        request: &'a ExecutableCommand,
+    ) -> ExecutionFuture<'a> {
+        Box::pin(async move {
+            self.limits.check(request)?;
+            self.inner.execute_in_session(session, request).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    #[test]
+    fn checks_each_written_value_against_its_key_limit() {
+        let config = Config::from_value(json!({
+            "max_value_size": 4,
+            "value_size_limits": [{"key_pattern": "blob:*", "max_bytes": 8}]
+        }))
+        .unwrap();
+        let limits = ValueSizeLimits::new(&config);
+
+        assert!(limits.check(&command("SET", &["k", "1234"])).is_ok());
+        assert!(limits
+            .check(&command("set", &["blob:1", "12345678"]))
+            .is_ok());
+        assert!(limits.check(&command("GET", &["k", "123456789"])).is_ok());
+        assert!(limits
+            .check(&command("HSET", &["h", "a", "1", "big-field-name", "2"]))
+            .is_ok());
+        let error = limits
+            .check(&command("MSET", &["blob:1", "1", "k", "12345"]))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "value for key k is 5 bytes, over the 4-byte limit"
+        );
+        assert!(limits
+            .check(&command("SETEX", &["k", "60", "12345"]))
+            .is_err());
+        assert!(limits
+            .check(&command("HMSET", &["h", "a", "12345"]))
+            .is_err());
+    }
+}
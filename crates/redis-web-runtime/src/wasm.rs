@@ -0,0 +1,409 @@
+//! Command hooks loaded from the WebAssembly modules listed in `hooks`.
+//!
+//! A module talks to the server through a small ABI (version 1) of plain
+//! exports; it may not import anything:
+//!
+//! - `memory`: the linear memory inputs and outputs live in.
+//! - `redis_web_alloc(len: i32) -> i32`: returns the address of `len` free
+//!   bytes, where the server writes the input of the next call.
+//! - `redis_web_before(ptr: i32, len: i32) -> i64`, optional: receives the
+//!   command as a RESP array of bulk strings, its name first.
+//! - `redis_web_after(ptr: i32, len: i32) -> i64`, optional: receives the
+//!   command array followed by the reply, also in RESP.
+//!
+//! Both hooks return `0` to leave their input as it was, or the address of
+//! their output in the high 32 bits and its length in the low 32 bits. The
+//! output of `redis_web_before` is either a new command array or a RESP
+//! error line, whose message refuses the command; that of `redis_web_after`
+//! is the new reply. Each call runs in a fresh instance with `fuel` to
+//! spend and at most [`MAX_MEMORY`] bytes of memory, so modules keep no
+//! state between commands. Calls run synchronously on the worker thread
+//! handling the command, so `fuel`, capped at [`MAX_FUEL`], also bounds how
+//! long a hook holds that thread.
+
+use redis::Value as RedisValue;
+use redis_web_core::config::{Config, HookConfig};
+use redis_web_core::interfaces::{CommandHook, ExecutableCommand};
+use redis_web_core::resp;
+use std::sync::Arc;
+use tracing::warn;
+use wasmtime::{
+    Engine, InstancePre, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc,
+};
+
+/// Fuel of a call when its `hooks` entry sets none.
+const DEFAULT_FUEL: u64 = 10_000_000;
+
+/// The most fuel a `hooks` entry may set, about a second of work.
+pub const MAX_FUEL: u64 = 1_000_000_000;
+
+/// Linear memory one call may grow to.
+pub const MAX_MEMORY: usize = 64 << 20;
+
+const ALLOC: &str = "redis_web_alloc";
+const BEFORE: &str = "redis_web_before";
+const AFTER: &str = "redis_web_after";
+
+/// Compiles the modules listed in `hooks`, in order.
+pub fn load(config: &Config) -> Result<Vec<Arc<dyn CommandHook>>, String> {
+    let entries = config.hooks.as_deref().unwrap_or_default();
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut engine_config = wasmtime::Config::new();
+    engine_config.consume_fuel(true);
+    let engine = Engine::new(&engine_config).map_err(|error| error.to_string())?;
+    entries
+        .iter()
+        .map(|entry| {
+            WasmHook::new(&engine, entry)
+                .map(|hook| Arc::new(hook) as Arc<dyn CommandHook>)
+                .map_err(|error| format!("{}: {error}", entry.path))
+        })
+        .collect()
+}
+
+/// One compiled `hooks` module.
+struct WasmHook {
+    path: String,
+    engine: Engine,
+    module: InstancePre<StoreLimits>,
+    fuel: u64,
+    before: bool,
+    after: bool,
+}
+
+impl WasmHook {
+    fn new(engine: &Engine, entry: &HookConfig) -> Result<Self, String> {
+        let fuel = entry.fuel.unwrap_or(DEFAULT_FUEL);
+        if fuel > MAX_FUEL {
+            return Err(format!("fuel must be at most {MAX_FUEL}"));
+        }
+        let module =
+            Module::from_file(engine, &entry.path).map_err(|error| format!("{error:#}"))?;
+        let exports = |name: &str| module.get_export(name).is_some();
+        let (before, after) = (exports(BEFORE), exports(AFTER));
+        if !before && !after {
+            return Err(format!("exports neither {BEFORE} nor {AFTER}"));
+        }
+        let module = Linker::new(engine)
+            .instantiate_pre(&module)
+            .map_err(|error| format!("must not import anything: {error}"))?;
+        let hook = Self {
+            path: entry.path.clone(),
+            engine: engine.clone(),
+            module,
+            fuel,
+            before,
+            after,
+        };
+        // Checks the exports' types once rather than on every command.
+        for (present, export) in [(before, BEFORE), (after, AFTER)] {
+            if present {
+                hook.instantiate(export)?;
+            }
+        }
+        Ok(hook)
+    }
+
+    /// A fresh instance with its memory, allocator and `export`.
+    fn instantiate(&self, export: &str) -> Result<Instance, String> {
+        let error = |error: wasmtime::Error| error.root_cause().to_string();
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel).map_err(error)?;
+        let instance = self.module.instantiate(&mut store).map_err(error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("exports no memory")?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, ALLOC)
+            .map_err(error)?;
+        let hook = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, export)
+            .map_err(error)?;
+        Ok(Instance {
+            store,
+            memory,
+            alloc,
+            hook,
+        })
+    }
+
+    /// Calls `export` on `input`; `None` keeps the input.
+    fn call(&self, export: &str, input: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let error = |error: wasmtime::Error| error.root_cause().to_string();
+        let Instance {
+            mut store,
+            memory,
+            alloc,
+            hook,
+        } = self.instantiate(export)?;
+        let len = i32::try_from(input.len()).map_err(|_| "input too large")?;
+        let ptr = alloc.call(&mut store, len).map_err(error)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|error| error.to_string())?;
+        let packed = hook.call(&mut store, (ptr, len)).map_err(error)? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        // Checked before allocating, so a bad length cannot claim host memory.
+        if ptr
+            .checked_add(len)
+            .is_none_or(|end| end > memory.data_size(&store))
+        {
+            return Err("returned output outside its memory".to_string());
+        }
+        Ok(Some(memory.data(&store)[ptr..ptr + len].to_vec()))
+    }
+}
+
+impl CommandHook for WasmHook {
+    fn before(&self, request: &mut ExecutableCommand) -> Result<(), String> {
+        if !self.before {
+            return Ok(());
+        }
+        let output = self
+            .call(BEFORE, &command_to_resp(request))
+            .map_err(|error| format!("hook {} failed: {error}", self.path))?;
+        let Some(output) = output else {
+            return Ok(());
+        };
+        if let Some(message) = output.strip_prefix(b"-") {
+            let message = message.strip_suffix(b"\r\n").unwrap_or(message);
+            return Err(String::from_utf8_lossy(message).into_owned());
+        }
+        match resp::parse_command(&output) {
+            Ok(Some((mut args, _))) if !args.is_empty() => {
+                request.command_name = String::from_utf8_lossy(&args.remove(0)).into_owned();
+                request.args = args;
+                Ok(())
+            }
+            _ => Err(format!(
+                "hook {} returned neither a command nor an error",
+                self.path
+            )),
+        }
+    }
+
+    fn after(&self, request: &ExecutableCommand, reply: &mut RedisValue) {
+        if !self.after {
+            return;
+        }
+        let mut input = command_to_resp(request);
+        input.extend_from_slice(&resp::value_to_resp(reply));
+        let replaced = self.call(AFTER, &input).and_then(|output| {
+            output
+                .map(|output| redis::parse_redis_value(&output).map_err(|error| error.to_string()))
+                .transpose()
+        });
+        match replaced {
+            Ok(Some(value)) => *reply = value,
+            Ok(None) => {}
+            // The reply goes out unchanged; `after` cannot fail the command.
+            Err(error) => warn!(
+                "Hook {} failed on the reply to {}: {}",
+                self.path, request.command_name, error
+            ),
+        }
+    }
+}
+
+/// A module instance ready for one call.
+struct Instance {
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    hook: TypedFunc<(i32, i32), i64>,
+}
+
+/// The RESP array of the command name and its arguments.
+fn command_to_resp(command: &ExecutableCommand) -> Vec<u8> {
+    let items = std::iter::once(command.command_name.as_bytes().to_vec())
+        .chain(command.args.iter().cloned())
+        .map(RedisValue::BulkString)
+        .collect();
+    resp::value_to_resp(&RedisValue::Array(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bump allocator and a hook returning the bytes of a data segment.
+    fn module(hooks: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 4096))
+                (func (export "redis_web_alloc") (param $len i32) (result i32)
+                  (global.get $next)
+                  (global.set $next (i32.add (global.get $next) (local.get $len))))
+                {hooks})"#
+        )
+    }
+
+    fn hook(source: &str, fuel: Option<u64>) -> Result<Arc<dyn CommandHook>, String> {
+        let file = std::env::temp_dir().join(format!(
+            "redis-web-hook-{}.wat",
+            uuid::Uuid::new_v4().simple()
+        ));
+        std::fs::write(&file, source).unwrap();
+        let mut config = Config::default();
+        config.hooks = Some(vec![HookConfig {
+            path: file.to_string_lossy().into_owned(),
+            fuel,
+        }]);
+        let loaded = load(&config);
+        std::fs::remove_file(&file).unwrap();
+        loaded.map(|mut hooks| hooks.remove(0))
+    }
+
+    fn command(name: &str, args: &[&str]) -> ExecutableCommand {
+        ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: name.to_string(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        }
+    }
+
+    /// Returns `output`, stored at address 16, from `export`.
+    fn returning(export: &str, output: &str) -> String {
+        format!(
+            r#"(data (i32.const 16) "{}")
+               (func (export "{export}") (param i32 i32) (result i64)
+                 (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const {})))"#,
+            output.replace("\r\n", "\\0d\\0a"),
+            output.len()
+        )
+    }
+
+    #[test]
+    fn before_rewrites_and_refuses_commands() {
+        let rewrite = hook(
+            &module(&returning(
+                BEFORE,
+                "*2\r\n$3\r\nGET\r\n$10\r\ntenant:key\r\n",
+            )),
+            None,
+        )
+        .unwrap();
+        let mut get = command("GET", &["key"]);
+        rewrite.before(&mut get).unwrap();
+        assert_eq!(get.command_name, "GET");
+        assert_eq!(get.args, [b"tenant:key".to_vec()]);
+
+        let refuse = hook(
+            &module(&returning(BEFORE, "-FLUSHALL is disabled\r\n")),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            refuse.before(&mut command("FLUSHALL", &[])),
+            Err("FLUSHALL is disabled".to_string())
+        );
+    }
+
+    #[test]
+    fn after_rewrites_replies() {
+        let redact = hook(&module(&returning(AFTER, "$8\r\nredacted\r\n")), None).unwrap();
+        let mut reply = RedisValue::BulkString(b"secret".to_vec());
+        redact.after(&command("GET", &["key"]), &mut reply);
+        assert_eq!(reply, RedisValue::BulkString(b"redacted".to_vec()));
+    }
+
+    #[test]
+    fn hooks_see_the_command_in_resp() {
+        // Echoes its input back, so the command comes out unchanged.
+        let echo = hook(
+            &module(
+                r#"(func (export "redis_web_before") (param $ptr i32) (param $len i32) (result i64)
+                     (i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                             (i64.extend_i32_u (local.get $len))))"#,
+            ),
+            None,
+        )
+        .unwrap();
+        let mut set = command("SET", &["key", "a\r\nb"]);
+        echo.before(&mut set).unwrap();
+        assert_eq!(set.command_name, "SET");
+        assert_eq!(set.args, [b"key".to_vec(), b"a\r\nb".to_vec()]);
+    }
+
+    #[test]
+    fn runaway_hooks_refuse_the_command() {
+        let spin = hook(
+            &module(
+                r#"(func (export "redis_web_before") (param i32 i32) (result i64)
+                     (loop $spin (br_if $spin (i32.const 1)))
+                     (i64.const 0))"#,
+            ),
+            Some(1_000),
+        )
+        .unwrap();
+        let error = spin.before(&mut command("GET", &["key"])).unwrap_err();
+        assert!(error.contains("fuel"), "{error}");
+    }
+
+    #[test]
+    fn hooks_stay_inside_their_memory() {
+        // Claims 4 GiB of output from a 64 KiB memory.
+        let overrun = hook(
+            &module(
+                r#"(func (export "redis_web_before") (param i32 i32) (result i64)
+                     (i64.const 0xffffffff))"#,
+            ),
+            None,
+        )
+        .unwrap();
+        let error = overrun.before(&mut command("GET", &["key"])).unwrap_err();
+        assert!(error.contains("outside its memory"), "{error}");
+
+        // Refuses the command only when growing past the cap fails.
+        let grow = hook(
+            &module(
+                r#"(data (i32.const 16) "-denied\0d\0a")
+                   (func (export "redis_web_before") (param i32 i32) (result i64)
+                     (if (result i64) (i32.eq (memory.grow (i32.const 2048)) (i32.const -1))
+                       (then (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 9)))
+                       (else (i64.const 0))))"#,
+            ),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            grow.before(&mut command("GET", &["key"])),
+            Err("denied".to_string())
+        );
+    }
+
+    #[test]
+    fn fuel_is_capped() {
+        let error = hook(&module(&returning(AFTER, "+OK\r\n")), Some(MAX_FUEL + 1))
+            .err()
+            .unwrap();
+        assert!(error.contains("fuel must be at most"), "{error}");
+    }
+
+    #[test]
+    fn modules_need_a_hook_and_no_imports() {
+        let none = hook(&module(""), None);
+        assert!(none.err().unwrap().contains("exports neither"));
+
+        let imports = hook(
+            r#"(module
+                 (import "env" "log" (func))
+                 (memory (export "memory") 1)
+                 (func (export "redis_web_alloc") (param i32) (result i32) (i32.const 0))
+                 (func (export "redis_web_before") (param i32 i32) (result i64) (i64.const 0)))"#,
+            None,
+        );
+        assert!(imports.err().unwrap().contains("must not import anything"));
+    }
+}
@@ -1,138 +1,907 @@
-use crate::handler::redis_value_to_json;
-use crate::handler::AppState;
+use crate::admission;
+use crate::errors::execution_error_code;
+use crate::executor::changes_session;
+use crate::handler::redis_value_to_json_with_nil;
+use crate::handler::{execution_error_status, raw_error_frame, shut_down, AppState};
+use crate::pubsub::{gap_notice_json, stream_item_json, subscription_items, StreamItem, Topic};
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Query, State,
     },
+    http::{header, HeaderMap, StatusCode},
     response::Response,
 };
+use base64::{engine::general_purpose, Engine as _};
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
 use futures::{sink::SinkExt, stream::StreamExt};
-use redis_web_core::interfaces::{CommandExecutionError, ExecutableCommand};
+use redis::Value as RedisValue;
+use redis_web_core::acl::{basic_credentials, Acl};
+use redis_web_core::config::JsonNil;
+use redis_web_core::format::OutputFormat;
+use redis_web_core::interfaces::{
+    AuthContext, CommandExecutionError, CommandSession, ExecutableCommand,
+};
+use redis_web_core::resp::{error_to_resp, value_to_resp};
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+use tokio_stream::StreamMap;
+
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let auth = upgrade_auth(addr, &headers, &params);
+    // `?binary=1` sends bulk string replies as binary frames by default.
+    let format = match params.get("binary").map(String::as_str) {
+        Some("1" | "true") => OutputFormat::Text,
+        _ => OutputFormat::Json,
+    };
+    with_limits(ws, &state).on_upgrade(move |socket| handle_socket(socket, state, auth, format))
+}
+
+/// Credentials presented at upgrade: the `Authorization` header, or `?token=`
+/// carrying the same base64 `user:password` for browsers, which cannot set
+/// headers on WebSocket requests.
+fn upgrade_auth(
+    addr: SocketAddr,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+) -> AuthContext {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| params.get("token").map(|token| format!("Basic {token}")));
+    AuthContext {
+        client_ip: addr.ip(),
+        authorization,
+    }
+}
+
+/// Handles `AUTH user password` sent over the socket. On success, the
+/// credentials replace those of the upgrade for the `acl` checks of later
+/// commands.
+fn authenticate(
+    acl: &Acl,
+    auth: &mut AuthContext,
+    args: &[impl AsRef<[u8]>],
+) -> Result<(), &'static str> {
+    let [user, password] = args else {
+        return Err("wrong number of arguments for 'auth' command");
+    };
+    let credentials = [user.as_ref(), password.as_ref()].join(&b':');
+    let credentials = String::from_utf8(credentials)
+        .ok()
+        .filter(|credentials| acl.knows_credentials(credentials))
+        .ok_or("invalid username-password pair")?;
+    auth.authorization = Some(format!(
+        "Basic {}",
+        general_purpose::STANDARD.encode(credentials)
+    ));
+    Ok(())
+}
 
-pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+/// Whether the socket may run commands yet: always, unless
+/// `websocket_require_auth` is set and the upgrade carried no credentials of
+/// an `http_basic_auth` rule.
+fn authenticated_at_upgrade(state: &AppState, auth: &AuthContext) -> bool {
+    !state.websocket_require_auth
+        || auth
+            .authorization
+            .as_deref()
+            .and_then(basic_credentials)
+            .is_some_and(|credentials| state.acl.knows_credentials(&credentials))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+/// Applies `websocket_max_message_size` and `websocket_max_frame_size`, so
+/// oversized frames fail on their header instead of being buffered.
+fn with_limits(ws: WebSocketUpgrade, state: &AppState) -> WebSocketUpgrade {
+    ws.max_message_size(state.websocket_max_message_size)
+        .max_frame_size(state.websocket_max_frame_size)
+}
+
+/// The close frame for a read that failed on the size limits, if it did.
+fn size_limit_close(err: axum::Error) -> Option<CloseFrame> {
+    let err = err.into_inner();
+    matches!(
+        err.downcast_ref::<tungstenite::Error>(),
+        Some(tungstenite::Error::Capacity(_))
+    )
+    .then(message_too_big)
+}
+
+fn message_too_big() -> CloseFrame {
+    close_frame(close_code::SIZE, "message_too_big")
+}
+
+/// Close code after a failed `AUTH`. Codes 4000-4999 are left to applications.
+const CLOSE_AUTH_FAILED: u16 = 4001;
+/// Close code after the `acl` rules denied a command or subscription.
+const CLOSE_FORBIDDEN: u16 = 4003;
+
+fn close_frame(code: u16, reason: &'static str) -> CloseFrame {
+    CloseFrame {
+        code,
+        reason: reason.into(),
+    }
+}
+
+/// Builds the error frame of `/.json`: `{"error": {"code": ..., "message": ...}}`.
+fn error_frame(code: &str, message: impl std::fmt::Display) -> serde_json::Value {
+    serde_json::json!({"error": {"code": code, "message": message.to_string()}})
+}
+
+/// Main loop for JSON WebSocket connections.
+///
+/// Commands and subscriptions share the socket. Replies, subscription
+/// confirmations, and Pub/Sub messages are all produced by this loop, so each
+/// confirmation precedes the messages of its topic and frames never overlap.
+async fn handle_socket(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    mut auth: AuthContext,
+    format: OutputFormat,
+) {
     let (mut sender, mut receiver) = socket.split();
-    let (tx, mut rx) = mpsc::channel::<Message>(100);
+    let (tx, mut rx) = mpsc::channel::<Frame>(state.websocket_send_queue_size);
+    let slow_client_timeout = state.sse_limits.slow_client_timeout();
+    let mut out = Outbox {
+        tx,
+        slow_client_timeout,
+    };
+    // Signalled when frames stayed queued past the slow-client timeout.
+    let too_slow = Arc::new(Notify::new());
 
-    // Spawn a task to forward messages from the mpsc channel to the websocket sender
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            if sender.send(msg).await.is_err() {
+    // Spawn a task to forward frames from the mpsc channel to the websocket
+    // sender. Under `drop_oldest` it keeps receiving while a write is pending,
+    // so a full queue gives up its oldest Pub/Sub messages.
+    let close_slow = too_slow.clone();
+    let limits = state.sse_limits.clone();
+    let mut queue = FrameQueue::new(state.websocket_send_queue_size);
+    let mut writer = tokio::spawn(async move {
+        let mut receiving = true;
+        loop {
+            let frame = match queue.pop() {
+                Some(frame) => frame,
+                None if !receiving => break,
+                None => tokio::select! {
+                    frame = rx.recv() => match frame {
+                        Some(frame) => frame,
+                        None => break,
+                    },
+                    _ = close_slow.notified() => {
+                        limits.record_slow_disconnect();
+                        let close = Message::Close(Some(close_frame(close_code::POLICY, "slow_client")));
+                        let _ = tokio::time::timeout(Duration::from_secs(1), sender.send(close)).await;
+                        break;
+                    }
+                },
+            };
+            let send = sender.send(frame.into_message());
+            tokio::pin!(send);
+            let sent = loop {
+                tokio::select! {
+                    sent = &mut send => break sent.is_ok(),
+                    frame = rx.recv(), if receiving && slow_client_timeout.is_none() => match frame {
+                        Some(frame) => queue.push(frame, &limits),
+                        None => receiving = false,
+                    },
+                    _ = close_slow.notified() => {
+                        limits.record_slow_disconnect();
+                        break false;
+                    }
+                }
+            };
+            if !sent {
                 break;
             }
         }
     });
 
-    while let Some(msg) = receiver.next().await {
-        let msg = if let Ok(msg) = msg {
-            msg
-        } else {
-            // client disconnected
-            return;
-        };
+    let mut subscriptions: StreamMap<Topic, SubscriptionItems> = StreamMap::new();
+    // Commands share one Redis connection so MULTI/EXEC and WATCH work.
+    let mut session = CommandSession::new();
+    let mut idle = IdleRelease::new(&state);
+    let mut keepalive = Keepalive::new(&state);
+    let mut shutdown = state.shutdown.subscribe();
+    // Id-tagged commands running beside the ordered ones, up to
+    // `websocket_max_inflight_commands`; each resolves to its reply.
+    let mut inflight: FuturesUnordered<BoxFuture<'static, Message>> = FuturesUnordered::new();
+    // `AUTH` is only handled here when the `acl` rules have credentials to
+    // check; otherwise it reaches Redis like any other command.
+    let intercept_auth = state.acl.has_basic_auth();
+    let mut authenticated = authenticated_at_upgrade(&state, &auth);
 
-        if let Message::Text(text) = msg {
-            // Parse message as JSON array: ["COMMAND", "arg1", "arg2"]
-            if let Ok(parsed) = serde_json::from_str::<Vec<String>>(&text) {
-                if parsed.is_empty() {
-                    continue;
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = shut_down(&mut shutdown) => {
+                let close = close_frame(close_code::AWAY, "server_shutdown");
+                let _ = out.tx.send(Frame::Control(Message::Close(Some(close)))).await;
+                break;
+            }
+            Some(done) = inflight.next(), if !inflight.is_empty() => {
+                match out.send(done).await {
+                    Queued::Sent => continue,
+                    Queued::TooSlow => too_slow.notify_one(),
+                    Queued::Closed => {}
                 }
+                break;
+            }
+            Some((topic, item)) = subscriptions.next(), if !subscriptions.is_empty() => {
+                keepalive.active();
+                match out.send_item(topic, &item).await {
+                    Queued::Sent => continue,
+                    Queued::TooSlow => too_slow.notify_one(),
+                    Queued::Closed => {}
+                }
+                break;
+            }
+            // The writer only stops early for a slow or vanished client.
+            _ = &mut writer => break,
+            _ = until(idle.deadline(&session)) => {
+                session.release_idle();
+                continue;
+            }
+            _ = until(keepalive.deadline()) => {
+                let (frame, closing) = match keepalive.expire() {
+                    Some(KeepaliveEvent::Ping) => (Message::Ping(Default::default()), false),
+                    Some(KeepaliveEvent::Close(frame)) => (Message::Close(Some(frame)), true),
+                    None => continue,
+                };
+                if out.tx.send(Frame::Control(frame)).await.is_err() || closing {
+                    break;
+                }
+                continue;
+            }
+        };
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(err)) => {
+                if let Some(frame) = size_limit_close(err) {
+                    let _ = out
+                        .tx
+                        .send(Frame::Control(Message::Close(Some(frame))))
+                        .await;
+                }
+                break;
+            }
+            // client disconnected
+            None => break,
+        };
+        keepalive.received(&msg);
 
-                let cmd_name = &parsed[0];
-                let args = &parsed[1..];
+        let Message::Text(text) = msg else {
+            continue;
+        };
+        // Parse message as JSON array: ["COMMAND", "arg1", "arg2"], optionally
+        // wrapped with an id: {"id": 7, "cmd": ["COMMAND", "arg1"]}
+        let Some(frame) = CommandFrame::parse(&text).filter(|frame| !frame.parsed.is_empty())
+        else {
+            let error = error_frame(
+                "invalid_frame",
+                r#"expected ["COMMAND", ...] or {"cmd": ["COMMAND", ...]}"#,
+            );
+            match out.send(json_frame(error)).await {
+                Queued::Sent => continue,
+                Queued::TooSlow => too_slow.notify_one(),
+                Queued::Closed => {}
+            }
+            break;
+        };
+        idle.command();
+        let CommandFrame { id, parsed, .. } = &frame;
 
-                // Check ACL (TODO: Need IP here, but WebSocketUpgrade doesn't provide it easily without wrapper)
-                // For now, skipping ACL check for WS or assuming allow.
+        let cmd_name = &parsed[0];
+        let args = &parsed[1..];
+        let mut replies = Vec::new();
+        let reply = |frame| json_frame(with_id(frame, id));
+        // Set when the socket closes after this command's replies.
+        let mut close = None;
 
-                if cmd_name.eq_ignore_ascii_case("SUBSCRIBE") {
-                    if args.is_empty() {
-                        continue;
+        // Commands other than the socket's own go through `routes` and the
+        // `acl` rules of their backend, like on the command URLs.
+        let started = std::time::Instant::now();
+        let mut command = ExecutableCommand {
+            backend: None,
+            target_database: state.default_database,
+            command_name: cmd_name.clone(),
+            args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
+            timeout: None,
+            credentials: None,
+        };
+        let handled = socket_command(cmd_name)
+            .filter(|command| intercept_auth || !matches!(command, SocketCommand::Auth));
+        // Hooks see the commands bound for Redis as the client sent them.
+        let hooked = match handled {
+            None if authenticated => state.hooks.before(&mut command),
+            _ => Ok(()),
+        };
+        admission::route(&state, &auth, &mut command);
+        match handled {
+            Some(SocketCommand::Auth) => {
+                replies.push(reply(match authenticate(&state.acl, &mut auth, args) {
+                    Ok(()) => {
+                        authenticated = true;
+                        serde_json::json!({cmd_name: "OK"})
                     }
-                    let channel = args[0].clone();
-                    let mut pubsub_rx = state.pubsub.subscribe(channel).await;
-                    let tx_clone = tx.clone();
-
-                    // Spawn a task to forward Pub/Sub messages to the websocket
-                    tokio::spawn(async move {
-                        loop {
-                            match pubsub_rx.recv().await {
-                                Ok(msg) => {
-                                    let response = serde_json::json!({"message": msg}); // Webdis format?
-                                                                                        // Webdis format for pubsub: {"SUBSCRIBE":["message","channel","payload"]}
-                                                                                        // Actually, Webdis C format is: {"SUBSCRIBE": ["message", "channel", "payload"]}
-                                                                                        // But my PubSubManager only sends payload.
-                                                                                        // I should probably include channel in the broadcast message or change PubSubManager.
-                                                                                        // For now, let's just send the payload as a string or JSON.
-                                                                                        // Let's wrap it: {"message": payload}
-                                    if tx_clone
-                                        .send(Message::Text(response.to_string().into()))
-                                        .await
-                                        .is_err()
-                                    {
-                                        break;
-                                    }
-                                }
-                                Err(_) => break,
-                            }
+                    Err(e) => {
+                        close = Some(close_frame(CLOSE_AUTH_FAILED, "auth_failed"));
+                        error_frame("auth_failed", e)
+                    }
+                }));
+            }
+            _ if !authenticated => {
+                replies.push(reply(error_frame(
+                    "auth_required",
+                    "Authentication required",
+                )));
+            }
+            Some(SocketCommand::Subscribe(topic_for)) => {
+                for name in args {
+                    let topic = topic_for(name.clone());
+                    if !state
+                        .acl
+                        .check_channel(&auth, topic.subscribe_command(), topic.name())
+                    {
+                        replies.push(reply(error_frame("forbidden", "Forbidden")));
+                        close = Some(close_frame(CLOSE_FORBIDDEN, "forbidden"));
+                        break;
+                    }
+                    if !subscriptions.contains_key(&topic) {
+                        let subscription = state.pubsub.subscribe_topic(topic.clone()).await;
+                        subscriptions.insert(topic.clone(), subscription_items(subscription));
+                    }
+                    replies.push(reply(subscription_reply(&topic, true, subscriptions.len())));
+                }
+            }
+            Some(SocketCommand::Unsubscribe(topic_for)) => {
+                // Without arguments, leave every topic of that kind, like Redis.
+                let kind = topic_for(String::new());
+                let topics: Vec<Topic> = if args.is_empty() {
+                    subscriptions
+                        .keys()
+                        .filter(|topic| {
+                            std::mem::discriminant(*topic) == std::mem::discriminant(&kind)
+                        })
+                        .cloned()
+                        .collect()
+                } else {
+                    args.iter().map(|name| topic_for(name.clone())).collect()
+                };
+                if topics.is_empty() {
+                    // Redis confirms even when nothing was subscribed.
+                    let key = kind.unsubscribe_command();
+                    replies.push(reply(
+                        serde_json::json!({ key: [key.to_ascii_lowercase(), null, 0] }),
+                    ));
+                }
+                for topic in topics {
+                    // Dropping the stream drops its subscription, which lets the
+                    // manager unsubscribe from Redis once nobody else listens.
+                    subscriptions.remove(&topic);
+                    replies.push(reply(subscription_reply(
+                        &topic,
+                        false,
+                        subscriptions.len(),
+                    )));
+                }
+            }
+            None if hooked.is_err() => {
+                if let Err(error) = &hooked {
+                    let status = execution_error_status(error);
+                    admission::audit(&state, &auth, &command, status, started);
+                }
+                let refused = hooked.map(|()| RedisValue::Nil);
+                let format = frame.format.unwrap_or(format);
+                replies.push(command_reply(refused, cmd_name, id, format, state.json_nil));
+            }
+            None if !admission::authorize(&state, &auth, &command) => {
+                admission::audit(&state, &auth, &command, StatusCode::FORBIDDEN, started);
+                replies.push(reply(error_frame("forbidden", "Forbidden")));
+                close = Some(close_frame(CLOSE_FORBIDDEN, "forbidden"));
+            }
+            None => match admission::charge(&state, &auth, 1).await {
+                Err(retry_after) => {
+                    replies.push(reply(error_frame(
+                        "quota_exceeded",
+                        format!("Usage quota exceeded, retry after {retry_after} seconds"),
+                    )));
+                }
+                Ok(charge) => {
+                    let format = frame.format.unwrap_or(format);
+                    // Only id-tagged JSON replies can be told apart out of order,
+                    // and transactions and connection state need the socket's
+                    // own connection, so an `id` never changes which database
+                    // or Redis user serves a command.
+                    if id.is_some()
+                        && format == OutputFormat::Json
+                        && state.websocket_max_inflight_commands > 1
+                        && !session.is_held()
+                        && !session.is_stateful()
+                        && !changes_session(cmd_name)
+                    {
+                        while inflight.len() >= state.websocket_max_inflight_commands {
+                            replies.extend(inflight.next().await);
                         }
-                    });
-                    continue;
+                        let (state, auth) = (state.clone(), auth.clone());
+                        let (id, cmd_name) = (id.clone(), cmd_name.clone());
+                        inflight.push(Box::pin(async move {
+                            let result = admission::execute(&state, &command).await;
+                            let status = admission::outcome_status(&result);
+                            admission::audit(&state, &auth, &command, status, started);
+                            let reply = command_reply(
+                                result,
+                                &cmd_name,
+                                &id,
+                                OutputFormat::Json,
+                                state.json_nil,
+                            );
+                            charge.record_bytes(&state, frame_len(&reply));
+                            reply
+                        }));
+                    } else {
+                        // Ordered commands wait for concurrent ones to finish first.
+                        while let Some(done) = inflight.next().await {
+                            replies.push(done);
+                        }
+                        let result =
+                            admission::execute_in_session(&state, &mut session, &command).await;
+                        let status = admission::outcome_status(&result);
+                        admission::audit(&state, &auth, &command, status, started);
+                        let reply = command_reply(result, cmd_name, id, format, state.json_nil);
+                        charge.record_bytes(&state, frame_len(&reply));
+                        replies.push(reply);
+                    }
                 }
+            },
+        }
 
-                let command = ExecutableCommand {
-                    target_database: state.default_database,
-                    command_name: cmd_name.clone(),
-                    args: args.iter().map(|arg| arg.as_bytes().to_vec()).collect(),
-                };
+        for reply in replies
+            .into_iter()
+            .chain(close.map(|frame| Message::Close(Some(frame))))
+        {
+            let closing = matches!(reply, Message::Close(_));
+            match out.send(reply).await {
+                Queued::Sent if closing => return,
+                Queued::Sent => {}
+                Queued::TooSlow => {
+                    too_slow.notify_one();
+                    return;
+                }
+                Queued::Closed => return,
+            }
+        }
+    }
+}
 
-                match state.command_executor.execute(&command).await {
-                    Ok(val) => {
-                        let json_val = redis_value_to_json(val);
-                        let response = serde_json::json!({cmd_name: json_val});
-                        // Axum 0.8 requires Utf8Bytes for Message::Text; .into() handles the conversion from String.
-                        let _ = tx.send(Message::Text(response.to_string().into())).await;
-                    }
-                    Err(e) => {
-                        let _ = tx
-                            .send(Message::Text(
-                                serde_json::json!({"error": e.to_string()})
-                                    .to_string()
-                                    .into(),
-                            ))
-                            .await;
-                    }
+type SubscriptionItems = std::pin::Pin<Box<dyn futures::Stream<Item = StreamItem> + Send>>;
+
+/// The queue in front of the socket writer.
+struct Outbox {
+    tx: mpsc::Sender<Frame>,
+    /// Set under the `disconnect` slow-client policy.
+    slow_client_timeout: Option<Duration>,
+}
+
+enum Queued {
+    Sent,
+    /// The queue stayed full for the slow-client timeout.
+    TooSlow,
+    Closed,
+}
+
+impl Outbox {
+    async fn send(&mut self, msg: Message) -> Queued {
+        self.queue(Frame::Control(msg)).await
+    }
+
+    /// Queues a Pub/Sub item, which the writer may drop under `drop_oldest`.
+    async fn send_item(&mut self, topic: Topic, item: &StreamItem) -> Queued {
+        self.queue(Frame::Item(topic, json_frame(stream_item_json(item))))
+            .await
+    }
+
+    async fn queue(&mut self, frame: Frame) -> Queued {
+        let Some(timeout) = self.slow_client_timeout else {
+            return match self.tx.send(frame).await {
+                Ok(()) => Queued::Sent,
+                Err(_) => Queued::Closed,
+            };
+        };
+        match self.tx.send_timeout(frame, timeout).await {
+            Ok(()) => Queued::Sent,
+            Err(mpsc::error::SendTimeoutError::Timeout(_)) => Queued::TooSlow,
+            Err(mpsc::error::SendTimeoutError::Closed(_)) => Queued::Closed,
+        }
+    }
+}
+
+/// A frame on its way to the socket.
+enum Frame {
+    /// Replies, confirmations and control frames, which are never dropped.
+    Control(Message),
+    /// A Pub/Sub message of a topic.
+    Item(Topic, Message),
+    /// Tells the client that a topic lost messages.
+    Dropped(Topic),
+}
+
+impl Frame {
+    fn into_message(self) -> Message {
+        match self {
+            Frame::Control(msg) | Frame::Item(_, msg) => msg,
+            Frame::Dropped(topic) => json_frame(gap_notice_json(&topic, "dropped")),
+        }
+    }
+}
+
+/// Frames the writer holds while the socket is busy, under `drop_oldest`.
+/// Past `capacity`, the oldest Pub/Sub messages give way to newer frames, and
+/// the first message a topic loses turns into its `dropped` notice, so the
+/// notice precedes the messages that follow the gap. Notices, at most one
+/// pending per topic, do not count against the capacity.
+struct FrameQueue {
+    frames: VecDeque<Frame>,
+    capacity: usize,
+    notices: usize,
+}
+
+impl FrameQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            capacity,
+            notices: 0,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Frame> {
+        let frame = self.frames.pop_front()?;
+        if matches!(frame, Frame::Dropped(_)) {
+            self.notices -= 1;
+        }
+        Some(frame)
+    }
+
+    fn push(&mut self, frame: Frame, limits: &crate::pubsub::StreamLimits) {
+        self.frames.push_back(frame);
+        while self.frames.len() - self.notices > self.capacity {
+            let Some(oldest) = self
+                .frames
+                .iter()
+                .position(|frame| matches!(frame, Frame::Item(..)))
+            else {
+                break;
+            };
+            let Frame::Item(topic, _) = &self.frames[oldest] else {
+                unreachable!("the position matched an item");
+            };
+            let noticed = self
+                .frames
+                .range(..oldest)
+                .any(|frame| matches!(frame, Frame::Dropped(dropped) if dropped == topic));
+            limits.record_websocket_drop();
+            if noticed {
+                self.frames.remove(oldest);
+            } else {
+                self.frames[oldest] = Frame::Dropped(topic.clone());
+                self.notices += 1;
+            }
+        }
+    }
+}
+
+/// Builds the `/.json` reply to a command in `format`. `Raw` sends the reply
+/// as RESP and `Text` sends bulk strings as their bytes, both in binary frames
+/// without the id; errors and other replies stay JSON, with Nil rendered as
+/// `nil` asks.
+fn command_reply(
+    result: Result<RedisValue, CommandExecutionError>,
+    cmd_name: &str,
+    id: &Option<serde_json::Value>,
+    format: OutputFormat,
+    nil: JsonNil,
+) -> Message {
+    let frame = match (result, format) {
+        (Ok(val), OutputFormat::Raw) => return Message::Binary(value_to_resp(&val).into()),
+        (Ok(RedisValue::BulkString(bytes)), OutputFormat::Text) => {
+            return Message::Binary(bytes.into())
+        }
+        (Ok(RedisValue::Nil), _) if nil == JsonNil::Omit => serde_json::json!({}),
+        (Ok(val), _) => serde_json::json!({cmd_name: redis_value_to_json_with_nil(val, nil)}),
+        (Err(e), _) => error_frame(execution_error_code(&e), &e),
+    };
+    json_frame(with_id(frame, id))
+}
+
+/// The payload bytes of a reply frame, counted against the byte `quotas`.
+fn frame_len(msg: &Message) -> u64 {
+    match msg {
+        Message::Text(text) => text.len() as u64,
+        Message::Binary(bytes) => bytes.len() as u64,
+        _ => 0,
+    }
+}
+
+/// A command sent to `/.json`, with the optional id its reply echoes.
+#[derive(Debug, PartialEq)]
+struct CommandFrame {
+    id: Option<serde_json::Value>,
+    parsed: Vec<String>,
+    /// Overrides the socket's `?binary=` setting for this command, from
+    /// `"format"` or the `"binary"` shorthand for `"txt"`.
+    format: Option<OutputFormat>,
+}
+
+impl CommandFrame {
+    /// Accepts `["GET", "k"]` or `{"id": 7, "cmd": ["GET", "k"], "format": "raw"}`.
+    /// An unknown format makes the frame invalid.
+    fn parse(text: &str) -> Option<Self> {
+        let (id, cmd, format, binary) = match crate::json::parse(text.as_bytes())? {
+            serde_json::Value::Object(mut frame) => (
+                frame.remove("id"),
+                frame.remove("cmd")?,
+                frame.remove("format"),
+                frame.remove("binary"),
+            ),
+            array => (None, array, None, None),
+        };
+        let format = match format {
+            Some(format) => Some(OutputFormat::from_extension(format.as_str()?)?),
+            None => binary.and_then(|binary| binary.as_bool()).map(|binary| {
+                if binary {
+                    OutputFormat::Text
+                } else {
+                    OutputFormat::Json
                 }
+            }),
+        };
+        Some(Self {
+            id: id.filter(|id| !id.is_null()),
+            parsed: serde_json::from_value(cmd).ok()?,
+            format,
+        })
+    }
+}
+
+fn json_frame(frame: serde_json::Value) -> Message {
+    // Axum 0.8 requires Utf8Bytes for Message::Text; .into() handles the conversion from String.
+    Message::Text(frame.to_string().into())
+}
+
+/// Adds the request id to a reply object, so clients with several commands in
+/// flight can match replies to requests.
+fn with_id(mut reply: serde_json::Value, id: &Option<serde_json::Value>) -> serde_json::Value {
+    if let (Some(id), Some(object)) = (id, reply.as_object_mut()) {
+        object.insert("id".to_string(), id.clone());
+    }
+    reply
+}
+
+/// When a socket hands its pinned Redis connection back to the pool, from
+/// `websocket_connection_idle_sec`.
+///
+/// The deadline counts from the last command, so pings, pongs and Pub/Sub
+/// messages do not keep the connection.
+struct IdleRelease {
+    limit: Option<Duration>,
+    last_command: Instant,
+}
+
+impl IdleRelease {
+    fn new(state: &AppState) -> Self {
+        Self {
+            limit: state.websocket_connection_idle,
+            last_command: Instant::now(),
+        }
+    }
+
+    /// Records a command from the client.
+    fn command(&mut self) {
+        self.last_command = Instant::now();
+    }
+
+    /// When the session's connection is due back, if it can be released.
+    fn deadline(&self, session: &CommandSession) -> Option<Instant> {
+        let limit = self.limit?;
+        session.is_releasable().then(|| self.last_command + limit)
+    }
+}
+
+/// Ping and idle deadlines of one socket, from `websocket_ping_interval_sec`
+/// and `websocket_idle_timeout_sec`.
+struct Keepalive {
+    ping_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    next_ping: Instant,
+    /// Whether nothing arrived since the last ping.
+    awaiting_pong: bool,
+    last_activity: Instant,
+}
+
+enum KeepaliveEvent {
+    Ping,
+    Close(CloseFrame),
+}
+
+impl Keepalive {
+    fn new(state: &AppState) -> Self {
+        let now = Instant::now();
+        Self {
+            ping_interval: state.websocket_ping_interval,
+            idle_timeout: state.websocket_idle_timeout,
+            next_ping: now + state.websocket_ping_interval.unwrap_or_default(),
+            awaiting_pong: false,
+            last_activity: now,
+        }
+    }
+
+    /// The next time [`Keepalive::expire`] has something to do.
+    fn deadline(&self) -> Option<Instant> {
+        let ping = self.ping_interval.map(|_| self.next_ping);
+        let idle = self.idle_timeout.map(|limit| self.last_activity + limit);
+        match (ping, idle) {
+            (Some(ping), Some(idle)) => Some(ping.min(idle)),
+            (deadline, None) | (None, deadline) => deadline,
+        }
+    }
+
+    /// Any frame shows the client is still there; only data frames count as
+    /// activity.
+    fn received(&mut self, msg: &Message) {
+        self.awaiting_pong = false;
+        if matches!(msg, Message::Text(_) | Message::Binary(_)) {
+            self.active();
+        }
+    }
+
+    /// Records a data frame sent to the client.
+    fn active(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    fn expire(&mut self) -> Option<KeepaliveEvent> {
+        let now = Instant::now();
+        if let Some(limit) = self.idle_timeout {
+            if now >= self.last_activity + limit {
+                return Some(KeepaliveEvent::Close(close_frame(
+                    close_code::AWAY,
+                    "idle_timeout",
+                )));
             }
         }
+        let interval = self.ping_interval?;
+        if now < self.next_ping {
+            return None;
+        }
+        if self.awaiting_pong {
+            return Some(KeepaliveEvent::Close(close_frame(
+                close_code::AWAY,
+                "pong_timeout",
+            )));
+        }
+        self.awaiting_pong = true;
+        self.next_ping = now + interval;
+        Some(KeepaliveEvent::Ping)
+    }
+}
+
+/// Sleeps until `deadline`; never completes without one.
+async fn until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
     }
 }
 
+/// Builds the topic an (un)subscribe command argument refers to.
+type TopicFor = fn(String) -> Topic;
+
+/// Commands `/.json` handles itself instead of sending them to Redis.
+enum SocketCommand {
+    Auth,
+    Subscribe(TopicFor),
+    Unsubscribe(TopicFor),
+}
+
+fn socket_command(name: &str) -> Option<SocketCommand> {
+    let command = match name.to_ascii_uppercase().as_str() {
+        "AUTH" => SocketCommand::Auth,
+        "SUBSCRIBE" => SocketCommand::Subscribe(Topic::Channel),
+        "PSUBSCRIBE" => SocketCommand::Subscribe(Topic::Pattern),
+        "SSUBSCRIBE" => SocketCommand::Subscribe(Topic::Shard),
+        "UNSUBSCRIBE" => SocketCommand::Unsubscribe(Topic::Channel),
+        "PUNSUBSCRIBE" => SocketCommand::Unsubscribe(Topic::Pattern),
+        "SUNSUBSCRIBE" => SocketCommand::Unsubscribe(Topic::Shard),
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// Builds a `SUBSCRIBE`-style (or unsubscribe) confirmation carrying the
+/// number of topics the socket is still subscribed to.
+fn subscription_reply(topic: &Topic, subscribed: bool, count: usize) -> serde_json::Value {
+    let key = if subscribed {
+        topic.subscribe_command()
+    } else {
+        topic.unsubscribe_command()
+    };
+    serde_json::json!({ key: [key.to_ascii_lowercase(), topic.name(), count] })
+}
+
 /// Axum handler for raw RESP WebSocket connections at `/.raw`.
 ///
 /// This endpoint allows clients to send and receive raw Redis protocol frames.
-pub async fn ws_handler_raw(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
-    ws.on_upgrade(|socket| handle_socket_raw(socket, state))
+pub async fn ws_handler_raw(
+    ws: WebSocketUpgrade,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let auth = upgrade_auth(addr, &headers, &params);
+    with_limits(ws, &state).on_upgrade(|socket| handle_socket_raw(socket, state, auth))
 }
 
 /// Main loop for raw RESP WebSocket connections.
 ///
 /// It maintains a buffer for incoming data, parses complete RESP commands,
 /// executes them against Redis, and sends the raw RESP responses back.
-async fn handle_socket_raw(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket_raw(socket: WebSocket, state: Arc<AppState>, mut auth: AuthContext) {
     let (mut sender, mut receiver) = socket.split();
     let mut buffer = Vec::new();
+    let mut session = CommandSession::new();
+    let mut idle = IdleRelease::new(&state);
+    let mut keepalive = Keepalive::new(&state);
+    let mut shutdown = state.shutdown.subscribe();
+    let intercept_auth = state.acl.has_basic_auth();
+    let mut authenticated = authenticated_at_upgrade(&state, &auth);
 
-    while let Some(msg) = receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => msg,
+            _ = shut_down(&mut shutdown) => {
+                let close = close_frame(close_code::AWAY, "server_shutdown");
+                let _ = sender.send(Message::Close(Some(close))).await;
+                return;
+            }
+            _ = until(idle.deadline(&session)) => {
+                session.release_idle();
+                continue;
+            }
+            _ = until(keepalive.deadline()) => {
+                let (frame, closing) = match keepalive.expire() {
+                    Some(KeepaliveEvent::Ping) => (Message::Ping(Default::default()), false),
+                    Some(KeepaliveEvent::Close(frame)) => (Message::Close(Some(frame)), true),
+                    None => continue,
+                };
+                if sender.send(frame).await.is_err() || closing {
+                    return;
+                }
+                continue;
+            }
+        };
         let msg = match msg {
-            Ok(msg) => msg,
-            Err(_) => return, // Client disconnected or error
+            Some(Ok(msg)) => msg,
+            Some(Err(err)) => {
+                if let Some(frame) = size_limit_close(err) {
+                    let _ = sender.send(Message::Close(Some(frame))).await;
+                }
+                return;
+            }
+            None => return, // Client disconnected
         };
+        keepalive.received(&msg);
 
         // Handle different message types
         match msg {
@@ -159,31 +928,101 @@ async fn handle_socket_raw(socket: WebSocket, state: Arc<AppState>) {
                         continue;
                     }
 
-                    // Get a connection from the default DB pool.
-                    let command = ExecutableCommand {
+                    idle.command();
+                    let command_name = String::from_utf8_lossy(&args[0]).to_string();
+                    let started = std::time::Instant::now();
+                    // Runs on the socket's own connection unless `routes`
+                    // send it to another backend.
+                    let mut command = ExecutableCommand {
+                        backend: None,
                         target_database: state.default_database,
-                        command_name: String::from_utf8_lossy(&args[0]).to_string(),
+                        command_name: command_name.clone(),
                         args: args[1..].to_vec(),
+                        timeout: None,
+                        credentials: None,
+                    };
+                    let is_auth = intercept_auth && command_name.eq_ignore_ascii_case("AUTH");
+                    let hooked = if is_auth || !authenticated {
+                        Ok(())
+                    } else {
+                        state.hooks.before(&mut command)
+                    };
+                    admission::route(&state, &auth, &mut command);
+                    // Handled here: the reply, and the close that follows it.
+                    let mut charge = None;
+                    let handled = if is_auth {
+                        match authenticate(&state.acl, &mut auth, &args[1..]) {
+                            Ok(()) => {
+                                authenticated = true;
+                                Some((b"+OK\r\n".to_vec(), None))
+                            }
+                            Err(e) => Some((
+                                error_to_resp(e),
+                                Some(close_frame(CLOSE_AUTH_FAILED, "auth_failed")),
+                            )),
+                        }
+                    } else if !authenticated {
+                        Some((b"-NOAUTH Authentication required.\r\n".to_vec(), None))
+                    } else if let Err(error) = hooked {
+                        let status = execution_error_status(&error);
+                        admission::audit(&state, &auth, &command, status, started);
+                        Some((raw_error_frame(&error.to_string()), None))
+                    } else if !admission::authorize(&state, &auth, &command) {
+                        admission::audit(&state, &auth, &command, StatusCode::FORBIDDEN, started);
+                        Some((
+                            error_to_resp("forbidden"),
+                            Some(close_frame(CLOSE_FORBIDDEN, "forbidden")),
+                        ))
+                    } else {
+                        match admission::charge(&state, &auth, 1).await {
+                            Ok(admitted) => {
+                                charge = Some(admitted);
+                                None
+                            }
+                            Err(retry_after) => Some((
+                                error_to_resp(&format!(
+                                    "usage quota exceeded, retry after {retry_after} seconds"
+                                )),
+                                None,
+                            )),
+                        }
                     };
+                    if let Some((reply, close)) = handled {
+                        if sender
+                            .send(Message::Binary(reply.into()))
+                            .await
+                            .is_err()
+                        {
+                            return;
+                        }
+                        if let Some(close) = close {
+                            let _ = sender.send(Message::Close(Some(close))).await;
+                            return;
+                        }
+                        continue;
+                    }
 
-                    match state.command_executor.execute(&command).await {
+                    let Some(charge) = charge else {
+                        continue;
+                    };
+                    let result =
+                        admission::execute_in_session(&state, &mut session, &command).await;
+                    let status = admission::outcome_status(&result);
+                    admission::audit(&state, &auth, &command, status, started);
+                    match result {
                         Ok(val) => {
                             // Convert result to RESP and send as binary message
                             let resp = redis_web_core::resp::value_to_resp(&val);
+                            charge.record_bytes(&state, resp.len() as u64);
                             if sender.send(Message::Binary(resp.into())).await.is_err() {
                                 return;
                             }
                         }
                         Err(e) => {
-                            // Forward Redis error as RESP error
-                            let err_resp = match e {
-                                CommandExecutionError::ServiceUnavailable(message)
-                                | CommandExecutionError::ExecutionFailed(message) => {
-                                    format!("-ERR {message}\r\n")
-                                }
-                            };
+                            // Forward Redis error as RESP error, keeping its code
+                            let err_resp = raw_error_frame(&e.to_string());
                             if sender
-                                .send(Message::Binary(err_resp.into_bytes().into()))
+                                .send(Message::Binary(err_resp.into()))
                                 .await
                                 .is_err()
                             {
@@ -193,27 +1032,150 @@ async fn handle_socket_raw(socket: WebSocket, state: Arc<AppState>) {
                     }
                 }
                 Ok(None) => break, // Need more data for a complete command
-                Err(e) => {
-                    let err_msg = match e {
-                        redis_web_core::resp::RespError::Incomplete => break, // Should not happen with current parser but handled for safety
-                        _ => {
-                            // Fatal command format error, clear buffer and inform client
-                            buffer.clear();
-                            "-ERR Invalid RESP\r\n"
-                        }
-                    };
-                    if err_msg.starts_with("-ERR") {
-                        if sender
-                            .send(Message::Binary(err_msg.as_bytes().to_vec().into()))
-                            .await
-                            .is_err()
-                        {
-                            return;
-                        }
+                // Should not happen with current parser but handled for safety
+                Err(redis_web_core::resp::RespError::Incomplete) => break,
+                Err(_) => {
+                    // Fatal command format error, clear buffer and inform client
+                    buffer.clear();
+                    let err_msg = b"-ERR Invalid RESP\r\n";
+                    if sender
+                        .send(Message::Binary(err_msg.to_vec().into()))
+                        .await
+                        .is_err()
+                    {
+                        return;
                     }
                     break;
                 }
             }
         }
+
+        // A command split across frames is capped like a single message.
+        if buffer.len() > state.websocket_max_message_size {
+            let _ = sender.send(Message::Close(Some(message_too_big()))).await;
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::{PubSubMessage, StreamLimits};
+
+    fn message(channel: &str, payload: &str) -> StreamItem {
+        StreamItem::Message(PubSubMessage {
+            channel: channel.to_string(),
+            payload: payload.to_string(),
+            pattern: None,
+            sharded: false,
+            id: None,
+        })
+    }
+
+    fn text(msg: Message) -> String {
+        match msg {
+            Message::Text(text) => text.to_string(),
+            other => panic!("unexpected frame: {other:?}"),
+        }
+    }
+
+    fn item(channel: &str, payload: &str) -> Frame {
+        Frame::Item(
+            Topic::Channel(channel.to_string()),
+            json_frame(stream_item_json(&message(channel, payload))),
+        )
+    }
+
+    /// Pings every 30 seconds must not push back the release of a connection
+    /// idle for 60.
+    #[tokio::test(start_paused = true)]
+    async fn idle_connections_are_released_while_pings_run() {
+        let started = Instant::now();
+        let mut session = CommandSession::new();
+        session.pin(());
+        let idle = IdleRelease {
+            limit: Some(Duration::from_secs(60)),
+            last_command: started,
+        };
+        let mut keepalive = Keepalive {
+            ping_interval: Some(Duration::from_secs(30)),
+            idle_timeout: None,
+            next_ping: started + Duration::from_secs(30),
+            awaiting_pong: false,
+            last_activity: started,
+        };
+
+        let mut pings = 0;
+        while session.is_pinned() {
+            tokio::select! {
+                _ = until(idle.deadline(&session)) => {
+                    assert!(session.release_idle());
+                }
+                _ = until(keepalive.deadline()) => {
+                    if let Some(KeepaliveEvent::Ping) = keepalive.expire() {
+                        pings += 1;
+                    }
+                    keepalive.received(&Message::Pong(Default::default()));
+                }
+            }
+        }
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+        assert!(pings >= 1);
+
+        // A connection carrying state has no deadline at all.
+        session.pin(());
+        session.set_stateful();
+        assert_eq!(idle.deadline(&session), None);
+    }
+
+    #[test]
+    fn full_queues_drop_the_oldest_messages_and_send_a_notice() {
+        let limits = StreamLimits::new(None);
+        let mut queue = FrameQueue::new(2);
+        for payload in ["a", "b", "c", "d"] {
+            queue.push(item("news", payload), &limits);
+        }
+        assert_eq!(limits.stats().websocket_drops, 2);
+
+        // The notice takes the place of the first lost message; the newest
+        // messages survive.
+        let frames: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|frame| text(frame.into_message()))
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                r#"{"SUBSCRIBE":["error","news","dropped"]}"#,
+                r#"{"SUBSCRIBE":["message","news","c"]}"#,
+                r#"{"SUBSCRIBE":["message","news","d"]}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn full_queues_keep_replies_and_other_topics_notified() {
+        let limits = StreamLimits::new(None);
+        let mut queue = FrameQueue::new(2);
+        queue.push(
+            Frame::Control(json_frame(serde_json::json!({"GET": "v"}))),
+            &limits,
+        );
+        queue.push(item("news", "a"), &limits);
+        queue.push(item("sports", "b"), &limits);
+        queue.push(item("sports", "c"), &limits);
+
+        let frames: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|frame| text(frame.into_message()))
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                r#"{"GET":"v"}"#,
+                r#"{"SUBSCRIBE":["error","news","dropped"]}"#,
+                r#"{"SUBSCRIBE":["error","sports","dropped"]}"#,
+                r#"{"SUBSCRIBE":["message","sports","c"]}"#,
+            ]
+        );
     }
 }
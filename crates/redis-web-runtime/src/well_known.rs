@@ -0,0 +1,80 @@
+//! `/favicon.ico` and `/robots.txt`.
+//!
+//! Browsers ask for `/favicon.ico` on their own. Without a route of its own
+//! the request would run as the Redis command `FAVICON.ICO` and log an error
+//! for every page view, so an icon is always served: the file named by
+//! `favicon`, or a built-in one. `robots_txt` adds a `/robots.txt` that asks
+//! crawlers to stay away from the whole server.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const BUILT_IN_FAVICON: &[u8] = include_bytes!("../assets/favicon.ico");
+const ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+const CACHE_CONTROL: &str = "public, max-age=86400";
+
+/// Handles `GET /favicon.ico`. A configured file is read on every request, so
+/// replacing it takes effect without a restart; a file that is gone answers
+/// `404`.
+pub async fn handle_favicon(file: Option<Arc<PathBuf>>) -> Response {
+    let Some(file) = file else {
+        return icon_response("image/x-icon", BUILT_IN_FAVICON.to_vec());
+    };
+    match tokio::fs::read(file.as_path()).await {
+        Ok(bytes) => icon_response(content_type(&file), bytes),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Handles `GET /robots.txt`.
+pub async fn handle_robots() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        ROBOTS_TXT,
+    )
+        .into_response()
+}
+
+fn icon_response(content_type: &'static str, bytes: Vec<u8>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+/// `Content-Type` of an icon file; anything but PNG and SVG is taken to be
+/// an ICO file.
+fn content_type(file: &Path) -> &'static str {
+    let extension = file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        _ => "image/x-icon",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_favicon_is_an_icon_file() {
+        assert_eq!(&BUILT_IN_FAVICON[..4], &[0, 0, 1, 0]);
+        assert_eq!(content_type(Path::new("/srv/icon.PNG")), "image/png");
+        assert_eq!(content_type(Path::new("/srv/favicon")), "image/x-icon");
+    }
+}
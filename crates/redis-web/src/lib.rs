@@ -2,13 +2,13 @@ use clap::Parser;
 use redis_web_compat::{
     legacy_alias_notice, resolve_default_config, InvocationKind, LEGACY_CONFIG_NAME,
 };
-use redis_web_core::config::{Config, TransportMode, DEFAULT_VERBOSITY};
+use redis_web_core::config::{Config, RuntimeFlavor, TransportMode, DEFAULT_VERBOSITY};
 use redis_web_runtime::{grpc, server};
 use std::fs;
 use std::io;
 use std::path::Path;
 use std::process;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 const HTTP_APP_NAME: &str = "redis-web";
@@ -176,23 +176,32 @@ fn init_logging(config: &Config, config_path: &str, app_name: &str) {
 }
 
 fn start_http_runtime(config: Config) {
-    info!("Building Tokio runtime");
-    let mut runtime = tokio::runtime::Builder::new_multi_thread();
-    runtime.enable_all();
-    if let Some(worker_threads) = config.runtime_worker_threads {
-        runtime.worker_threads(worker_threads);
-    }
-    runtime.build().unwrap().block_on(async_main_http(config));
+    build_tokio_runtime(&config).block_on(async_main_http(config));
 }
 
 fn start_grpc_runtime(config: Config) {
-    info!("Building Tokio runtime");
-    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    build_tokio_runtime(&config).block_on(async_main_grpc(config));
+}
+
+fn build_tokio_runtime(config: &Config) -> tokio::runtime::Runtime {
+    info!("Building Tokio runtime: flavor={:?}", config.runtime_flavor);
+    let mut runtime = match config.runtime_flavor {
+        RuntimeFlavor::MultiThread => {
+            let mut runtime = tokio::runtime::Builder::new_multi_thread();
+            if let Some(worker_threads) = config.runtime_worker_threads {
+                runtime.worker_threads(worker_threads);
+            }
+            runtime
+        }
+        RuntimeFlavor::CurrentThread => {
+            if config.runtime_worker_threads.is_some() {
+                warn!("runtime_worker_threads is ignored with runtime_flavor current_thread");
+            }
+            tokio::runtime::Builder::new_current_thread()
+        }
+    };
     runtime.enable_all();
-    if let Some(worker_threads) = config.runtime_worker_threads {
-        runtime.worker_threads(worker_threads);
-    }
-    runtime.build().unwrap().block_on(async_main_grpc(config));
+    runtime.build().unwrap()
 }
 
 async fn async_main_http(config: Config) {
@@ -203,14 +212,20 @@ async fn async_main_http(config: Config) {
             process::exit(1);
         }
     };
+    if let Err(error) = server::wait_for_redis(&config, &components).await {
+        error!("Server startup failed: {error}");
+        process::exit(1);
+    }
 
+    start_mqtt(&config, &components);
+    let shutdown = components.app_state.shutdown.clone();
     let app = server::build_router_from_components(&config, components);
 
     info!(
         "Starting HTTP server on {}:{}",
         config.http_host, config.http_port
     );
-    if let Err(error) = server::serve(&config, app).await {
+    if let Err(error) = server::serve_with_shutdown(&config, app, shutdown).await {
         error!("Failed to serve HTTP traffic: {}", error);
         process::exit(1);
     }
@@ -224,6 +239,10 @@ async fn async_main_grpc(config: Config) {
             process::exit(1);
         }
     };
+    if let Err(error) = server::wait_for_redis(&config, &components).await {
+        error!("Server startup failed: {error}");
+        process::exit(1);
+    }
 
     log_ignored_rest_settings(&config);
     info!(
@@ -236,6 +255,30 @@ async fn async_main_grpc(config: Config) {
     }
 }
 
+/// Starts the MQTT bridge next to the HTTP server when `mqtt.enabled` is set.
+fn start_mqtt(config: &Config, components: &server::RuntimeComponents) {
+    if !config.mqtt.as_ref().is_some_and(|mqtt| mqtt.enabled) {
+        return;
+    }
+    #[cfg(feature = "mqtt")]
+    {
+        let config = config.clone();
+        let state = components.app_state.clone();
+        tokio::spawn(async move {
+            if let Err(error) = redis_web_runtime::mqtt::serve(&config, state).await {
+                error!("Failed to serve MQTT traffic: {}", error);
+                process::exit(1);
+            }
+        });
+    }
+    #[cfg(not(feature = "mqtt"))]
+    {
+        let _ = components;
+        error!("mqtt.enabled is set but this build does not include the mqtt feature");
+        process::exit(1);
+    }
+}
+
 fn log_ignored_rest_settings(config: &Config) {
     let mut ignored = Vec::new();
     if config.websockets {
@@ -244,6 +287,15 @@ fn log_ignored_rest_settings(config: &Config) {
     if config.default_root.is_some() {
         ignored.push("default_root");
     }
+    if config.root_commands.is_some() {
+        ignored.push("root_commands");
+    }
+    if config.favicon.is_some() {
+        ignored.push("favicon");
+    }
+    if config.robots_txt {
+        ignored.push("robots_txt");
+    }
     if config
         .compat_hiredis
         .as_ref()
@@ -251,6 +303,9 @@ fn log_ignored_rest_settings(config: &Config) {
     {
         ignored.push("compat_hiredis");
     }
+    if config.mqtt.as_ref().is_some_and(|mqtt| mqtt.enabled) {
+        ignored.push("mqtt");
+    }
     if config.http_host != "0.0.0.0" || config.http_port != 7379 {
         ignored.push("http_host/http_port");
     }
@@ -12,8 +12,9 @@ use std::io::Write;
 use std::sync::Mutex;
 
 use redis_web_core::config::{
-    Config, TransportMode, DEFAULT_HTTP_MAX_REQUEST_SIZE, DEFAULT_HTTP_THREADS,
-    DEFAULT_POOL_SIZE_PER_THREAD, DEFAULT_VERBOSITY,
+    Config, JsonNil, QuotaLimits, RedisAuthConfig, RedisConnectionMode, RedisProtocol,
+    RuntimeFlavor, SlowClientPolicy, TransportMode, DEFAULT_HTTP_MAX_REQUEST_SIZE,
+    DEFAULT_HTTP_THREADS, DEFAULT_POOL_SIZE_PER_THREAD, DEFAULT_VERBOSITY,
 };
 use redis_web_runtime::redis;
 use serde_json::json;
@@ -58,7 +59,7 @@ fn test_config_loading() {
 
     // Verify required fields
     assert_eq!(config.redis_host, "127.0.0.1");
-    assert_eq!(config.websockets, true);
+    assert!(config.websockets);
 
     // Verify optional fields are correctly parsed as Some(value)
     assert_eq!(config.http_max_request_size, Some(1024));
@@ -93,7 +94,7 @@ fn test_default_values() {
     let config = Config::new(path).unwrap();
 
     // Verify boolean defaults are false
-    assert_eq!(config.websockets, false);
+    assert!(!config.websockets);
     assert_eq!(config.transport_mode, TransportMode::Rest);
     assert_eq!(config.runtime_worker_threads, None);
 
@@ -124,6 +125,313 @@ fn test_config_from_value() {
     assert_eq!(config.http_host, "0.0.0.0");
 }
 
+/// `redis_host` may be a failover list; the first entry stays in `redis_host`.
+#[test]
+fn test_redis_host_list_parses() {
+    let config = Config::from_value(json!({
+        "redis_host": ["redis-a", "redis-b:6380"]
+    }))
+    .unwrap();
+    assert_eq!(config.redis_host, "redis-a");
+    assert_eq!(config.redis_hosts, vec!["redis-a", "redis-b:6380"]);
+    assert_eq!(
+        config.redis_host_candidates(),
+        vec!["redis-a", "redis-b:6380"]
+    );
+
+    let single = Config::from_value(json!({ "redis_host": "localhost" })).unwrap();
+    assert!(single.redis_hosts.is_empty());
+    assert_eq!(single.redis_host_candidates(), vec!["localhost"]);
+
+    assert!(Config::from_value(json!({ "redis_host": [] })).is_err());
+}
+
+/// A failover list serializes back as the `redis_host` list, so the admin
+/// config and saved documents keep every host.
+#[test]
+fn test_redis_host_list_round_trips() {
+    let config = Config::from_value(json!({
+        "redis_host": ["redis-a", "redis-b:6380"]
+    }))
+    .unwrap();
+    let document = serde_json::to_value(&config).unwrap();
+    assert_eq!(document["redis_host"], json!(["redis-a", "redis-b:6380"]));
+    assert_eq!(
+        config.redacted_document()["redis_host"],
+        json!(["redis-a", "redis-b:6380"])
+    );
+    let reloaded = Config::from_value(document).unwrap();
+    assert_eq!(reloaded.redis_host, "redis-a");
+    assert_eq!(reloaded.redis_hosts, vec!["redis-a", "redis-b:6380"]);
+
+    let single = Config::from_value(json!({ "redis_host": "localhost" })).unwrap();
+    assert_eq!(
+        serde_json::to_value(&single).unwrap()["redis_host"],
+        "localhost"
+    );
+}
+
+/// `redis_protocol` defaults to RESP2 and accepts the lowercase protocol names.
+#[test]
+fn test_redis_protocol_parses() {
+    assert_eq!(Config::default().redis_protocol, RedisProtocol::Resp2);
+    let config = Config::from_value(json!({ "redis_protocol": "resp3" })).unwrap();
+    assert_eq!(config.redis_protocol, RedisProtocol::Resp3);
+    assert!(Config::from_value(json!({ "redis_protocol": "resp4" })).is_err());
+}
+
+/// `redis_connection_mode` defaults to the checkout pool.
+#[test]
+fn test_redis_connection_mode_parses() {
+    let config = Config::from_value(json!({})).unwrap();
+    assert_eq!(config.redis_connection_mode, RedisConnectionMode::Pool);
+    assert_eq!(config.multiplexed_connections, None);
+
+    let config = Config::from_value(json!({
+        "redis_connection_mode": "multiplexed",
+        "multiplexed_connections": 2
+    }))
+    .unwrap();
+    assert_eq!(
+        config.redis_connection_mode,
+        RedisConnectionMode::Multiplexed
+    );
+    assert_eq!(config.multiplexed_connections, Some(2));
+}
+
+#[test]
+fn test_pubsub_connections_parse() {
+    let config = Config::from_value(json!({})).unwrap();
+    assert_eq!(config.pubsub_connections, None);
+
+    let config = Config::from_value(json!({ "pubsub_connections": 4 })).unwrap();
+    assert_eq!(config.pubsub_connections, Some(4));
+}
+
+#[test]
+fn test_pubsub_replay_parses() {
+    let config = Config::from_value(json!({})).unwrap();
+    assert!(config.pubsub_replay.is_none());
+
+    let config = Config::from_value(json!({
+        "pubsub_replay": {
+            "enabled": true,
+            "history_size": 500,
+            "redis_stream_prefix": "redis-web:sse:"
+        }
+    }))
+    .unwrap();
+    let replay = config.pubsub_replay.unwrap();
+    assert!(replay.enabled);
+    assert_eq!(replay.history_size, Some(500));
+    assert_eq!(replay.retention_ms, None);
+    assert_eq!(
+        replay.redis_stream_prefix.as_deref(),
+        Some("redis-web:sse:")
+    );
+}
+
+#[test]
+fn test_metrics_and_admin_parse() {
+    let config = Config::from_value(json!({})).unwrap();
+    assert!(config.metrics.is_none());
+    assert!(config.admin.is_none());
+
+    let config = Config::from_value(json!({
+        "metrics": { "enabled": true },
+        "admin": { "enabled": true, "ip": "10.0.0.0/8" }
+    }))
+    .unwrap();
+    let metrics = config.metrics.unwrap();
+    assert!(metrics.enabled);
+    assert_eq!(metrics.path, None);
+    assert_eq!(metrics.latency_probe_interval_ms, None);
+    let admin = config.admin.unwrap();
+    assert!(admin.enabled);
+    assert_eq!(admin.ip.as_deref(), Some("10.0.0.0/8"));
+    assert_eq!(admin.http_basic_auth, None);
+    assert!(!admin.console);
+}
+
+#[test]
+fn test_latency_probe_interval_parses() {
+    let config = Config::from_value(json!({
+        "metrics": { "enabled": true, "latency_probe_interval_ms": 1000 }
+    }))
+    .unwrap();
+    assert_eq!(
+        config.metrics.unwrap().latency_probe_interval_ms,
+        Some(1000)
+    );
+}
+
+#[test]
+fn test_openapi_parse() {
+    assert!(Config::default().openapi.is_none());
+    let config = Config::from_value(json!({"openapi": {"enabled": true}})).unwrap();
+    let openapi = config.openapi.unwrap();
+    assert!(openapi.enabled);
+    assert!(!openapi.swagger_ui);
+}
+
+#[test]
+fn test_static_dir_parse() {
+    assert!(Config::default().static_dir.is_none());
+    let config = Config::from_value(json!({"static_dir": {"dir": "./public"}})).unwrap();
+    let static_dir = config.static_dir.unwrap();
+    assert_eq!(static_dir.dir, "./public");
+    assert_eq!(static_dir.path_prefix, "/static");
+    assert_eq!(static_dir.max_age_sec, 0);
+}
+
+#[test]
+fn test_favicon_and_robots_txt_parse() {
+    let config = Config::default();
+    assert!(config.favicon.is_none());
+    assert!(!config.robots_txt);
+    let config =
+        Config::from_value(json!({"favicon": "./public/icon.png", "robots_txt": true})).unwrap();
+    assert_eq!(config.favicon.as_deref(), Some("./public/icon.png"));
+    assert!(config.robots_txt);
+}
+
+#[test]
+fn test_downloads_parse() {
+    assert!(Config::default().downloads.is_none());
+    let config = Config::from_value(json!({"downloads": [
+        {"commands": ["GET"], "key_pattern": "report:*", "filename": "{key}.csv"}
+    ]}))
+    .unwrap();
+    let rules = config.downloads.unwrap();
+    assert_eq!(rules[0].key_pattern.as_deref(), Some("report:*"));
+    assert_eq!(rules[0].filename, "{key}.csv");
+    assert!(Config::from_value(json!({"downloads": [{"key_pattern": "a*"}]})).is_err());
+}
+
+#[test]
+fn test_server_timing_parse() {
+    assert!(!Config::default().server_timing);
+    let config = Config::from_value(json!({"server_timing": true})).unwrap();
+    assert!(config.server_timing);
+}
+
+#[test]
+fn test_ttl_header_parse() {
+    assert!(!Config::default().ttl_header);
+    let config = Config::from_value(json!({"ttl_header": true})).unwrap();
+    assert!(config.ttl_header);
+}
+
+#[test]
+fn test_last_modified_parse() {
+    assert!(!Config::default().last_modified);
+    let config = Config::from_value(json!({"last_modified": true})).unwrap();
+    assert!(config.last_modified);
+}
+
+#[test]
+fn test_pubsub_filters_parse() {
+    let config = Config::from_value(json!({
+        "pubsub_filters": [
+            { "channel": "logs.*", "regex": "^ERROR" },
+            { "channel": "orders", "json_path": "$.status", "equals": "failed" }
+        ]
+    }))
+    .unwrap();
+    let filters = config.pubsub_filters.unwrap();
+    assert_eq!(filters.len(), 2);
+    assert_eq!(filters[0].channel, "logs.*");
+    assert_eq!(filters[0].regex.as_deref(), Some("^ERROR"));
+    assert_eq!(filters[1].json_path.as_deref(), Some("$.status"));
+    assert_eq!(filters[1].equals.as_deref(), Some("failed"));
+    assert_eq!(filters[1].template, None);
+}
+
+#[test]
+fn test_sse_slow_client_policy_parse() {
+    let config = Config::from_value(json!({
+        "sse": { "slow_client_policy": "disconnect", "slow_client_timeout_sec": 5 }
+    }))
+    .unwrap();
+    let sse = config.sse.unwrap();
+    assert_eq!(sse.slow_client_policy, Some(SlowClientPolicy::Disconnect));
+    assert_eq!(sse.slow_client_timeout_sec, Some(5));
+    assert!(Config::from_value(json!({"sse": {"slow_client_policy": "buffer"}})).is_err());
+}
+
+#[test]
+fn test_websocket_keepalive_parse() {
+    let config = Config::from_value(json!({
+        "websocket_ping_interval_sec": 0,
+        "websocket_idle_timeout_sec": 300
+    }))
+    .unwrap();
+    assert_eq!(config.websocket_ping_interval_sec, Some(0));
+    assert_eq!(config.websocket_idle_timeout_sec, Some(300));
+    let defaults = Config::from_value(json!({})).unwrap();
+    assert_eq!(defaults.websocket_ping_interval_sec, None);
+    assert_eq!(defaults.websocket_idle_timeout_sec, None);
+}
+
+#[test]
+fn test_websocket_size_limits_parse() {
+    let config = Config::from_value(json!({
+        "websocket_max_message_size": 65536,
+        "websocket_max_frame_size": 16384
+    }))
+    .unwrap();
+    assert_eq!(config.websocket_max_message_size, Some(65536));
+    assert_eq!(config.websocket_max_frame_size, Some(16384));
+}
+
+#[test]
+fn test_websocket_send_queue_size_parse() {
+    let config = Config::from_value(json!({"websocket_send_queue_size": 500})).unwrap();
+    assert_eq!(config.websocket_send_queue_size, Some(500));
+    assert_eq!(Config::default().websocket_send_queue_size, None);
+}
+
+#[test]
+fn test_websocket_max_inflight_commands_parse() {
+    let config = Config::from_value(json!({"websocket_max_inflight_commands": 8})).unwrap();
+    assert_eq!(config.websocket_max_inflight_commands, Some(8));
+    assert_eq!(Config::default().websocket_max_inflight_commands, None);
+}
+
+#[test]
+fn test_websocket_require_auth_parse() {
+    let config = Config::from_value(json!({"websocket_require_auth": true})).unwrap();
+    assert!(config.websocket_require_auth);
+    assert!(!Config::default().websocket_require_auth);
+}
+
+#[test]
+fn test_mqtt_parse() {
+    let config = Config::from_value(json!({
+        "mqtt": { "enabled": true, "port": 1884, "channel_prefix": "mqtt:" }
+    }))
+    .unwrap();
+    let mqtt = config.mqtt.unwrap();
+    assert!(mqtt.enabled);
+    assert_eq!(mqtt.host, None);
+    assert_eq!(mqtt.port, Some(1884));
+    assert_eq!(mqtt.channel_prefix.as_deref(), Some("mqtt:"));
+    assert_eq!(mqtt.max_packet_size, None);
+}
+
+#[test]
+fn test_hooks_parse() {
+    let config = Config::from_value(json!({
+        "hooks": [{ "path": "tenant.wasm" }, { "path": "redact.wat", "fuel": 5000 }]
+    }))
+    .unwrap();
+    let hooks = config.hooks.unwrap();
+    assert_eq!(hooks[0].path, "tenant.wasm");
+    assert_eq!(hooks[0].fuel, None);
+    assert_eq!(hooks[1].path, "redact.wat");
+    assert_eq!(hooks[1].fuel, Some(5000));
+}
+
 /// Ensures the generated default configuration document contains the expected
 /// defaults and omits unset optional fields.
 #[test]
@@ -341,6 +649,45 @@ fn test_redis_socket_env_var_expansion_works() {
     assert_eq!(config.redis_socket.as_deref(), Some("/tmp/redis.sock"));
 }
 
+/// The redacted document masks credentials and records where each key came from.
+#[test]
+fn test_redacted_document_and_sources() {
+    let _guard = ENV_LOCK.lock().unwrap();
+
+    std::env::set_var("REDIS_WEB_TEST_ADMIN_AUTH", "ops:secret");
+
+    let config = Config::from_value(json!({
+        "redis_port": 6380,
+        "redis_auth": ["user", "hunter2"],
+        "acl": [{"http_basic_auth": "user:password", "enabled": ["DEBUG"]}],
+        "admin": {"enabled": true, "http_basic_auth": "$REDIS_WEB_TEST_ADMIN_AUTH"}
+    }))
+    .unwrap();
+    assert_eq!(
+        config.admin.as_ref().unwrap().http_basic_auth.as_deref(),
+        Some("ops:secret")
+    );
+
+    let document = config.redacted_document();
+    assert_eq!(document["redis_port"], 6380);
+    assert_eq!(document["redis_auth"], "[redacted]");
+    assert_eq!(document["acl"][0]["http_basic_auth"], "[redacted]");
+    assert_eq!(document["admin"]["http_basic_auth"], "[redacted]");
+    assert!(!document.to_string().contains("hunter2"));
+
+    assert_eq!(config.sources.source_of("redis_port"), "file");
+    assert_eq!(config.sources.source_of("admin"), "env");
+    assert_eq!(config.sources.source_of("http_port"), "default");
+    assert_eq!(
+        config
+            .sources
+            .env_vars
+            .get("admin.http_basic_auth")
+            .map(String::as_str),
+        Some("REDIS_WEB_TEST_ADMIN_AUTH")
+    );
+}
+
 /// When both `redis_socket` and TCP settings are provided, `redis_socket` takes precedence.
 ///
 /// We validate this by asserting we fail fast due to the socket path, even if TCP
@@ -407,6 +754,249 @@ fn test_hiredis_keep_alive_sec_parses() {
     );
 }
 
+#[test]
+fn test_http_put_stream_chunk_size_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, r#"{{"http_put_stream_chunk_size": 1048576}}"#).unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.http_put_stream_chunk_size, Some(1048576));
+    assert_eq!(Config::default().http_put_stream_chunk_size, None);
+}
+
+#[test]
+fn test_http_get_stream_chunk_size_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, r#"{{"http_get_stream_chunk_size": 1048576}}"#).unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.http_get_stream_chunk_size, Some(1048576));
+    assert_eq!(Config::default().http_get_stream_chunk_size, None);
+    assert_eq!(Config::default().http_list_stream_page_size, None);
+    let config = Config::from_value(json!({"http_list_stream_page_size": 1000})).unwrap();
+    assert_eq!(config.http_list_stream_page_size, Some(1000));
+}
+
+#[test]
+fn test_runtime_flavor_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, r#"{{"runtime_flavor": "current_thread"}}"#).unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.runtime_flavor, RuntimeFlavor::CurrentThread);
+    assert_eq!(config.effective_http_threads(), 1);
+
+    let config = Config::default();
+    assert_eq!(config.runtime_flavor, RuntimeFlavor::MultiThread);
+    assert_eq!(config.effective_http_threads(), DEFAULT_HTTP_THREADS);
+}
+
+#[test]
+fn test_root_commands_parse() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(
+        file,
+        r#"{{"root_commands": {{"get": "PING", "put": "SET/last", "post_body_command": true}}}}"#
+    )
+    .unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    let root = config.root_commands.expect("root_commands should parse");
+    assert_eq!(root.get.as_deref(), Some("PING"));
+    assert_eq!(root.post, None);
+    assert_eq!(root.put.as_deref(), Some("SET/last"));
+    assert!(root.post_body_command);
+    assert!(Config::default().root_commands.is_none());
+}
+
+#[test]
+fn test_json_nil_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, r#"{{"json_nil": "false"}}"#).unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.json_nil, JsonNil::False);
+    assert_eq!(Config::default().json_nil, JsonNil::Null);
+}
+
+#[test]
+fn test_error_format_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(
+        file,
+        r#"{{"error_format": {{"code_field": "code", "request_id_header": "X-Request-Id", "envelope": "error"}}}}"#
+    )
+    .unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    let format = config.error_format.unwrap();
+    assert_eq!(format.message_field, "error");
+    assert_eq!(format.code_field.as_deref(), Some("code"));
+    assert_eq!(format.request_id_header.as_deref(), Some("X-Request-Id"));
+    assert_eq!(format.request_id_field, "request_id");
+    assert_eq!(format.envelope.as_deref(), Some("error"));
+    assert!(Config::default().error_format.is_none());
+}
+
+#[test]
+fn test_wait_for_redis_sec_parses() {
+    assert!(Config::default().wait_for_redis_sec.is_none());
+    let config = Config::from_value(json!({"wait_for_redis_sec": 30})).unwrap();
+    assert_eq!(config.wait_for_redis_sec, Some(30));
+}
+
+#[test]
+fn test_backend_health_parses() {
+    assert!(Config::default().backend_health.is_none());
+    let config = Config::from_value(json!({
+        "backends": {"replica": {"redis_host": "10.0.0.2"}},
+        "backend_health": {"interval_sec": 2, "unhealthy_threshold": 4}
+    }))
+    .unwrap();
+    let health = config.backend_health.unwrap();
+    assert_eq!(health.interval_sec, Some(2));
+    assert_eq!(health.timeout_ms, None);
+    assert_eq!(health.unhealthy_threshold, Some(4));
+    assert_eq!(health.healthy_threshold, None);
+}
+
+#[test]
+fn test_command_concurrency_parses() {
+    assert!(Config::default().command_concurrency.is_none());
+    let config = Config::from_value(json!({"command_concurrency": {"max_in_flight": 64}})).unwrap();
+    let limit = config.command_concurrency.unwrap();
+    assert_eq!(limit.max_in_flight, 64);
+    assert_eq!(limit.max_queued, None);
+    assert!(Config::from_value(json!({"command_concurrency": {"max_queued": 8}})).is_err());
+}
+
+#[test]
+fn test_shadow_parses() {
+    assert!(Config::default().shadow.is_none());
+    let config = Config::from_value(json!({
+        "backends": {"next": {"redis_host": "10.0.0.9"}},
+        "shadow": {"backend": "next", "percent": 12.5, "commands": ["get"]}
+    }))
+    .unwrap();
+    let shadow = config.shadow.unwrap();
+    assert_eq!(shadow.backend, "next");
+    assert_eq!(shadow.percent, Some(12.5));
+    assert_eq!(shadow.commands, Some(vec!["get".to_string()]));
+}
+
+#[test]
+fn test_acl_rule_redis_auth_parses() {
+    let config = Config::from_value(json!({
+        "acl": [
+            {"http_basic_auth": "alice:secret", "redis_auth": ["tenant-alice", "pw"]},
+            {"ip": "10.0.0.0/8", "redis_auth": "legacy-pw"},
+            {"disabled": ["DEBUG"]}
+        ]
+    }))
+    .unwrap();
+    let rules = config.acl.unwrap();
+    assert!(matches!(
+        &rules[0].redis_auth,
+        Some(RedisAuthConfig::ACL(parts)) if parts == &["tenant-alice", "pw"]
+    ));
+    assert!(matches!(
+        &rules[1].redis_auth,
+        Some(RedisAuthConfig::Legacy(password)) if password == "legacy-pw"
+    ));
+    assert!(rules[2].redis_auth.is_none());
+}
+
+#[test]
+fn test_acl_rule_schedule_parses() {
+    let config = Config::from_value(json!({
+        "acl": [{
+            "enabled": ["FLUSHDB"],
+            "schedule": {"days": ["sun"], "hours": "02:00-04:00", "timezone": "Europe/Paris"}
+        }]
+    }))
+    .unwrap();
+    let schedule = config.acl.unwrap()[0].schedule.clone().unwrap();
+    assert_eq!(schedule.days, Some(vec!["sun".to_string()]));
+    assert_eq!(schedule.hours.as_deref(), Some("02:00-04:00"));
+    assert_eq!(schedule.timezone.as_deref(), Some("Europe/Paris"));
+}
+
+#[test]
+fn test_quotas_parse() {
+    assert!(Config::default().quotas.is_none());
+    let config = Config::from_value(json!({"quotas": {
+        "commands_per_day": 10000,
+        "bytes_per_month": 1073741824,
+        "identities": {"partner-a": {"commands_per_day": 1000000}, "internal": {}}
+    }}))
+    .unwrap();
+    let quotas = config.quotas.unwrap();
+    assert_eq!(quotas.commands_per_day, Some(10000));
+    assert_eq!(quotas.commands_per_month, None);
+    assert_eq!(quotas.bytes_per_month, Some(1073741824));
+    let identities = quotas.identities.unwrap();
+    assert_eq!(identities["partner-a"].commands_per_day, Some(1000000));
+    assert_eq!(identities["internal"], QuotaLimits::default());
+}
+
+#[test]
+fn test_value_size_limits_parse() {
+    assert!(Config::default().max_value_size.is_none());
+    let config = Config::from_value(json!({
+        "max_value_size": 1048576,
+        "value_size_limits": [{"key_pattern": "blob:*", "max_bytes": 16777216}]
+    }))
+    .unwrap();
+    assert_eq!(config.max_value_size, Some(1048576));
+    let limits = config.value_size_limits.unwrap();
+    assert_eq!(limits[0].key_pattern, "blob:*");
+    assert_eq!(limits[0].max_bytes, 16777216);
+    assert!(Config::from_value(json!({"value_size_limits": [{"key_pattern": "a*"}]})).is_err());
+}
+
+#[test]
+fn test_audit_parse() {
+    assert!(Config::default().audit.is_none());
+    let config = Config::from_value(json!({"audit": {"max_len": 5000, "writes": false}})).unwrap();
+    let audit = config.audit.unwrap();
+    assert_eq!(audit.stream, None);
+    assert_eq!(audit.max_len, Some(5000));
+    assert_eq!(audit.writes, Some(false));
+    assert_eq!(audit.denied, None);
+}
+
+#[test]
+fn test_shards_parse() {
+    assert!(Config::default().shards.is_none());
+    let config = Config::from_value(json!({
+        "backends": {"a": {"redis_port": 7001}, "b": {"redis_port": 7002}},
+        "shards": ["a", "b"]
+    }))
+    .unwrap();
+    assert_eq!(config.shards, Some(vec!["a".to_string(), "b".to_string()]));
+}
+
+#[test]
+fn test_pool_wait_settings_parse() {
+    let config = Config::default();
+    assert!(config.pool_wait_timeout_ms.is_none());
+    assert!(config.pool_max_waiting.is_none());
+    let config =
+        Config::from_value(json!({"pool_wait_timeout_ms": 250, "pool_max_waiting": 32})).unwrap();
+    assert_eq!(config.pool_wait_timeout_ms, Some(250));
+    assert_eq!(config.pool_max_waiting, Some(32));
+}
+
+#[test]
+fn test_pool_shards_parses() {
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, r#"{{"pool_shards": 8}}"#).unwrap();
+
+    let config = Config::new(file.path().to_str().unwrap()).unwrap();
+    assert_eq!(config.pool_shards, Some(8));
+    assert_eq!(Config::default().pool_shards, None);
+}
+
 #[test]
 fn test_compat_hiredis_config_parses() {
     let config_json = r#"{
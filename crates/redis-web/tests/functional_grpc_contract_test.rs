@@ -1,7 +1,7 @@
 mod support;
 
 use base64::{engine::general_purpose, Engine as _};
-use redis_web_core::config::{AclConfig, TransportMode};
+use redis_web_core::config::{AclConfig, BackendConfig, RouteConfig, TransportMode};
 use redis_web_runtime::grpc::proto::{
     self, redis_gateway_client::RedisGatewayClient, stream_command_reply,
 };
@@ -49,6 +49,92 @@ async fn test_grpc_execute_and_binary_args() {
     }
 }
 
+#[tokio::test]
+async fn test_grpc_commands_count_against_quotas() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.transport_mode = TransportMode::Grpc;
+    cfg.quotas = Some(redis_web_core::config::QuotaConfig {
+        commands_per_day: Some(2),
+        ..Default::default()
+    });
+
+    let server = GrpcFunctionalServer::spawn(cfg, executor).await;
+    let mut client = RedisGatewayClient::connect(format!("http://{}", server.addr))
+        .await
+        .unwrap();
+    let get = || proto::CommandRequest {
+        command: "GET".to_string(),
+        database: None,
+        args: vec![b"k".to_vec()],
+    };
+
+    client.execute(get()).await.unwrap();
+    let status = client
+        .pipeline(proto::PipelineRequest {
+            commands: vec![get(), get()],
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert!(status.message().contains("quota"));
+}
+
+#[tokio::test]
+async fn test_grpc_commands_follow_routes_and_backend_acls() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.transport_mode = TransportMode::Grpc;
+    cfg.backends = Some(std::collections::BTreeMap::from([(
+        "analytics".to_string(),
+        BackendConfig {
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: 6380,
+            redis_socket: None,
+            database: 2,
+            redis_auth: None,
+            acl: Some(vec![AclConfig {
+                http_basic_auth: None,
+                ip: None,
+                enabled: None,
+                disabled: Some(vec!["DEL".to_string()]),
+                channels: None,
+                redis_auth: None,
+                schedule: None,
+            }]),
+        },
+    )]));
+    cfg.routes = Some(vec![RouteConfig {
+        commands: Some(vec!["GET".to_string(), "DEL".to_string()]),
+        key_pattern: Some("stats:*".to_string()),
+        backend: "analytics".to_string(),
+    }]);
+
+    let server = GrpcFunctionalServer::spawn(cfg, executor.clone()).await;
+    let mut client = RedisGatewayClient::connect(format!("http://{}", server.addr))
+        .await
+        .unwrap();
+    let command = |name: &str, key: &str| proto::CommandRequest {
+        command: name.to_string(),
+        database: None,
+        args: vec![key.as_bytes().to_vec()],
+    };
+
+    client.execute(command("GET", "stats:daily")).await.unwrap();
+    client.execute(command("GET", "users:1")).await.unwrap();
+    let status = client
+        .execute(command("DEL", "stats:daily"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].backend.as_deref(), Some("analytics"));
+    assert_eq!(seen[0].target_database, 2);
+    assert_eq!(seen[1].backend, None);
+}
+
 #[tokio::test]
 async fn test_grpc_execute_stream_keeps_per_message_errors_in_payload() {
     let executor = Arc::new(ScriptedStubExecutor::new());
@@ -114,12 +200,18 @@ async fn test_grpc_acl_uses_authorization_metadata() {
             ip: None,
             enabled: None,
             disabled: Some(vec!["DEBUG".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
         },
         AclConfig {
             http_basic_auth: Some("user:password".to_string()),
             ip: None,
             enabled: Some(vec!["DEBUG".to_string()]),
             disabled: None,
+            channels: None,
+            redis_auth: None,
+            schedule: None,
         },
     ]);
 
@@ -154,3 +246,67 @@ async fn test_grpc_acl_uses_authorization_metadata() {
 
     client.execute(request).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_grpc_pipeline_returns_one_value_per_command() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.transport_mode = TransportMode::Grpc;
+    cfg.acl = Some(vec![AclConfig {
+        http_basic_auth: None,
+        ip: None,
+        enabled: None,
+        disabled: Some(vec!["DEBUG".to_string()]),
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    }]);
+
+    let server = GrpcFunctionalServer::spawn(cfg, executor).await;
+    let mut client = RedisGatewayClient::connect(format!("http://{}", server.addr))
+        .await
+        .unwrap();
+    let command = |name: &str, args: &[&[u8]]| proto::CommandRequest {
+        command: name.to_string(),
+        database: None,
+        args: args.iter().map(|arg| arg.to_vec()).collect(),
+    };
+
+    let reply = client
+        .pipeline(proto::PipelineRequest {
+            commands: vec![
+                command("SET", &[b"hello", b"world"]),
+                command("GET", &[b"hello"]),
+            ],
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(reply.values.len(), 2);
+    match reply.values[1].kind.as_ref().unwrap() {
+        proto::redis_value::Kind::BulkBytes(bytes) => assert_eq!(bytes, b"world"),
+        other => panic!("expected bulk bytes reply, got {:?}", other),
+    }
+
+    let denied = client
+        .pipeline(proto::PipelineRequest {
+            commands: vec![
+                command("SET", &[b"hello", b"again"]),
+                command("DEBUG", &[b"OBJECT", b"hello"]),
+            ],
+        })
+        .await
+        .expect_err("a denied command fails the pipeline");
+    assert_eq!(denied.code(), Code::PermissionDenied);
+
+    // The allowed SET did not run either.
+    let reply = client
+        .execute(command("GET", &[b"hello"]))
+        .await
+        .unwrap()
+        .into_inner();
+    match reply.value.unwrap().kind.unwrap() {
+        proto::redis_value::Kind::BulkBytes(bytes) => assert_eq!(bytes, b"world"),
+        other => panic!("expected bulk bytes reply, got {:?}", other),
+    }
+}
@@ -1,5 +1,10 @@
 mod support;
 
+use redis_web_core::config::{
+    DownloadRuleConfig, ErrorFormatConfig, JsonNil, OutputFormatRuleConfig, RootCommandsConfig,
+    RouteConfig, ShadowConfig,
+};
+use redis_web_core::interfaces::{CommandHook, ExecutableCommand};
 use reqwest::Client;
 use std::sync::Arc;
 use support::process_harness::parse_jsonp_body;
@@ -42,87 +47,1568 @@ async fn test_request_body_size_limit() {
 }
 
 #[tokio::test]
-async fn test_content_type_override_and_jsonp_raw_exclusion() {
+async fn test_root_commands_per_method() {
     let executor = Arc::new(ScriptedStubExecutor::new());
-    let server = FunctionalServer::spawn(functional_config(), executor).await;
+    let mut cfg = functional_config();
+    cfg.root_commands = Some(RootCommandsConfig {
+        get: Some("PING".to_string()),
+        put: Some("SET/last".to_string()),
+        post_body_command: true,
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
     let client = Client::new();
+    let root = format!("http://{}/", server.addr);
 
-    let _ = client
-        .get(format!("http://{}/SET/hello/world", server.addr))
+    let resp = client.get(&root).send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"PING": "OK"}));
+
+    let resp = client
+        .post(&root)
+        .body("SET/greeting/hi\n")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client.put(&root).body("payload").send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .get(format!("http://{}/GET/greeting.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.text().await.unwrap(), "hi");
+    let resp = client
+        .get(format!("http://{}/GET/last.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.text().await.unwrap(), "payload");
+
+    let resp = client
+        .post(&root)
+        .body(vec![0xff, 0xfe])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // Without `root_commands`, only OPTIONS is served on `/`.
+    let plain = FunctionalServer::spawn(functional_config(), executor).await;
+    let resp = client
+        .post(format!("http://{}/", plain.addr))
+        .body("PING")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn test_root_commands_expand_request_placeholders() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.root_commands = Some(RootCommandsConfig {
+        post: Some("LPUSH/audit:{ip}/{header:User-Agent}".to_string()),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let resp = Client::new()
+        .post(format!("http://{}/", server.addr))
+        .header("User-Agent", "curl/8.5.0")
+        .body("ping")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let requests = executor.seen_requests().await;
+    let args: Vec<_> = requests[0].args.iter().map(|arg| arg.as_slice()).collect();
+    assert_eq!(requests[0].command_name, "LPUSH");
+    assert_eq!(args, vec![&b"audit:127.0.0.1"[..], b"curl/8.5.0", b"ping"]);
+}
+
+#[tokio::test]
+async fn test_empty_path_segments_are_empty_arguments() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/SET/key/", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let requests = executor.seen_requests().await;
+    assert_eq!(requests[0].args, vec![b"key".to_vec(), Vec::new()]);
+
+    let resp = client
+        .get(format!("http://{}//GET/key", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(executor.seen_requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_sep_parameter_allows_slashes_in_arguments() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/SET|path/to/key|v?sep=|", server.addr))
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(executor.keys().await, vec!["path/to/key".to_string()]);
+
+    let resp = client
+        .get(format!("http://{}/GET|key?sep=ab", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_output_formats_apply_to_urls_without_extension() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.output_formats = Some(vec![
+        OutputFormatRuleConfig {
+            commands: Some(vec!["GET".to_string()]),
+            key_pattern: Some("img:*".to_string()),
+            format: "png".to_string(),
+        },
+        OutputFormatRuleConfig {
+            commands: Some(vec!["GET".to_string()]),
+            key_pattern: Some("resp:*".to_string()),
+            format: "raw".to_string(),
+        },
+    ]);
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    for key in ["img:logo", "resp:1", "plain"] {
+        let resp = client
+            .put(format!("http://{}/SET/{key}", server.addr))
+            .body(&b"\x89PNG"[..])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    }
+
+    let resp = client
+        .get(format!("http://{}/GET/img:logo?jsonp=cb", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "image/png");
+    assert_eq!(resp.bytes().await.unwrap(), &b"\x89PNG"[..]);
+
+    let resp = client
+        .get(format!("http://{}/GET/resp:1", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "text/plain");
+    assert_eq!(resp.bytes().await.unwrap(), &b"$4\r\n\x89PNG\r\n"[..]);
+
+    // An extension in the URL wins, and other keys stay JSON.
+    let resp = client
+        .get(format!("http://{}/GET/img:logo.json", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"GET": "\u{fffd}PNG"}));
+    let resp = client
+        .get(format!("http://{}/GET/plain", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/json");
+}
+
+#[tokio::test]
+async fn test_downloads_add_content_disposition() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.downloads = Some(vec![DownloadRuleConfig {
+        commands: None,
+        key_pattern: Some("report:*".to_string()),
+        filename: "{key}.csv".to_string(),
+    }]);
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    client
+        .put(format!("http://{}/SET/report:1", server.addr))
+        .body("a,b\n")
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .get(format!("http://{}/GET/report:1.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.headers()["content-disposition"],
+        "attachment; filename=\"report:1.csv\""
+    );
+    assert_eq!(resp.text().await.unwrap(), "a,b\n");
 
     let resp = client
         .get(format!(
-            "http://{}/GET/hello?type=application/pdf",
+            "http://{}/GET/report:1.raw?filename=out.resp",
             server.addr
         ))
         .send()
         .await
         .unwrap();
     assert_eq!(
-        resp.headers()[reqwest::header::CONTENT_TYPE],
-        "application/pdf"
+        resp.headers()["content-disposition"],
+        "attachment; filename=\"out.resp\""
     );
 
+    // JSON replies stay inline.
     let resp = client
-        .get(format!("http://{}/GET/hello?jsonp=myFn", server.addr))
+        .get(format!(
+            "http://{}/GET/report:1?filename=out.csv",
+            server.addr
+        ))
         .send()
         .await
         .unwrap();
-    let body = resp.text().await.unwrap();
-    let (cb, json) = parse_jsonp_body(&body);
-    assert_eq!(cb, "myFn");
-    assert_eq!(json["GET"], "world");
+    assert!(resp.headers().get("content-disposition").is_none());
+}
+
+#[tokio::test]
+async fn test_ttl_header_on_single_key_reads() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.ttl_header = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
 
     let resp = client
-        .get(format!("http://{}/GET/hello.raw?jsonp=myFn", server.addr))
+        .get(format!("http://{}/SET/k/v", server.addr))
         .send()
         .await
         .unwrap();
-    let body = resp.text().await.unwrap();
-    assert_eq!(body, "$5\r\nworld\r\n");
+    assert!(resp.headers().get("x-ttl-ms").is_none());
+
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["x-ttl-ms"], "-1");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"GET": "v"}));
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen.last().unwrap().command_name, "PTTL");
+
+    // A missing key has no TTL to report.
+    let resp = client
+        .get(format!("http://{}/GET/missing", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("x-ttl-ms").is_none());
 }
 
 #[tokio::test]
-async fn test_etag_positive_and_negative_paths() {
+async fn test_last_modified_from_shadow_timestamp_keys() {
     let executor = Arc::new(ScriptedStubExecutor::new());
-    let server = FunctionalServer::spawn(functional_config(), executor).await;
+    let mut cfg = functional_config();
+    cfg.last_modified = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
     let client = Client::new();
 
-    let _ = client
-        .get(format!("http://{}/SET/etag_key/foo", server.addr))
+    let resp = client
+        .get(format!("http://{}/SET/k/v", server.addr))
         .send()
         .await
         .unwrap();
+    assert!(resp.headers().get("last-modified").is_none());
+    assert_eq!(executor.keys().await, ["k", "k:__mtime__"]);
 
-    let first = client
-        .get(format!("http://{}/GET/etag_key", server.addr))
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
         .send()
         .await
         .unwrap();
-    let etag = first.headers().get("ETag").unwrap().clone();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let last_modified = resp.headers()["last-modified"].clone();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"GET": "v"}));
 
-    let not_modified = client
-        .get(format!("http://{}/GET/etag_key", server.addr))
-        .header("If-None-Match", etag.clone())
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .header("If-Modified-Since", last_modified.clone())
         .send()
         .await
         .unwrap();
-    assert_eq!(not_modified.status(), reqwest::StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers()["last-modified"], last_modified);
+    assert_eq!(resp.headers()["access-control-allow-origin"], "*");
 
-    let _ = client
-        .get(format!("http://{}/SET/etag_key/bar", server.addr))
+    // An older date, or an If-None-Match that does not match, gets the value.
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .header("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .header("If-Modified-Since", last_modified.clone())
+        .header("If-None-Match", "\"other\"")
         .send()
         .await
         .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
 
-    let changed = client
-        .get(format!("http://{}/GET/etag_key", server.addr))
-        .header("If-None-Match", etag)
+    // Deleting the key drops its timestamp.
+    client
+        .get(format!("http://{}/DEL/k", server.addr))
         .send()
         .await
         .unwrap();
-    assert_eq!(changed.status(), reqwest::StatusCode::OK);
+    assert!(executor.keys().await.is_empty());
+    let resp = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .header("If-Modified-Since", last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert!(resp.headers().get("last-modified").is_none());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"GET": null}));
+}
+
+#[tokio::test]
+async fn test_audit_stream_records_writes_and_denied_requests() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.audit = Some(redis_web_core::config::AuditConfig {
+        stream: Some("audit".to_string()),
+        ..Default::default()
+    });
+    cfg.acl = serde_json::from_value(serde_json::json!([{"disabled": ["FLUSHALL"]}])).unwrap();
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    for path in ["SET/k/v", "GET/k", "FLUSHALL"] {
+        client
+            .get(format!("http://{}/{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        records = executor
+            .seen_requests()
+            .await
+            .into_iter()
+            .filter(|request| request.command_name == "XADD")
+            .collect();
+        if records.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(records.len(), 2);
+    let fields = |index: usize| -> Vec<String> {
+        records[index]
+            .args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect()
+    };
+    let set = fields(0);
+    assert_eq!(set[0], "audit");
+    assert!(set.windows(2).any(|pair| pair == ["command", "SET"]));
+    assert!(set.windows(2).any(|pair| pair == ["identity", "127.0.0.1"]));
+    let denied = fields(1);
+    assert!(denied
+        .windows(2)
+        .any(|pair| pair == ["command", "FLUSHALL"]));
+    assert!(denied.windows(2).any(|pair| pair == ["status", "403"]));
+}
+
+#[tokio::test]
+async fn test_json_nil_controls_nil_replies() {
+    let client = Client::new();
+    for (json_nil, expected) in [
+        (JsonNil::Null, serde_json::json!({"GET": null})),
+        (JsonNil::False, serde_json::json!({"GET": false})),
+        (JsonNil::Omit, serde_json::json!({})),
+    ] {
+        let mut cfg = functional_config();
+        cfg.json_nil = json_nil;
+        let server = FunctionalServer::spawn(cfg, Arc::new(ScriptedStubExecutor::new())).await;
+        let resp = client
+            .get(format!("http://{}/GET/missing", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body, expected, "{json_nil:?}");
+    }
+}
+
+#[tokio::test]
+async fn test_error_format_shapes_error_bodies() {
+    let mut cfg = functional_config();
+    cfg.error_format = Some(ErrorFormatConfig {
+        message_field: "message".to_string(),
+        code_field: Some("code".to_string()),
+        request_id_header: Some("X-Request-Id".to_string()),
+        envelope: Some("error".to_string()),
+        ..ErrorFormatConfig::default()
+    });
+    let server = FunctionalServer::spawn(cfg, Arc::new(ScriptedStubExecutor::new())).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/FAIL/key", server.addr))
+        .header("X-Request-Id", "7f3a")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"error": {
+            "message": "stub execution failure",
+            "code": "command_failed",
+            "request_id": "7f3a",
+        }})
+    );
+
+    let resp = client
+        .post(format!("http://{}/_query", server.addr))
+        .body("[]")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "bad_request");
+    assert!(body["error"].get("request_id").is_none());
+}
+
+#[tokio::test]
+async fn test_put_set_streams_large_bodies_in_chunks() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.http_put_stream_chunk_size = Some(1024);
+    cfg.http_max_request_size = Some(16 * 1024);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let payload: Vec<u8> = (0..10_000).map(|idx| b'a' + (idx % 26) as u8).collect();
+    let resp = client
+        .put(format!("http://{}/SET/big", server.addr))
+        .body(payload.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["SET"], "OK");
+
+    let resp = client
+        .get(format!("http://{}/GET/big.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.bytes().await.unwrap(), payload);
+    assert_eq!(executor.keys().await, vec!["big".to_string()]);
+    let appends = executor
+        .seen_requests()
+        .await
+        .iter()
+        .filter(|request| request.command_name == "APPEND")
+        .count();
+    assert_eq!(appends, 9);
+
+    // Other commands and small bodies are buffered as before.
+    let resp = client
+        .put(format!("http://{}/SET/small", server.addr))
+        .body("value")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    // An oversized upload is refused and its temporary key removed.
+    let resp = client
+        .put(format!("http://{}/SET/huge", server.addr))
+        .body(vec![b'x'; 32 * 1024])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        executor.keys().await,
+        vec!["big".to_string(), "small".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_get_streams_large_values_in_chunks() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.http_get_stream_chunk_size = Some(1024);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let mut payload: Vec<u8> = (0..10_000).map(|idx| b'a' + (idx % 26) as u8).collect();
+    payload.extend_from_slice("caf\u{e9} \"quoted\"\n".as_bytes());
+    let resp = client
+        .put(format!("http://{}/SET/big", server.addr))
+        .body(payload.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let resp = client
+        .get(format!("http://{}/GET/big.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/plain");
+    assert!(resp.headers().get("etag").is_none());
+    assert_eq!(resp.bytes().await.unwrap(), payload);
+
+    let resp = client
+        .get(format!("http://{}/GET/big", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"GET": String::from_utf8(payload).unwrap()})
+    );
+    let ranges = executor
+        .seen_requests()
+        .await
+        .iter()
+        .filter(|request| request.command_name == "GETRANGE")
+        .count();
+    assert_eq!(ranges, 2 * 10);
+
+    // Short values take the usual GET, ETag included.
+    let resp = client
+        .put(format!("http://{}/SET/small", server.addr))
+        .body("value")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .get(format!("http://{}/GET/small", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("etag").is_some());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["GET"], "value");
+}
+
+#[tokio::test]
+async fn test_lrange_streams_large_ranges_in_pages() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.http_list_stream_page_size = Some(10);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let items: Vec<String> = (0..25).map(|idx| format!("item \"{idx}\"")).collect();
+    let mut url = format!("http://{}/RPUSH/list", server.addr);
+    for item in &items {
+        url.push('/');
+        url.push_str(&item.replace('"', "%22").replace(' ', "%20"));
+    }
+    client.get(url).send().await.unwrap();
+
+    let resp = client
+        .get(format!("http://{}/LRANGE/list/2/-1", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "application/json");
+    assert!(resp.headers().get("etag").is_none());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"LRANGE": items[2..]}));
+    let pages = executor
+        .seen_requests()
+        .await
+        .iter()
+        .filter(|request| request.command_name == "LRANGE")
+        .count();
+    assert_eq!(pages, 3);
+
+    let resp = client
+        .get(format!("http://{}/LRANGE/list/0/-1?jsonp=cb", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    let expected = serde_json::json!({"LRANGE": items}).to_string();
+    assert_eq!(body, format!("cb({expected})"));
+
+    // Short ranges take the usual LRANGE, ETag included.
+    let resp = client
+        .get(format!("http://{}/LRANGE/list/0/4", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("etag").is_some());
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"LRANGE": items[..5]}));
+}
+
+#[tokio::test]
+async fn test_path_segments_are_percent_decoded() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    // The client percent-encodes the space and the non-ASCII characters.
+    let resp = client
+        .get(format!(
+            "http://{}/SET/dir%2Fcaf\u{e9} \u{2713}/1+1",
+            server.addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let resp = client
+        .get(format!(
+            "http://{}/GET/dir%2Fcaf%C3%A9%20%E2%9C%93",
+            server.addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["GET"], "1+1");
+    assert_eq!(
+        executor.keys().await,
+        vec!["dir/caf\u{e9} \u{2713}".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_query_args_follow_path_args_in_order() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!(
+            "http://{}/SET?arg=a%2Fb&jsonp=cb&arg=x+y%20%26z",
+            server.addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .put(format!("http://{}/APPEND?arg=a%2Fb", server.addr))
+        .body("!")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen[0].args, vec![b"a/b".to_vec(), b"x y &z".to_vec()]);
+    assert_eq!(seen[1].args, vec![b"a/b".to_vec(), b"!".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_raw_and_text_outputs_keep_binary_values_intact() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor).await;
+    let client = Client::new();
+
+    let value = vec![0xff, 0x00, b'a', 0xc3, 0x28];
+    let resp = client
+        .put(format!("http://{}/SET/blob", server.addr))
+        .body(value.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let resp = client
+        .get(format!("http://{}/GET/blob.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.bytes().await.unwrap(), value);
+
+    let mut raw = b"$5\r\n".to_vec();
+    raw.extend_from_slice(&value);
+    raw.extend_from_slice(b"\r\n");
+    let resp = client
+        .get(format!("http://{}/GET/blob.raw", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.bytes().await.unwrap(), raw);
+
+    let resp = client
+        .get(format!("http://{}/SET/path/%FF%00a%C3%28", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let resp = client
+        .get(format!("http://{}/GET/path.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.bytes().await.unwrap(), value);
+
+    let resp = client
+        .get(format!("http://{}/GET/missing.raw", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(resp.bytes().await.unwrap(), "$-1\r\n");
+
+    let resp = client
+        .get(format!("http://{}/GET/missing.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    assert!(resp.bytes().await.unwrap().is_empty());
+
+    let resp = client
+        .get(format!("http://{}/WRONGTYPE/blob.raw", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(
+        resp.bytes().await.unwrap(),
+        "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+    );
+
+    let resp = client
+        .get(format!("http://{}/FAIL/blob.raw", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        resp.bytes().await.unwrap(),
+        "-ERR stub execution failure\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_content_type_override_and_jsonp_raw_exclusion() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor).await;
+    let client = Client::new();
+
+    let _ = client
+        .get(format!("http://{}/SET/hello/world", server.addr))
+        .send()
+        .await
+        .unwrap();
+
+    let resp = client
+        .get(format!(
+            "http://{}/GET/hello?type=application/pdf",
+            server.addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.headers()[reqwest::header::CONTENT_TYPE],
+        "application/pdf"
+    );
+
+    let resp = client
+        .get(format!("http://{}/GET/hello?jsonp=myFn", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    let (cb, json) = parse_jsonp_body(&body);
+    assert_eq!(cb, "myFn");
+    assert_eq!(json["GET"], "world");
+
+    let resp = client
+        .get(format!("http://{}/GET/hello.raw?jsonp=myFn", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let body = resp.text().await.unwrap();
+    assert_eq!(body, "$5\r\nworld\r\n");
+
+    let resp = client
+        .get(format!(
+            "http://{}/GET/hello?callback=alert(document.cookie)//",
+            server.addr
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    let content_type = resp.headers()[reqwest::header::CONTENT_TYPE].clone();
+    assert!(content_type
+        .to_str()
+        .unwrap()
+        .starts_with("application/json"));
+    let body = resp.text().await.unwrap();
+    assert!(!body.contains("alert"), "{body}");
+}
+
+#[tokio::test]
+async fn test_etag_positive_and_negative_paths() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor).await;
+    let client = Client::new();
+
+    let _ = client
+        .get(format!("http://{}/SET/etag_key/foo", server.addr))
+        .send()
+        .await
+        .unwrap();
+
+    let first = client
+        .get(format!("http://{}/GET/etag_key", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let etag = first.headers().get("ETag").unwrap().clone();
+
+    let not_modified = client
+        .get(format!("http://{}/GET/etag_key", server.addr))
+        .header("If-None-Match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(not_modified.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    let _ = client
+        .get(format!("http://{}/SET/etag_key/bar", server.addr))
+        .send()
+        .await
+        .unwrap();
+
+    let changed = client
+        .get(format!("http://{}/GET/etag_key", server.addr))
+        .header("If-None-Match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(changed.status(), reqwest::StatusCode::OK);
+
+    // Raw replies carry their own tag; lists, weak tags and `*` match.
+    let raw = client
+        .get(format!("http://{}/GET/etag_key.raw", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let raw_etag = raw.headers()["ETag"].to_str().unwrap().to_string();
+    for if_none_match in [
+        format!("\"stale\", W/{raw_etag}"),
+        raw_etag.clone(),
+        "*".to_string(),
+    ] {
+        let resp = client
+            .get(format!("http://{}/GET/etag_key.raw", server.addr))
+            .header("If-None-Match", if_none_match)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_MODIFIED);
+    }
+    let resp = client
+        .get(format!("http://{}/GET/etag_key.txt", server.addr))
+        .header("If-None-Match", raw_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_metrics_and_admin_stats_endpoints() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.metrics = Some(redis_web_core::config::MetricsConfig {
+        enabled: true,
+        path: None,
+        latency_probe_interval_ms: None,
+    });
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        http_basic_auth: Some("ops:secret".to_string()),
+        ip: None,
+        console: false,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/_metrics", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body = resp.text().await.unwrap();
+    assert!(body.contains("redis_web_pubsub_messages_received_total 0"));
+
+    let resp = client
+        .get(format!("http://{}/_admin/stats", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(format!("http://{}/_admin/stats", server.addr))
+        .basic_auth("ops", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let stats: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(stats["pubsub"]["topics"], serde_json::json!([]));
+    assert_eq!(stats["pubsub"]["reconnects"], 0);
+}
+
+#[tokio::test]
+async fn test_admin_config_masks_credentials() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.redis_auth = Some(redis_web_core::config::RedisAuthConfig::Legacy(
+        "hunter2".to_string(),
+    ));
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        http_basic_auth: Some("ops:secret".to_string()),
+        ip: None,
+        console: false,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+    let url = format!("http://{}/_admin/config", server.addr);
+
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(&url)
+        .basic_auth("ops", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["config"]["redis_auth"], "[redacted]");
+    assert_eq!(body["config"]["admin"]["http_basic_auth"], "[redacted]");
+    assert_eq!(body["sources"]["http_port"], "default");
+    assert!(!body.to_string().contains("secret"));
+}
+
+#[tokio::test]
+async fn test_console_is_served_behind_admin_auth() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        http_basic_auth: Some("ops:secret".to_string()),
+        ip: None,
+        console: true,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+    let url = format!("http://{}/_console", server.addr);
+
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().contains_key("www-authenticate"));
+
+    let resp = client
+        .get(&url)
+        .basic_auth("ops", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert!(resp.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(resp.text().await.unwrap().contains("/.json"));
+}
+
+#[tokio::test]
+async fn test_openapi_document_and_swagger_ui() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.openapi = Some(redis_web_core::config::OpenApiConfig {
+        enabled: true,
+        swagger_ui: true,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/_openapi.json", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let doc: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(doc["openapi"], "3.1.0");
+    assert!(doc["paths"]["/{command}"]["get"].is_object());
+    assert!(doc["paths"]["/_publish"]["post"].is_object());
+
+    let resp = client
+        .get(format!("http://{}/_openapi", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert!(resp.text().await.unwrap().contains("/_openapi.json"));
+}
+
+#[tokio::test]
+async fn test_static_dir_serves_files_with_cache_headers() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("index.html"), "<h1>home</h1>").unwrap();
+    std::fs::create_dir(dir.path().join("js")).unwrap();
+    std::fs::write(dir.path().join("js/app.js"), "console.log(1);").unwrap();
+
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.static_dir = Some(redis_web_core::config::StaticDirConfig {
+        dir: dir.path().to_string_lossy().into_owned(),
+        path_prefix: "/app".to_string(),
+        max_age_sec: 60,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/app", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert_eq!(resp.text().await.unwrap(), "<h1>home</h1>");
+
+    let resp = client
+        .get(format!("http://{}/app/js/app.js", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(
+        resp.headers()["content-type"],
+        "text/javascript; charset=utf-8"
+    );
+    assert_eq!(resp.headers()["cache-control"], "public, max-age=60");
+    let etag = resp.headers()["etag"].clone();
+
+    let resp = client
+        .get(format!("http://{}/app/js/app.js", server.addr))
+        .header("if-none-match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_MODIFIED);
+
+    for path in ["/app/missing.js", "/app/js/..%2F..%2Fsecret"] {
+        let resp = client
+            .get(format!("http://{}{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND, "{path}");
+    }
+
+    // Commands are still served next to the files.
+    let resp = client
+        .get(format!("http://{}/GET/key", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_favicon_and_robots_txt_skip_redis() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/favicon.ico", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "image/x-icon");
+    assert!(!resp.bytes().await.unwrap().is_empty());
+    assert!(executor.seen_requests().await.is_empty());
+
+    let dir = tempfile::tempdir().unwrap();
+    let icon = dir.path().join("icon.png");
+    std::fs::write(&icon, b"png bytes").unwrap();
+    let mut cfg = functional_config();
+    cfg.favicon = Some(icon.to_string_lossy().into_owned());
+    cfg.robots_txt = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let resp = client
+        .get(format!("http://{}/favicon.ico", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()["content-type"], "image/png");
+    assert_eq!(resp.bytes().await.unwrap(), &b"png bytes"[..]);
+
+    let resp = client
+        .get(format!("http://{}/robots.txt", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.text().await.unwrap(), "User-agent: *\nDisallow: /\n");
+    assert!(executor.seen_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_server_timing_header_is_opt_in() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/GET/key", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.headers().get("server-timing").is_none());
+
+    let mut cfg = functional_config();
+    cfg.server_timing = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let resp = client
+        .get(format!("http://{}/GET/key", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let timing = resp.headers()["server-timing"].to_str().unwrap();
+    assert!(timing.starts_with("pool;dur="), "{timing}");
+    assert!(timing.contains(", redis;dur=") && timing.contains(", total;dur="));
+}
+
+#[tokio::test]
+async fn test_info_describes_the_instance_without_redis() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let resp = Client::new()
+        .get(format!("http://{}/_info", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let info: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(info["name"], "redis-web");
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["git_hash"].is_string());
+    assert_eq!(info["capabilities"]["websockets"], true);
+    assert_eq!(info["listeners"][0]["protocol"], "http");
+    assert!(executor.seen_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_sse_per_ip_limit() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.sse = Some(redis_web_core::config::SseConfig {
+        max_connections_per_ip: Some(1),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+    let url = format!("http://{}/SUBSCRIBE/news", server.addr);
+
+    let open = client.get(&url).send().await.unwrap();
+    assert_eq!(open.status(), reqwest::StatusCode::OK);
+
+    let rejected = client.get(&url).send().await.unwrap();
+    assert_eq!(rejected.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+
+    drop(open);
+    // The slot is released once the server notices the closed stream.
+    let mut status = reqwest::StatusCode::TOO_MANY_REQUESTS;
+    for _ in 0..50 {
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        let retry = client.get(&url).send().await.unwrap();
+        status = retry.status();
+        if status == reqwest::StatusCode::OK {
+            break;
+        }
+    }
+    assert_eq!(status, reqwest::StatusCode::OK);
+}
+
+#[test]
+fn test_admin_needs_an_access_condition() {
+    let mut cfg = functional_config();
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        ..Default::default()
+    });
+    let error = redis_web_runtime::server::build_runtime(&cfg)
+        .err()
+        .expect("an open admin config must be refused");
+    assert_eq!(
+        error.to_string(),
+        "invalid admin config: enabled needs ip or http_basic_auth"
+    );
+}
+
+#[tokio::test]
+async fn test_admin_ratelimits_lists_open_streams() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.sse = Some(redis_web_core::config::SseConfig {
+        max_connections_per_ip: Some(3),
+        ..Default::default()
+    });
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        ip: Some("127.0.0.1/32".to_string()),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    let open = client
+        .get(format!("http://{}/SUBSCRIBE/news", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(open.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = client
+        .get(format!("http://{}/_admin/ratelimits", server.addr))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"streams": {
+            "max_connections": null,
+            "max_connections_per_ip": 3,
+            "clients": [{"ip": "127.0.0.1", "open_streams": 1, "remaining": 2}],
+        }, "quotas": null})
+    );
+}
+
+#[tokio::test]
+async fn test_admin_ratelimits_lists_and_resets_quota_blocks() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.quotas = Some(redis_web_core::config::QuotaConfig {
+        commands_per_day: Some(1),
+        ..Default::default()
+    });
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        ip: Some("127.0.0.1/32".to_string()),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+    let get = format!("http://{}/GET/k", server.addr);
+    let ratelimits = format!("http://{}/_admin/ratelimits", server.addr);
+
+    assert_eq!(client.get(&get).send().await.unwrap().status(), 200);
+    assert_eq!(client.get(&get).send().await.unwrap().status(), 429);
+
+    let body: serde_json::Value = client
+        .get(&ratelimits)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let clients = body["quotas"]["clients"].as_array().unwrap();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0]["identity"], "127.0.0.1");
+    assert_eq!(clients[0]["commands_today"], 2);
+    assert!(clients[0]["blocked_for"].as_u64().unwrap() > 0);
+
+    let reset: serde_json::Value = client
+        .delete(format!("{ratelimits}/127.0.0.1"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        reset,
+        serde_json::json!({"identity": "127.0.0.1", "cleared": 1})
+    );
+    assert_eq!(client.get(&get).send().await.unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn test_sse_sends_shutdown_event_and_ends() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(functional_config(), executor).await;
+
+    let mut resp = Client::new()
+        .get(format!("http://{}/SUBSCRIBE/news", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    server.shutdown.send_replace(true);
+
+    let mut body = Vec::new();
+    while let Some(chunk) = tokio::time::timeout(std::time::Duration::from_secs(5), resp.chunk())
+        .await
+        .expect("stream should end on shutdown")
+        .unwrap()
+    {
+        body.extend_from_slice(&chunk);
+    }
+    let body = String::from_utf8(body).unwrap();
+    assert!(
+        body.ends_with("event: shutdown\ndata: server_shutdown\n\n"),
+        "unexpected body: {body:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_sse_keep_alive_uses_configured_text() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.sse = Some(redis_web_core::config::SseConfig {
+        keep_alive_sec: Some(1),
+        keep_alive_text: Some("heartbeat".to_string()),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let mut resp = Client::new()
+        .get(format!("http://{}/SUBSCRIBE/news", server.addr))
+        .send()
+        .await
+        .unwrap();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), resp.chunk())
+        .await
+        .expect("keep-alive should arrive")
+        .unwrap()
+        .unwrap();
+    assert_eq!(&chunk[..], b": heartbeat\n\n");
+}
+
+#[tokio::test]
+async fn test_shadow_copies_sampled_reads() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.backends = Some(serde_json::from_value(serde_json::json!({"next": {}})).unwrap());
+    cfg.shadow = Some(ShadowConfig {
+        backend: "next".to_string(),
+        percent: None,
+        commands: None,
+    });
+    cfg.metrics = Some(redis_web_core::config::MetricsConfig {
+        enabled: true,
+        path: None,
+        latency_probe_interval_ms: None,
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    for path in ["SET/shadow_key/v", "GET/shadow_key"] {
+        let resp = client
+            .get(format!("http://{}/{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    }
+
+    let mut metrics = String::new();
+    for _ in 0..50 {
+        metrics = client
+            .get(format!("http://{}/_metrics", server.addr))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        if metrics.contains("redis_web_shadow_commands_total{result=\"match\"} 1\n") {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(metrics.contains("redis_web_shadow_commands_total{result=\"match\"} 1\n"));
+    assert!(metrics.contains("redis_web_shadow_commands_total{result=\"mismatch\"} 0\n"));
+
+    let backends: Vec<_> = executor
+        .seen_requests()
+        .await
+        .into_iter()
+        .map(|request| (request.command_name, request.backend))
+        .collect();
+    assert_eq!(
+        backends,
+        vec![
+            ("SET".to_string(), None),
+            ("GET".to_string(), None),
+            ("GET".to_string(), Some("next".to_string())),
+        ]
+    );
+}
+
+/// Moves keys under `tenant:`, renames `FORGET` to `DEL`, refuses `FLUSHALL`
+/// and upper-cases bulk string replies.
+struct TenantHook;
+
+impl CommandHook for TenantHook {
+    fn before(&self, request: &mut ExecutableCommand) -> Result<(), String> {
+        if request.command_name.eq_ignore_ascii_case("FLUSHALL") {
+            return Err("FLUSHALL is disabled".to_string());
+        }
+        if request.command_name.eq_ignore_ascii_case("FORGET") {
+            request.command_name = "DEL".to_string();
+        }
+        if let Some(key) = request.args.first_mut() {
+            key.splice(0..0, b"tenant:".iter().copied());
+        }
+        Ok(())
+    }
+
+    fn after(&self, _request: &ExecutableCommand, reply: &mut redis::Value) {
+        if let redis::Value::BulkString(bytes) = reply {
+            bytes.make_ascii_uppercase();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_hooks_run_before_routing_and_acls_but_not_on_internal_commands() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.backends = Some(serde_json::from_value(serde_json::json!({"tenants": {}})).unwrap());
+    cfg.routes = Some(vec![RouteConfig {
+        commands: None,
+        key_pattern: Some("tenant:*".to_string()),
+        backend: "tenants".to_string(),
+    }]);
+    cfg.acl = serde_json::from_value(serde_json::json!([{"disabled": ["DEL"]}])).unwrap();
+    cfg.audit = Some(redis_web_core::config::AuditConfig {
+        stream: Some("audit".to_string()),
+        ..Default::default()
+    });
+    let server =
+        FunctionalServer::spawn_with_hooks(cfg, executor.clone(), vec![Arc::new(TenantHook)]).await;
+    let client = Client::new();
+    let get = |path: &str| client.get(format!("http://{}/{path}", server.addr)).send();
+
+    assert_eq!(get("SET/k/v").await.unwrap().status(), 200);
+    let body: serde_json::Value = get("GET/k").await.unwrap().json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"GET": "V"}));
+    // The ACL sees the command the hook made.
+    assert_eq!(get("FORGET/k").await.unwrap().status(), 403);
+    let refused = get("FLUSHALL").await.unwrap();
+    assert_eq!(refused.status(), 400);
+    assert!(refused
+        .text()
+        .await
+        .unwrap()
+        .contains("FLUSHALL is disabled"));
+
+    let mut seen = Vec::new();
+    for _ in 0..50 {
+        seen = executor.seen_requests().await;
+        if seen.iter().filter(|r| r.command_name == "XADD").count() == 3 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let commands: Vec<_> = seen
+        .iter()
+        .filter(|request| request.command_name != "XADD")
+        .map(|request| {
+            (
+                request.command_name.as_str(),
+                String::from_utf8_lossy(&request.args[0]).into_owned(),
+                request.backend.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        commands,
+        [
+            ("SET", "tenant:k".to_string(), Some("tenants")),
+            ("GET", "tenant:k".to_string(), Some("tenants")),
+        ]
+    );
+    // Audit records of the SET, the denied DEL and the refused FLUSHALL
+    // reach their stream untouched.
+    let streams: Vec<_> = seen
+        .iter()
+        .filter(|request| request.command_name == "XADD")
+        .map(|request| request.args[0].as_slice())
+        .collect();
+    assert_eq!(streams, [b"audit"; 3]);
 }
@@ -1,7 +1,10 @@
 mod support;
 
-use redis_web_core::config::AclConfig;
+use redis_web_core::acl::Acl;
+use redis_web_core::config::{AclConfig, BackendConfig, RedisAuthConfig, RouteConfig};
+use redis_web_core::interfaces::{CommandExecutor, ExecutableCommand};
 use reqwest::Client;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use support::router_harness::{functional_config, FunctionalServer};
 use support::stub_executor::ScriptedStubExecutor;
@@ -56,6 +59,59 @@ async fn test_error_mapping_for_parser_and_executor() {
         .await
         .unwrap();
     assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+
+    let resp = client
+        .get(format!("http://{}/SLOW", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::GATEWAY_TIMEOUT);
+
+    let resp = client
+        .get(format!("http://{}/CROSSSLOT", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // Errors Redis replied with map by their code.
+    let resp = client
+        .get(format!("http://{}/WRONGTYPE", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .get(format!("http://{}/NOAUTH", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "NOAUTH Authentication required.");
+
+    // `ERR` is a client error only when Redis could not accept the request.
+    let resp = client
+        .get(format!("http://{}/RENAME/only-one", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .get(format!("http://{}/RENAME/missing/other", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+
+    let resp = client
+        .get(format!("http://{}/GET/key?timeout=later", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
 }
 
 #[tokio::test]
@@ -67,6 +123,9 @@ async fn test_acl_denied_before_executor() {
         ip: None,
         enabled: None,
         disabled: Some(vec!["PING".to_string()]),
+        channels: None,
+        redis_auth: None,
+        schedule: None,
     }]);
 
     let server = FunctionalServer::spawn(cfg, executor).await;
@@ -79,3 +138,605 @@ async fn test_acl_denied_before_executor() {
         .unwrap();
     assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
 }
+
+#[tokio::test]
+async fn test_acl_rule_credentials_follow_the_client() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![AclConfig {
+        http_basic_auth: Some("alice:secret".to_string()),
+        ip: None,
+        enabled: None,
+        disabled: None,
+        channels: None,
+        redis_auth: Some(RedisAuthConfig::ACL(vec![
+            "tenant-alice".to_string(),
+            "pw".to_string(),
+        ])),
+        schedule: None,
+    }]);
+    let alice = Acl::redis_credentials(cfg.acl.as_deref().unwrap())
+        .map(|(credentials, _)| credentials)
+        .next();
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    for user in [None, Some("alice")] {
+        let mut request = client.get(format!("http://{}/GET/tenant_key", server.addr));
+        if let Some(user) = user {
+            request = request.basic_auth(user, Some("secret"));
+        }
+        let resp = request.send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    }
+
+    let credentials: Vec<_> = executor
+        .seen_requests()
+        .await
+        .into_iter()
+        .map(|request| request.credentials)
+        .collect();
+    assert_eq!(credentials, vec![None, alice]);
+}
+
+#[tokio::test]
+async fn test_acl_guards_sse_subscriptions() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["SUBSCRIBE".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: Some(vec!["SUBSCRIBE".to_string()]),
+            disabled: None,
+            channels: Some(vec!["public.*".to_string()]),
+            redis_auth: None,
+            schedule: None,
+        },
+    ]);
+
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    for path in ["SUBSCRIBE/private", "SUBSCRIBE/public.news/private"] {
+        let resp = client
+            .get(format!("http://{}/{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN, "{path}");
+    }
+
+    let resp = client
+        .get(format!("http://{}/SUBSCRIBE/public.news", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_acl_guards_sse_pattern_subscriptions() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["PSUBSCRIBE".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: Some(vec!["PSUBSCRIBE".to_string()]),
+            disabled: None,
+            channels: Some(vec!["public.*".to_string()]),
+            redis_auth: None,
+            schedule: None,
+        },
+    ]);
+
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+
+    // The pattern text is matched, so `*` is not covered by `public.*`.
+    for path in ["PSUBSCRIBE/*", "PSUBSCRIBE/public.*,private.*"] {
+        let resp = client
+            .get(format!("http://{}/{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN, "{path}");
+    }
+
+    let resp = client
+        .get(format!("http://{}/PSUBSCRIBE/public.*", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_commands_lists_what_the_acl_allows() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["SET".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: Some("user:password".to_string()),
+            ip: None,
+            enabled: Some(vec!["SET".to_string()]),
+            disabled: None,
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+    ]);
+
+    let server = FunctionalServer::spawn(cfg, executor).await;
+    let client = Client::new();
+    let url = format!("http://{}/_commands", server.addr);
+
+    let body: serde_json::Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"commands": [
+            {"name": "GET", "categories": ["@read"], "readonly": true}
+        ]})
+    );
+
+    let body: serde_json::Value = client
+        .get(&url)
+        .basic_auth("user", Some("password"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<_> = body["commands"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|command| command["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["GET", "SET"]);
+}
+
+#[tokio::test]
+async fn test_query_returns_named_replies() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![AclConfig {
+        http_basic_auth: None,
+        ip: None,
+        enabled: None,
+        disabled: Some(vec!["HGETALL".to_string()]),
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    }]);
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+    let url = format!("http://{}/_query", server.addr);
+    executor
+        .execute(&ExecutableCommand {
+            backend: None,
+            target_database: 0,
+            command_name: "SET".to_string(),
+            args: vec![b"name".to_vec(), b"Ada".to_vec()],
+            timeout: None,
+            credentials: None,
+        })
+        .await
+        .unwrap();
+
+    let resp = client
+        .post(&url)
+        .body(r#"{"name": ["GET", "name"], "length": ["STRLEN", "name"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body, serde_json::json!({"name": "Ada", "length": 3}));
+
+    let resp = client
+        .post(&url)
+        .body(r#"{"name": ["GET", "name"], "user": ["HGETALL", "user:1"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // Writes are refused even where the `acl` rules allow them.
+    let resp = client
+        .post(&url)
+        .body(r#"{"name": ["GET", "name"], "write": ["SET", "name", "Bob"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert!(resp
+        .text()
+        .await
+        .unwrap()
+        .contains("SET, which is not a read-only command"));
+    let sets = executor
+        .seen_requests()
+        .await
+        .into_iter()
+        .filter(|request| request.command_name == "SET")
+        .count();
+    assert_eq!(sets, 1);
+
+    let resp = client.post(&url).body("[]").send().await.unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_publish_fans_out_to_channels_and_patterns() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = None;
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("http://{}/_publish", server.addr))
+        .body(r#"{"message":"hi","channels":["alerts","audit"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"PUBLISH": {"alerts": 1, "audit": 1}})
+    );
+
+    let resp = client
+        .post(format!("http://{}/_publish", server.addr))
+        .body(r#"{"message":"hi","pattern":"news.*"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({"PUBLISH": {"news.a": 1, "news.b": 1}})
+    );
+
+    let seen = executor.seen_requests().await;
+    let commands: Vec<_> = seen
+        .iter()
+        .map(|request| {
+            let mut parts = vec![request.command_name.clone()];
+            parts.extend(
+                request
+                    .args
+                    .iter()
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned()),
+            );
+            parts.join(" ")
+        })
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            "PUBLISH alerts hi",
+            "PUBLISH audit hi",
+            "PUBSUB CHANNELS news.*",
+            "PUBLISH news.a hi",
+            "PUBLISH news.b hi",
+        ]
+    );
+
+    let resp = client
+        .post(format!("http://{}/_publish", server.addr))
+        .body(r#"{"message":"hi"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_quotas_count_query_and_publish_commands_after_the_acl() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![AclConfig {
+        disabled: Some(vec!["HGETALL".to_string()]),
+        enabled: None,
+        http_basic_auth: None,
+        ip: None,
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    }]);
+    cfg.quotas = Some(redis_web_core::config::QuotaConfig {
+        commands_per_day: Some(3),
+        bytes_per_day: Some(1_000),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+    let query = format!("http://{}/_query", server.addr);
+
+    // Denied commands are not counted.
+    let resp = client
+        .post(&query)
+        .body(r#"{"user": ["HGETALL", "user:1"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // Each command of a batch counts.
+    let resp = client
+        .post(&query)
+        .body(r#"{"a": ["GET", "a"], "b": ["GET", "b"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(resp.headers()["x-quota-remaining"], "1");
+    let sent = resp.bytes().await.unwrap().len();
+
+    let resp = client
+        .post(format!("http://{}/_publish", server.addr))
+        .body(r#"{"message": "hi", "channels": ["a", "b"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("retry-after"));
+    assert!(!executor
+        .seen_requests()
+        .await
+        .iter()
+        .any(|request| request.command_name == "PUBLISH"));
+
+    // The reply bytes are counted in the background once the body is sent.
+    let mut counted = None;
+    for _ in 0..50 {
+        let key = executor
+            .keys()
+            .await
+            .into_iter()
+            .find(|key| key.ends_with(":bytes"));
+        if let Some(key) = key {
+            counted = executor.value(&key).await;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(counted, Some(sent.to_string().into_bytes()));
+}
+
+#[tokio::test]
+async fn test_publish_checks_channel_acls() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = Some(vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["PUBLISH".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: Some(vec!["PUBLISH".to_string()]),
+            disabled: None,
+            channels: Some(vec!["public.*".to_string()]),
+            redis_auth: None,
+            schedule: None,
+        },
+    ]);
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("http://{}/_publish", server.addr))
+        .body(r#"{"message":"hi","channels":["public.news","private"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    assert!(executor.seen_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_publish_follows_routes_and_backend_acls() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.acl = None;
+    cfg.backends = Some(BTreeMap::from([(
+        "events".to_string(),
+        BackendConfig {
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: 6380,
+            redis_socket: None,
+            database: 4,
+            redis_auth: None,
+            acl: Some(vec![AclConfig {
+                http_basic_auth: None,
+                ip: None,
+                enabled: None,
+                disabled: Some(vec!["PUBLISH".to_string()]),
+                channels: Some(vec!["events.private".to_string()]),
+                redis_auth: None,
+                schedule: None,
+            }]),
+        },
+    )]));
+    cfg.routes = Some(vec![RouteConfig {
+        commands: Some(vec!["PUBLISH".to_string()]),
+        key_pattern: Some("events.*".to_string()),
+        backend: "events".to_string(),
+    }]);
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+    let url = format!("http://{}/_publish", server.addr);
+
+    let resp = client
+        .post(&url)
+        .body(r#"{"message":"hi","channels":["events.a","news"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].backend.as_deref(), Some("events"));
+    assert_eq!(seen[0].target_database, 4);
+    assert_eq!(seen[1].backend, None);
+
+    // The backend's channel rules deny this one.
+    let resp = client
+        .post(&url)
+        .body(r#"{"message":"hi","channels":["events.private"]}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(executor.seen_requests().await.len(), 2);
+}
+
+#[tokio::test]
+async fn test_backend_prefix_routing_and_backend_acl() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.backends = Some(BTreeMap::from([(
+        "sessions".to_string(),
+        BackendConfig {
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: 6380,
+            redis_socket: None,
+            database: 3,
+            redis_auth: None,
+            acl: Some(vec![AclConfig {
+                http_basic_auth: None,
+                ip: None,
+                enabled: None,
+                disabled: Some(vec!["FLUSHDB".to_string()]),
+                channels: None,
+                redis_auth: None,
+                schedule: None,
+            }]),
+        },
+    )]));
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("http://{}/sessions/HGETALL/u1", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+
+    let seen = executor.seen_requests().await;
+    let last = seen.last().unwrap();
+    assert_eq!(last.backend.as_deref(), Some("sessions"));
+    assert_eq!(last.target_database, 3);
+    assert_eq!(last.command_name, "HGETALL");
+
+    // The backend ACL only applies to requests routed to that backend.
+    let resp = client
+        .get(format!("http://{}/sessions/FLUSHDB", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(format!("http://{}/FLUSHDB", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    assert_eq!(executor.seen_requests().await.last().unwrap().backend, None);
+}
+
+#[tokio::test]
+async fn test_routes_send_matching_commands_to_backend() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.backends = Some(BTreeMap::from([(
+        "replica".to_string(),
+        BackendConfig {
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: 6380,
+            redis_socket: None,
+            database: 0,
+            redis_auth: None,
+            acl: None,
+        },
+    )]));
+    cfg.routes = Some(vec![
+        RouteConfig {
+            commands: Some(vec!["SCAN".to_string(), "KEYS".to_string()]),
+            key_pattern: None,
+            backend: "replica".to_string(),
+        },
+        RouteConfig {
+            commands: Some(vec!["GET".to_string()]),
+            key_pattern: Some("analytics:*".to_string()),
+            backend: "replica".to_string(),
+        },
+    ]);
+
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = Client::new();
+
+    for (path, backend) in [
+        ("KEYS/*", Some("replica")),
+        ("GET/analytics:daily", Some("replica")),
+        ("GET/users:1", None),
+        ("SET/analytics:daily/1", None),
+    ] {
+        let resp = client
+            .get(format!("http://{}/{path}", server.addr))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK, "{path}");
+        let seen = executor.seen_requests().await;
+        assert_eq!(seen.last().unwrap().backend.as_deref(), backend, "{path}");
+    }
+}
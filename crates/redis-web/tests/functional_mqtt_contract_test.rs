@@ -0,0 +1,134 @@
+#![cfg(feature = "mqtt")]
+
+mod support;
+
+use redis_web_core::config::{AclConfig, MqttConfig};
+use std::sync::Arc;
+use support::router_harness::{functional_config, MqttFunctionalServer};
+use support::stub_executor::ScriptedStubExecutor;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn mqtt_config(acl: Option<Vec<AclConfig>>) -> redis_web_core::config::Config {
+    let mut cfg = functional_config();
+    cfg.mqtt = Some(MqttConfig {
+        enabled: true,
+        channel_prefix: Some("mqtt:".to_string()),
+        ..MqttConfig::default()
+    });
+    cfg.acl = acl;
+    cfg
+}
+
+fn string(value: &[u8]) -> Vec<u8> {
+    let mut out = (value.len() as u16).to_be_bytes().to_vec();
+    out.extend_from_slice(value);
+    out
+}
+
+fn packet(first: u8, body: Vec<u8>) -> Vec<u8> {
+    assert!(body.len() < 128);
+    let mut out = vec![first, body.len() as u8];
+    out.extend(body);
+    out
+}
+
+async fn connect(server: &MqttFunctionalServer, user: Option<(&str, &str)>) -> TcpStream {
+    let mut stream = TcpStream::connect(server.addr).await.unwrap();
+    let mut body = string(b"MQTT");
+    body.extend_from_slice(&[4, if user.is_some() { 0xc2 } else { 0x02 }, 0, 60]);
+    body.extend(string(b"device-1"));
+    if let Some((name, password)) = user {
+        body.extend(string(name.as_bytes()));
+        body.extend(string(password.as_bytes()));
+    }
+    stream.write_all(&packet(0x10, body)).await.unwrap();
+    let mut connack = [0u8; 4];
+    stream.read_exact(&mut connack).await.unwrap();
+    assert_eq!(connack, [0x20, 2, 0, 0]);
+    stream
+}
+
+#[tokio::test]
+async fn test_mqtt_publish_maps_topic_to_prefixed_channel() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = MqttFunctionalServer::spawn(mqtt_config(None), executor.clone()).await;
+    let mut stream = connect(&server, None).await;
+
+    let mut body = string(b"sensors/1");
+    body.extend_from_slice(&[0, 7]);
+    body.extend_from_slice(b"21.5");
+    stream.write_all(&packet(0x32, body)).await.unwrap();
+    let mut puback = [0u8; 4];
+    stream.read_exact(&mut puback).await.unwrap();
+    assert_eq!(puback, [0x40, 2, 0, 7]);
+
+    stream.write_all(&[0xc0, 0]).await.unwrap();
+    let mut pingresp = [0u8; 2];
+    stream.read_exact(&mut pingresp).await.unwrap();
+    assert_eq!(pingresp, [0xd0, 0]);
+
+    let requests = executor.seen_requests().await;
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].command_name, "PUBLISH");
+    assert_eq!(
+        requests[0].args,
+        vec![b"mqtt:sensors/1".to_vec(), b"21.5".to_vec()]
+    );
+}
+
+#[tokio::test]
+async fn test_mqtt_credentials_are_checked_against_channel_acls() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let acl = vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["PUBLISH".to_string(), "PSUBSCRIBE".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: Some("device:secret".to_string()),
+            ip: None,
+            enabled: Some(vec!["PUBLISH".to_string(), "PSUBSCRIBE".to_string()]),
+            disabled: None,
+            channels: Some(vec!["mqtt:public/*".to_string()]),
+            redis_auth: None,
+            schedule: None,
+        },
+    ];
+    let server = MqttFunctionalServer::spawn(mqtt_config(Some(acl)), executor.clone()).await;
+
+    let mut stream = connect(&server, Some(("device", "secret"))).await;
+    let mut body = 3u16.to_be_bytes().to_vec();
+    body.extend(string(b"private/#"));
+    body.push(0);
+    stream.write_all(&packet(0x82, body)).await.unwrap();
+    let mut suback = [0u8; 5];
+    stream.read_exact(&mut suback).await.unwrap();
+    assert_eq!(suback, [0x90, 3, 0, 3, 0x80]);
+
+    let mut body = string(b"public/news");
+    body.extend_from_slice(b"hi");
+    stream.write_all(&packet(0x30, body)).await.unwrap();
+
+    // Without credentials the publish is refused and the connection closed.
+    let mut anonymous = connect(&server, None).await;
+    let mut body = string(b"public/news");
+    body.extend_from_slice(b"hi");
+    anonymous.write_all(&packet(0x30, body)).await.unwrap();
+    let mut rest = Vec::new();
+    anonymous.read_to_end(&mut rest).await.unwrap();
+    assert!(rest.is_empty());
+
+    // Flush the first connection before looking at the executor.
+    stream.write_all(&[0xc0, 0]).await.unwrap();
+    let mut pingresp = [0u8; 2];
+    stream.read_exact(&mut pingresp).await.unwrap();
+    let requests = executor.seen_requests().await;
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].args[0], b"mqtt:public/news");
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "wasm-hooks")]
+
+mod support;
+
+use redis_web_core::config::HookConfig;
+use reqwest::Client;
+use std::sync::Arc;
+use support::router_harness::{functional_config, FunctionalServer};
+use support::stub_executor::ScriptedStubExecutor;
+
+/// Refuses `FLUSHALL` and replaces every reply with `redacted`. The module
+/// only looks at the command name, which starts at byte 8 of a command
+/// array with fewer than ten items and a name shorter than ten bytes.
+const REDACT: &str = r#"(module
+  (memory (export "memory") 1)
+  (data (i32.const 16) "-FLUSHALL is disabled\0d\0a")
+  (data (i32.const 64) "$8\0d\0aredacted\0d\0a")
+  (global $next (mut i32) (i32.const 4096))
+  (func (export "redis_web_alloc") (param $len i32) (result i32)
+    (global.get $next)
+    (global.set $next (i32.add (global.get $next) (local.get $len))))
+  (func (export "redis_web_before") (param $ptr i32) (param $len i32) (result i64)
+    ;; "FLUSHALL" read as a little-endian i64 is 0x4c4c414853554c46.
+    (if (result i64)
+      (i64.eq (i64.load (i32.add (local.get $ptr) (i32.const 8)))
+              (i64.const 0x4c4c414853554c46))
+      (then (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 23)))
+      (else (i64.const 0))))
+  (func (export "redis_web_after") (param i32 i32) (result i64)
+    (i64.or (i64.shl (i64.const 64) (i64.const 32)) (i64.const 14))))"#;
+
+#[tokio::test]
+async fn test_wasm_hooks_from_config_refuse_commands_and_rewrite_replies() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("redact.wat");
+    std::fs::write(&path, REDACT).unwrap();
+    let mut cfg = functional_config();
+    cfg.hooks = Some(vec![HookConfig {
+        path: path.to_string_lossy().into_owned(),
+        fuel: None,
+    }]);
+    let hooks = redis_web_runtime::wasm::load(&cfg).unwrap();
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn_with_hooks(cfg, executor.clone(), hooks).await;
+    let client = Client::new();
+
+    let body: serde_json::Value = client
+        .get(format!("http://{}/GET/k", server.addr))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body, serde_json::json!({"GET": "redacted"}));
+
+    let body: serde_json::Value = client
+        .post(format!("http://{}/_query", server.addr))
+        .body(r#"{"a": ["GET", "a"], "b": ["HGETALL", "b"]}"#)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body, serde_json::json!({"a": "redacted", "b": "redacted"}));
+
+    let refused = client
+        .get(format!("http://{}/FLUSHALL", server.addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), 400);
+    assert!(refused
+        .text()
+        .await
+        .unwrap()
+        .contains("FLUSHALL is disabled"));
+    assert!(!executor
+        .seen_requests()
+        .await
+        .iter()
+        .any(|request| request.command_name == "FLUSHALL"));
+}
@@ -1,10 +1,14 @@
 mod support;
 
+use base64::{engine::general_purpose, Engine as _};
 use futures_util::{SinkExt, StreamExt};
+use redis_web_core::config::{AclConfig, BackendConfig, RouteConfig};
 use std::sync::Arc;
+use std::time::Duration;
 use support::router_harness::{functional_config, FunctionalServer};
 use support::stub_executor::ScriptedStubExecutor;
 use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
 #[tokio::test]
@@ -30,3 +34,985 @@ async fn test_raw_ws_invalid_resp_returns_err_without_redis() {
         data
     );
 }
+
+#[tokio::test]
+async fn test_raw_ws_keeps_redis_error_code() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    ws_stream
+        .send(Message::Binary(b"*1\r\n$9\r\nWRONGTYPE\r\n".to_vec().into()))
+        .await
+        .unwrap();
+
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(
+        msg.into_data().as_ref(),
+        b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_ws_commands_count_against_quotas() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.quotas = Some(redis_web_core::config::QuotaConfig {
+        commands_per_day: Some(1),
+        ..Default::default()
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        ws_stream
+            .send(Message::Text(r#"["GET", "k"]"#.into()))
+            .await
+            .unwrap();
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_str::<serde_json::Value>(msg.to_text().unwrap()).unwrap());
+    }
+    assert_eq!(replies[0], serde_json::json!({"GET": null}));
+    assert_eq!(replies[1]["error"]["code"], "quota_exceeded");
+
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut raw, _) = connect_async(url).await.unwrap();
+    raw.send(Message::Binary(
+        b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n".to_vec().into(),
+    ))
+    .await
+    .unwrap();
+    let data = raw.next().await.unwrap().unwrap().into_data();
+    assert!(data.starts_with(b"-ERR usage quota exceeded"), "{data:?}");
+}
+
+#[tokio::test]
+async fn test_ws_writes_and_denied_commands_are_audited() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.audit = Some(redis_web_core::config::AuditConfig::default());
+    cfg.acl = Some(vec![AclConfig {
+        http_basic_auth: None,
+        ip: None,
+        enabled: None,
+        disabled: Some(vec!["DEL".to_string()]),
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    }]);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [r#"["SET", "k", "v"]"#, r#"["GET", "k"]"#, r#"["DEL", "k"]"#] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+        ws_stream.next().await.unwrap().unwrap();
+    }
+
+    // Records are appended in the background.
+    let field = |args: &[Vec<u8>], name: &[u8]| {
+        let at = args.iter().position(|arg| arg == name).unwrap();
+        String::from_utf8_lossy(&args[at + 1]).into_owned()
+    };
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        records = executor
+            .seen_requests()
+            .await
+            .into_iter()
+            .filter(|request| request.command_name == "XADD")
+            .map(|request| {
+                (
+                    field(&request.args, b"command"),
+                    field(&request.args, b"status"),
+                )
+            })
+            .collect();
+        if records.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        records,
+        [
+            ("SET".to_string(), "200".to_string()),
+            ("DEL".to_string(), "403".to_string())
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ws_writes_update_last_modified_timestamps() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.last_modified = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"["SET", "k", "v"]"#.into()))
+        .await
+        .unwrap();
+    ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(executor.keys().await, ["k", "k:__mtime__"]);
+
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut raw, _) = connect_async(url).await.unwrap();
+    raw.send(Message::Binary(
+        b"*3\r\n$3\r\nSET\r\n$1\r\nr\r\n$1\r\nv\r\n".to_vec().into(),
+    ))
+    .await
+    .unwrap();
+    raw.next().await.unwrap().unwrap();
+    assert_eq!(
+        executor.keys().await,
+        ["k", "k:__mtime__", "r", "r:__mtime__"]
+    );
+}
+
+#[tokio::test]
+async fn test_json_ws_echoes_command_ids() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    for frame in [
+        r#"{"id": 7, "cmd": ["SET", "k", "v"]}"#,
+        r#"{"id": "b", "cmd": ["GET", "k"]}"#,
+        r#"{"id": 9, "cmd": ["FAIL"]}"#,
+        r#"["GET", "k"]"#,
+        r#"{"id": 10}"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+
+    let mut replies = Vec::new();
+    for _ in 0..5 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    assert_eq!(replies[0], serde_json::json!({"id": 7, "SET": "OK"}));
+    assert_eq!(replies[1], serde_json::json!({"id": "b", "GET": "v"}));
+    assert_eq!(replies[2]["id"], 9);
+    assert_eq!(replies[2]["error"]["code"], "command_failed");
+    assert!(replies[2]["error"]["message"].is_string());
+    assert_eq!(replies[3], serde_json::json!({"GET": "v"}));
+    // Frames without a command cannot carry an id back.
+    assert_eq!(replies[4]["error"]["code"], "invalid_frame");
+}
+
+#[tokio::test]
+async fn test_json_ws_psubscribe_confirms_patterns() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    for frame in [
+        r#"["PSUBSCRIBE", "news.*", "alerts.?"]"#,
+        r#"{"id": 3, "cmd": ["PUNSUBSCRIBE", "news.*"]}"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    assert_eq!(
+        replies,
+        vec![
+            serde_json::json!({"PSUBSCRIBE": ["psubscribe", "news.*", 1]}),
+            serde_json::json!({"PSUBSCRIBE": ["psubscribe", "alerts.?", 2]}),
+            serde_json::json!({"id": 3, "PUNSUBSCRIBE": ["punsubscribe", "news.*", 1]}),
+        ]
+    );
+    // Subscriptions never reach the command executor.
+    assert!(executor.seen_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_json_ws_mixes_commands_and_subscriptions() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    for frame in [
+        r#"["UNSUBSCRIBE"]"#,
+        r#"["SUBSCRIBE", "news"]"#,
+        r#"["SET", "key", "value"]"#,
+        r#"["SUBSCRIBE", "news", "alerts"]"#,
+        r#"["UNSUBSCRIBE"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+
+    let mut replies = Vec::new();
+    for _ in 0..7 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    assert_eq!(
+        replies[0],
+        serde_json::json!({"UNSUBSCRIBE": ["unsubscribe", null, 0]})
+    );
+    assert_eq!(
+        replies[1],
+        serde_json::json!({"SUBSCRIBE": ["subscribe", "news", 1]})
+    );
+    assert_eq!(replies[2], serde_json::json!({"SET": "OK"}));
+    // Subscribing twice still confirms, without counting the topic again.
+    assert_eq!(
+        replies[3],
+        serde_json::json!({"SUBSCRIBE": ["subscribe", "news", 1]})
+    );
+    assert_eq!(
+        replies[4],
+        serde_json::json!({"SUBSCRIBE": ["subscribe", "alerts", 2]})
+    );
+    // A bare UNSUBSCRIBE leaves every channel, counting down to zero.
+    let counts: Vec<_> = replies[5..]
+        .iter()
+        .map(|reply| reply["UNSUBSCRIBE"][2].clone())
+        .collect();
+    assert_eq!(counts, vec![serde_json::json!(1), serde_json::json!(0)]);
+    assert_eq!(executor.seen_requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_json_ws_unsubscribe_releases_the_channel_and_keeps_the_socket() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        http_basic_auth: Some("ops:secret".to_string()),
+        ip: None,
+        console: false,
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+    let client = reqwest::Client::new();
+    let topics = || async {
+        let stats: serde_json::Value = client
+            .get(format!("http://{}/_admin/stats", server.addr))
+            .basic_auth("ops", Some("secret"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        stats["pubsub"]["topics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|topic| topic["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    let mut send_and_read = async |frame: &str, count: usize| {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+        let mut replies = Vec::new();
+        for _ in 0..count {
+            let msg = ws_stream.next().await.unwrap().unwrap();
+            replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+        }
+        replies
+    };
+
+    send_and_read(r#"["SUBSCRIBE", "news", "alerts"]"#, 2).await;
+    assert_eq!(topics().await, ["alerts", "news"]);
+
+    assert_eq!(
+        send_and_read(r#"["UNSUBSCRIBE", "news"]"#, 1).await,
+        [serde_json::json!({"UNSUBSCRIBE": ["unsubscribe", "news", 1]})]
+    );
+    // The socket's subscription was the only one, so the channel is released.
+    let mut remaining = topics().await;
+    for _ in 0..20 {
+        if remaining == ["alerts"] {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+        remaining = topics().await;
+    }
+    assert_eq!(remaining, ["alerts"]);
+
+    // The socket stays open for commands and further subscriptions.
+    assert_eq!(
+        send_and_read(r#"["SET", "key", "value"]"#, 1).await,
+        [serde_json::json!({"SET": "OK"})]
+    );
+    assert_eq!(
+        send_and_read(r#"["SUBSCRIBE", "news"]"#, 1).await,
+        [serde_json::json!({"SUBSCRIBE": ["subscribe", "news", 2]})]
+    );
+}
+
+#[tokio::test]
+async fn test_json_ws_repeated_subscriptions_share_one_subscriber() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.admin = Some(redis_web_core::config::AdminConfig {
+        enabled: true,
+        http_basic_auth: Some("ops:secret".to_string()),
+        ip: None,
+        console: false,
+    });
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        r#"["SUBSCRIBE", "news", "alerts", "news"]"#,
+        r#"["SUBSCRIBE", "alerts"]"#,
+        r#"["PSUBSCRIBE", "news"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut counts = Vec::new();
+    for _ in 0..5 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        let reply: serde_json::Value = serde_json::from_slice(&msg.into_data()).unwrap();
+        let (_, confirmation) = reply.as_object().unwrap().iter().next().unwrap();
+        counts.push(confirmation[2].as_u64().unwrap());
+    }
+    // A pattern is its own subscription even when it names the same text.
+    assert_eq!(counts, [1, 2, 2, 2, 3]);
+
+    let stats: serde_json::Value = reqwest::Client::new()
+        .get(format!("http://{}/_admin/stats", server.addr))
+        .basic_auth("ops", Some("secret"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let topics: Vec<_> = stats["pubsub"]["topics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|topic| {
+            (
+                topic["kind"].as_str().unwrap().to_string(),
+                topic["name"].as_str().unwrap().to_string(),
+                topic["subscribers"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(topics.len(), 3);
+    assert!(topics.iter().all(|(_, _, subscribers)| *subscribers == 1));
+}
+
+#[tokio::test]
+async fn test_ws_sends_pings_and_closes_idle_sockets() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.websocket_ping_interval_sec = Some(1);
+    cfg.websocket_idle_timeout_sec = Some(2);
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    let next = tokio::time::timeout(Duration::from_secs(5), ws_stream.next());
+    assert!(matches!(next.await.unwrap(), Some(Ok(Message::Ping(_)))));
+
+    // Answering pings keeps the socket alive but does not count as activity.
+    let close = loop {
+        let next = tokio::time::timeout(Duration::from_secs(5), ws_stream.next());
+        match next.await.unwrap() {
+            Some(Ok(Message::Ping(_))) => continue,
+            Some(Ok(Message::Close(frame))) => break frame.unwrap(),
+            other => panic!("unexpected frame: {other:?}"),
+        }
+    };
+    assert_eq!(u16::from(close.code), 1001);
+    assert_eq!(close.reason.as_str(), "idle_timeout");
+}
+
+#[tokio::test]
+async fn test_ws_closes_oversized_messages_with_1009() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.websocket_max_message_size = Some(64);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    let frame = format!(r#"["SET", "key", "{}"]"#, "x".repeat(100));
+    ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    let Some(Ok(Message::Close(Some(close)))) = ws_stream.next().await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(u16::from(close.code), 1009);
+    assert_eq!(close.reason.as_str(), "message_too_big");
+
+    // On /.raw a command spread over small messages is capped as a whole.
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    let header = "*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$1000\r\n";
+    ws_stream
+        .send(Message::Binary(header.into()))
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        let chunk = "x".repeat(20);
+        if ws_stream.send(Message::Binary(chunk.into())).await.is_err() {
+            break;
+        }
+    }
+    let Some(Ok(Message::Close(Some(close)))) = ws_stream.next().await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(u16::from(close.code), 1009);
+    assert!(executor.seen_requests().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_json_ws_returns_bulk_replies_as_binary_frames() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        r#"["SET", "blob", "hello"]"#,
+        r#"{"cmd": ["GET", "blob"], "binary": true}"#,
+        r#"["GET", "blob"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        replies.push(ws_stream.next().await.unwrap().unwrap());
+    }
+    assert_eq!(replies[0], Message::Text(r#"{"SET":"OK"}"#.into()));
+    assert_eq!(replies[1], Message::Binary(b"hello".to_vec().into()));
+    assert_eq!(replies[2], Message::Text(r#"{"GET":"hello"}"#.into()));
+
+    // `?binary=1` makes binary the default; commands can still opt out, and
+    // replies other than bulk strings stay JSON.
+    let url = format!("ws://{}/.json?binary=1", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        r#"["GET", "blob"]"#,
+        r#"{"id": 1, "cmd": ["GET", "blob"], "binary": false}"#,
+        r#"["SET", "blob", "bye"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        replies.push(ws_stream.next().await.unwrap().unwrap());
+    }
+    assert_eq!(replies[0], Message::Binary(b"hello".to_vec().into()));
+    assert_eq!(
+        replies[1],
+        Message::Text(r#"{"GET":"hello","id":1}"#.into())
+    );
+    assert_eq!(replies[2], Message::Text(r#"{"SET":"OK"}"#.into()));
+}
+
+#[tokio::test]
+async fn test_json_ws_selects_reply_format_per_command() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        r#"{"cmd": ["SET", "blob", "hello"], "format": "raw"}"#,
+        r#"{"cmd": ["GET", "blob"], "format": "raw"}"#,
+        r#"{"cmd": ["GET", "blob"], "format": "png"}"#,
+        r#"{"id": 1, "cmd": ["GET", "blob"], "format": "json"}"#,
+        r#"{"id": 2, "cmd": ["FAIL"], "format": "raw"}"#,
+        r#"{"id": 3, "cmd": ["GET", "blob"], "format": "msgpack"}"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..6 {
+        replies.push(ws_stream.next().await.unwrap().unwrap());
+    }
+    assert_eq!(replies[0], Message::Binary(b"+OK\r\n".to_vec().into()));
+    assert_eq!(
+        replies[1],
+        Message::Binary(b"$5\r\nhello\r\n".to_vec().into())
+    );
+    assert_eq!(replies[2], Message::Binary(b"hello".to_vec().into()));
+    assert_eq!(
+        replies[3],
+        Message::Text(r#"{"GET":"hello","id":1}"#.into())
+    );
+    let error: serde_json::Value = serde_json::from_slice(&replies[4].clone().into_data()).unwrap();
+    assert_eq!(error["id"], 2);
+    assert_eq!(error["error"]["code"], "command_failed");
+    let invalid: serde_json::Value =
+        serde_json::from_slice(&replies[5].clone().into_data()).unwrap();
+    assert_eq!(invalid["error"]["code"], "invalid_frame");
+}
+
+#[tokio::test]
+async fn test_json_ws_runs_id_tagged_commands_concurrently() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.websocket_max_inflight_commands = Some(4);
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        r#"{"id": 1, "cmd": ["SLEEP", "300"]}"#,
+        r#"{"id": 2, "cmd": ["SET", "k", "v"]}"#,
+        r#"{"id": 3, "cmd": ["SLEEP", "100"]}"#,
+        r#"["GET", "k"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..4 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    // Id-tagged replies arrive as they complete; a command without an id
+    // waits for them.
+    assert_eq!(replies[0], serde_json::json!({"id": 2, "SET": "OK"}));
+    assert_eq!(replies[1], serde_json::json!({"id": 3, "SLEEP": "OK"}));
+    assert_eq!(replies[2], serde_json::json!({"id": 1, "SLEEP": "OK"}));
+    assert_eq!(replies[3], serde_json::json!({"GET": "v"}));
+}
+
+#[tokio::test]
+async fn test_ws_closes_with_going_away_on_shutdown() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor).await;
+
+    let url = format!("ws://{}/.json", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    server.shutdown.send_replace(true);
+
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("close frame should arrive")
+        .unwrap()
+        .unwrap();
+    let Message::Close(Some(close)) = msg else {
+        panic!("expected a close frame, got {msg:?}");
+    };
+    assert_eq!(u16::from(close.code), 1001);
+    assert_eq!(close.reason.as_str(), "server_shutdown");
+}
+
+fn auth_required_config() -> redis_web_core::config::Config {
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.acl = Some(vec![
+        AclConfig {
+            http_basic_auth: None,
+            ip: None,
+            enabled: None,
+            disabled: Some(vec!["*".to_string()]),
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+        AclConfig {
+            http_basic_auth: Some("user:password".to_string()),
+            ip: None,
+            enabled: Some(vec!["*".to_string()]),
+            disabled: None,
+            channels: None,
+            redis_auth: None,
+            schedule: None,
+        },
+    ]);
+    cfg
+}
+
+/// Reads frames up to the close frame and returns the JSON ones with it.
+async fn read_until_close<S>(ws_stream: &mut S) -> (Vec<serde_json::Value>, (u16, String))
+where
+    S: futures_util::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    let mut replies = Vec::new();
+    loop {
+        match ws_stream.next().await.unwrap().unwrap() {
+            Message::Close(Some(close)) => {
+                return (replies, (u16::from(close.code), close.reason.to_string()));
+            }
+            msg => replies.push(serde_json::from_slice(&msg.into_data()).unwrap()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_json_ws_requires_auth_before_commands() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(auth_required_config(), executor.clone()).await;
+    let url = format!("ws://{}/.json", server.addr);
+
+    // A denied command ends the socket with 4003.
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    ws_stream
+        .send(Message::Text(
+            r#"{"id": 1, "cmd": ["SET", "key", "value"]}"#.into(),
+        ))
+        .await
+        .unwrap();
+    let (replies, close) = read_until_close(&mut ws_stream).await;
+    assert_eq!(
+        replies,
+        vec![serde_json::json!({
+            "id": 1,
+            "error": {"code": "forbidden", "message": "Forbidden"}
+        })]
+    );
+    assert_eq!(close, (4003, "forbidden".to_string()));
+
+    // So does a denied subscription.
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"["SUBSCRIBE", "news"]"#.into()))
+        .await
+        .unwrap();
+    let (_, close) = read_until_close(&mut ws_stream).await;
+    assert_eq!(close.0, 4003);
+
+    // A failed AUTH ends it with 4001.
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"["AUTH", "user", "wrong"]"#.into()))
+        .await
+        .unwrap();
+    let (replies, close) = read_until_close(&mut ws_stream).await;
+    assert_eq!(
+        replies,
+        vec![serde_json::json!({
+            "error": {"code": "auth_failed", "message": "invalid username-password pair"}
+        })]
+    );
+    assert_eq!(close, (4001, "auth_failed".to_string()));
+
+    // After AUTH succeeds, commands run.
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    for frame in [
+        r#"["AUTH", "user", "password"]"#,
+        r#"["SET", "key", "value"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    assert_eq!(
+        replies,
+        vec![
+            serde_json::json!({"AUTH": "OK"}),
+            serde_json::json!({"SET": "OK"}),
+        ]
+    );
+    assert_eq!(executor.seen_requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_json_ws_checks_each_command_with_upgrade_headers_and_ip() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = auth_required_config();
+    cfg.acl.as_mut().unwrap().push(AclConfig {
+        http_basic_auth: None,
+        ip: Some("127.0.0.0/8".to_string()),
+        enabled: None,
+        disabled: Some(vec!["DEL".to_string()]),
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    });
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let mut request = format!("ws://{}/.json", server.addr)
+        .into_client_request()
+        .unwrap();
+    let authorization = format!(
+        "Basic {}",
+        general_purpose::STANDARD.encode("user:password")
+    );
+    request
+        .headers_mut()
+        .insert("authorization", authorization.parse().unwrap());
+    let (mut ws_stream, _) = connect_async(request).await.unwrap();
+    for frame in [r#"["SET", "key", "value"]"#, r#"["DEL", "key"]"#] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let (replies, close) = read_until_close(&mut ws_stream).await;
+    assert_eq!(
+        replies,
+        vec![
+            serde_json::json!({"SET": "OK"}),
+            serde_json::json!({"error": {"code": "forbidden", "message": "Forbidden"}}),
+        ]
+    );
+    assert_eq!(close, (4003, "forbidden".to_string()));
+    assert_eq!(executor.seen_requests().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_ws_accepts_token_at_upgrade() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let server = FunctionalServer::spawn(auth_required_config(), executor.clone()).await;
+
+    let token = general_purpose::STANDARD.encode("user:password");
+    let url = format!("ws://{}/.json?token={token}", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"["SET", "key", "value"]"#.into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg, Message::Text(r#"{"SET":"OK"}"#.into()));
+
+    // `/.raw` answers in RESP and also takes AUTH as its first command.
+    let url = format!("ws://{}/.raw", server.addr);
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    for command in [
+        "*3\r\n$4\r\nAUTH\r\n$4\r\nuser\r\n$8\r\npassword\r\n",
+        "*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n",
+    ] {
+        ws_stream
+            .send(Message::Binary(command.into()))
+            .await
+            .unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        replies.push(ws_stream.next().await.unwrap().unwrap().into_data());
+    }
+    assert_eq!(replies[0], &b"+OK\r\n"[..]);
+    assert_eq!(replies[1], &b"$5\r\nvalue\r\n"[..]);
+
+    let (mut ws_stream, _) = connect_async(&url).await.unwrap();
+    ws_stream
+        .send(Message::Binary("*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg.into_data(), &b"-ERR forbidden\r\n"[..]);
+    let Some(Ok(Message::Close(Some(close)))) = ws_stream.next().await else {
+        panic!("expected a close frame");
+    };
+    assert_eq!(u16::from(close.code), 4003);
+}
+
+#[tokio::test]
+async fn test_ws_forwards_auth_to_redis_without_basic_auth_rules() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    // Without `http_basic_auth` rules, `AUTH <password>` is Redis's to check.
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.json", server.addr))
+        .await
+        .unwrap();
+    ws_stream
+        .send(Message::Text(r#"["AUTH", "requirepass-secret"]"#.into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg, Message::Text(r#"{"AUTH":"OK"}"#.into()));
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.raw", server.addr))
+        .await
+        .unwrap();
+    ws_stream
+        .send(Message::Binary(
+            "*3\r\n$4\r\nAUTH\r\n$5\r\nalice\r\n$2\r\npw\r\n".into(),
+        ))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg.into_data(), &b"+OK\r\n"[..]);
+
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen.len(), 2);
+    assert!(seen
+        .iter()
+        .all(|command| command.command_name.eq_ignore_ascii_case("AUTH")));
+    assert_eq!(seen[0].args, vec![b"requirepass-secret".to_vec()]);
+    assert_eq!(seen[1].args, vec![b"alice".to_vec(), b"pw".to_vec()]);
+}
+
+#[tokio::test]
+async fn test_ws_require_auth_holds_commands_until_auth() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.websocket_require_auth = true;
+    cfg.acl = Some(vec![AclConfig {
+        http_basic_auth: Some("user:password".to_string()),
+        ip: None,
+        enabled: Some(vec!["*".to_string()]),
+        disabled: None,
+        channels: None,
+        redis_auth: None,
+        schedule: None,
+    }]);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.json", server.addr))
+        .await
+        .unwrap();
+    for frame in [
+        r#"["SET", "key", "value"]"#,
+        r#"["AUTH", "user", "password"]"#,
+        r#"["SET", "key", "value"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..3 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_slice::<serde_json::Value>(&msg.into_data()).unwrap());
+    }
+    assert_eq!(
+        replies,
+        vec![
+            serde_json::json!({
+                "error": {"code": "auth_required", "message": "Authentication required"}
+            }),
+            serde_json::json!({"AUTH": "OK"}),
+            serde_json::json!({"SET": "OK"}),
+        ]
+    );
+    assert_eq!(executor.seen_requests().await.len(), 1);
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.raw", server.addr))
+        .await
+        .unwrap();
+    ws_stream
+        .send(Message::Binary("*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(
+        msg.into_data(),
+        &b"-NOAUTH Authentication required.\r\n"[..]
+    );
+
+    // Credentials at the upgrade count as authenticating.
+    let token = general_purpose::STANDARD.encode("user:password");
+    let url = format!("ws://{}/.json?token={token}", server.addr);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    ws_stream
+        .send(Message::Text(r#"["GET", "key"]"#.into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg, Message::Text(r#"{"GET":"value"}"#.into()));
+}
+
+#[tokio::test]
+async fn test_ws_commands_follow_routes_and_backend_acls() {
+    let executor = Arc::new(ScriptedStubExecutor::new());
+    let mut cfg = functional_config();
+    cfg.websockets = true;
+    cfg.backends = Some(std::collections::BTreeMap::from([(
+        "analytics".to_string(),
+        BackendConfig {
+            redis_host: "127.0.0.1".to_string(),
+            redis_port: 6380,
+            redis_socket: None,
+            database: 2,
+            redis_auth: None,
+            acl: Some(vec![AclConfig {
+                http_basic_auth: None,
+                ip: None,
+                enabled: None,
+                disabled: Some(vec!["DEL".to_string()]),
+                channels: None,
+                redis_auth: None,
+                schedule: None,
+            }]),
+        },
+    )]));
+    cfg.routes = Some(vec![RouteConfig {
+        commands: Some(vec!["GET".to_string(), "DEL".to_string()]),
+        key_pattern: Some("stats:*".to_string()),
+        backend: "analytics".to_string(),
+    }]);
+    let server = FunctionalServer::spawn(cfg, executor.clone()).await;
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.json", server.addr))
+        .await
+        .unwrap();
+    for frame in [
+        r#"["GET", "stats:daily"]"#,
+        r#"["GET", "users:1"]"#,
+        r#"["DEL", "stats:daily"]"#,
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let (replies, close) = read_until_close(&mut ws_stream).await;
+    assert_eq!(replies.len(), 3);
+    assert_eq!(
+        replies[2],
+        serde_json::json!({"error": {"code": "forbidden", "message": "Forbidden"}})
+    );
+    assert_eq!(close, (4003, "forbidden".to_string()));
+    let seen = executor.seen_requests().await;
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen[0].backend.as_deref(), Some("analytics"));
+    assert_eq!(seen[0].target_database, 2);
+    assert_eq!(seen[1].backend, None);
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{}/.raw", server.addr))
+        .await
+        .unwrap();
+    ws_stream
+        .send(Message::Binary(
+            "*2\r\n$3\r\nDEL\r\n$11\r\nstats:daily\r\n".into(),
+        ))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg.into_data(), &b"-ERR forbidden\r\n"[..]);
+    assert_eq!(executor.seen_requests().await.len(), 2);
+}
@@ -1,4 +1,8 @@
-use redis_web_runtime::handler::parse_info_output;
+use redis::Value as RedisValue;
+use redis_web_core::config::JsonNil;
+use redis_web_runtime::handler::{
+    parse_info_output, redis_value_to_json, redis_value_to_json_with_nil,
+};
 use serde_json::json;
 
 #[test]
@@ -30,3 +34,55 @@ fn test_parse_info_output_no_colon() {
     let expected = json!({"key": "value"});
     assert_eq!(parse_info_output(input), expected);
 }
+
+#[test]
+fn test_resp3_values_map_to_native_json() {
+    let reply = RedisValue::Map(vec![
+        (
+            RedisValue::BulkString(b"ratio".to_vec()),
+            RedisValue::Double(0.5),
+        ),
+        (
+            RedisValue::SimpleString("enabled".to_string()),
+            RedisValue::Boolean(true),
+        ),
+        (
+            RedisValue::Int(7),
+            RedisValue::Set(vec![RedisValue::Int(1)]),
+        ),
+    ]);
+    assert_eq!(
+        redis_value_to_json(reply),
+        json!({"ratio": 0.5, "enabled": true, "7": [1]})
+    );
+    assert_eq!(
+        redis_value_to_json(RedisValue::Double(f64::INFINITY)),
+        json!("inf")
+    );
+}
+
+#[test]
+fn test_nil_rendering_follows_json_nil() {
+    let reply = || {
+        RedisValue::Array(vec![
+            RedisValue::Nil,
+            RedisValue::Map(vec![
+                (RedisValue::BulkString(b"set".to_vec()), RedisValue::Int(1)),
+                (RedisValue::BulkString(b"unset".to_vec()), RedisValue::Nil),
+            ]),
+        ])
+    };
+    assert_eq!(
+        redis_value_to_json_with_nil(reply(), JsonNil::Null),
+        json!([null, {"set": 1, "unset": null}])
+    );
+    assert_eq!(
+        redis_value_to_json_with_nil(reply(), JsonNil::False),
+        json!([false, {"set": 1, "unset": false}])
+    );
+    assert_eq!(
+        redis_value_to_json_with_nil(reply(), JsonNil::Omit),
+        json!([null, {"set": 1}])
+    );
+    assert_eq!(redis_value_to_json(RedisValue::Nil), json!(null));
+}
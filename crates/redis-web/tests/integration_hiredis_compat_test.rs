@@ -131,7 +131,7 @@ async fn test_compat_bridge_is_not_mounted_by_default() {
         .await
         .expect("compat probe request failed");
 
-    assert_eq!(resp.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
     let body = resp.text().await.expect("compat probe body failed");
     assert!(
         body.contains("unknown command '__compat'"),
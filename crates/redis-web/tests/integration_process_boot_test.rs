@@ -122,6 +122,38 @@ async fn test_invalid_unix_socket_path_fails_fast() {
     assert!(!output.status.success());
 }
 
+#[tokio::test]
+async fn test_wait_for_redis_gives_up_after_its_window() {
+    let config_content = serde_json::json!({
+        "redis_host": "127.0.0.1",
+        "redis_port": 1,
+        "wait_for_redis_sec": 1,
+        "http_host": "127.0.0.1",
+        "http_port": 0,
+        "verbosity": 4
+    });
+
+    let mut config_file = Builder::new()
+        .suffix(".json")
+        .tempfile()
+        .expect("Failed to create temp config file");
+    use std::io::Write;
+    write!(config_file, "{}", config_content).expect("Failed to write config");
+
+    ensure_redis_web_debug_binaries();
+    let started = std::time::Instant::now();
+    let output = std::process::Command::new(redis_web_binary_path())
+        .arg(config_file.path())
+        .output()
+        .expect("Failed to run redis-web");
+
+    assert!(!output.status.success());
+    assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(logs.contains("Waiting for Redis"), "{logs}");
+    assert!(logs.contains("Redis did not become reachable"), "{logs}");
+}
+
 #[test]
 fn test_redis_web_uses_redis_web_json_by_default() {
     ensure_redis_web_debug_binaries();
@@ -237,3 +237,46 @@ async fn test_info_command_returns_structured_json() {
     let body: serde_json::Value = resp.json().await.unwrap();
     assert!(body["INFO"].is_object());
 }
+
+#[tokio::test]
+async fn test_exhausted_pool_fails_after_its_wait_timeout() {
+    let config_content = serde_json::json!({
+        "redis_host": "127.0.0.1",
+        "redis_port": 6379,
+        "http_host": "127.0.0.1",
+        "http_port": 0,
+        "http_threads": 1,
+        "pool_size_per_thread": 1,
+        "pool_wait_timeout_ms": 200,
+        "verbosity": 4
+    });
+    let config_file = tempfile::Builder::new()
+        .suffix(".json")
+        .tempfile()
+        .expect("Failed to create temp config file");
+    let server = TestServer::spawn_with_config_and_env(config_file, config_content, &[]).await;
+    let client = Client::new();
+
+    // BLPOP holds the only pooled connection for two seconds.
+    let blocking = tokio::spawn({
+        let client = client.clone();
+        let url = format!(
+            "http://127.0.0.1:{}/BLPOP/pool_exhaustion_{}/2",
+            server.port, server.port
+        );
+        async move { client.get(url).send().await.unwrap().status() }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/PING", server.port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers()["Retry-After"], "1");
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "Redis connection pool exhausted");
+
+    assert_eq!(blocking.await.unwrap(), reqwest::StatusCode::OK);
+}
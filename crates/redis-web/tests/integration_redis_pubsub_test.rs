@@ -1,9 +1,12 @@
 mod support;
 
+use futures_util::{SinkExt, StreamExt};
 use reqwest::Client;
 use std::time::Duration;
 use support::process_harness::{read_stream_lines, redis_publish, TestServer};
 use tokio::time::sleep;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
 
 #[tokio::test]
 async fn test_subscribe_chunked_json_stream() {
@@ -27,6 +30,189 @@ async fn test_subscribe_chunked_json_stream() {
 
     let lines = read_stream_lines(response, 2, Duration::from_secs(3)).await;
     assert_eq!(lines.len(), 2);
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(
+        first,
+        serde_json::json!({"SUBSCRIBE": ["message", channel, "hello"]})
+    );
+}
+
+#[tokio::test]
+async fn test_subscribe_multiple_channels_on_one_stream() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let first = format!("multi_a_{}", server.port);
+    let second = format!("multi_b_{}", server.port);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/SUBSCRIBE/{}/{}",
+            server.port, first, second
+        ))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(150)).await;
+    let _ = redis_publish(&first, "one").await;
+    sleep(Duration::from_millis(50)).await;
+    let _ = redis_publish(&second, "two").await;
+
+    let lines = read_stream_lines(response, 2, Duration::from_secs(3)).await;
+    let messages: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![
+            serde_json::json!({"SUBSCRIBE": ["message", first, "one"]}),
+            serde_json::json!({"SUBSCRIBE": ["message", second, "two"]}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_psubscribe_delivers_concrete_channel() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let pattern = format!("pattern_{}:*", server.port);
+    let channel = format!("pattern_{}:news", server.port);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/PSUBSCRIBE/{}",
+            server.port, pattern
+        ))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(150)).await;
+    let _ = redis_publish(&channel, "hello").await;
+
+    let lines = read_stream_lines(response, 1, Duration::from_secs(3)).await;
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(
+        first,
+        serde_json::json!({"PSUBSCRIBE": ["pmessage", pattern, channel, "hello"]})
+    );
+}
+
+#[tokio::test]
+async fn test_websocket_psubscribe_delivers_pmessage() {
+    let server = TestServer::new().await;
+    let pattern = format!("ws_pattern_{}:*", server.port);
+    let channel = format!("ws_pattern_{}:news", server.port);
+
+    let url = format!("ws://127.0.0.1:{}/.json", server.port);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    ws_stream
+        .send(Message::Text(
+            serde_json::json!(["PSUBSCRIBE", pattern])
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+    let confirmation = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&confirmation.into_data()).unwrap(),
+        serde_json::json!({"PSUBSCRIBE": ["psubscribe", pattern, 1]})
+    );
+
+    sleep(Duration::from_millis(150)).await;
+    let _ = redis_publish(&channel, "hello").await;
+
+    let message = tokio::time::timeout(Duration::from_secs(3), ws_stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&message.into_data()).unwrap(),
+        serde_json::json!({"PSUBSCRIBE": ["pmessage", pattern, channel, "hello"]})
+    );
+}
+
+#[tokio::test]
+async fn test_websocket_tags_messages_and_dedupes_subscriptions() {
+    let server = TestServer::new().await;
+    let first = format!("ws_multi_a_{}", server.port);
+    let second = format!("ws_multi_b_{}", server.port);
+
+    let url = format!("ws://127.0.0.1:{}/.json", server.port);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+    for frame in [
+        serde_json::json!(["SUBSCRIBE", first, second]),
+        serde_json::json!(["SUBSCRIBE", first]),
+    ] {
+        ws_stream
+            .send(Message::Text(frame.to_string().into()))
+            .await
+            .unwrap();
+    }
+    for _ in 0..3 {
+        ws_stream.next().await.unwrap().unwrap();
+    }
+
+    sleep(Duration::from_millis(150)).await;
+    assert_eq!(redis_publish(&first, "one").await, 1);
+    let _ = redis_publish(&second, "two").await;
+
+    let mut messages = Vec::new();
+    for _ in 0..2 {
+        let message = tokio::time::timeout(Duration::from_secs(3), ws_stream.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        messages.push(serde_json::from_slice::<serde_json::Value>(&message.into_data()).unwrap());
+    }
+    // The repeated subscription delivers "one" once.
+    assert_eq!(
+        messages,
+        vec![
+            serde_json::json!({"SUBSCRIBE": ["message", first, "one"]}),
+            serde_json::json!({"SUBSCRIBE": ["message", second, "two"]}),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_ssubscribe_receives_spublish() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let channel = format!("sharded_{}", server.port);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/SSUBSCRIBE/{}",
+            server.port, channel
+        ))
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(300)).await;
+    client
+        .get(format!(
+            "http://127.0.0.1:{}/SPUBLISH/{}/hello",
+            server.port, channel
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    let lines = read_stream_lines(response, 1, Duration::from_secs(3)).await;
+    let first: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(
+        first,
+        serde_json::json!({"SSUBSCRIBE": ["smessage", channel, "hello"]})
+    );
 }
 
 #[tokio::test]
@@ -70,6 +256,70 @@ async fn test_subscribe_sse_default_compatibility() {
     let expected_payload = "sse-payload";
     let _ = redis_publish(&channel, expected_payload).await;
 
-    let lines = read_stream_lines(response, 1, Duration::from_secs(3)).await;
-    assert!(lines[0].contains("data:"));
+    let lines = read_stream_lines(response, 2, Duration::from_secs(3)).await;
+    assert_eq!(lines[0], format!("event: {channel}"));
+    assert_eq!(lines[1], format!("data: {expected_payload}"));
+}
+
+#[tokio::test]
+async fn test_subscribe_sse_comma_separated_channels() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let first = format!("sse_comma_a_{}", server.port);
+    let second = format!("sse_comma_b_{}", server.port);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/SUBSCRIBE/{},{}",
+            server.port, first, second
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(150)).await;
+    let _ = redis_publish(&first, "one").await;
+    sleep(Duration::from_millis(50)).await;
+    let _ = redis_publish(&second, "two").await;
+
+    let lines = read_stream_lines(response, 4, Duration::from_secs(3)).await;
+    assert_eq!(
+        lines,
+        vec![
+            format!("event: {first}"),
+            "data: one".to_string(),
+            format!("event: {second}"),
+            "data: two".to_string(),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_last_subscriber_disconnect_unsubscribes_from_redis() {
+    let server = TestServer::new().await;
+    let client = Client::new();
+    let channel = format!("sse_gc_{}", server.port);
+
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/SUBSCRIBE/{}",
+            server.port, channel
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(150)).await;
+    assert_eq!(redis_publish(&channel, "before").await, 1);
+
+    drop(response);
+    let mut receivers = 1;
+    for _ in 0..20 {
+        sleep(Duration::from_millis(100)).await;
+        receivers = redis_publish(&channel, "after").await;
+        if receivers == 0 {
+            break;
+        }
+    }
+    assert_eq!(receivers, 0);
 }
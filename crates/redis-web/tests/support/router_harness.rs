@@ -2,6 +2,7 @@
 
 use crate::support::stub_executor::ScriptedStubExecutor;
 use redis_web_core::config::Config;
+use redis_web_core::interfaces::CommandHook;
 use redis_web_core::request::WebdisRequestParser;
 use redis_web_runtime::grpc;
 use redis_web_runtime::server::{self, ServerDependencies};
@@ -12,6 +13,8 @@ use tokio::task::JoinHandle;
 
 pub struct FunctionalServer {
     pub addr: SocketAddr,
+    /// Set to `true` to start a graceful shutdown of open streams and sockets.
+    pub shutdown: tokio::sync::watch::Sender<bool>,
     _task: JoinHandle<()>,
 }
 
@@ -22,6 +25,15 @@ pub struct GrpcFunctionalServer {
 
 impl FunctionalServer {
     pub async fn spawn(config: Config, executor: Arc<ScriptedStubExecutor>) -> Self {
+        Self::spawn_with_hooks(config, executor, Vec::new()).await
+    }
+
+    /// Spawns a server running `hooks` on client commands.
+    pub async fn spawn_with_hooks(
+        config: Config,
+        executor: Arc<ScriptedStubExecutor>,
+        hooks: Vec<Arc<dyn CommandHook>>,
+    ) -> Self {
         let pool =
             redis_web_runtime::redis::create_pool(&config).expect("pool config should be valid");
         let pools = Arc::new(redis_web_runtime::redis::DatabasePoolRegistry::new(
@@ -32,16 +44,19 @@ impl FunctionalServer {
             .expect("pubsub client config should be valid");
         let pubsub = redis_web_runtime::pubsub::PubSubManager::new(pubsub_client);
 
-        let app = server::build_router_with_dependencies(
+        let components = server::build_runtime_with_dependencies(
             &config,
             ServerDependencies {
                 request_parser: Arc::new(WebdisRequestParser),
                 command_executor: executor,
+                hooks,
             },
             pools,
             pubsub,
             None,
         );
+        let shutdown = components.app_state.shutdown.clone();
+        let app = server::build_router_from_components(&config, components);
 
         let listener = TcpListener::bind(("127.0.0.1", 0))
             .await
@@ -57,7 +72,11 @@ impl FunctionalServer {
             .expect("functional server crashed");
         });
 
-        Self { addr, _task: task }
+        Self {
+            addr,
+            shutdown,
+            _task: task,
+        }
     }
 }
 
@@ -78,6 +97,7 @@ impl GrpcFunctionalServer {
             ServerDependencies {
                 request_parser: Arc::new(WebdisRequestParser),
                 command_executor: executor,
+                hooks: Vec::new(),
             },
             pools,
             pubsub,
@@ -99,6 +119,52 @@ impl GrpcFunctionalServer {
     }
 }
 
+#[cfg(feature = "mqtt")]
+pub struct MqttFunctionalServer {
+    pub addr: SocketAddr,
+    _task: JoinHandle<()>,
+}
+
+#[cfg(feature = "mqtt")]
+impl MqttFunctionalServer {
+    pub async fn spawn(config: Config, executor: Arc<ScriptedStubExecutor>) -> Self {
+        let pool =
+            redis_web_runtime::redis::create_pool(&config).expect("pool config should be valid");
+        let pools = Arc::new(redis_web_runtime::redis::DatabasePoolRegistry::new(
+            config.clone(),
+            pool,
+        ));
+        let pubsub_client = redis_web_runtime::redis::create_pubsub_client(&config)
+            .expect("pubsub client config should be valid");
+        let pubsub = redis_web_runtime::pubsub::PubSubManager::new(pubsub_client);
+
+        let components = server::build_runtime_with_dependencies(
+            &config,
+            ServerDependencies {
+                request_parser: Arc::new(WebdisRequestParser),
+                command_executor: executor,
+                hooks: Vec::new(),
+            },
+            pools,
+            pubsub,
+            None,
+        );
+
+        let listener = TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .expect("bind failed");
+        let addr = listener.local_addr().expect("addr missing");
+
+        let task = tokio::spawn(async move {
+            redis_web_runtime::mqtt::serve_with_listener(&config, components.app_state, listener)
+                .await
+                .expect("functional MQTT server crashed");
+        });
+
+        Self { addr, _task: task }
+    }
+}
+
 pub fn functional_config() -> Config {
     let mut cfg = Config::default();
     cfg.redis_host = "127.0.0.1".to_string();
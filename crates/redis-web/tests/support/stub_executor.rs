@@ -3,6 +3,7 @@
 use redis_web_core::interfaces::{
     CommandExecutionError, CommandExecutor, ExecutableCommand, ExecutionFuture,
 };
+use redis_web_core::routing::glob_match;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -10,6 +11,7 @@ use tokio::sync::RwLock;
 #[derive(Default)]
 pub struct ScriptedStubExecutor {
     values: Arc<RwLock<HashMap<String, Vec<u8>>>>,
+    lists: Arc<RwLock<HashMap<String, Vec<Vec<u8>>>>>,
     requests: Arc<RwLock<Vec<ExecutableCommand>>>,
 }
 
@@ -21,6 +23,16 @@ impl ScriptedStubExecutor {
     pub async fn seen_requests(&self) -> Vec<ExecutableCommand> {
         self.requests.read().await.clone()
     }
+
+    pub async fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.values.read().await.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub async fn value(&self, key: &str) -> Option<Vec<u8>> {
+        self.values.read().await.get(key).cloned()
+    }
 }
 
 impl CommandExecutor for ScriptedStubExecutor {
@@ -36,6 +48,27 @@ impl CommandExecutor for ScriptedStubExecutor {
                 "FAIL" => Err(CommandExecutionError::ExecutionFailed(
                     "stub execution failure".to_string(),
                 )),
+                "WRONGTYPE" => Err(CommandExecutionError::ExecutionFailed(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+                )),
+                "NOAUTH" => Err(CommandExecutionError::ExecutionFailed(
+                    "NOAUTH Authentication required.".to_string(),
+                )),
+                "SLOW" => Err(CommandExecutionError::Timeout(
+                    "stub command timed out".to_string(),
+                )),
+                "CROSSSLOT" => Err(CommandExecutionError::Rejected(
+                    "stub cross-slot rejection".to_string(),
+                )),
+                "SLEEP" => {
+                    let millis = request
+                        .args
+                        .first()
+                        .and_then(|value| std::str::from_utf8(value).ok()?.parse().ok())
+                        .unwrap_or(0);
+                    tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+                    Ok(redis::Value::SimpleString("OK".to_string()))
+                }
                 "SET" => {
                     let key = request
                         .args
@@ -52,14 +85,152 @@ impl CommandExecutor for ScriptedStubExecutor {
                         .first()
                         .map(|value| String::from_utf8_lossy(value).into_owned())
                         .unwrap_or_default();
-                    let value = self
+                    match self.values.read().await.get(&key) {
+                        Some(value) => Ok(redis::Value::BulkString(value.clone())),
+                        None => Ok(redis::Value::Nil),
+                    }
+                }
+                "APPEND" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let mut values = self.values.write().await;
+                    let value = values.entry(key).or_default();
+                    value.extend_from_slice(request.args.get(1).map_or(&[][..], Vec::as_slice));
+                    Ok(redis::Value::Int(value.len() as i64))
+                }
+                "RENAME" => {
+                    let keys: Vec<_> = request
+                        .args
+                        .iter()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .collect();
+                    let mut values = self.values.write().await;
+                    match (keys.first(), keys.get(1)) {
+                        (Some(from), Some(to)) => match values.remove(from) {
+                            Some(value) => {
+                                values.insert(to.clone(), value);
+                                Ok(redis::Value::Okay)
+                            }
+                            None => Err(CommandExecutionError::ExecutionFailed(
+                                "ERR no such key".to_string(),
+                            )),
+                        },
+                        _ => Err(CommandExecutionError::ExecutionFailed(
+                            "ERR wrong number of arguments for 'rename' command".to_string(),
+                        )),
+                    }
+                }
+                "INCRBY" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let by = request
+                        .args
+                        .get(1)
+                        .and_then(|by| std::str::from_utf8(by).ok()?.parse::<i64>().ok())
+                        .unwrap_or(1);
+                    let mut values = self.values.write().await;
+                    let value = values.entry(key).or_default();
+                    let count = std::str::from_utf8(value)
+                        .ok()
+                        .and_then(|count| count.parse::<i64>().ok())
+                        .unwrap_or(0)
+                        + by;
+                    *value = count.to_string().into_bytes();
+                    Ok(redis::Value::Int(count))
+                }
+                "SCAN" => {
+                    // Returns every key matching `MATCH` in a single page.
+                    let pattern = request.args.get(2).cloned().unwrap_or(b"*".to_vec());
+                    let mut keys: Vec<_> = self
                         .values
                         .read()
                         .await
-                        .get(&key)
+                        .keys()
+                        .filter(|key| glob_match(&pattern, key.as_bytes()))
                         .cloned()
+                        .collect();
+                    keys.sort();
+                    Ok(redis::Value::Array(vec![
+                        redis::Value::BulkString(b"0".to_vec()),
+                        redis::Value::Array(
+                            keys.into_iter()
+                                .map(|key| redis::Value::BulkString(key.into_bytes()))
+                                .collect(),
+                        ),
+                    ]))
+                }
+                "DEL" => {
+                    let mut values = self.values.write().await;
+                    let removed = request
+                        .args
+                        .iter()
+                        .filter(|key| values.remove(&*String::from_utf8_lossy(key)).is_some())
+                        .count();
+                    Ok(redis::Value::Int(removed as i64))
+                }
+                "RPUSH" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let mut lists = self.lists.write().await;
+                    let list = lists.entry(key).or_default();
+                    list.extend(request.args.iter().skip(1).cloned());
+                    Ok(redis::Value::Int(list.len() as i64))
+                }
+                "LLEN" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
                         .unwrap_or_default();
-                    Ok(redis::Value::BulkString(value))
+                    let len = self.lists.read().await.get(&key).map_or(0, Vec::len);
+                    Ok(redis::Value::Int(len as i64))
+                }
+                "LRANGE" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let index = |idx: usize| {
+                        request
+                            .args
+                            .get(idx)
+                            .and_then(|value| std::str::from_utf8(value).ok()?.parse::<i64>().ok())
+                            .unwrap_or(0)
+                    };
+                    let lists = self.lists.read().await;
+                    let list = lists.get(&key).map_or(&[][..], Vec::as_slice);
+                    let len = list.len() as i64;
+                    let resolve = |index: i64| if index < 0 { len + index } else { index };
+                    let start = resolve(index(1)).max(0);
+                    let stop = resolve(index(2)).min(len - 1);
+                    let elements = if start > stop {
+                        Vec::new()
+                    } else {
+                        list[start as usize..=stop as usize]
+                            .iter()
+                            .map(|value| redis::Value::BulkString(value.clone()))
+                            .collect()
+                    };
+                    Ok(redis::Value::Array(elements))
+                }
+                "PTTL" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let exists = self.values.read().await.contains_key(&key);
+                    Ok(redis::Value::Int(if exists { -1 } else { -2 }))
                 }
                 "STRLEN" => {
                     let key = request
@@ -76,6 +247,55 @@ impl CommandExecutor for ScriptedStubExecutor {
                         .unwrap_or_default();
                     Ok(redis::Value::Int(value.len() as i64))
                 }
+                "GETRANGE" => {
+                    let key = request
+                        .args
+                        .first()
+                        .map(|value| String::from_utf8_lossy(value).into_owned())
+                        .unwrap_or_default();
+                    let bound = |idx: usize| -> usize {
+                        request
+                            .args
+                            .get(idx)
+                            .and_then(|value| std::str::from_utf8(value).ok()?.parse().ok())
+                            .unwrap_or(0)
+                    };
+                    let value = self
+                        .values
+                        .read()
+                        .await
+                        .get(&key)
+                        .cloned()
+                        .unwrap_or_default();
+                    let end = (bound(2) + 1).min(value.len());
+                    let start = bound(1).min(end);
+                    Ok(redis::Value::BulkString(value[start..end].to_vec()))
+                }
+                "PUBLISH" => Ok(redis::Value::Int(1)),
+                "COMMAND" => Ok(redis::Value::Array(
+                    [("get", "readonly", "@read"), ("set", "write", "@write")]
+                        .into_iter()
+                        .map(|(name, flag, category)| {
+                            redis::Value::Array(vec![
+                                redis::Value::BulkString(name.as_bytes().to_vec()),
+                                redis::Value::Int(-2),
+                                redis::Value::Array(vec![redis::Value::SimpleString(
+                                    flag.to_string(),
+                                )]),
+                                redis::Value::Int(1),
+                                redis::Value::Int(1),
+                                redis::Value::Int(1),
+                                redis::Value::Array(vec![redis::Value::SimpleString(
+                                    category.to_string(),
+                                )]),
+                            ])
+                        })
+                        .collect(),
+                )),
+                "PUBSUB" => Ok(redis::Value::Array(vec![
+                    redis::Value::BulkString(b"news.a".to_vec()),
+                    redis::Value::BulkString(b"news.b".to_vec()),
+                ])),
                 _ => Ok(redis::Value::SimpleString("OK".to_string())),
             }
         })
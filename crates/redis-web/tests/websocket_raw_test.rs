@@ -1,9 +1,12 @@
-//! Integration tests for the raw RESP WebSocket endpoint (`/.raw`).
+//! Integration tests for the raw RESP WebSocket endpoint (`/.raw`) and the
+//! connection state WebSocket sessions keep.
 
 mod support;
 
 use futures_util::{SinkExt, StreamExt};
-use support::process_harness::TestServer;
+use std::time::Duration;
+use support::process_harness::{redis_connect_local_db, TestServer};
+use support::redis_fixtures::unique_key;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::protocol::Message;
 
@@ -58,3 +61,106 @@ async fn test_websocket_raw_streaming_and_error() {
     let msg = ws_stream.next().await.unwrap().unwrap();
     assert!(msg.into_data().starts_with(b"-ERR"));
 }
+
+#[tokio::test]
+async fn test_websocket_raw_transaction_uses_one_connection() {
+    let server = TestServer::new().await;
+    let url = format!("ws://127.0.0.1:{}/.raw", server.port);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    let commands: [&[u8]; 3] = [
+        b"*1\r\n$5\r\nMULTI\r\n",
+        b"*3\r\n$3\r\nSET\r\n$10\r\ntest_key_t\r\n$2\r\ntx\r\n",
+        b"*1\r\n$4\r\nEXEC\r\n",
+    ];
+    let mut replies = Vec::new();
+    for command in commands {
+        ws_stream
+            .send(Message::Binary(command.to_vec().into()))
+            .await
+            .unwrap();
+        replies.push(ws_stream.next().await.unwrap().unwrap().into_data());
+    }
+    assert_eq!(&replies[0][..], b"+OK\r\n");
+    assert_eq!(&replies[1][..], b"+QUEUED\r\n");
+    assert_eq!(&replies[2][..], b"*1\r\n+OK\r\n");
+}
+
+/// Starts a server whose sockets may run four id-tagged commands at once and
+/// release idle connections after a second.
+async fn session_server() -> TestServer {
+    let config_file = tempfile::Builder::new()
+        .suffix(".json")
+        .tempfile()
+        .expect("Failed to create temp config file");
+    let config = serde_json::json!({
+        "redis_host": "127.0.0.1",
+        "redis_port": 6379,
+        "http_host": "127.0.0.1",
+        "http_port": 0,
+        "database": 0,
+        "websockets": true,
+        "websocket_connection_idle_sec": 1,
+        "websocket_max_inflight_commands": 4
+    });
+    TestServer::spawn_with_config_and_env(config_file, config, &[]).await
+}
+
+/// Stores `value` under a fresh key in database 5 and returns the key.
+async fn seed_database_5(value: &str) -> String {
+    let key = unique_key("ws_select");
+    let mut conn = redis_connect_local_db(5).await;
+    let _: () = redis::cmd("SET")
+        .arg(&key)
+        .arg(value)
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    key
+}
+
+#[tokio::test]
+async fn test_websocket_select_outlives_the_idle_release() {
+    let server = session_server().await;
+    let key = seed_database_5("db5").await;
+    let url = format!("ws://127.0.0.1:{}/.json", server.port);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    ws_stream
+        .send(Message::Text(r#"["SELECT", "5"]"#.into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    assert_eq!(msg.to_text().unwrap(), r#"{"SELECT":"OK"}"#);
+
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    ws_stream
+        .send(Message::Text(format!(r#"["GET", "{key}"]"#).into()))
+        .await
+        .unwrap();
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    let reply: serde_json::Value = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+    assert_eq!(reply, serde_json::json!({"GET": "db5"}));
+}
+
+#[tokio::test]
+async fn test_websocket_id_tagged_commands_follow_select() {
+    let server = session_server().await;
+    let key = seed_database_5("db5").await;
+    let url = format!("ws://127.0.0.1:{}/.json", server.port);
+    let (mut ws_stream, _) = connect_async(url).await.unwrap();
+
+    for frame in [
+        r#"{"id": 1, "cmd": ["SELECT", "5"]}"#.to_string(),
+        format!(r#"{{"id": 2, "cmd": ["GET", "{key}"]}}"#),
+    ] {
+        ws_stream.send(Message::Text(frame.into())).await.unwrap();
+    }
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        let msg = ws_stream.next().await.unwrap().unwrap();
+        replies.push(serde_json::from_str::<serde_json::Value>(msg.to_text().unwrap()).unwrap());
+    }
+    assert_eq!(replies[0], serde_json::json!({"id": 1, "SELECT": "OK"}));
+    assert_eq!(replies[1], serde_json::json!({"id": 2, "GET": "db5"}));
+}